@@ -1,5 +1,127 @@
+use std::path::Path;
+
 use auto_generate_cdp::init;
 
 fn main() {
     init();
+
+    let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    let protocol_file = Path::new(&out_dir).join("protocol.rs");
+    let mut protocol = std::fs::read_to_string(&protocol_file).unwrap();
+    if let Some(with_catch_all) = with_catch_all_event(&protocol) {
+        std::fs::write(&protocol_file, &with_catch_all).unwrap();
+        protocol = with_catch_all;
+    }
+
+    std::fs::write(
+        Path::new(&out_dir).join("event_methods.rs"),
+        event_methods_source() + &event_method_impl(&protocol),
+    )
+    .unwrap();
+}
+
+const EVENT_ENUM: &str = "pub enum Event {";
+
+const CATCH_ALL_EVENT: &str = "#[serde(skip)] Other { method: String, params: serde_json::Value },";
+
+/// Gives the generated `Event` enum an `Other` variant for events the protocol definitions don't
+/// know, and has serde derive its (de)serialization as inherent functions (`remote = "Self"`), for
+/// the `Serialize` and `Deserialize` impls in `types.rs` to fall back on. Returns None if the
+/// protocol already has them, as the generated file is kept between builds.
+fn with_catch_all_event(protocol: &str) -> Option<String> {
+    if protocol.contains(CATCH_ALL_EVENT) {
+        return None;
+    }
+
+    let enum_start = protocol
+        .find(EVENT_ENUM)
+        .expect("no Event enum in the generated protocol");
+    let attrs_start = protocol[..enum_start]
+        .rfind("#[derive(")
+        .expect("no derive for the generated Event enum");
+
+    let attrs = &protocol[attrs_start..enum_start];
+    let remote_attrs = attrs
+        .replace("#[derive(Deserialize,", "#[derive(Serialize, Deserialize,")
+        .replace(
+            r#"#[serde(tag = "method")]"#,
+            r#"#[serde(tag = "method", remote = "Self")]"#,
+        );
+    assert!(
+        remote_attrs.contains("remote") && remote_attrs.contains("Serialize,"),
+        "unexpected attributes on the generated Event enum: {attrs}"
+    );
+
+    Some(format!(
+        "{}{remote_attrs}{EVENT_ENUM}\n{CATCH_ALL_EVENT}{}",
+        &protocol[..attrs_start],
+        &protocol[enum_start + EVENT_ENUM.len()..],
+    ))
+}
+
+/// The sorted names of every event in the protocol definitions the `Event` enum is generated
+/// from, so that `Event`'s `Deserialize` impl can tell unknown events from malformed ones.
+fn event_methods_source() -> String {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let mut methods = Vec::new();
+    for file in ["browser_protocol.json", "js_protocol.json"] {
+        let path = Path::new(&manifest_dir).join("json").join(file);
+        let protocol: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        for domain in protocol["domains"].as_array().unwrap() {
+            for event in domain["events"].as_array().into_iter().flatten() {
+                methods.push(format!(
+                    "{}.{}",
+                    domain["domain"].as_str().unwrap(),
+                    event["name"].as_str().unwrap()
+                ));
+            }
+        }
+    }
+    methods.sort();
+
+    let methods: String = methods
+        .iter()
+        .map(|method| format!("    {method:?},\n"))
+        .collect();
+    format!("pub(crate) const EVENT_METHODS: &[&str] = &[\n{methods}];\n")
+}
+
+/// `Event::method`, matching each of the generated enum's variants to the method it's renamed
+/// to, so that an event's name can be had without serializing it.
+fn event_method_impl(protocol: &str) -> String {
+    let enum_start = protocol
+        .find(EVENT_ENUM)
+        .expect("no Event enum in the generated protocol");
+    let mut arms = String::new();
+    let mut method = None;
+    for line in protocol[enum_start + EVENT_ENUM.len()..].lines() {
+        let line = line.trim();
+        if line == "}" {
+            break;
+        }
+        if let Some(rename) = line.strip_prefix("#[serde(rename = \"") {
+            method = rename.strip_suffix("\")]");
+        } else if let (Some(method), Some((variant, _))) = (method.take(), line.split_once('(')) {
+            arms.push_str(&format!("            Event::{variant}(_) => {method:?},\n"));
+        }
+    }
+    assert!(
+        !arms.is_empty(),
+        "no variants found in the generated Event enum"
+    );
+
+    format!(
+        "
+impl Event {{
+    /// The method the event was sent as, e.g. `Page.loadEventFired`.
+    #[allow(clippy::too_many_lines)]
+    pub fn method(&self) -> &str {{
+        match self {{
+            Event::Other {{ method, .. }} => method,
+{arms}        }}
+    }}
+}}
+"
+    )
 }
@@ -0,0 +1,251 @@
+//! An interactive shell for exploring the DevTools protocol. It launches Chrome (or connects to
+//! one with `--connect <WebSocket URL>`), opens a tab, then sends the methods typed in to it and
+//! prints their results, along with the events the tab receives:
+//!
+//! ```text
+//! $ cargo run --features repl --bin cdp-repl
+//! tab> Page.navigate {"url": "https://example.com"}
+//! tab> Runtime.evaluate {"expression": "document.title", "returnByValue": true}
+//! ```
+//!
+//! Method names tab-complete from the protocol definitions this crate is generated from. Type
+//! `:help` for the other commands.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde::Deserialize;
+use serde_json::Value as Json;
+
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::{Browser, LaunchOptions};
+
+const PROTOCOLS: [&str; 2] = [
+    include_str!("../../json/browser_protocol.json"),
+    include_str!("../../json/js_protocol.json"),
+];
+
+const HELP: &str = "\
+<Domain.method> [JSON params]  calls a method, e.g. DOM.getDocument {\"depth\": 1}
+:browser                       sends methods to the browser rather than the tab
+:tab                           sends methods to the tab (the default)
+:events                        turns printing the tab's events off or back on
+:events <Domain.event>         only prints that event
+:help                          shows this
+:quit                          exits";
+
+const META_COMMANDS: [&str; 5] = [":browser", ":tab", ":events", ":help", ":quit"];
+
+#[derive(Deserialize)]
+struct Protocol {
+    domains: Vec<ProtocolDomain>,
+}
+
+#[derive(Deserialize)]
+struct ProtocolDomain {
+    domain: String,
+    #[serde(default)]
+    commands: Vec<Named>,
+    #[serde(default)]
+    events: Vec<Named>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+/// The names of the protocol's methods and of its events, as `Domain.name`.
+fn protocol_names() -> Result<(Vec<String>, Vec<String>)> {
+    let mut methods = Vec::new();
+    let mut events = Vec::new();
+    for protocol in PROTOCOLS {
+        let protocol: Protocol = serde_json::from_str(protocol)?;
+        for domain in protocol.domains {
+            let qualify = |named: Named| format!("{}.{}", domain.domain, named.name);
+            methods.extend(domain.commands.into_iter().map(qualify));
+            events.extend(domain.events.into_iter().map(qualify));
+        }
+    }
+    methods.sort();
+    events.sort();
+    Ok((methods, events))
+}
+
+struct ReplHelper {
+    methods: Vec<String>,
+    events: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let typed = &line[..pos];
+        // Completes the method name, or the event name after `:events`
+        let (start, word, names): (usize, &str, Vec<&str>) = match typed.split_once(' ') {
+            None => (
+                0,
+                typed,
+                META_COMMANDS
+                    .iter()
+                    .copied()
+                    .chain(self.methods.iter().map(String::as_str))
+                    .collect(),
+            ),
+            Some((":events", event)) if !event.contains(' ') => (
+                ":events ".len(),
+                event,
+                self.events.iter().map(String::as_str).collect(),
+            ),
+            Some(_) => return Ok((pos, Vec::new())),
+        };
+        let candidates = names
+            .into_iter()
+            .filter(|name| name.starts_with(word))
+            .map(|name| Pair {
+                display: name.to_string(),
+                replacement: name.to_string(),
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Which events of the tab get printed.
+#[derive(Debug, Clone, PartialEq)]
+enum EventFilter {
+    All,
+    Only(String),
+    None,
+}
+
+/// Splits `Domain.method {"some": "params"}` into the method's name and its params.
+fn parse_call(line: &str) -> Result<(&str, Json)> {
+    let (method, params) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if !method.contains('.') {
+        return Err(anyhow!(
+            "{method} isn't a method name, which look like Domain.method"
+        ));
+    }
+    let params = if params.trim().is_empty() {
+        Json::Object(serde_json::Map::new())
+    } else {
+        serde_json::from_str(params)?
+    };
+    Ok((method, params))
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let mut connect_to = None;
+    let mut headless = true;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--connect" => connect_to = args.next(),
+            "--headful" => headless = false,
+            _ => {
+                return Err(anyhow!(
+                    "usage: cdp-repl [--connect <WebSocket URL>] [--headful]"
+                ))
+            }
+        }
+    }
+
+    let browser = match connect_to {
+        Some(ws_url) => Browser::connect(ws_url)?,
+        None => Browser::new(
+            LaunchOptions::default_builder()
+                .headless(headless)
+                .build()?,
+        )?,
+    };
+    let tab = browser.new_tab()?;
+    println!("{}", browser.get_version()?.product);
+
+    let event_filter = Arc::new(std::sync::Mutex::new(EventFilter::All));
+    let event_filter_clone = Arc::clone(&event_filter);
+    tab.add_event_listener(Arc::new(move |event: &Event| {
+        let printed = match &*event_filter_clone.lock().unwrap() {
+            EventFilter::All => true,
+            EventFilter::Only(name) => event.method() == name,
+            EventFilter::None => false,
+        };
+        if printed {
+            if let Ok(event) = serde_json::to_string(event) {
+                println!("<- {event}");
+            }
+        }
+    }))?;
+
+    let (methods, events) = protocol_names()?;
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ReplHelper { methods, events }));
+
+    let mut on_browser = false;
+    loop {
+        let prompt = if on_browser { "browser> " } else { "tab> " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+
+        match line.split_once(' ').unwrap_or((line, "")) {
+            (":quit" | ":q", _) => break,
+            (":help", _) => println!("{HELP}"),
+            (":browser", _) => on_browser = true,
+            (":tab", _) => on_browser = false,
+            (":events", name) => {
+                let mut filter = event_filter.lock().unwrap();
+                *filter = match (name.trim(), &*filter) {
+                    ("", EventFilter::None) => EventFilter::All,
+                    ("", _) => EventFilter::None,
+                    (name, _) => EventFilter::Only(name.to_string()),
+                };
+                println!("Printing events: {filter:?}");
+            }
+            _ => {
+                let result = parse_call(line).and_then(|(method, params)| {
+                    if on_browser {
+                        browser.call_raw(method, params)
+                    } else {
+                        tab.call_raw(method, params)
+                    }
+                });
+                match result {
+                    Ok(result) => println!("{}", serde_json::to_string_pretty(&result)?),
+                    Err(err) => println!("Error: {err}"),
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,361 @@
+//! A client for [WebDriver BiDi](https://w3c.github.io/webdriver-bidi/), the cross-browser
+//! successor of the DevTools protocol, spoken over the same kind of WebSocket connection. See
+//! `Browser::connect_bidi`.
+//!
+//! Only the basics are typed (navigating, evaluating script, subscribing to events); anything
+//! else can be sent with `BidiSession::call`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::logging::{trace, warn};
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+use thiserror::Error;
+use url::Url;
+
+use super::transport::{Connection, MessageHandler, WebSocketConnection};
+use crate::util::Timeout;
+
+/// An error the remote end answered a command with.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("BiDi command failed with {error}: {message}")]
+pub struct BidiError {
+    /// The error code, e.g. `no such frame` or `invalid argument`.
+    pub error: String,
+    pub message: String,
+}
+
+/// An event the session is subscribed to, e.g. `log.entryAdded`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BidiEvent {
+    pub method: String,
+    pub params: Json,
+}
+
+pub type BidiEventListener = dyn Fn(&BidiEvent) + Send + Sync;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum IncomingMessage {
+    Success {
+        id: u64,
+        result: Json,
+    },
+    Error {
+        id: Option<u64>,
+        error: String,
+        message: String,
+    },
+    Event {
+        method: String,
+        params: Json,
+    },
+}
+
+type PendingCommands = Arc<Mutex<HashMap<u64, mpsc::Sender<Result<Json, BidiError>>>>>;
+
+/// Routes the messages read from the WebSocket: responses to the commands waiting for them,
+/// events to the thread calling the listeners.
+struct BidiMessages {
+    pending: PendingCommands,
+    events_tx: mpsc::Sender<BidiEvent>,
+}
+
+impl MessageHandler for BidiMessages {
+    fn text(&self, message_text: &str) -> bool {
+        match serde_json::from_str(message_text) {
+            Ok(IncomingMessage::Success { id, result }) => {
+                if let Some(tx) = self.pending.lock().unwrap().remove(&id) {
+                    tx.send(Ok(result)).ok();
+                }
+            }
+            Ok(IncomingMessage::Error {
+                id: Some(id),
+                error,
+                message,
+            }) => {
+                if let Some(tx) = self.pending.lock().unwrap().remove(&id) {
+                    tx.send(Err(BidiError { error, message })).ok();
+                }
+            }
+            Ok(IncomingMessage::Error {
+                id: None,
+                error,
+                message,
+            }) => {
+                warn!("BiDi error not related to any command: {error}: {message}");
+            }
+            Ok(IncomingMessage::Event { method, params }) => {
+                return self.events_tx.send(BidiEvent { method, params }).is_ok();
+            }
+            Err(err) => trace!("Incoming BiDi message isn't recognised: {message_text} - {err}"),
+        }
+        true
+    }
+
+    fn binary(&self, _payload: Vec<u8>) -> bool {
+        true
+    }
+
+    fn closed(&self) {
+        // Dropping the senders fails the commands still waiting for a response
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+/// A WebDriver BiDi session, e.g. with Firefox (`ws://127.0.0.1:9222/session` when started with
+/// `--remote-debugging-port=9222`) or with Chrome through chromedriver (the `webSocketUrl` a
+/// WebDriver session created with `"webSocketUrl": true` returns).
+pub struct BidiSession {
+    connection: WebSocketConnection,
+    next_id: AtomicU64,
+    pending: PendingCommands,
+    listeners: Arc<Mutex<Vec<Arc<BidiEventListener>>>>,
+    timeout: Duration,
+}
+
+impl std::fmt::Debug for BidiSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "BidiSession {{}}")
+    }
+}
+
+impl BidiSession {
+    /// Connects to a BiDi WebSocket endpoint. `timeout` is how long to wait for the response
+    /// to each command.
+    pub fn connect(ws_url: &str, timeout: Duration) -> Result<Self> {
+        Self::connect_with_close_handler(ws_url, timeout, || {})
+    }
+
+    /// Like `connect`, but calls `on_closed` once the connection has closed and the listeners
+    /// have been given every event received before that.
+    pub(crate) fn connect_with_close_handler<F>(
+        ws_url: &str,
+        timeout: Duration,
+        on_closed: F,
+    ) -> Result<Self>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let url = Url::parse(ws_url)?;
+        let pending = Arc::new(Mutex::new(HashMap::new()));
+        let listeners: Arc<Mutex<Vec<Arc<BidiEventListener>>>> = Arc::new(Mutex::new(Vec::new()));
+        let (events_tx, events_rx) = mpsc::channel::<BidiEvent>();
+
+        // Listeners are called from their own thread so they can make calls themselves, which
+        // wouldn't get their responses if the WebSocket's thread was busy calling the listeners
+        let event_listeners = Arc::clone(&listeners);
+        std::thread::spawn(move || {
+            for event in events_rx {
+                let listeners = event_listeners.lock().unwrap().clone();
+                for listener in listeners {
+                    listener(&event);
+                }
+            }
+            on_closed();
+        });
+
+        let connection = WebSocketConnection::new(
+            &url,
+            None,
+            BidiMessages {
+                pending: Arc::clone(&pending),
+                events_tx,
+            },
+            None,
+        )?;
+
+        Ok(Self {
+            connection,
+            next_id: AtomicU64::new(1),
+            pending,
+            listeners,
+            timeout,
+        })
+    }
+
+    /// Sends a command, e.g. `browsingContext.reload`, and waits for its result.
+    pub fn call(&self, method: &str, params: Json) -> Result<Json> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = mpsc::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let message = json!({ "id": id, "method": method, "params": params });
+        if let Err(err) = self.connection.send_message(&message.to_string()) {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(err);
+        }
+
+        match rx.recv_timeout(self.timeout) {
+            Ok(result) => Ok(result?),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(Timeout.into())
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(super::transport::ConnectionClosed {}.into())
+            }
+        }
+    }
+
+    /// Starts a session without any particular capabilities, which endpoints other than
+    /// chromedriver's require before any other command.
+    pub fn new_session(&self) -> Result<Json> {
+        self.call("session.new", json!({ "capabilities": {} }))
+    }
+
+    /// Subscribes to events, by name (`log.entryAdded`) or module (`network`), for
+    /// `add_event_listener`'s listeners to get.
+    pub fn subscribe(&self, events: &[&str]) -> Result<&Self> {
+        self.call("session.subscribe", json!({ "events": events }))?;
+        Ok(self)
+    }
+
+    /// Calls `listener` for each event the session is subscribed to. Returns a weak reference
+    /// to it, which can be passed to `remove_event_listener`.
+    pub fn add_event_listener(&self, listener: Arc<BidiEventListener>) -> Weak<BidiEventListener> {
+        let weak = Arc::downgrade(&listener);
+        self.listeners.lock().unwrap().push(listener);
+        weak
+    }
+
+    pub fn remove_event_listener(&self, listener: &Weak<BidiEventListener>) {
+        if let Some(listener) = listener.upgrade() {
+            self.listeners
+                .lock()
+                .unwrap()
+                .retain(|other| !Arc::ptr_eq(other, &listener));
+        }
+    }
+
+    /// The ids of the top-level browsing contexts, i.e. the tabs.
+    pub fn get_browsing_contexts(&self) -> Result<Vec<String>> {
+        let tree = self.call("browsingContext.getTree", json!({ "maxDepth": 0 }))?;
+        Ok(tree["contexts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|context| context["context"].as_str().map(String::from))
+            .collect())
+    }
+
+    /// Navigates a browsing context to `url`, waiting until the page has loaded.
+    pub fn navigate(&self, context: &str, url: &str) -> Result<&Self> {
+        self.call(
+            "browsingContext.navigate",
+            json!({ "context": context, "url": url, "wait": "complete" }),
+        )?;
+        Ok(self)
+    }
+
+    /// Evaluates `expression` in a browsing context, awaiting it if it's a promise, and returns
+    /// the result as BiDi serializes it (e.g. `{"type": "number", "value": 2}`).
+    pub fn evaluate(&self, context: &str, expression: &str) -> Result<Json> {
+        let evaluated = self.call(
+            "script.evaluate",
+            json!({
+                "expression": expression,
+                "target": { "context": context },
+                "awaitPromise": true,
+            }),
+        )?;
+        if evaluated["type"] == "exception" {
+            return Err(BidiError {
+                error: "javascript error".to_string(),
+                message: evaluated["exceptionDetails"]["text"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            }
+            .into());
+        }
+        Ok(evaluated["result"].clone())
+    }
+
+    /// Ends the session, which closes the browser for endpoints which launched it.
+    pub fn end(&self) -> Result<()> {
+        self.call("session.end", json!({}))?;
+        Ok(())
+    }
+
+    /// Closes the WebSocket without ending the session.
+    pub(crate) fn shutdown(&self) {
+        self.connection.shutdown();
+    }
+}
+
+impl Drop for BidiSession {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// A BiDi endpoint answering `script.evaluate` with an event and then the result, and any
+    /// other command with an error.
+    fn fake_endpoint() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            while let Ok(tungstenite::protocol::Message::Text(text)) = socket.read_message() {
+                let command: Json = serde_json::from_str(&text).unwrap();
+                let replies = if command["method"] == "script.evaluate" {
+                    vec![
+                        json!({"type": "event", "method": "log.entryAdded", "params": {"text": "hi"}}),
+                        json!({"type": "success", "id": command["id"], "result": {
+                            "type": "success",
+                            "result": {"type": "number", "value": 2},
+                        }}),
+                    ]
+                } else {
+                    vec![
+                        json!({"type": "error", "id": command["id"], "error": "unknown command", "message": "nope"}),
+                    ]
+                };
+                for reply in replies {
+                    let frame = tungstenite::protocol::Message::Text(reply.to_string());
+                    socket.write_message(frame).unwrap();
+                }
+            }
+        });
+        format!("ws://127.0.0.1:{port}")
+    }
+
+    #[test]
+    fn calls_commands_and_dispatches_events() {
+        let session = BidiSession::connect(&fake_endpoint(), Duration::from_secs(5)).unwrap();
+        let (events_tx, events_rx) = mpsc::channel();
+        let events_tx = Mutex::new(events_tx);
+        session.add_event_listener(Arc::new(move |event: &BidiEvent| {
+            events_tx.lock().unwrap().send(event.clone()).unwrap();
+        }));
+
+        let result = session.evaluate("context", "1 + 1").unwrap();
+        assert_eq!(json!({"type": "number", "value": 2}), result);
+        let event = events_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!("log.entryAdded", event.method);
+
+        let error = session
+            .call("browsingContext.reload", json!({}))
+            .unwrap_err();
+        assert_eq!(
+            Some(&BidiError {
+                error: "unknown command".to_string(),
+                message: "nope".to_string(),
+            }),
+            error.downcast_ref::<BidiError>()
+        );
+    }
+}
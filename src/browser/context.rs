@@ -1,10 +1,208 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
 
-use crate::browser::tab::Tab;
+use crate::browser::tab::middleware::{MiddlewareId, SyncMiddleware};
+use crate::browser::tab::{RequestPausedDecision, Tab};
+use crate::protocol::cdp::types::Method;
+use crate::protocol::cdp::Browser::{GrantPermissions, ResetPermissions};
+use crate::protocol::cdp::Fetch::{
+    events::RequestPausedEvent, FulfillRequest, HeaderEntry, RequestPattern,
+};
+use crate::protocol::cdp::IndexedDB::{KeyPath, KeyPathType, ObjectStoreIndex};
+use crate::protocol::cdp::Network::{Cookie, CookieParam, SetCookies};
+use crate::protocol::cdp::Storage::{ClearDataForOrigin, GetUsageAndQuota};
 use crate::protocol::cdp::Target::CreateTarget;
 
+pub use crate::protocol::cdp::Storage::{
+    GetUsageAndQuotaReturnObject as UsageAndQuota, StorageType,
+};
+
+pub use crate::protocol::cdp::Browser::PermissionType as Permission;
+
+/// The proxy a context created with `Browser.new_context_with_proxy` sends its traffic through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextProxy {
+    /// The proxy's URL, e.g. `http://proxy.example.com:8080` or `socks5://127.0.0.1:1080`.
+    pub server: String,
+    /// Comma-separated hosts which are connected to directly, e.g. `localhost,*.internal`.
+    pub bypass_list: Option<String>,
+}
+
+/// A context's cookies and the `localStorage` and IndexedDB databases of the origins its tabs
+/// are on, see `Context.storage_state`. Serialize it to reuse a login in later runs, with
+/// `Browser.new_context_with_state`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageState {
+    pub cookies: Vec<Cookie>,
+    pub origins: Vec<OriginState>,
+}
+
+/// What an origin has stored, as part of a [`StorageState`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OriginState {
+    /// E.g. `https://example.com`.
+    pub origin: String,
+    /// `(key, value)` pairs.
+    pub local_storage: Vec<(String, String)>,
+    pub indexed_db: Vec<IndexedDbState>,
+}
+
+/// An IndexedDB database's schema and records, as part of an [`OriginState`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedDbState {
+    pub name: String,
+    pub version: f64,
+    pub object_stores: Vec<ObjectStoreState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectStoreState {
+    pub name: String,
+    pub key_path: KeyPath,
+    pub auto_increment: bool,
+    pub indexes: Vec<ObjectStoreIndex>,
+    pub records: Vec<IndexedDbRecord>,
+}
+
+/// A record of an object store. Values are read back as JSON, so e.g. `Date`s become strings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedDbRecord {
+    /// The record's primary key.
+    pub key: Json,
+    pub value: Json,
+}
+
+/// Writes an origin's `localStorage` and IndexedDB databases from the state
+/// `OriginState::to_script_argument` makes.
+const RESTORE_ORIGIN_STATE: &str = r"async function restoreOriginState(state) {
+    for (const [key, value] of state.localStorage) {
+        localStorage.setItem(key, value);
+    }
+    for (const db of state.indexedDb) {
+        await new Promise((resolve, reject) => {
+            const request = indexedDB.open(db.name, db.version);
+            request.onupgradeneeded = () => {
+                for (const store of db.objectStores) {
+                    const created = request.result.createObjectStore(store.name, {
+                        keyPath: store.keyPath,
+                        autoIncrement: store.autoIncrement,
+                    });
+                    for (const index of store.indexes) {
+                        created.createIndex(index.name, index.keyPath, {
+                            unique: index.unique,
+                            multiEntry: index.multiEntry,
+                        });
+                    }
+                }
+            };
+            request.onerror = () => reject(request.error);
+            request.onsuccess = () => {
+                const database = request.result;
+                if (db.objectStores.length === 0) {
+                    database.close();
+                    return resolve();
+                }
+                const names = db.objectStores.map((store) => store.name);
+                const transaction = database.transaction(names, 'readwrite');
+                for (const store of db.objectStores) {
+                    const objectStore = transaction.objectStore(store.name);
+                    for (const record of store.records) {
+                        if (store.keyPath === null) {
+                            objectStore.put(record.value, record.key);
+                        } else {
+                            objectStore.put(record.value);
+                        }
+                    }
+                }
+                transaction.oncomplete = () => {
+                    database.close();
+                    resolve();
+                };
+                transaction.onerror = () => reject(transaction.error);
+            };
+        });
+    }
+}";
+
+impl OriginState {
+    fn read(tab: &Tab, origin: String) -> Result<Self> {
+        let local_storage = tab.local_storage()?.items()?;
+
+        let indexed_db = tab.indexeddb(&origin)?;
+        let mut databases = Vec::new();
+        for name in indexed_db.database_names()? {
+            let database = indexed_db.database(&name)?;
+            let mut object_stores = Vec::new();
+            for store in database.object_stores {
+                let records = indexed_db
+                    .request_data::<Json>(&name, &store.name, None)?
+                    .into_iter()
+                    .map(|entry| IndexedDbRecord {
+                        key: entry.primary_key,
+                        value: entry.value,
+                    })
+                    .collect();
+                object_stores.push(ObjectStoreState {
+                    name: store.name,
+                    key_path: store.key_path,
+                    auto_increment: store.auto_increment,
+                    indexes: store.indexes,
+                    records,
+                });
+            }
+            databases.push(IndexedDbState {
+                name,
+                version: database.version,
+                object_stores,
+            });
+        }
+
+        Ok(Self {
+            origin,
+            local_storage,
+            indexed_db: databases,
+        })
+    }
+
+    /// The state as `RESTORE_ORIGIN_STATE` takes it, with key paths as IndexedDB takes them.
+    fn to_script_argument(&self) -> Json {
+        fn key_path(key_path: &KeyPath) -> Json {
+            match key_path.Type {
+                KeyPathType::Null => Json::Null,
+                KeyPathType::String => json!(key_path.string),
+                KeyPathType::Array => json!(key_path.array),
+            }
+        }
+
+        json!({
+            "localStorage": self.local_storage,
+            "indexedDb": self.indexed_db.iter().map(|db| json!({
+                "name": db.name,
+                "version": db.version,
+                "objectStores": db.object_stores.iter().map(|store| json!({
+                    "name": store.name,
+                    "keyPath": key_path(&store.key_path),
+                    "autoIncrement": store.auto_increment,
+                    "indexes": store.indexes.iter().map(|index| json!({
+                        "name": index.name,
+                        "keyPath": key_path(&index.key_path),
+                        "unique": index.unique,
+                        "multiEntry": index.multi_entry,
+                    })).collect::<Vec<_>>(),
+                    "records": store.records,
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
 /// Equivalent to a new incognito window
 pub struct Context<'a> {
     id: String,
@@ -39,6 +237,162 @@ impl<'a> Context<'a> {
         &self.id
     }
 
+    /// Grants the given permissions to `origin` within this context, so that pages from that
+    /// origin never show a permission prompt for them.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::{browser::context::Permission, Browser};
+    /// let browser = Browser::default()?;
+    /// let context = browser.new_context()?;
+    /// context.grant_permissions(
+    ///     "https://example.com",
+    ///     &[Permission::Geolocation, Permission::Notifications],
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn grant_permissions(&self, origin: &str, permissions: &[Permission]) -> Result<&Self> {
+        self.browser.call_method(GrantPermissions {
+            permissions: permissions.to_vec(),
+            origin: Some(origin.to_string()),
+            browser_context_id: Some(self.id.clone()),
+        })?;
+        Ok(self)
+    }
+
+    /// Resets all permission management for this context, undoing any `grant_permissions` calls.
+    pub fn reset_permissions(&self) -> Result<&Self> {
+        self.browser.call_method(ResetPermissions {
+            browser_context_id: Some(self.id.clone()),
+        })?;
+        Ok(self)
+    }
+
+    /// Clears the given kinds of storage (cookies, localStorage, IndexedDB, cache storage, ...)
+    /// for `origin` within this context. Pass `StorageType::All` to clear everything.
+    pub fn clear_storage(&self, origin: &str, types: &[StorageType]) -> Result<&Self> {
+        let storage_types = types
+            .iter()
+            .map(|t| serde_json::to_value(t).map(|v| v.as_str().unwrap_or_default().to_string()))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(",");
+        self.call_method_on_tab(ClearDataForOrigin {
+            origin: origin.to_string(),
+            storage_Types: storage_types,
+        })?;
+        Ok(self)
+    }
+
+    /// Returns how much storage `origin` is using within this context, broken down by type,
+    /// along with its quota.
+    pub fn get_usage_and_quota(&self, origin: &str) -> Result<UsageAndQuota> {
+        self.call_method_on_tab(GetUsageAndQuota {
+            origin: origin.to_string(),
+        })
+    }
+
+    /// Returns this context's cookies, and the `localStorage` and IndexedDB databases of the
+    /// origins its tabs are on, for `Browser.new_context_with_state` to restore.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::default()?;
+    /// let context = browser.new_context()?;
+    /// let tab = context.new_tab()?;
+    /// tab.navigate_to("https://example.com/login")?.wait_until_navigated()?;
+    /// // ... log in ...
+    /// let state = context.storage_state()?;
+    /// std::fs::write("state.json", serde_json::to_string(&state)?)?;
+    ///
+    /// // In a later run
+    /// let state = serde_json::from_str(&std::fs::read_to_string("state.json")?)?;
+    /// let context = browser.new_context_with_state(&state)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn storage_state(&self) -> Result<StorageState> {
+        // The generated `Storage.getCookies` expects a single cookie rather than a list
+        let cookies = self
+            .browser
+            .call_raw("Storage.getCookies", json!({ "browserContextId": self.id }))?;
+        let cookies = serde_json::from_value(cookies["cookies"].clone())?;
+
+        let mut origins: Vec<OriginState> = Vec::new();
+        for tab in self.get_tabs()? {
+            let Ok(url) = url::Url::parse(&tab.get_url()) else {
+                continue;
+            };
+            if !matches!(url.scheme(), "http" | "https") {
+                continue;
+            }
+            let origin = url.origin().ascii_serialization();
+            if origins.iter().any(|state| state.origin == origin) {
+                continue;
+            }
+            origins.push(OriginState::read(&tab, origin)?);
+        }
+
+        Ok(StorageState { cookies, origins })
+    }
+
+    /// Sets the cookies of `state`, and writes its origins' `localStorage` and IndexedDB
+    /// databases from a blank page on each origin, which is served without going to the network.
+    pub(crate) fn restore_storage_state(&self, state: &StorageState) -> Result<()> {
+        let tab = self.new_tab()?;
+        let restored = restore_storage_state_in(&tab, state);
+        tab.close_target()?;
+        restored
+    }
+
+    /// Adds a middleware which handles the requests of every tab in this context, including
+    /// ones opened later. It runs before any middleware added to the tabs themselves, see
+    /// `Tab.add_middleware`.
+    pub fn add_middleware(&self, middleware: Arc<SyncMiddleware>) -> Result<MiddlewareId> {
+        let chain = self.browser.get_context_middleware(&self.id);
+        let id = chain.add(middleware);
+        for tab in self.get_tabs()? {
+            tab.set_context_middleware(Arc::clone(&chain))?;
+        }
+        Ok(id)
+    }
+
+    /// Removes a middleware added with `add_middleware`, returning whether it was still
+    /// registered.
+    pub fn remove_middleware(&self, id: MiddlewareId) -> Result<bool> {
+        let chain = self.browser.get_context_middleware(&self.id);
+        let removed = chain.remove(id);
+        if removed && chain.is_empty() {
+            for tab in self.get_tabs()? {
+                tab.set_context_middleware(Arc::clone(&chain))?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Storage commands act on the storage partition of the session they're sent to, so they have
+    /// to go to a tab in this context. If there isn't one, a blank tab is opened for the call.
+    fn call_method_on_tab<C>(&self, method: C) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize + std::fmt::Debug,
+    {
+        if let Some(tab) = self.get_tabs()?.first() {
+            return tab.call_method(method);
+        }
+        let tab = self.new_tab()?;
+        let result = tab.call_method(method);
+        tab.close_target()?;
+        result
+    }
+
     /// Any tabs created in this context
     pub fn get_tabs(&self) -> Result<Vec<Arc<Tab>>> {
         let browser_tabs = self.browser.get_tabs().lock().unwrap();
@@ -53,3 +407,63 @@ impl<'a> Context<'a> {
         Ok(tabs)
     }
 }
+
+fn restore_storage_state_in(tab: &Tab, state: &StorageState) -> Result<()> {
+    let cookies = state.cookies.iter().map(cookie_param).collect();
+    tab.call_method(SetCookies { cookies })?;
+
+    if state.origins.is_empty() {
+        return Ok(());
+    }
+    tab.enable_request_interception(Arc::new(
+        |_transport, _session_id, event: RequestPausedEvent| {
+            RequestPausedDecision::Fulfill(FulfillRequest {
+                request_id: event.params.request_id,
+                response_code: 200,
+                response_headers: Some(vec![HeaderEntry {
+                    name: "Content-Type".to_string(),
+                    value: "text/html".to_string(),
+                }]),
+                binary_response_headers: None,
+                body: Some(base64::encode("<html></html>")),
+                response_phrase: None,
+            })
+        },
+    ))?;
+    for origin in &state.origins {
+        let pattern = RequestPattern {
+            url_pattern: Some(format!("{}/*", origin.origin)),
+            resource_Type: None,
+            request_stage: None,
+        };
+        tab.acquire_fetch("restore_state", Some(&[pattern]), false)?;
+        tab.navigate_to(&format!("{}/", origin.origin))?
+            .wait_until_navigated()?;
+        tab.evaluate(
+            &format!("({RESTORE_ORIGIN_STATE})({})", origin.to_script_argument()),
+            true,
+        )?;
+    }
+    tab.release_fetch("restore_state")?;
+    Ok(())
+}
+
+fn cookie_param(cookie: &Cookie) -> CookieParam {
+    CookieParam {
+        name: cookie.name.clone(),
+        value: cookie.value.clone(),
+        url: None,
+        domain: Some(cookie.domain.clone()),
+        path: Some(cookie.path.clone()),
+        secure: Some(cookie.secure),
+        http_only: Some(cookie.http_only),
+        same_site: cookie.same_site.clone(),
+        // Session cookies have an expiry of -1
+        expires: (!cookie.session).then_some(cookie.expires),
+        priority: Some(cookie.priority.clone()),
+        same_party: None,
+        source_scheme: Some(cookie.source_scheme.clone()),
+        source_port: Some(cookie.source_port),
+        partition_key: cookie.partition_key.clone(),
+    }
+}
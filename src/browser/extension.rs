@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use crate::logging::{debug, trace};
+use anyhow::Result;
+use thiserror::Error;
+
+use crate::browser::transport::{SessionId, Transport};
+use crate::protocol::cdp::{types::Method, Runtime, Target};
+
+#[derive(Debug, Error)]
+#[error("Extension {extension_id} has no background page or service worker running")]
+pub struct ExtensionNotFound {
+    pub extension_id: String,
+}
+
+/// The id of the extension a target belongs to, if it's an extension's page or worker.
+pub fn extension_id(target_info: &Target::TargetInfo) -> Option<&str> {
+    let rest = target_info.url.strip_prefix("chrome-extension://")?;
+    rest.split('/').next().filter(|id| !id.is_empty())
+}
+
+/// Where an extension's own code runs: its service worker (Manifest V3) or background page (V2).
+/// Evaluating in it lets you drive the extension through its own APIs, e.g. to configure a
+/// proxy extension or read what a password manager stored. See `Browser::attach_to_extension`.
+pub struct ExtensionBackground {
+    target_info: Target::TargetInfo,
+    session_id: SessionId,
+    transport: Arc<Transport>,
+}
+
+impl ExtensionBackground {
+    pub(crate) fn attach(
+        target_info: Target::TargetInfo,
+        transport: Arc<Transport>,
+    ) -> Result<Self> {
+        let session_id = transport
+            .call_method_on_browser(Target::AttachToTarget {
+                target_id: target_info.target_id.clone(),
+                flatten: None,
+            })?
+            .session_id
+            .into();
+        debug!(
+            "Attached to the {} of {}",
+            target_info.Type, target_info.url
+        );
+        Ok(Self {
+            target_info,
+            session_id,
+            transport,
+        })
+    }
+
+    pub fn extension_id(&self) -> &str {
+        extension_id(&self.target_info).unwrap_or_default()
+    }
+
+    pub fn get_target_info(&self) -> &Target::TargetInfo {
+        &self.target_info
+    }
+
+    pub fn call_method<C>(&self, method: C) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize + std::fmt::Debug,
+    {
+        trace!("Calling method on extension: {method:?}");
+        self.transport
+            .call_method_on_target(self.session_id.clone(), method)
+    }
+
+    /// Evaluates `expression` in the extension's global scope, where the `chrome.*` extension
+    /// APIs its manifest asks for are available.
+    pub fn evaluate(&self, expression: &str, await_promise: bool) -> Result<Runtime::RemoteObject> {
+        let result = self
+            .call_method(Runtime::Evaluate {
+                expression: expression.to_string(),
+                return_by_value: Some(true),
+                generate_preview: None,
+                silent: Some(false),
+                await_promise: Some(await_promise),
+                include_command_line_api: Some(false),
+                user_gesture: Some(false),
+                object_group: None,
+                context_id: None,
+                throw_on_side_effect: None,
+                timeout: None,
+                disable_breaks: None,
+                repl_mode: None,
+                allow_unsafe_eval_blocked_by_csp: None,
+                unique_context_id: None,
+            })?
+            .result;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn extracts_extension_ids() {
+        let target = |url: &str| -> Target::TargetInfo {
+            serde_json::from_value(json!({
+                "targetId": "1",
+                "type": "service_worker",
+                "title": "",
+                "url": url,
+                "attached": false,
+                "canAccessOpener": false,
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            Some("abcdefghijklmnop"),
+            extension_id(&target("chrome-extension://abcdefghijklmnop/background.js"))
+        );
+        assert_eq!(None, extension_id(&target("https://example.com/sw.js")));
+        assert_eq!(None, extension_id(&target("chrome-extension:///")));
+    }
+}
@@ -10,9 +10,9 @@ use std::fs::File;
 #[cfg(not(target_os = "macos"))]
 use std::io::BufWriter;
 
+use crate::logging::*;
 use anyhow::{anyhow, Result};
 use directories::ProjectDirs;
-use log::*;
 use ureq;
 use walkdir::WalkDir;
 #[cfg(not(target_os = "macos"))]
@@ -0,0 +1,390 @@
+//! Counters for operating services built on this crate, see `Browser::metrics` and
+//! `BrowserPool::metrics`. Snapshots can be rendered in the Prometheus text format with
+//! `to_prometheus`, e.g. to serve them from a `/metrics` endpoint. With the `metrics` feature,
+//! the same counters are also recorded with the `metrics` crate's global recorder as they
+//! change.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Upper bounds of the call latency histogram's buckets, in seconds: Prometheus clients'
+/// default buckets.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// What a `Transport` counts as it makes calls.
+#[derive(Debug, Default)]
+pub(crate) struct CallCounters {
+    commands_sent: AtomicU64,
+    responses_received: AtomicU64,
+    error_responses: AtomicU64,
+    timeouts: AtomicU64,
+    events_received: AtomicU64,
+    events_dropped: AtomicU64,
+    /// How many calls took at most the matching `LATENCY_BUCKETS` bound, not cumulative.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    latency_micros_sum: AtomicU64,
+}
+
+impl CallCounters {
+    pub(crate) fn command_sent(&self) {
+        self.commands_sent.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_commands_sent_total").increment(1);
+    }
+
+    pub(crate) fn response_received(&self, latency: Duration, is_error: bool) {
+        self.responses_received.fetch_add(1, Ordering::Relaxed);
+        if is_error {
+            self.error_responses.fetch_add(1, Ordering::Relaxed);
+        }
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("headless_chrome_responses_received_total").increment(1);
+            if is_error {
+                metrics::counter!("headless_chrome_error_responses_total").increment(1);
+            }
+            metrics::histogram!("headless_chrome_call_duration_seconds")
+                .record(latency.as_secs_f64());
+        }
+        let seconds = latency.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        self.latency_micros_sum
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn timed_out(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_call_timeouts_total").increment(1);
+    }
+
+    pub(crate) fn event_received(&self) {
+        self.events_received.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_events_received_total").increment(1);
+    }
+
+    pub(crate) fn event_dropped(&self) {
+        self.events_dropped.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_events_dropped_total").increment(1);
+    }
+
+    pub(crate) fn snapshot(&self) -> TransportMetrics {
+        let mut cumulative = 0;
+        let buckets = LATENCY_BUCKETS
+            .iter()
+            .zip(&self.latency_buckets)
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound, cumulative)
+            })
+            .collect();
+        TransportMetrics {
+            commands_sent: self.commands_sent.load(Ordering::Relaxed),
+            responses_received: self.responses_received.load(Ordering::Relaxed),
+            error_responses: self.error_responses.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            events_received: self.events_received.load(Ordering::Relaxed),
+            events_dropped: self.events_dropped.load(Ordering::Relaxed),
+            call_latency: LatencyHistogram {
+                buckets,
+                count: self.responses_received.load(Ordering::Relaxed),
+                sum: Duration::from_micros(self.latency_micros_sum.load(Ordering::Relaxed)),
+            },
+        }
+    }
+}
+
+/// How many calls took how long, from sending them to getting their response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencyHistogram {
+    /// `(upper bound in seconds, how many calls took at most that long)`, cumulative like
+    /// Prometheus's buckets. Calls which took longer than the last bound only count towards
+    /// `count`.
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+/// A snapshot of the calls made over a browser's connection.
+///
+/// There's no count of reconnects, as a transport never reconnects: once its connection is lost,
+/// so is the browser's state (tabs, sessions, listeners), and the browser has to be relaunched.
+/// In a `BrowserPool` that shows up as `PoolMetrics::replacements`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransportMetrics {
+    /// Protocol commands sent, to the browser or any of its targets.
+    pub commands_sent: u64,
+    pub responses_received: u64,
+    /// Responses which were errors, e.g. for a node which no longer exists.
+    pub error_responses: u64,
+    /// Calls given up on after the browser didn't answer them in time.
+    pub timeouts: u64,
+    pub events_received: u64,
+    /// Events which didn't reach their listeners because of the transport's
+    /// `EventBackpressure`.
+    pub events_dropped: u64,
+    pub call_latency: LatencyHistogram,
+}
+
+/// A snapshot of a browser's activity, see `Browser::metrics`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BrowserMetrics {
+    pub transport: TransportMetrics,
+    pub open_tabs: usize,
+}
+
+impl BrowserMetrics {
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let transport = &self.transport;
+        let mut out = String::new();
+        counter(
+            &mut out,
+            "headless_chrome_commands_sent_total",
+            "Protocol commands sent.",
+            transport.commands_sent,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_responses_received_total",
+            "Responses received to protocol commands.",
+            transport.responses_received,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_error_responses_total",
+            "Protocol commands which failed.",
+            transport.error_responses,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_call_timeouts_total",
+            "Protocol commands which weren't answered in time.",
+            transport.timeouts,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_events_received_total",
+            "Protocol events received.",
+            transport.events_received,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_events_dropped_total",
+            "Protocol events dropped because their listener had too many waiting.",
+            transport.events_dropped,
+        );
+
+        let latency = &transport.call_latency;
+        let name = "headless_chrome_call_duration_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Time from sending protocol commands to receiving their responses."
+        );
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (bound, count) in &latency.buckets {
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", latency.count);
+        let _ = writeln!(out, "{name}_sum {}", latency.sum.as_secs_f64());
+        let _ = writeln!(out, "{name}_count {}", latency.count);
+
+        gauge(
+            &mut out,
+            "headless_chrome_open_tabs",
+            "Tabs open in the browser.",
+            self.open_tabs,
+        );
+        out
+    }
+}
+
+/// What a `BrowserPool` counts as browsers are checked out, launched and replaced.
+#[derive(Debug, Default)]
+pub(crate) struct PoolCounters {
+    checkouts: AtomicU64,
+    checkout_timeouts: AtomicU64,
+    launches: AtomicU64,
+    launch_failures: AtomicU64,
+    replacements: AtomicU64,
+    discards: AtomicU64,
+}
+
+impl PoolCounters {
+    pub(crate) fn checked_out(&self) {
+        self.checkouts.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_pool_checkouts_total").increment(1);
+    }
+
+    pub(crate) fn checkout_timed_out(&self) {
+        self.checkout_timeouts.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_pool_checkout_timeouts_total").increment(1);
+    }
+
+    pub(crate) fn launched(&self, succeeded: bool) {
+        if succeeded {
+            self.launches.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("headless_chrome_pool_launches_total").increment(1);
+        } else {
+            self.launch_failures.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            metrics::counter!("headless_chrome_pool_launch_failures_total").increment(1);
+        }
+    }
+
+    pub(crate) fn replaced(&self) {
+        self.replacements.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_pool_replacements_total").increment(1);
+    }
+
+    pub(crate) fn discarded(&self) {
+        self.discards.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("headless_chrome_pool_discards_total").increment(1);
+    }
+
+    pub(crate) fn snapshot(&self, running: usize, idle: usize) -> PoolMetrics {
+        PoolMetrics {
+            running,
+            idle,
+            checkouts: self.checkouts.load(Ordering::Relaxed),
+            checkout_timeouts: self.checkout_timeouts.load(Ordering::Relaxed),
+            launches: self.launches.load(Ordering::Relaxed),
+            launch_failures: self.launch_failures.load(Ordering::Relaxed),
+            replacements: self.replacements.load(Ordering::Relaxed),
+            discards: self.discards.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A snapshot of a `BrowserPool`'s activity, see `BrowserPool::metrics`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PoolMetrics {
+    /// Browsers running, whether idle or checked out.
+    pub running: usize,
+    pub idle: usize,
+    pub checkouts: u64,
+    pub checkout_timeouts: u64,
+    pub launches: u64,
+    pub launch_failures: u64,
+    /// Browsers closed and replaced for having crashed, failed their health check or reached
+    /// `PoolOptions::max_age`.
+    pub replacements: u64,
+    /// Browsers closed with `PooledBrowser::discard`.
+    pub discards: u64,
+}
+
+impl PoolMetrics {
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        gauge(
+            &mut out,
+            "headless_chrome_pool_running_browsers",
+            "Browsers running in the pool, whether idle or checked out.",
+            self.running,
+        );
+        gauge(
+            &mut out,
+            "headless_chrome_pool_idle_browsers",
+            "Browsers waiting in the pool to be checked out.",
+            self.idle,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_pool_checkouts_total",
+            "Browsers checked out of the pool.",
+            self.checkouts,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_pool_checkout_timeouts_total",
+            "Checkouts which timed out waiting for a browser.",
+            self.checkout_timeouts,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_pool_launches_total",
+            "Browsers launched by the pool.",
+            self.launches,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_pool_launch_failures_total",
+            "Browsers the pool failed to launch.",
+            self.launch_failures,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_pool_replacements_total",
+            "Browsers replaced for crashing, failing their health check or getting too old.",
+            self.replacements,
+        );
+        counter(
+            &mut out,
+            "headless_chrome_pool_discards_total",
+            "Browsers discarded by their users.",
+            self.discards,
+        );
+        out
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: usize) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    let _ = writeln!(out, "{name} {value}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_cumulative_latency_buckets() {
+        let counters = CallCounters::default();
+        counters.command_sent();
+        counters.command_sent();
+        counters.response_received(Duration::from_millis(3), false);
+        counters.response_received(Duration::from_millis(200), true);
+        counters.response_received(Duration::from_secs(30), false);
+
+        let metrics = counters.snapshot();
+        assert_eq!(2, metrics.commands_sent);
+        assert_eq!(1, metrics.error_responses);
+        assert_eq!((0.005, 1), metrics.call_latency.buckets[0]);
+        assert_eq!((0.25, 2), metrics.call_latency.buckets[5]);
+        assert_eq!((10.0, 2), metrics.call_latency.buckets[10]);
+
+        let text = BrowserMetrics {
+            transport: metrics,
+            open_tabs: 1,
+        }
+        .to_prometheus();
+        assert!(text.contains("headless_chrome_commands_sent_total 2\n"));
+        assert!(text.contains("headless_chrome_call_duration_seconds_bucket{le=\"0.25\"} 2\n"));
+        assert!(text.contains("headless_chrome_call_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(text.contains("headless_chrome_call_duration_seconds_sum 30.203\n"));
+        assert!(text.contains("headless_chrome_open_tabs 1\n"));
+    }
+}
@@ -1,23 +1,39 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
 
+use crate::logging::{debug, error, info, trace, warn};
 use anyhow::Result;
-use log::{debug, error, info, trace};
+use thiserror::Error;
 
 use process::Process;
-pub use process::{LaunchOptions, LaunchOptionsBuilder, DEFAULT_ARGS};
+pub use process::{
+    BrowserProduct, FontRenderHinting, HeadlessMode, LaunchOptions, LaunchOptionsBuilder,
+    LaunchProfile, DEFAULT_ARGS,
+};
+use tab::crash_recovery::CrashRecovery;
+use tab::Domain;
 pub use tab::Tab;
 pub use transport::ConnectionClosed;
-use transport::Transport;
+use transport::{
+    EventBackpressure, MethodDestination, ProtocolLogger, ProtocolRecorder, Transport,
+};
 use url::Url;
 use which::which;
 
-use crate::protocol::cdp::{types::Event, types::Method, Browser as B, Target, CSS, DOM};
+use crate::protocol::cdp::{
+    types::Event, types::Method, Browser as B, Fetch, Network, Security, SystemInfo, Target,
+};
 
 use crate::browser::context::Context;
+use crate::browser::extension::{ExtensionBackground, ExtensionNotFound};
+use crate::browser::metrics::BrowserMetrics;
+use crate::browser::scheduler::{NavigationMetrics, NavigationScheduler};
+use crate::browser::tab::middleware::MiddlewareChain;
 use crate::util;
 use Target::{CreateTarget, SetDiscoverTargets};
 use B::GetVersion;
@@ -26,10 +42,15 @@ pub use B::GetVersionReturnObject;
 #[cfg(feature = "fetch")]
 pub use fetcher::FetcherOptions;
 
+pub mod bidi;
 pub mod context;
+pub mod extension;
 #[cfg(feature = "fetch")]
 mod fetcher;
+pub mod metrics;
+pub mod pool;
 mod process;
+pub mod scheduler;
 pub mod tab;
 pub mod transport;
 
@@ -75,13 +96,132 @@ pub struct Browser {
     inner: Arc<BrowserInner>,
 }
 
+/// Something that crashed, as reported to `Browser.on_crash` callbacks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Crash {
+    /// The renderer process of a tab died, e.g. because the page ran out of memory.
+    Renderer {
+        target_id: String,
+        status: String,
+        error_code: u32,
+    },
+    /// The connection to the browser was lost without the `Browser` being dropped, e.g. because
+    /// its process died.
+    Browser,
+}
+
+pub type CrashHandler = dyn Fn(&Crash) + Send + Sync;
+
+/// The major version of the DevTools protocol this crate's bindings were generated from.
+const PROTOCOL_MAJOR_VERSION: &str = "1";
+
+#[derive(Debug, Error)]
+#[error("{product} speaks version {protocol_version} of the DevTools protocol, which isn't compatible with version {PROTOCOL_MAJOR_VERSION}.x")]
+pub struct IncompatibleProtocol {
+    pub product: String,
+    pub protocol_version: String,
+}
+
+/// Returned when the browser connected to turns out to be Firefox, which is detected from
+/// `Browser.getVersion` and rejected before anything else is sent. Firefox isn't supported over
+/// the DevTools protocol; use `Browser::connect_firefox` (with the `firefox` feature) or
+/// `Browser::connect_bidi` to drive it over WebDriver BiDi instead.
+#[derive(Debug, Error)]
+#[error("{product} isn't a Chromium-based browser, and can't be driven over the DevTools protocol")]
+pub struct UnsupportedBrowser {
+    pub product: String,
+}
+
+/// Returned by `Browser::attach_to_target` for targets that can't be driven as a `Tab`, such as
+/// workers (which are reached through their page with `Tab::workers`) or the browser itself.
+#[derive(Debug, Error)]
+#[error("target {target_id} is a {target_type}, not a page")]
+pub struct NotAPage {
+    pub target_id: String,
+    pub target_type: String,
+}
+
+/// The types of target that have a document, and so can be attached to as a `Tab`.
+const PAGE_LIKE_TARGET_TYPES: [&str; 4] = ["page", "iframe", "webview", "background_page"];
+
+impl IncompatibleProtocol {
+    /// Checks that the browser is one we can drive, i.e. is Chromium-based (unless `translated`
+    /// for it, see `Connection::translates_protocol`) and speaks the same major version of the
+    /// protocol.
+    fn check(version: &GetVersionReturnObject, translated: bool) -> Result<()> {
+        if !translated && version.product.starts_with("Firefox") {
+            Err(UnsupportedBrowser {
+                product: version.product.clone(),
+            }
+            .into())
+        } else if version.protocol_version.split('.').next() == Some(PROTOCOL_MAJOR_VERSION) {
+            Ok(())
+        } else {
+            Err(IncompatibleProtocol {
+                product: version.product.clone(),
+                protocol_version: version.protocol_version.clone(),
+            }
+            .into())
+        }
+    }
+}
+
+/// How to connect to an externally-launched Chrome process, see [`Browser::connect_with_options`].
+#[derive(Debug, Clone)]
+pub struct ConnectOptions {
+    /// How long to keep the WebSocket to the browser for after not receiving any events from it.
+    /// Defaults to 30 seconds.
+    pub idle_browser_timeout: Duration,
+
+    /// Have the browser load pages despite certificate errors, which is what
+    /// `LaunchOptions.ignore_certificate_errors` does for browsers we launch ourselves.
+    /// Defaults to false.
+    pub ignore_certificate_errors: bool,
+
+    /// How often to send a WebSocket Ping frame to the browser, to keep proxies between us and it
+    /// from closing the connection while it's idle. Defaults to None (no pings).
+    pub ping_interval: Option<Duration>,
+
+    /// Record all protocol traffic to this file, so that it can be played back by a
+    /// `ReplayServer`. Defaults to None (no recording).
+    pub record_protocol_to: Option<std::path::PathBuf>,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            idle_browser_timeout: Duration::from_secs(30),
+            ignore_certificate_errors: false,
+            ping_interval: None,
+            record_protocol_to: None,
+        }
+    }
+}
+
 pub struct BrowserInner {
     process: Option<Process>,
     transport: Arc<Transport>,
     tabs: Arc<Mutex<Vec<Arc<Tab>>>>,
+    context_middleware: Arc<Mutex<HashMap<String, Arc<MiddlewareChain>>>>,
+    navigation_scheduler: Arc<NavigationScheduler>,
+    crash_handlers: Arc<Mutex<Vec<Arc<CrashHandler>>>>,
+    restore_crashed_tabs: Arc<AtomicBool>,
+    stealth_mode: Arc<AtomicBool>,
+    eager_domains: Arc<Mutex<Vec<Domain>>>,
     loop_shutdown_tx: mpsc::SyncSender<()>,
 }
 
+/// The requests paused so that the proxy's authentication challenges get answered. Chrome keeps
+/// the credentials once the proxy accepts them, so only the first request of a tab (its
+/// navigation) needs to be paused, rather than every subresource too.
+fn proxy_auth_patterns() -> Vec<Fetch::RequestPattern> {
+    vec![Fetch::RequestPattern {
+        url_pattern: None,
+        resource_Type: Some(Network::ResourceType::Document),
+        request_stage: None,
+    }]
+}
+
 impl Browser {
     /// Launch a new Chrome browser.
     ///
@@ -89,16 +229,36 @@ impl Browser {
     /// The browser process will be killed when this struct is dropped.
     pub fn new(launch_options: LaunchOptions) -> Result<Self> {
         let idle_browser_timeout = launch_options.idle_browser_timeout;
-        let process = Process::new(launch_options)?;
+        let proxy_credentials = launch_options.proxy_credentials.clone();
+        let ping_interval = launch_options.ping_interval;
+        let recorder = launch_options
+            .record_protocol_to
+            .as_ref()
+            .map(ProtocolRecorder::to_file)
+            .transpose()?;
+        let mut process = Process::new(launch_options)?;
         let process_id = process.get_id();
 
-        let transport = Arc::new(Transport::new(
-            process.debug_ws_url.clone(),
-            Some(process_id),
-            idle_browser_timeout,
-        )?);
+        let transport = Arc::new(match (process.take_pipe(), &process.debug_ws_url) {
+            (Some((reader, writer)), _) => {
+                Transport::with_pipe(reader, writer, Some(process_id), idle_browser_timeout)?
+            }
+            (None, Some(debug_ws_url)) => Transport::with_ping_interval(
+                debug_ws_url.clone(),
+                Some(process_id),
+                idle_browser_timeout,
+                ping_interval,
+            )?,
+            (None, None) => unreachable!("Chrome launched with neither a port nor a pipe"),
+        });
+        transport.set_protocol_recorder(recorder);
 
-        Self::create_browser(Some(process), transport, idle_browser_timeout)
+        Self::create_browser(
+            Some(process),
+            transport,
+            idle_browser_timeout,
+            proxy_credentials,
+        )
     }
 
     /// Calls [`Browser::new`] with options to launch a headless browser using whatever Chrome / Chromium
@@ -122,19 +282,231 @@ impl Browser {
     pub fn connect_with_timeout(
         debug_ws_url: String,
         idle_browser_timeout: Duration,
+    ) -> Result<Self> {
+        Self::connect_with_options(
+            debug_ws_url,
+            ConnectOptions {
+                idle_browser_timeout,
+                ..ConnectOptions::default()
+            },
+        )
+    }
+
+    /// Allows you to drive an externally-launched Chrome process instead of launch one via [`Browser::new`].
+    ///
+    /// `debug_ws_url` may be a `ws://` or, when built with the `native-tls` or `rustls` feature, a
+    /// `wss://` URL (e.g. for a browser behind a TLS-terminating proxy).
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::ConnectOptions;
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::connect_with_options(
+    ///     "wss://chrome.example.com/devtools/browser/1a2b3c".to_string(),
+    ///     ConnectOptions {
+    ///         ignore_certificate_errors: true,
+    ///         ..ConnectOptions::default()
+    ///     },
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_with_options(debug_ws_url: String, options: ConnectOptions) -> Result<Self> {
+        let url = Url::parse(&debug_ws_url)?;
+
+        let transport = Transport::with_ping_interval(
+            url,
+            None,
+            options.idle_browser_timeout,
+            options.ping_interval,
+        )?;
+        Self::connect_transport(transport, &options)
+    }
+
+    /// Like [`Browser::connect_with_options`], but talks to the browser over an already
+    /// connected `stream` rather than connecting to `debug_ws_url`'s host itself, e.g. to go
+    /// out through a particular local interface, or through a tunnel whose local end the stream
+    /// is connected to.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use std::net::TcpStream;
+    ///
+    /// use headless_chrome::browser::ConnectOptions;
+    /// use headless_chrome::Browser;
+    ///
+    /// let stream = TcpStream::connect("10.0.0.5:9222")?;
+    /// let browser = Browser::connect_via(
+    ///     stream,
+    ///     "ws://10.0.0.5:9222/devtools/browser/1a2b3c".to_string(),
+    ///     ConnectOptions::default(),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_via(
+        stream: std::net::TcpStream,
+        debug_ws_url: String,
+        options: ConnectOptions,
+    ) -> Result<Self> {
+        let url = Url::parse(&debug_ws_url)?;
+
+        let transport = Transport::connect_via(
+            stream,
+            &url,
+            options.idle_browser_timeout,
+            options.ping_interval,
+        )?;
+        Self::connect_transport(transport, &options)
+    }
+
+    /// Like [`Browser::connect_via`], but over the Unix domain socket at `path`, e.g. one an SSH
+    /// tunnel forwards to the browser's debugging port:
+    /// `ssh -L /tmp/chrome.sock:localhost:9222 farm-host`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::ConnectOptions;
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::connect_via_unix_socket(
+    ///     "/tmp/chrome.sock",
+    ///     "ws://localhost:9222/devtools/browser/1a2b3c".to_string(),
+    ///     ConnectOptions::default(),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    pub fn connect_via_unix_socket(
+        path: impl AsRef<std::path::Path>,
+        debug_ws_url: String,
+        options: ConnectOptions,
     ) -> Result<Self> {
         let url = Url::parse(&debug_ws_url)?;
 
-        let transport = Arc::new(Transport::new(url, None, idle_browser_timeout)?);
+        let transport = Transport::connect_via_unix_socket(
+            path,
+            &url,
+            options.idle_browser_timeout,
+            options.ping_interval,
+        )?;
+        Self::connect_transport(transport, &options)
+    }
+
+    /// Connects to a browser whose debugging port is forwarded to `endpoint` (`host:port`), e.g.
+    /// a browser on a remote machine only listening on its localhost, reached through
+    /// `ssh -L 9222:localhost:9222 farm-host`. Unlike [`Browser::connect`], this doesn't need
+    /// the browser's WebSocket URL: it's looked up through the forwarded port.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::ConnectOptions;
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::connect_forwarded("127.0.0.1:9222", ConnectOptions::default())?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_forwarded(endpoint: &str, options: ConnectOptions) -> Result<Self> {
+        let url = transport::forwarded_ws_url(endpoint)?;
+        Self::connect_with_options(url.to_string(), options)
+    }
+
+    fn connect_transport(transport: Transport, options: &ConnectOptions) -> Result<Self> {
+        let transport = Arc::new(transport);
+        if let Some(path) = &options.record_protocol_to {
+            transport.set_protocol_recorder(Some(ProtocolRecorder::to_file(path)?));
+        }
         trace!("created transport");
 
-        Self::create_browser(None, transport, idle_browser_timeout)
+        let browser = Self::create_browser(None, transport, options.idle_browser_timeout, None)?;
+        if options.ignore_certificate_errors {
+            browser.set_ignore_certificate_errors(true)?;
+        }
+        Ok(browser)
+    }
+
+    /// Connects to a browser over [WebDriver BiDi](https://w3c.github.io/webdriver-bidi/)
+    /// rather than the DevTools protocol, e.g. Firefox, which doesn't speak the latter, and
+    /// starts a session. The returned session is a much thinner client than `Browser` and
+    /// `Tab`, see the `bidi` module. For endpoints handing out the URL of an existing session,
+    /// like chromedriver, use `BidiSession::connect` instead.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::Browser;
+    ///
+    /// // firefox --headless --remote-debugging-port=9222
+    /// let session = Browser::connect_bidi("ws://127.0.0.1:9222/session".to_string())?;
+    /// let context = &session.get_browsing_contexts()?[0];
+    /// session.navigate(context, "https://example.com")?;
+    /// let title = session.evaluate(context, "document.title")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_bidi(ws_url: String) -> Result<bidi::BidiSession> {
+        let session = bidi::BidiSession::connect(&ws_url, Duration::from_secs(30))?;
+        session.new_session()?;
+        Ok(session)
+    }
+
+    /// **Experimental**: connects to Firefox over WebDriver BiDi, translating the DevTools
+    /// protocol for it so that `Browser` and `Tab` can be used much like with Chrome, e.g. to run
+    /// the same smoke tests against both. Only part of the API works so far: opening, closing
+    /// and navigating tabs, `Tab::evaluate`, screenshots, PDFs, dialogs and the viewport size.
+    /// Anything else fails with a `RemoteError`; see `transport::FirefoxConnection::capability`.
+    ///
+    /// Needs the `firefox` feature.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::ConnectOptions;
+    /// use headless_chrome::Browser;
+    ///
+    /// // firefox --headless --remote-debugging-port=9222
+    /// let browser = Browser::connect_firefox(
+    ///     "ws://127.0.0.1:9222/session".to_string(),
+    ///     ConnectOptions::default(),
+    /// )?;
+    /// let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// let title = tab.evaluate("document.title", false)?.value;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "firefox")]
+    pub fn connect_firefox(ws_url: String, options: ConnectOptions) -> Result<Self> {
+        let url = Url::parse(&ws_url)?;
+
+        let transport = Transport::connect_firefox(&url, options.idle_browser_timeout)?;
+        Self::connect_transport(transport, &options)
     }
 
     fn create_browser(
         process: Option<Process>,
         transport: Arc<Transport>,
         idle_browser_timeout: Duration,
+        proxy_credentials: Option<(String, String)>,
     ) -> Result<Self> {
         let tabs = Arc::new(Mutex::new(vec![]));
 
@@ -144,6 +516,12 @@ impl Browser {
             inner: Arc::new(BrowserInner {
                 process,
                 tabs,
+                context_middleware: Arc::new(Mutex::new(HashMap::new())),
+                navigation_scheduler: Arc::new(NavigationScheduler::default()),
+                crash_handlers: Arc::new(Mutex::new(Vec::new())),
+                restore_crashed_tabs: Arc::new(AtomicBool::new(false)),
+                stealth_mode: Arc::new(AtomicBool::new(false)),
+                eager_domains: Arc::new(Mutex::new(Vec::new())),
                 transport,
                 loop_shutdown_tx: shutdown_tx,
             }),
@@ -156,17 +534,25 @@ impl Browser {
             browser.get_process_id(),
             shutdown_rx,
             idle_browser_timeout,
+            proxy_credentials,
         );
         trace!("created browser event listener");
 
+        let version = browser.get_version()?;
+        IncompatibleProtocol::check(&version, browser.inner.transport.translates_protocol())?;
+        debug!(
+            "Connected to {} (protocol {})",
+            version.product, version.protocol_version
+        );
+
         // so we get events like 'targetCreated' and 'targetDestroyed'
         trace!("Calling set discover");
         browser.call_method(SetDiscoverTargets { discover: true })?;
 
         let tab = browser.wait_for_initial_tab()?;
 
-        tab.call_method(DOM::Enable(None))?;
-        tab.call_method(CSS::Enable(None))?;
+        tab.acquire_domain(Domain::Dom, "css")?;
+        tab.acquire_domain(Domain::Css, "css")?;
 
         Ok(browser)
     }
@@ -175,6 +561,152 @@ impl Browser {
         self.inner.process.as_ref().map(process::Process::get_id)
     }
 
+    /// Caps how many tabs of this browser can be navigating at once, to keep Chrome stable under
+    /// load. `Tab.navigate_to` waits (up to the tab's default timeout) for a slot; slots are
+    /// handed out in the order they were asked for. `None`, the default, removes the cap.
+    pub fn set_max_concurrent_navigations(&self, limit: Option<usize>) {
+        self.inner.navigation_scheduler.set_limit(limit);
+    }
+
+    /// Starts logging every command, response and event exchanged with the browser (including
+    /// those of its tabs) with `logger`, or stops logging if it's `None`. See `ProtocolLogger`.
+    pub fn set_protocol_logger(&self, logger: Option<ProtocolLogger>) {
+        self.inner.transport.set_protocol_logger(logger);
+    }
+
+    /// Limits how many events may be waiting to be handled for each tab, and for the browser
+    /// itself, so that memory doesn't balloon when a page makes lots of requests faster than
+    /// they're handled. See `EventBackpressure`.
+    pub fn set_event_backpressure(&self, backpressure: EventBackpressure) {
+        self.inner.transport.set_event_backpressure(backpressure);
+    }
+
+    /// Closes the connection to the browser cleanly, letting the calls already made finish, see
+    /// `Transport::shutdown_gracefully`. A browser this launched is killed when it's dropped,
+    /// as usual.
+    pub fn shutdown_gracefully(&self, timeout: Duration) -> Result<()> {
+        // So that the connection closing isn't taken for the browser crashing
+        let _ = self.inner.loop_shutdown_tx.try_send(());
+        self.inner.transport.shutdown_gracefully(timeout)
+    }
+
+    /// How many navigations are running and queued, and how long they've had to wait.
+    pub fn navigation_metrics(&self) -> NavigationMetrics {
+        self.inner.navigation_scheduler.metrics()
+    }
+
+    /// Counts of the protocol calls made over the browser's connection (including its tabs'),
+    /// and how many tabs it has open. See `BrowserMetrics::to_prometheus` for exposing them.
+    pub fn metrics(&self) -> BrowserMetrics {
+        BrowserMetrics {
+            transport: self.inner.transport.metrics(),
+            open_tabs: self.inner.tabs.lock().unwrap().len(),
+        }
+    }
+
+    /// Calls `callback` whenever a tab's renderer process crashes, or the browser itself goes
+    /// away without having been dropped.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::Crash;
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::default()?;
+    /// browser.set_restore_crashed_tabs(true);
+    /// browser.on_crash(|crash| {
+    ///     if let Crash::Renderer { target_id, status, .. } = crash {
+    ///         eprintln!("tab {target_id} crashed ({status}), reloading it");
+    ///     }
+    /// });
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_crash<F>(&self, callback: F)
+    where
+        F: Fn(&Crash) + Send + Sync + 'static,
+    {
+        self.inner
+            .crash_handlers
+            .lock()
+            .unwrap()
+            .push(Arc::new(callback));
+    }
+
+    /// Whether tabs whose renderer crashed should be reloaded automatically, by setting the
+    /// crash recovery of every open tab and every tab created from now on to
+    /// `CrashRecovery::Reload` (or back to `CrashRecovery::Error`). Off by default. Tabs whose
+    /// crash recovery was set with `Tab::set_crash_recovery` keep it.
+    pub fn set_restore_crashed_tabs(&self, restore: bool) {
+        self.inner
+            .restore_crashed_tabs
+            .store(restore, Ordering::SeqCst);
+        let recovery = if restore {
+            CrashRecovery::Reload
+        } else {
+            CrashRecovery::Error
+        };
+        for tab in self.inner.tabs.lock().unwrap().iter() {
+            tab.set_default_crash_recovery(recovery);
+        }
+    }
+
+    /// Applies `Tab::enable_stealth_mode` to every open tab, and to every tab created from now on.
+    /// Off by default. Turning it off only affects tabs created afterwards.
+    pub fn set_stealth_mode(&self, enabled: bool) -> Result<()> {
+        self.inner.stealth_mode.store(enabled, Ordering::SeqCst);
+        if enabled {
+            let tabs: Vec<_> = self.inner.tabs.lock().unwrap().clone();
+            for tab in tabs {
+                tab.enable_stealth_mode()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Enables `domains` in every open tab, and in every tab as it's created from now on, rather
+    /// than when a feature first needs them. Useful to catch the events a domain reports from the
+    /// very start of a page's life. Domains which were enabled eagerly before but aren't in
+    /// `domains` are released, see `Tab::release_domain`.
+    pub fn set_eager_domains(&self, domains: &[Domain]) -> Result<()> {
+        let previous = std::mem::replace(
+            &mut *self.inner.eager_domains.lock().unwrap(),
+            domains.to_vec(),
+        );
+        let tabs: Vec<_> = self.inner.tabs.lock().unwrap().clone();
+        for tab in tabs {
+            for domain in previous.iter().filter(|domain| !domains.contains(domain)) {
+                tab.release_domain(*domain, "eager")?;
+            }
+            for domain in domains {
+                tab.acquire_domain(*domain, "eager")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns every process of the browser (the browser process itself, renderers, the GPU
+    /// process...) along with how much CPU time it has used so far. Sample it periodically to
+    /// monitor CPU usage; see `Tab.get_performance_metrics` for memory usage.
+    pub fn get_process_info(&self) -> Result<Vec<SystemInfo::ProcessInfo>> {
+        Ok(self
+            .call_method(SystemInfo::GetProcessInfo(None))?
+            .process_info)
+    }
+
+    /// The middleware shared by every tab in the given context, see `Context.add_middleware`.
+    pub(crate) fn get_context_middleware(&self, context_id: &str) -> Arc<MiddlewareChain> {
+        let mut context_middleware = self.inner.context_middleware.lock().unwrap();
+        Arc::clone(
+            context_middleware
+                .entry(context_id.to_string())
+                .or_default(),
+        )
+    }
+
     /// The tabs are behind an `Arc` and `Mutex` because they're accessible from multiple threads
     /// (including the one that handles incoming protocol events about new or changed tabs).
     pub fn get_tabs(&self) -> &Arc<Mutex<Vec<Arc<Tab>>>> {
@@ -223,20 +755,38 @@ impl Browser {
         self.new_tab_with_options(default_blank_tab)
     }
 
-    /// Create a new tab with a starting url, height / width, context ID and 'frame control'
-    /// ```rust
+    /// Opens `url` in a new tab without switching to it, leaving the current foreground tab
+    /// visible. Use `Tab::activate` or `Tab::bring_to_front` to switch to it later, e.g. before
+    /// taking screenshots which depend on the page being visible.
+    pub fn new_background_tab(&self, url: &str) -> Result<Arc<Tab>> {
+        self.new_tab_with_options(CreateTarget {
+            url: url.to_string(),
+            width: None,
+            height: None,
+            browser_context_id: None,
+            enable_begin_frame_control: None,
+            new_window: None,
+            background: Some(true),
+        })
+    }
+
+    /// Create a new tab with a starting url, height / width, context ID, 'frame control' and
+    /// whether it opens in the background
+    /// ```rust,no_run
     /// # use anyhow::Result;
     /// # fn main() -> Result<()> {
     /// #
-    /// # use headless_chrome::{Browser, protocol::target::methods::CreateTarget};
+    /// # use headless_chrome::{Browser, protocol::cdp::Target::CreateTarget};
     /// # let browser = Browser::default()?;
-    ///    let new_tab = browser.new_tab_with_options(CreateTarget {
-    ///    url: "chrome://version",
-    ///    width: Some(1024),
-    ///    height: Some(800),
-    ///    browser_context_id: None,
-    ///    enable_begin_frame_control: None,
-    ///    })?;
+    /// let new_tab = browser.new_tab_with_options(CreateTarget {
+    ///     url: "chrome://version".to_string(),
+    ///     width: Some(1024),
+    ///     height: Some(800),
+    ///     browser_context_id: None,
+    ///     enable_begin_frame_control: None,
+    ///     new_window: None,
+    ///     background: Some(true),
+    /// })?;
     /// #
     /// # Ok(())
     /// # }
@@ -258,14 +808,203 @@ impl Browser {
             .map_err(Into::into)
     }
 
+    /// Returns a handle to an existing target, e.g. a page the user already had open in a browser
+    /// we've connected to. Pages are picked up as they're discovered, so for those this is the
+    /// handle in `get_tabs`; other targets with a document (such as `webview`s) are attached to
+    /// on demand. Fails with `NotAPage` for anything else, such as workers.
+    pub fn attach_to_target(&self, target_id: &str) -> Result<Arc<Tab>> {
+        let target_info = self
+            .call_method(Target::GetTargetInfo {
+                target_id: Some(target_id.to_string()),
+            })?
+            .target_info;
+        if !PAGE_LIKE_TARGET_TYPES.contains(&target_info.Type.as_str()) {
+            return Err(NotAPage {
+                target_id: target_info.target_id,
+                target_type: target_info.Type,
+            }
+            .into());
+        }
+        let find_tab = || {
+            self.inner
+                .tabs
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|tab| *tab.get_target_id() == target_info.target_id)
+                .map(Arc::clone)
+        };
+
+        if target_info.Type == "page" {
+            return util::Wait::with_timeout(Duration::from_secs(20))
+                .until(find_tab)
+                .map_err(Into::into);
+        }
+        if let Some(tab) = find_tab() {
+            return Ok(tab);
+        }
+
+        let tab = Arc::new(Tab::new(target_info, Arc::clone(&self.inner.transport))?);
+        tab.set_navigation_scheduler(Arc::clone(&self.inner.navigation_scheduler));
+        self.inner.tabs.lock().unwrap().push(Arc::clone(&tab));
+        Ok(tab)
+    }
+
+    /// Attaches to the first page-like target (page, iframe, webview...) matching `predicate`, if
+    /// any. Other targets, such as workers, are never passed to `predicate`. See
+    /// `attach_to_target`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::connect("ws://127.0.0.1:9222/devtools/browser/...".to_string())?;
+    /// if let Some(tab) = browser.find_target(|info| info.url.contains("checkout"))? {
+    ///     tab.wait_for_element("button#pay")?.click()?;
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn find_target<F>(&self, predicate: F) -> Result<Option<Arc<Tab>>>
+    where
+        F: Fn(&Target::TargetInfo) -> bool,
+    {
+        let target_infos = self
+            .call_method(Target::GetTargets { filter: None })?
+            .target_infos;
+        target_infos
+            .iter()
+            .filter(|target_info| PAGE_LIKE_TARGET_TYPES.contains(&target_info.Type.as_str()))
+            .find(|target_info| predicate(target_info))
+            .map(|target_info| self.attach_to_target(&target_info.target_id))
+            .transpose()
+    }
+
+    /// The pages and workers of the extensions loaded in the browser (see
+    /// `LaunchOptionsBuilder::load_extension`), such as their service workers, background pages
+    /// and popups. `extension::extension_id` tells which extension each belongs to.
+    pub fn extension_targets(&self) -> Result<Vec<Target::TargetInfo>> {
+        Ok(self
+            .call_method(Target::GetTargets { filter: None })?
+            .target_infos
+            .into_iter()
+            .filter(|target_info| extension::extension_id(target_info).is_some())
+            .collect())
+    }
+
+    /// Attaches to the service worker (or background page) of the extension with the given id,
+    /// waiting up to 20 seconds for it to start.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::extension::extension_id;
+    /// use headless_chrome::{Browser, HeadlessMode, LaunchOptions};
+    ///
+    /// let browser = Browser::new(
+    ///     LaunchOptions::default_builder()
+    ///         .headless_mode(Some(HeadlessMode::New))
+    ///         .load_extension("extensions/proxy-switcher")
+    ///         .build()?,
+    /// )?;
+    /// let extension_id = browser
+    ///     .extension_targets()?
+    ///     .iter()
+    ///     .find_map(|target| extension_id(target).map(String::from))
+    ///     .expect("extension didn't load");
+    /// let background = browser.attach_to_extension(&extension_id)?;
+    /// background.evaluate("chrome.storage.local.set({ mode: 'direct' })", true)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn attach_to_extension(&self, extension_id: &str) -> Result<ExtensionBackground> {
+        let target_info = util::Wait::with_timeout(Duration::from_secs(20))
+            .until(|| {
+                self.extension_targets()
+                    .ok()?
+                    .into_iter()
+                    .find(|target_info| {
+                        extension::extension_id(target_info) == Some(extension_id)
+                            && matches!(
+                                target_info.Type.as_str(),
+                                "service_worker" | "background_page"
+                            )
+                    })
+            })
+            .map_err(|_| ExtensionNotFound {
+                extension_id: extension_id.to_string(),
+            })?;
+        ExtensionBackground::attach(target_info, Arc::clone(&self.inner.transport))
+    }
+
     /// Creates the equivalent of a new incognito window, AKA a browser context
     pub fn new_context(&self) -> Result<context::Context> {
+        self.create_context(None)
+    }
+
+    /// Creates a new browser context whose traffic goes through the given proxy, independently of
+    /// `--proxy-server` and of any other context.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::context::ContextProxy;
+    ///
+    /// let browser = Browser::default()?;
+    /// let context = browser.new_context_with_proxy(ContextProxy {
+    ///     server: "socks5://127.0.0.1:1080".to_string(),
+    ///     bypass_list: Some("localhost,*.internal".to_string()),
+    /// })?;
+    /// let tab = context.new_tab()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_context_with_proxy(
+        &self,
+        proxy: context::ContextProxy,
+    ) -> Result<context::Context<'_>> {
+        self.create_context(Some(proxy))
+    }
+
+    /// Creates a new browser context with the cookies and storage of `state`, which
+    /// `Context.storage_state` returned, e.g. to reuse a login from an earlier run. If the state
+    /// can't be restored, the context is disposed of again and the error returned.
+    pub fn new_context_with_state(
+        &self,
+        state: &context::StorageState,
+    ) -> Result<context::Context<'_>> {
+        let context = self.create_context(None)?;
+        if let Err(err) = context.restore_storage_state(state) {
+            // Rather than leaving a half-restored context behind
+            if let Err(dispose_err) = self.call_method(Target::DisposeBrowserContext {
+                browser_context_id: context.get_id().to_string(),
+            }) {
+                debug!("Couldn't dispose of the context whose state failed to restore: {dispose_err:?}");
+            }
+            return Err(err);
+        }
+        Ok(context)
+    }
+
+    fn create_context(&self, proxy: Option<context::ContextProxy>) -> Result<context::Context<'_>> {
         debug!("Creating new browser context");
+        let (proxy_server, proxy_bypass_list) = match proxy {
+            Some(proxy) => (Some(proxy.server), proxy.bypass_list),
+            None => (None, None),
+        };
         let context_id = self
             .call_method(Target::CreateBrowserContext {
                 dispose_on_detach: None,
-                proxy_server: None,
-                proxy_bypass_list: None,
+                proxy_server,
+                proxy_bypass_list,
                 origins_with_universal_network_access: None,
             })?
             .browser_context_id;
@@ -291,14 +1030,46 @@ impl Browser {
         self.call_method(GetVersion(None))
     }
 
+    /// Calls a browser-wide protocol method by name, like `Tab::call_raw` does for a tab's.
+    pub fn call_raw(
+        &self,
+        method_name: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.inner
+            .transport
+            .call_raw(method_name, params, MethodDestination::Browser)
+    }
+
+    /// Makes every tab of the browser load pages despite certificate errors (or stop doing so).
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Security#method-setIgnoreCertificateErrors>
+    pub fn set_ignore_certificate_errors(&self, ignore: bool) -> Result<()> {
+        self.call_method(Security::SetIgnoreCertificateErrors { ignore })?;
+        Ok(())
+    }
+
     fn handle_browser_level_events(
         &self,
-        events_rx: mpsc::Receiver<Event>,
+        events_rx: transport::EventReceiver,
         process_id: Option<u32>,
         shutdown_rx: mpsc::Receiver<()>,
         idle_browser_timeout: Duration,
+        proxy_credentials: Option<(String, String)>,
     ) {
         let tabs = Arc::clone(&self.inner.tabs);
+        let context_middleware = Arc::clone(&self.inner.context_middleware);
+        let navigation_scheduler = Arc::clone(&self.inner.navigation_scheduler);
+        let crash_handlers = Arc::clone(&self.inner.crash_handlers);
+        let restore_crashed_tabs = Arc::clone(&self.inner.restore_crashed_tabs);
+        let stealth_mode = Arc::clone(&self.inner.stealth_mode);
+        let eager_domains = Arc::clone(&self.inner.eager_domains);
+        let report_crash = move |crash: Crash| {
+            let handlers = crash_handlers.lock().unwrap().clone();
+            for handler in handlers {
+                handler(&crash);
+            }
+        };
         let transport = Arc::clone(&self.inner.transport);
 
         std::thread::spawn(move || {
@@ -326,6 +1097,10 @@ impl Browser {
                                     "Browser event sender disconnected while loop was waiting (Chrome #{:?})",
                                     process_id
                                 );
+                                // Unless the browser is being dropped, it went away on its own
+                                if shutdown_rx.try_recv().is_err() {
+                                    report_crash(Crash::Browser);
+                                }
                             }
                         }
                         break;
@@ -334,11 +1109,78 @@ impl Browser {
                         match event {
                             Event::TargetCreated(ev) => {
                                 let target_info = ev.params.target_info;
-                                trace!("Creating target: {:?}", target_info);
+                                trace!("Creating target: {target_info:?}");
                                 if target_info.Type == "page" {
+                                    let middleware = target_info
+                                        .browser_context_id
+                                        .as_ref()
+                                        .and_then(|context_id| {
+                                            context_middleware
+                                                .lock()
+                                                .unwrap()
+                                                .get(context_id)
+                                                .cloned()
+                                        });
                                     match Tab::new(target_info, Arc::clone(&transport)) {
                                         Ok(new_tab) => {
-                                            tabs.lock().unwrap().push(Arc::new(new_tab));
+                                            new_tab.set_navigation_scheduler(Arc::clone(
+                                                &navigation_scheduler,
+                                            ));
+                                            if let Some((username, password)) = &proxy_credentials {
+                                                if let Err(err) = new_tab
+                                                    .authenticate(
+                                                        Some(username.clone()),
+                                                        Some(password.clone()),
+                                                    )
+                                                    .and_then(|tab| {
+                                                        tab.acquire_fetch(
+                                                            "proxy_auth",
+                                                            Some(&proxy_auth_patterns()),
+                                                            true,
+                                                        )
+                                                    })
+                                                {
+                                                    warn!("Failed to set up proxy authentication for new tab: {err:?}");
+                                                }
+                                            }
+                                            if restore_crashed_tabs.load(Ordering::SeqCst) {
+                                                new_tab.set_default_crash_recovery(
+                                                    CrashRecovery::Reload,
+                                                );
+                                            }
+                                            if stealth_mode.load(Ordering::SeqCst) {
+                                                if let Err(err) = new_tab.enable_stealth_mode() {
+                                                    warn!("Failed to enable stealth mode for new tab: {err:?}");
+                                                }
+                                            }
+                                            let domains = eager_domains.lock().unwrap().clone();
+                                            for domain in domains {
+                                                if let Err(err) =
+                                                    new_tab.acquire_domain(domain, "eager")
+                                                {
+                                                    warn!("Failed to enable {domain:?} for new tab: {err:?}");
+                                                }
+                                            }
+                                            if let Some(middleware) = middleware {
+                                                if let Err(err) =
+                                                    new_tab.set_context_middleware(middleware)
+                                                {
+                                                    warn!("Failed to set up context middleware for new tab: {err:?}");
+                                                }
+                                            }
+                                            let new_tab = Arc::new(new_tab);
+                                            let mut locked_tabs = tabs.lock().unwrap();
+                                            if let Some(opener_id) =
+                                                &new_tab.target_info().opener_id
+                                            {
+                                                if let Some(opener) = locked_tabs
+                                                    .iter()
+                                                    .find(|tab| tab.get_target_id() == opener_id)
+                                                {
+                                                    opener.add_popup(&new_tab);
+                                                }
+                                            }
+                                            locked_tabs.push(new_tab);
                                         }
                                         Err(_tab_creation_err) => {
                                             info!("Failed to create a handle to new tab");
@@ -349,15 +1191,61 @@ impl Browser {
                             }
                             Event::TargetInfoChanged(ev) => {
                                 let target_info = ev.params.target_info;
-                                trace!("Target info changed: {:?}", target_info);
+                                trace!("Target info changed: {target_info:?}");
                                 if target_info.Type == "page" {
                                     let locked_tabs = tabs.lock().unwrap();
-                                    let updated_tab = locked_tabs
+                                    // The tab may not have been added yet, or already been closed
+                                    if let Some(updated_tab) = locked_tabs
                                         .iter()
                                         .find(|tab| *tab.get_target_id() == target_info.target_id)
-                                        .expect("got TargetInfoChanged event about a tab not in our list");
-                                    updated_tab.update_target_info(target_info);
+                                    {
+                                        updated_tab.update_target_info(target_info);
+                                    } else {
+                                        trace!(
+                                            "Got TargetInfoChanged event about a tab not in our list"
+                                        );
+                                    }
+                                }
+                            }
+                            Event::TargetCrashed(ev) => {
+                                let params = ev.params;
+                                warn!(
+                                    "Target {} crashed: {} ({})",
+                                    params.target_id, params.status, params.error_code
+                                );
+                                let crashed_tab = tabs
+                                    .lock()
+                                    .unwrap()
+                                    .iter()
+                                    .find(|tab| *tab.get_target_id() == params.target_id)
+                                    .cloned();
+                                if let Some(tab) = crashed_tab {
+                                    // In case the tab's own event hasn't arrived yet
+                                    tab.mark_crashed();
+                                    let tabs = Arc::clone(&tabs);
+                                    let transport = Arc::clone(&transport);
+                                    // Recovering waits for Chrome's replies, which this loop would
+                                    // otherwise hold up
+                                    std::thread::spawn(move || {
+                                        let recovered = match tab.crash_recovery() {
+                                            CrashRecovery::Error => Ok(()),
+                                            CrashRecovery::Reload => {
+                                                tab.recover_from_crash().map(|_| ())
+                                            }
+                                            CrashRecovery::Recreate => {
+                                                recreate_crashed_tab(&tab, &transport, &tabs)
+                                            }
+                                        };
+                                        if let Err(err) = recovered {
+                                            warn!("Failed to restore crashed tab: {err:?}");
+                                        }
+                                    });
                                 }
+                                report_crash(Crash::Renderer {
+                                    target_id: params.target_id,
+                                    status: params.status,
+                                    error_code: params.error_code,
+                                });
                             }
                             Event::TargetDestroyed(ev) => {
                                 trace!("Target destroyed: {:?}", ev.params.target_id);
@@ -367,7 +1255,7 @@ impl Browser {
                                     .position(|tab| *tab.get_target_id() == ev.params.target_id);
 
                                 if let Some(idx) = pos {
-                                    locked_tabs.remove(idx);
+                                    locked_tabs.remove(idx).mark_destroyed();
                                 }
                             }
                             _ => {
@@ -403,6 +1291,42 @@ impl Browser {
     }
 }
 
+/// Opens a new tab in the place of `crashed`, set up like it and navigated to where it was, then
+/// closes `crashed`. See `CrashRecovery::Recreate`.
+fn recreate_crashed_tab(
+    crashed: &Arc<Tab>,
+    transport: &Transport,
+    tabs: &Mutex<Vec<Arc<Tab>>>,
+) -> Result<()> {
+    let target_info = crashed.target_info();
+    let target_id = transport
+        .call_method_on_browser(CreateTarget {
+            url: "about:blank".to_string(),
+            width: None,
+            height: None,
+            browser_context_id: target_info.browser_context_id,
+            enable_begin_frame_control: None,
+            new_window: None,
+            background: None,
+        })?
+        .target_id;
+    let replacement = util::Wait::with_timeout(Duration::from_secs(20)).until(|| {
+        tabs.lock()
+            .unwrap()
+            .iter()
+            .find(|tab| *tab.get_target_id() == target_id)
+            .cloned()
+    })?;
+
+    replacement.take_over_from(crashed)?;
+    crashed.set_replaced_by(&replacement);
+    if let Err(err) = crashed.close_target() {
+        warn!("Failed to close crashed tab: {err:?}");
+    }
+    replacement.navigate_to(&crashed.committed_url())?;
+    Ok(())
+}
+
 /// [`Browser`] is being dropped!
 /// Dropping the inner browser means that there are no more references in the `Arc` inside [`Browser`].
 impl Drop for BrowserInner {
@@ -490,7 +1414,7 @@ pub fn default_executable() -> Result<std::path::PathBuf, String> {
 
 #[cfg(test)]
 mod test {
-    use super::Browser;
+    use super::{Browser, GetVersionReturnObject, IncompatibleProtocol, UnsupportedBrowser};
 
     fn is_sync<T>()
     where
@@ -502,4 +1426,27 @@ mod test {
     fn test_if_browser_is_sync() {
         is_sync::<Browser>();
     }
+
+    #[test]
+    fn checks_protocol_major_version() {
+        let version = |protocol_version: &str| GetVersionReturnObject {
+            protocol_version: protocol_version.to_string(),
+            product: "Edg/120.0.2210.91".to_string(),
+            revision: String::new(),
+            user_agent: String::new(),
+            js_version: String::new(),
+        };
+        assert!(IncompatibleProtocol::check(&version("1.3"), false).is_ok());
+        assert!(IncompatibleProtocol::check(&version("1.2"), false).is_ok());
+        let error = IncompatibleProtocol::check(&version("2.0"), false).unwrap_err();
+        assert!(error.is::<IncompatibleProtocol>());
+
+        let firefox = GetVersionReturnObject {
+            product: "Firefox/128.0".to_string(),
+            ..version("1.3")
+        };
+        let error = IncompatibleProtocol::check(&firefox, false).unwrap_err();
+        assert!(error.is::<UnsupportedBrowser>());
+        assert!(IncompatibleProtocol::check(&firefox, true).is_ok());
+    }
 }
@@ -0,0 +1,360 @@
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logging::{debug, warn};
+use anyhow::Result;
+use thiserror::Error;
+
+use super::metrics::{PoolCounters, PoolMetrics};
+use super::Browser;
+
+/// Options for a `BrowserPool`.
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    /// How many browsers the pool runs at most. Browsers are launched lazily, as they're needed.
+    pub max_browsers: usize,
+    /// Browsers which have been running for longer than this are closed and replaced the next
+    /// time they'd be checked out, which keeps memory leaks in long-lived pages in check.
+    pub max_age: Option<Duration>,
+    /// How long `checkout` waits for a browser to become available before giving up.
+    pub checkout_timeout: Duration,
+    /// Caps how many tabs of each browser can be navigating at once, see
+    /// `Browser.set_max_concurrent_navigations`.
+    pub max_concurrent_navigations: Option<usize>,
+    /// How many jobs queued with `spawn` can be waiting for a browser before `spawn` blocks.
+    pub max_queued_jobs: usize,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_browsers: 4,
+            max_age: Some(Duration::from_mins(30)),
+            checkout_timeout: Duration::from_mins(1),
+            max_concurrent_navigations: None,
+            max_queued_jobs: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Timed out waiting for a browser to become available in the pool")]
+pub struct PoolCheckoutTimeout {}
+
+type Launcher = dyn Fn() -> Result<Browser> + Send + Sync;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct PooledEntry {
+    browser: Browser,
+    launched_at: Instant,
+    /// Set if anything in the browser crashed, so that it gets replaced.
+    crashed: Arc<AtomicBool>,
+}
+
+struct PoolState {
+    idle: Vec<PooledEntry>,
+    /// How many browsers are running, whether idle or checked out.
+    running: usize,
+}
+
+struct PoolInner {
+    options: PoolOptions,
+    launcher: Box<Launcher>,
+    state: Mutex<PoolState>,
+    available: Condvar,
+    counters: PoolCounters,
+    /// Feeds the worker threads running the jobs queued with `spawn`, once there are any.
+    job_queue: OnceLock<SyncSender<Job>>,
+}
+
+/// A pool of browsers, for services which render lots of pages concurrently.
+///
+/// Browsers are checked out with `checkout` and automatically checked back in when the returned
+/// `PooledBrowser` is dropped. Before a browser is handed out it's health-checked, and replaced
+/// if it doesn't respond, has crashed or has reached `PoolOptions::max_age`. For fire-and-forget work, `spawn`
+/// queues a job for the pool's worker threads (one per browser) to run as soon as a browser is
+/// free.
+///
+/// The pool is cheap to clone; clones share the same browsers.
+///
+/// ```rust,no_run
+/// # use anyhow::Result;
+/// # fn main() -> Result<()> {
+/// #
+/// use headless_chrome::browser::pool::{BrowserPool, PoolOptions};
+/// use headless_chrome::{Browser, LaunchOptions};
+///
+/// let pool = BrowserPool::new(PoolOptions::default(), || {
+///     Browser::new(LaunchOptions::default_builder().build()?)
+/// });
+///
+/// let jobs: Vec<_> = ["https://example.com", "https://example.org"]
+///     .into_iter()
+///     .map(|url| {
+///         pool.spawn(move |browser| {
+///             let tab = browser.new_tab()?;
+///             let title = tab.navigate_to(url)?.wait_until_navigated()?.get_title()?;
+///             tab.close(false)?;
+///             Ok(title)
+///         })
+///     })
+///     .collect();
+/// for job in jobs {
+///     println!("{}", job.join().unwrap()?);
+/// }
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BrowserPool {
+    inner: Arc<PoolInner>,
+}
+
+impl BrowserPool {
+    /// Creates a pool which launches browsers by calling `launcher`, e.g. with `Browser::new` or
+    /// `Browser::connect`.
+    pub fn new<F>(options: PoolOptions, launcher: F) -> Self
+    where
+        F: Fn() -> Result<Browser> + Send + Sync + 'static,
+    {
+        Self {
+            inner: Arc::new(PoolInner {
+                options,
+                launcher: Box::new(launcher),
+                state: Mutex::new(PoolState {
+                    idle: Vec::new(),
+                    running: 0,
+                }),
+                available: Condvar::new(),
+                counters: PoolCounters::default(),
+                job_queue: OnceLock::new(),
+            }),
+        }
+    }
+
+    /// Takes a healthy browser out of the pool, launching one if none is idle and the pool isn't
+    /// full yet, otherwise waiting for one to be checked back in.
+    pub fn checkout(&self) -> Result<PooledBrowser> {
+        self.checkout_until(Some(Instant::now() + self.inner.options.checkout_timeout))
+    }
+
+    /// Like `checkout`, but waits until `deadline`, or for as long as it takes if there's none.
+    fn checkout_until(&self, deadline: Option<Instant>) -> Result<PooledBrowser> {
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if let Some(entry) = state.idle.pop() {
+                drop(state);
+                if self.is_usable(&entry) {
+                    self.inner.counters.checked_out();
+                    return Ok(self.wrap(entry));
+                }
+                debug!("Replacing pooled browser");
+                self.inner.counters.replaced();
+                drop(entry);
+                state = self.inner.state.lock().unwrap();
+                state.running -= 1;
+                continue;
+            }
+
+            if state.running < self.inner.options.max_browsers {
+                state.running += 1;
+                drop(state);
+                let launched = (self.inner.launcher)();
+                self.inner.counters.launched(launched.is_ok());
+                return match launched {
+                    Ok(browser) => {
+                        self.inner.counters.checked_out();
+                        browser.set_max_concurrent_navigations(
+                            self.inner.options.max_concurrent_navigations,
+                        );
+                        let crashed = Arc::new(AtomicBool::new(false));
+                        let crashed_clone = Arc::clone(&crashed);
+                        browser.on_crash(move |_| crashed_clone.store(true, Ordering::SeqCst));
+                        Ok(self.wrap(PooledEntry {
+                            browser,
+                            launched_at: Instant::now(),
+                            crashed,
+                        }))
+                    }
+                    Err(err) => {
+                        self.inner.state.lock().unwrap().running -= 1;
+                        self.inner.available.notify_one();
+                        Err(err)
+                    }
+                };
+            }
+
+            let Some(deadline) = deadline else {
+                state = self.inner.available.wait(state).unwrap();
+                continue;
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                self.inner.counters.checkout_timed_out();
+                return Err(PoolCheckoutTimeout {}.into());
+            }
+            state = self
+                .inner
+                .available
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Checks out a browser, runs `job` with it and checks it back in.
+    pub fn run<F, T>(&self, job: F) -> Result<T>
+    where
+        F: FnOnce(&Browser) -> Result<T>,
+    {
+        let browser = self.checkout()?;
+        job(&browser)
+    }
+
+    /// Queues `job` to run on one of the pool's worker threads as soon as a browser is available.
+    /// Queued jobs wait for a browser for as long as it takes, rather than for
+    /// `PoolOptions::checkout_timeout`. Blocks while `PoolOptions::max_queued_jobs` jobs are
+    /// already waiting.
+    pub fn spawn<F, T>(&self, job: F) -> SpawnedJob<T>
+    where
+        F: FnOnce(&Browser) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_tx, result_rx) = mpsc::channel();
+        let pool = self.clone();
+        let job: Job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let browser = pool.checkout_until(None)?;
+                job(&browser)
+            }));
+            // The job's handle may have been dropped
+            let _ = result_tx.send(result);
+        });
+        // The workers only stop once the pool (and so the queue's sender) is gone
+        self.job_queue()
+            .send(job)
+            .expect("pool workers stopped while the pool was alive");
+        SpawnedJob { result: result_rx }
+    }
+
+    /// The queue of jobs to `spawn`, starting a worker thread per browser the first time.
+    fn job_queue(&self) -> &SyncSender<Job> {
+        self.inner.job_queue.get_or_init(|| {
+            let (job_tx, job_rx) = mpsc::sync_channel::<Job>(self.inner.options.max_queued_jobs);
+            let job_rx = Arc::new(Mutex::new(job_rx));
+            for _ in 0..self.inner.options.max_browsers.max(1) {
+                let job_rx = Arc::clone(&job_rx);
+                thread::spawn(move || loop {
+                    let job = job_rx.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                });
+            }
+            job_tx
+        })
+    }
+
+    /// How many browsers are running, and how many of those are idle.
+    pub fn size(&self) -> (usize, usize) {
+        let state = self.inner.state.lock().unwrap();
+        (state.running, state.idle.len())
+    }
+
+    /// Counts of the browsers checked out, launched and replaced so far, and how many are running.
+    /// See `PoolMetrics::to_prometheus` for exposing them.
+    pub fn metrics(&self) -> PoolMetrics {
+        let (running, idle) = self.size();
+        self.inner.counters.snapshot(running, idle)
+    }
+
+    fn is_usable(&self, entry: &PooledEntry) -> bool {
+        if entry.crashed.load(Ordering::SeqCst) {
+            return false;
+        }
+        if let Some(max_age) = self.inner.options.max_age {
+            if entry.launched_at.elapsed() > max_age {
+                return false;
+            }
+        }
+        match entry.browser.get_version() {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("Pooled browser failed its health check: {err:?}");
+                false
+            }
+        }
+    }
+
+    fn wrap(&self, entry: PooledEntry) -> PooledBrowser {
+        PooledBrowser {
+            entry: Some(entry),
+            pool: self.clone(),
+        }
+    }
+
+    fn checkin(&self, entry: PooledEntry) {
+        self.inner.state.lock().unwrap().idle.push(entry);
+        self.inner.available.notify_one();
+    }
+
+    fn forget(&self) {
+        self.inner.state.lock().unwrap().running -= 1;
+        self.inner.available.notify_one();
+    }
+}
+
+/// A job queued with `BrowserPool::spawn`.
+pub struct SpawnedJob<T> {
+    result: Receiver<thread::Result<Result<T>>>,
+}
+
+impl<T> SpawnedJob<T> {
+    /// Waits for the job to finish, returning its result, or `Err` with the panic's payload if
+    /// it panicked (like `JoinHandle::join`).
+    pub fn join(self) -> thread::Result<Result<T>> {
+        self.result
+            .recv()
+            .unwrap_or_else(|_| Err(Box::new("The pool's worker stopped before running the job")))
+    }
+}
+
+/// A browser checked out of a `BrowserPool`. It's checked back in when dropped.
+pub struct PooledBrowser {
+    entry: Option<PooledEntry>,
+    pool: BrowserPool,
+}
+
+impl PooledBrowser {
+    /// Closes the browser instead of returning it to the pool, e.g. because it got into a bad
+    /// state. The pool launches a replacement when it's next needed.
+    pub fn discard(mut self) {
+        self.entry.take();
+        self.pool.inner.counters.discarded();
+        self.pool.forget();
+    }
+}
+
+impl Deref for PooledBrowser {
+    type Target = Browser;
+
+    fn deref(&self) -> &Browser {
+        &self.entry.as_ref().unwrap().browser
+    }
+}
+
+impl Drop for PooledBrowser {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            self.pool.checkin(entry);
+        }
+    }
+}
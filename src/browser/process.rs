@@ -10,8 +10,8 @@ use std::{
 #[cfg(test)]
 use std::cell::RefCell;
 
+use crate::logging::*;
 use anyhow::{anyhow, Result};
-use log::*;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use regex::Regex;
@@ -39,7 +39,10 @@ impl ForTesting {
 
 pub struct Process {
     child_process: TemporaryProcess,
-    pub debug_ws_url: Url,
+    /// None when talking to the browser over a pipe, see `LaunchOptions.remote_debugging_pipe`.
+    pub debug_ws_url: Option<Url>,
+    /// Our ends of the pipes connected to the browser's fds 4 and 3, until taken.
+    pipe: Option<(std::io::PipeReader, std::io::PipeWriter)>,
 }
 
 #[derive(Debug, Error)]
@@ -50,6 +53,9 @@ enum ChromeLaunchError {
     NoAvailablePorts,
     #[error("The chosen debugging port is already in use")]
     DebugPortInUse,
+    #[cfg(not(unix))]
+    #[error("Talking to Chrome over a pipe isn't supported on this platform")]
+    PipeUnsupported,
 }
 
 #[cfg(windows)]
@@ -75,6 +81,208 @@ impl Drop for TemporaryProcess {
     }
 }
 
+/// Which of Chrome's headless modes to run it in. Rendering (and PDF output) differs slightly
+/// between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadlessMode {
+    /// The headless mode sharing its code with regular Chrome (`--headless=new`).
+    New,
+    /// The original, separate headless implementation (`--headless=old`).
+    Old,
+    /// A regular, visible browser window.
+    Headful,
+}
+
+/// How strongly Chrome snaps glyph outlines to the pixel grid. Leaving it up to Chrome means
+/// using the system's font configuration, which makes text render differently across machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontRenderHinting {
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+impl FontRenderHinting {
+    fn as_str(self) -> &'static str {
+        match self {
+            FontRenderHinting::None => "none",
+            FontRenderHinting::Slight => "slight",
+            FontRenderHinting::Medium => "medium",
+            FontRenderHinting::Full => "full",
+        }
+    }
+}
+
+/// A curated set of flags tuning the browser for a kind of work, see `LaunchOptions::profiles`.
+/// They're passed after `DEFAULT_ARGS` and before `args`, so that flags given in `args` (and
+/// options such as `font_render_hinting`) take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchProfile {
+    /// Loads pages as cheaply as possible, for scraping their content: no images, web fonts,
+    /// audio, notifications or software rasterization. Pages relying on images loading (e.g.
+    /// for lazy loading) may behave differently.
+    FastScraping,
+    /// Renders the same way on every machine and every run, for pixel comparisons: no
+    /// LCD/subpixel text, a fixed device scale factor, hidden scrollbars and rasterization done
+    /// in full before each frame is drawn.
+    AccurateRendering,
+    /// Keeps the browser from leaving traces or phoning home: an incognito window, no pings,
+    /// component updates or domain reliability reports, and WebRTC kept from leaking local IPs.
+    Privacy,
+}
+
+impl LaunchProfile {
+    pub fn args(self) -> &'static [&'static str] {
+        match self {
+            LaunchProfile::FastScraping => &[
+                "--blink-settings=imagesEnabled=false",
+                "--disable-remote-fonts",
+                "--mute-audio",
+                "--disable-notifications",
+                "--disable-software-rasterizer",
+                "--disable-print-preview",
+                "--disable-speech-api",
+                "--disable-translate",
+                "--no-pings",
+            ],
+            LaunchProfile::AccurateRendering => &[
+                "--font-render-hinting=none",
+                "--disable-lcd-text",
+                "--disable-font-subpixel-positioning",
+                "--force-device-scale-factor=1",
+                "--hide-scrollbars",
+                "--disable-partial-raster",
+                "--disable-skia-runtime-opts",
+                "--run-all-compositor-stages-before-draw",
+                "--disable-threaded-animation",
+                "--disable-threaded-scrolling",
+                "--disable-checker-imaging",
+                "--disable-image-animation-resync",
+            ],
+            LaunchProfile::Privacy => &[
+                "--incognito",
+                "--no-pings",
+                "--disable-domain-reliability",
+                "--disable-component-update",
+                "--no-service-autorun",
+                "--webrtc-ip-handling-policy=disable_non_proxied_udp",
+                "--force-webrtc-ip-handling-policy",
+            ],
+        }
+    }
+}
+
+/// Which Chromium-based browser to launch. They all speak the DevTools protocol, but each
+/// needs a few flags of its own to run unattended, and they're installed under different names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowserProduct {
+    Chrome,
+    Chromium,
+    /// Microsoft Edge.
+    Edge,
+    Brave,
+}
+
+impl BrowserProduct {
+    /// Flags passed on top of `DEFAULT_ARGS` (unless `disable_default_args` is set), turning
+    /// off the product's own first-run prompts and background services.
+    pub fn default_args(self) -> &'static [&'static str] {
+        match self {
+            BrowserProduct::Chrome | BrowserProduct::Chromium => &[],
+            BrowserProduct::Edge => &["--no-default-browser-check"],
+            BrowserProduct::Brave => &["--no-default-browser-check", "--disable-brave-update"],
+        }
+    }
+
+    /// Features added to the `--disable-features` switch of `DEFAULT_ARGS`. They can't go in a
+    /// switch of their own, as the browser only honours the last one it's given.
+    pub fn disabled_features(self) -> &'static [&'static str] {
+        match self {
+            BrowserProduct::Edge => &[
+                "msEdgeSidebarV2",
+                "msImplicitSignin",
+                "msSmartScreenProtection",
+            ],
+            BrowserProduct::Chrome | BrowserProduct::Chromium | BrowserProduct::Brave => &[],
+        }
+    }
+
+    /// The names the product's binary goes by on the `PATH`.
+    fn executable_names(self) -> &'static [&'static str] {
+        match self {
+            BrowserProduct::Chrome => &[
+                "google-chrome-stable",
+                "google-chrome-beta",
+                "google-chrome-dev",
+                "google-chrome-unstable",
+                "google-chrome",
+                "chrome",
+            ],
+            BrowserProduct::Chromium => &["chromium", "chromium-browser"],
+            BrowserProduct::Edge => &[
+                "microsoft-edge-stable",
+                "microsoft-edge-beta",
+                "microsoft-edge-dev",
+                "microsoft-edge",
+                "msedge",
+            ],
+            BrowserProduct::Brave => &["brave-browser", "brave-browser-stable", "brave"],
+        }
+    }
+
+    /// Where the product is installed when it isn't on the `PATH`.
+    fn install_paths(self) -> &'static [&'static str] {
+        #[cfg(target_os = "macos")]
+        return match self {
+            BrowserProduct::Chrome => {
+                &["/Applications/Google Chrome.app/Contents/MacOS/Google Chrome"]
+            }
+            BrowserProduct::Chromium => &["/Applications/Chromium.app/Contents/MacOS/Chromium"],
+            BrowserProduct::Edge => {
+                &["/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge"]
+            }
+            BrowserProduct::Brave => {
+                &["/Applications/Brave Browser.app/Contents/MacOS/Brave Browser"]
+            }
+        };
+        #[cfg(windows)]
+        return match self {
+            BrowserProduct::Chrome => &[r"C:\Program Files\Google\Chrome\Application\chrome.exe"],
+            BrowserProduct::Chromium => &[],
+            BrowserProduct::Edge => {
+                &[r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe"]
+            }
+            BrowserProduct::Brave => {
+                &[r"C:\Program Files\BraveSoftware\Brave-Browser\Application\brave.exe"]
+            }
+        };
+        #[cfg(not(any(target_os = "macos", windows)))]
+        match self {
+            BrowserProduct::Chrome => &["/opt/google/chrome/chrome"],
+            BrowserProduct::Chromium => &[],
+            BrowserProduct::Edge => &["/opt/microsoft/msedge/msedge"],
+            BrowserProduct::Brave => &["/opt/brave.com/brave/brave-browser"],
+        }
+    }
+
+    /// Looks for an installed copy of the product, on the `PATH` and then where its installer
+    /// puts it.
+    pub fn find_executable(self) -> Result<std::path::PathBuf, String> {
+        for name in self.executable_names() {
+            if let Ok(path) = which::which(name) {
+                return Ok(path);
+            }
+        }
+        for path in self.install_paths() {
+            if std::path::Path::new(path).exists() {
+                return Ok(path.into());
+            }
+        }
+        Err(format!("Could not find an installed {self:?} executable"))
+    }
+}
+
 /// Represents the way in which Chrome is run. By default it will search for a Chrome
 /// binary on the system, use an available port for debugging, and start in headless mode.
 #[derive(Builder)]
@@ -83,6 +291,22 @@ pub struct LaunchOptions<'a> {
     #[builder(default = "true")]
     pub headless: bool,
 
+    /// Picks a specific headless mode (or headful), overriding `headless`. Defaults to None,
+    /// which passes a plain `--headless` if `headless` is set, leaving the mode up to Chrome.
+    #[builder(default = "None")]
+    pub headless_mode: Option<HeadlessMode>,
+
+    /// Overrides the font hinting (`--font-render-hinting`), so that text renders the same on
+    /// every machine, e.g. for comparing screenshots in CI. Defaults to None (the system's).
+    #[builder(default = "None")]
+    pub font_render_hinting: Option<FontRenderHinting>,
+
+    /// Which browser `path` is, or which to look for if it's None. Chrome and Chromium are
+    /// fetched (with the `fetch` feature) or found with `default_executable`, the others looked
+    /// for with `BrowserProduct::find_executable`. Defaults to Chrome.
+    #[builder(default = "BrowserProduct::Chrome")]
+    pub product: BrowserProduct,
+
     /// Determines whether to run the browser with a sandbox.
     #[builder(default = "true")]
     pub sandbox: bool,
@@ -94,6 +318,20 @@ pub struct LaunchOptions<'a> {
     /// Launch the browser with a specific debugging port.
     #[builder(default = "None")]
     pub port: Option<u16>,
+
+    /// Talk to the browser over a pair of pipes (`--remote-debugging-pipe`) rather than a
+    /// WebSocket on a debugging port, so that no TCP port is opened at all, which other
+    /// processes on the machine could connect to. Only supported on Unix. Defaults to false.
+    #[builder(default)]
+    pub remote_debugging_pipe: bool,
+
+    /// The address of the interface to listen for DevTools connections on, e.g. `0.0.0.0` to
+    /// accept them from other machines. Defaults to None, i.e. the browser's own default of
+    /// localhost only; for remote browsers, forwarding a port to that (e.g. over SSH) and using
+    /// `Browser::connect_forwarded` is safer than exposing it.
+    #[builder(default = "None")]
+    pub remote_debugging_address: Option<&'a str>,
+
     /// Determines whether SSL certificates should be verified.
     /// This is unsafe and can lead to MiTM attacks. Make sure you understand the risks
     /// See <https://www.owasp.org/index.php/Man-in-the-middle_attack>
@@ -112,12 +350,13 @@ pub struct LaunchOptions<'a> {
     #[builder(default = "None")]
     pub user_data_dir: Option<std::path::PathBuf>,
 
-    /// A list of Chrome extensions to load.
+    /// A list of Chrome extensions to load, see `LaunchOptionsBuilder::load_extension`.
     ///
     /// An extension should be a path to a folder containing the extension code.
     /// CRX files cannot be used directly and must be first extracted.
     ///
-    /// Note that Chrome does not support loading extensions in headless-mode.
+    /// Extensions only load in a headful browser or with `HeadlessMode::New`; the old headless
+    /// mode doesn't support them.
     /// See <https://bugs.chromium.org/p/chromium/issues/detail?id=706008#c5>
     #[builder(default)]
     pub extensions: Vec<&'a OsStr>,
@@ -131,6 +370,11 @@ pub struct LaunchOptions<'a> {
     #[builder(default)]
     pub disable_default_args: bool,
 
+    /// Presets of flags to launch the browser with, e.g. `LaunchProfile::FastScraping`.
+    /// Flags in more than one of them are only passed once. Defaults to none.
+    #[builder(default)]
+    pub profiles: Vec<LaunchProfile>,
+
     /// The options to use for fetching a version of chrome when `path` is None.
     ///
     /// By default, we'll use a revision guaranteed to work with our API and will
@@ -152,18 +396,39 @@ pub struct LaunchOptions<'a> {
     /// Setup the proxy server for headless chrome instance
     #[builder(default = "None")]
     pub proxy_server: Option<&'a str>,
+
+    /// The `(username, password)` to answer the proxy server's authentication challenges with.
+    /// Every tab pauses its document requests with the Fetch domain so that the challenges can
+    /// be handled; other requests aren't paused.
+    #[builder(default = "None")]
+    pub proxy_credentials: Option<(String, String)>,
+
+    /// How often to send a WebSocket Ping frame to the browser, to keep proxies from closing the
+    /// connection while it's idle. Defaults to None (no pings).
+    #[builder(default = "None")]
+    pub ping_interval: Option<Duration>,
+
+    /// Record all protocol traffic to this file, so that it can be played back by a
+    /// `ReplayServer`. Defaults to None (no recording).
+    #[builder(default = "None")]
+    pub record_protocol_to: Option<std::path::PathBuf>,
 }
 
 impl<'a> Default for LaunchOptions<'a> {
     fn default() -> Self {
         LaunchOptions {
             headless: true,
+            headless_mode: None,
+            font_render_hinting: None,
+            product: BrowserProduct::Chrome,
             sandbox: true,
             idle_browser_timeout: Duration::from_secs(30),
             window_size: None,
             path: None,
             user_data_dir: None,
             port: None,
+            remote_debugging_pipe: false,
+            remote_debugging_address: None,
             ignore_certificate_errors: true,
             extensions: Vec::new(),
             process_envs: None,
@@ -171,7 +436,11 @@ impl<'a> Default for LaunchOptions<'a> {
             fetcher_options: Default::default(),
             args: Vec::new(),
             disable_default_args: false,
+            profiles: Vec::new(),
             proxy_server: None,
+            proxy_credentials: None,
+            ping_interval: None,
+            record_protocol_to: None,
         }
     }
 }
@@ -180,8 +449,56 @@ impl<'a> LaunchOptions<'a> {
     pub fn default_builder() -> LaunchOptionsBuilder<'a> {
         LaunchOptionsBuilder::default()
     }
+
+    /// The flag selecting the headless mode, if any.
+    fn headless_arg(&self) -> Option<&'static str> {
+        match self.headless_mode {
+            Some(HeadlessMode::New) => Some("--headless=new"),
+            Some(HeadlessMode::Old) => Some("--headless=old"),
+            Some(HeadlessMode::Headful) => None,
+            None => self.headless.then_some("--headless"),
+        }
+    }
+}
+
+impl<'a> LaunchOptionsBuilder<'a> {
+    /// Sends all traffic through an authenticated HTTP or SOCKS5 proxy, e.g.
+    /// `http://proxy.example.com:8080`, answering its authentication challenges with the given
+    /// credentials.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::{Browser, LaunchOptionsBuilder};
+    /// let browser = Browser::new(
+    ///     LaunchOptionsBuilder::default()
+    ///         .proxy("http://proxy.example.com:8080", "user", "secret")
+    ///         .build()?,
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(&mut self, url: &'a str, username: &str, password: &str) -> &mut Self {
+        self.proxy_server(Some(url))
+            .proxy_credentials(Some((username.to_string(), password.to_string())))
+    }
+
+    /// Loads the unpacked extension in the folder at `path`, on top of any already added to
+    /// `extensions`. See `Browser::attach_to_extension` for driving it.
+    pub fn load_extension<P: AsRef<OsStr> + ?Sized>(&mut self, path: &'a P) -> &mut Self {
+        self.extensions
+            .get_or_insert_with(Vec::new)
+            .push(path.as_ref());
+        self
+    }
 }
 
+/// The `--disable-features` switch of `DEFAULT_ARGS`, which a product's `disabled_features` are
+/// appended to.
+const DEFAULT_DISABLE_FEATURES: &str = "--disable-features=TranslateUI,BlinkGenPropertyTrees";
+
 /// These are passed to the Chrome binary by default.
 /// Via <https://github.com/GoogleChrome/puppeteer/blob/master/lib/Launcher.js#L38>
 pub static DEFAULT_ARGS: [&str; 23] = [
@@ -196,7 +513,7 @@ pub static DEFAULT_ARGS: [&str; 23] = [
     "--disable-dev-shm-usage",
     "--disable-extensions",
     // BlinkGenPropertyTrees disabled due to crbug.com/937609
-    "--disable-features=TranslateUI,BlinkGenPropertyTrees",
+    DEFAULT_DISABLE_FEATURES,
     "--disable-hang-monitor",
     "--disable-ipc-flooding-protection",
     "--disable-popup-blocking",
@@ -213,6 +530,19 @@ pub static DEFAULT_ARGS: [&str; 23] = [
 
 impl Process {
     pub fn new(mut launch_options: LaunchOptions) -> Result<Self> {
+        if launch_options.path.is_none()
+            && !matches!(
+                launch_options.product,
+                BrowserProduct::Chrome | BrowserProduct::Chromium
+            )
+        {
+            launch_options.path = Some(
+                launch_options
+                    .product
+                    .find_executable()
+                    .map_err(|e| anyhow!("{e}"))?,
+            );
+        }
         if launch_options.path.is_none() {
             #[cfg(feature = "fetch")]
             {
@@ -225,6 +555,19 @@ impl Process {
             }
         }
 
+        if launch_options.remote_debugging_pipe {
+            let (process, pipe) = Self::start_process_with_pipe(&launch_options)?;
+            info!(
+                "Started Chrome with a debugging pipe. PID: {}",
+                process.0.id()
+            );
+            return Ok(Self {
+                child_process: process,
+                debug_ws_url: None,
+                pipe: Some(pipe),
+            });
+        }
+
         let mut process = Self::start_process(&launch_options)?;
 
         info!("Started Chrome. PID: {}", process.0.id());
@@ -264,10 +607,17 @@ impl Process {
 
         Ok(Self {
             child_process: process,
-            debug_ws_url: url,
+            debug_ws_url: Some(url),
+            pipe: None,
         })
     }
 
+    /// Hands over our ends of the pipes connected to the browser, if it was launched with
+    /// `remote_debugging_pipe`: the one to read its messages from and the one to write ours to.
+    pub fn take_pipe(&mut self) -> Option<(std::io::PipeReader, std::io::PipeWriter)> {
+        self.pipe.take()
+    }
+
     fn start_process(launch_options: &LaunchOptions) -> Result<TemporaryProcess> {
         let debug_port = if let Some(port) = launch_options.port {
             port
@@ -275,13 +625,86 @@ impl Process {
             get_available_port().ok_or(ChromeLaunchError::NoAvailablePorts {})?
         };
         let port_option = format!("--remote-debugging-port={debug_port}");
+        let (mut command, temp_user_data_dir) = Self::command(launch_options, &port_option)?;
+        if let Some(address) = launch_options.remote_debugging_address {
+            command.arg(format!("--remote-debugging-address={address}"));
+        }
+        Ok(TemporaryProcess(command.spawn()?, temp_user_data_dir))
+    }
+
+    #[cfg(unix)]
+    fn start_process_with_pipe(
+        launch_options: &LaunchOptions,
+    ) -> Result<(TemporaryProcess, (std::io::PipeReader, std::io::PipeWriter))> {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::process::CommandExt;
+
+        // The browser reads from its fd 3 and writes to its fd 4
+        let (browser_reader, our_writer) = std::io::pipe()?;
+        let (our_reader, browser_writer) = std::io::pipe()?;
+        let browser_read_fd = browser_reader.as_raw_fd();
+        let browser_write_fd = browser_writer.as_raw_fd();
+
+        let (mut command, temp_user_data_dir) =
+            Self::command(launch_options, "--remote-debugging-pipe")?;
+        // There's no WebSocket URL to look for in its output
+        command.stderr(Stdio::null());
+        // SAFETY: only async-signal-safe calls between fork and exec. The pipes are
+        // close-on-exec, but the copies dup2 makes aren't. They're first moved out of the way
+        // in case either pipe end already is fd 3 or 4, and those temporary copies closed
+        // again so that the browser doesn't inherit them.
+        unsafe {
+            command.pre_exec(move || {
+                let read_fd = libc::fcntl(browser_read_fd, libc::F_DUPFD, 10);
+                let write_fd = libc::fcntl(browser_write_fd, libc::F_DUPFD, 10);
+                if read_fd < 0
+                    || write_fd < 0
+                    || libc::dup2(read_fd, 3) < 0
+                    || libc::dup2(write_fd, 4) < 0
+                    || libc::close(read_fd) < 0
+                    || libc::close(write_fd) < 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn()?;
+        // Only the browser's copies of its ends must stay open, for us to see it exit
+        drop(browser_reader);
+        drop(browser_writer);
+
+        Ok((
+            TemporaryProcess(child, temp_user_data_dir),
+            (our_reader, our_writer),
+        ))
+    }
+
+    #[cfg(not(unix))]
+    fn start_process_with_pipe(
+        _launch_options: &LaunchOptions,
+    ) -> Result<(TemporaryProcess, (std::io::PipeReader, std::io::PipeWriter))> {
+        Err(ChromeLaunchError::PipeUnsupported.into())
+    }
 
+    /// The command launching the browser with `launch_options`, listening for the DevTools
+    /// protocol as `debugging_option` says, and the temporary user data directory it uses (if
+    /// any), to be removed once it's done.
+    fn command(
+        launch_options: &LaunchOptions,
+        debugging_option: &str,
+    ) -> Result<(Command, Option<tempfile::TempDir>)> {
         let window_size_option = if let Some((width, height)) = launch_options.window_size {
             format!("--window-size={width},{height}")
         } else {
             String::new()
         };
 
+        let font_render_hinting_option = launch_options
+            .font_render_hinting
+            .map(|hinting| format!("--font-render-hinting={}", hinting.as_str()))
+            .unwrap_or_default();
+
         let mut temp_user_data_dir = None;
 
         // User data directory
@@ -307,8 +730,13 @@ impl Process {
 
         trace!("Chrome will have profile: {}", data_dir_option);
 
+        let disable_features_option = std::iter::once(DEFAULT_DISABLE_FEATURES)
+            .chain(launch_options.product.disabled_features().iter().copied())
+            .collect::<Vec<_>>()
+            .join(",");
+
         let mut args = vec![
-            port_option.as_str(),
+            debugging_option,
             "--disable-gpu",
             "--enable-logging",
             "--verbose",
@@ -319,7 +747,29 @@ impl Process {
         ];
 
         if !launch_options.disable_default_args {
-            args.extend(DEFAULT_ARGS);
+            // Which would keep the extensions from loading
+            let disable_extensions = !launch_options.extensions.is_empty();
+            args.extend(
+                DEFAULT_ARGS
+                    .iter()
+                    .filter(|arg| !disable_extensions || **arg != "--disable-extensions")
+                    .map(|arg| {
+                        if *arg == DEFAULT_DISABLE_FEATURES {
+                            disable_features_option.as_str()
+                        } else {
+                            arg
+                        }
+                    }),
+            );
+            args.extend(launch_options.product.default_args());
+        }
+
+        for profile in &launch_options.profiles {
+            for arg in profile.args() {
+                if !args.contains(arg) {
+                    args.push(arg);
+                }
+            }
         }
 
         if !launch_options.args.is_empty() {
@@ -335,8 +785,12 @@ impl Process {
             args.extend([window_size_option.as_str()]);
         }
 
-        if launch_options.headless {
-            args.extend(["--headless"]);
+        if let Some(headless_arg) = launch_options.headless_arg() {
+            args.extend([headless_arg]);
+        }
+
+        if !font_render_hinting_option.is_empty() {
+            args.extend([font_render_hinting_option.as_str()]);
         }
 
         if launch_options.ignore_certificate_errors {
@@ -357,11 +811,19 @@ impl Process {
             args.extend(["--no-sandbox", "--disable-setuid-sandbox"]);
         }
 
-        let extension_args: Vec<String> = launch_options
+        let mut extension_args: Vec<String> = launch_options
             .extensions
             .iter()
             .map(|e| format!("--load-extension={}", e.to_str().unwrap()))
             .collect();
+        if !launch_options.extensions.is_empty() {
+            let paths: Vec<_> = launch_options
+                .extensions
+                .iter()
+                .map(|e| e.to_str().unwrap())
+                .collect();
+            extension_args.push(format!("--disable-extensions-except={}", paths.join(",")));
+        }
 
         args.extend(extension_args.iter().map(String::as_str));
 
@@ -377,11 +839,8 @@ impl Process {
             command.envs(process_envs);
         }
 
-        let process = TemporaryProcess(
-            command.args(&args).stderr(Stdio::piped()).spawn()?,
-            temp_user_data_dir,
-        );
-        Ok(process)
+        command.args(&args).stderr(Stdio::piped());
+        Ok((command, temp_user_data_dir))
     }
 
     fn ws_url_from_reader<R>(reader: BufReader<R>) -> Result<Option<String>>
@@ -467,6 +926,92 @@ mod tests {
         });
     }
 
+    #[test]
+    fn headless_mode_overrides_headless() {
+        let headless_arg = |headless, headless_mode| {
+            LaunchOptions::default_builder()
+                .headless(headless)
+                .headless_mode(headless_mode)
+                .build()
+                .unwrap()
+                .headless_arg()
+        };
+        assert_eq!(Some("--headless"), headless_arg(true, None));
+        assert_eq!(None, headless_arg(false, None));
+        assert_eq!(
+            Some("--headless=new"),
+            headless_arg(false, Some(HeadlessMode::New))
+        );
+        assert_eq!(
+            Some("--headless=old"),
+            headless_arg(true, Some(HeadlessMode::Old))
+        );
+        assert_eq!(None, headless_arg(true, Some(HeadlessMode::Headful)));
+    }
+
+    #[test]
+    fn profile_args_come_before_explicit_ones() {
+        let options = LaunchOptions::default_builder()
+            .path(Some("chrome".into()))
+            .profiles(vec![LaunchProfile::FastScraping, LaunchProfile::Privacy])
+            .args(vec![OsStr::new("--blink-settings=imagesEnabled=true")])
+            .build()
+            .unwrap();
+        let (command, _) = Process::command(&options, "--remote-debugging-port=0").unwrap();
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+
+        let position = |arg| args.iter().position(|a| *a == arg).unwrap();
+        assert!(
+            position("--blink-settings=imagesEnabled=false")
+                < position("--blink-settings=imagesEnabled=true")
+        );
+        assert!(args.contains(&"--incognito"));
+        assert_eq!(1, args.iter().filter(|arg| **arg == "--no-pings").count());
+    }
+
+    #[test]
+    fn product_features_join_the_default_disable_features() {
+        let options = LaunchOptions::default_builder()
+            .path(Some("msedge".into()))
+            .product(BrowserProduct::Edge)
+            .build()
+            .unwrap();
+        let (command, _) = Process::command(&options, "--remote-debugging-port=0").unwrap();
+        let disable_features: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .filter(|arg| arg.starts_with("--disable-features="))
+            .collect();
+
+        assert_eq!(
+            vec!["--disable-features=TranslateUI,BlinkGenPropertyTrees,msEdgeSidebarV2,msImplicitSignin,msSmartScreenProtection"],
+            disable_features
+        );
+    }
+
+    #[test]
+    fn loading_extensions_lifts_disable_extensions() {
+        let options = LaunchOptions::default_builder()
+            .path(Some("chrome".into()))
+            .load_extension("/ext/a")
+            .load_extension("/ext/b")
+            .build()
+            .unwrap();
+        let (command, _) = Process::command(&options, "--remote-debugging-port=0").unwrap();
+        let args: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+
+        assert!(!args.contains(&"--disable-extensions"));
+        assert!(args.contains(&"--load-extension=/ext/a"));
+        assert!(args.contains(&"--load-extension=/ext/b"));
+        assert!(args.contains(&"--disable-extensions-except=/ext/a,/ext/b"));
+    }
+
     #[test]
     fn can_launch_chrome_and_get_ws_url() {
         setup();
@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Timed out waiting for a navigation slot")]
+pub struct NavigationSlotTimeout {}
+
+/// A snapshot of how busy a `NavigationScheduler` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NavigationMetrics {
+    /// How many navigations are currently in progress.
+    pub in_flight: usize,
+    /// How many navigations are waiting for a slot.
+    pub queue_depth: usize,
+    /// How many navigations have been given a slot so far.
+    pub started: u64,
+    /// The total time navigations have spent waiting for a slot.
+    pub total_wait: Duration,
+    /// The longest time a navigation has waited for a slot.
+    pub max_wait: Duration,
+}
+
+impl NavigationMetrics {
+    /// The average time navigations have spent waiting for a slot.
+    pub fn average_wait(&self) -> Duration {
+        if self.started == 0 {
+            return Duration::ZERO;
+        }
+        self.total_wait / self.started as u32
+    }
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    limit: Option<usize>,
+    next_ticket: u64,
+    /// Tickets of the navigations waiting for a slot, in the order they asked for one.
+    queue: VecDeque<u64>,
+    metrics: NavigationMetrics,
+}
+
+/// Caps how many navigations run at once in a browser, queueing the rest.
+///
+/// Slots are handed out in the order they were asked for, so a busy tab can't starve the others.
+/// Every browser has one, see `Browser.set_max_concurrent_navigations`; there's no limit by
+/// default.
+#[derive(Debug, Default)]
+pub struct NavigationScheduler {
+    state: Mutex<SchedulerState>,
+    slot_freed: Condvar,
+}
+
+impl NavigationScheduler {
+    /// Sets how many navigations may run at once, or removes the limit if `limit` is `None`.
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.state.lock().unwrap().limit = limit;
+        self.slot_freed.notify_all();
+    }
+
+    pub fn metrics(&self) -> NavigationMetrics {
+        let state = self.state.lock().unwrap();
+        NavigationMetrics {
+            queue_depth: state.queue.len(),
+            ..state.metrics
+        }
+    }
+
+    /// Waits (for up to `timeout`) until it's this caller's turn and a slot is free. The slot is
+    /// given back when the returned permit is dropped.
+    pub fn acquire(self: &Arc<Self>, timeout: Duration) -> Result<NavigationPermit> {
+        let asked_at = Instant::now();
+        let deadline = asked_at + timeout;
+
+        let mut state = self.state.lock().unwrap();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push_back(ticket);
+
+        loop {
+            let has_slot = state
+                .limit
+                .is_none_or(|limit| state.metrics.in_flight < limit);
+            if has_slot && state.queue.front() == Some(&ticket) {
+                state.queue.pop_front();
+                let waited = asked_at.elapsed();
+                let metrics = &mut state.metrics;
+                metrics.in_flight += 1;
+                metrics.started += 1;
+                metrics.total_wait += waited;
+                metrics.max_wait = metrics.max_wait.max(waited);
+                // The next caller in line might be able to go too
+                self.slot_freed.notify_all();
+                return Ok(NavigationPermit {
+                    scheduler: Arc::clone(self),
+                });
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                state.queue.retain(|queued| *queued != ticket);
+                self.slot_freed.notify_all();
+                return Err(NavigationSlotTimeout {}.into());
+            }
+            state = self
+                .slot_freed
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().metrics.in_flight -= 1;
+        self.slot_freed.notify_all();
+    }
+}
+
+/// A navigation's slot in a `NavigationScheduler`, given back when dropped.
+#[derive(Debug)]
+pub struct NavigationPermit {
+    scheduler: Arc<NavigationScheduler>,
+}
+
+impl Drop for NavigationPermit {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn limits_concurrent_permits() {
+        let scheduler = Arc::new(NavigationScheduler::default());
+        scheduler.set_limit(Some(1));
+
+        let permit = scheduler.acquire(Duration::from_secs(1)).unwrap();
+        assert!(scheduler.acquire(Duration::from_millis(50)).is_err());
+        assert_eq!(1, scheduler.metrics().in_flight);
+        assert_eq!(0, scheduler.metrics().queue_depth);
+
+        drop(permit);
+        scheduler.acquire(Duration::from_secs(1)).unwrap();
+        assert_eq!(0, scheduler.metrics().in_flight);
+        assert_eq!(2, scheduler.metrics().started);
+    }
+
+    #[test]
+    fn hands_out_permits_in_order() {
+        let scheduler = Arc::new(NavigationScheduler::default());
+        scheduler.set_limit(Some(1));
+        let permit = scheduler.acquire(Duration::from_secs(1)).unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let waiters: Vec<_> = (0..3)
+            .map(|i| {
+                let waiter_scheduler = Arc::clone(&scheduler);
+                let order = Arc::clone(&order);
+                let waiter = thread::spawn(move || {
+                    let _permit = waiter_scheduler.acquire(Duration::from_secs(5)).unwrap();
+                    order.lock().unwrap().push(i);
+                });
+                // Make sure each waiter has queued up before starting the next
+                while scheduler.metrics().queue_depth <= i {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                waiter
+            })
+            .collect();
+
+        drop(permit);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+        assert_eq!(vec![0, 1, 2], *order.lock().unwrap());
+        assert!(scheduler.metrics().max_wait > Duration::ZERO);
+    }
+}
@@ -0,0 +1,55 @@
+use thiserror::Error;
+
+use crate::protocol::cdp::{types::Method, Fetch, Network, Page};
+
+/// What happens when a tab's renderer process crashes (e.g. because the page ran out of
+/// memory), see `Tab::set_crash_recovery`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrashRecovery {
+    /// The tab stays crashed, and calls on it fail with `TabCrashed` until it's reloaded or
+    /// navigated (e.g. with `Tab::recover_from_crash`).
+    #[default]
+    Error,
+    /// The tab's setup is applied again and the page reloaded, in the same tab.
+    Reload,
+    /// A new tab is opened in the crashed one's place, set up like it and navigated to its URL,
+    /// and the crashed tab is closed. The new tab is returned by `Tab::replacement`.
+    Recreate,
+}
+
+#[derive(Debug, Error)]
+#[error("Tab {target_id} crashed, so it can't be used until it's reloaded")]
+pub struct TabCrashed {
+    pub target_id: String,
+}
+
+/// Whether a method can still be called on a crashed tab: those which bring it back, and those
+/// handled by the browser rather than the page.
+pub(crate) fn callable_while_crashed(method_name: &str) -> bool {
+    method_name == Page::Reload::NAME
+        || method_name == Page::Navigate::NAME
+        || method_name.starts_with("Target.")
+}
+
+/// What has been set up on a tab which doesn't survive its renderer crashing (or which a tab
+/// replacing it needs), as the calls which set it up.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TabSetup {
+    pub(crate) extra_http_headers: Option<Network::SetExtraHTTPHeaders>,
+    pub(crate) user_agent: Option<Network::SetUserAgentOverride>,
+    pub(crate) fetch: Option<Fetch::Enable>,
+    pub(crate) scripts_on_new_document: Vec<(Page::ScriptIdentifier, String)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_browser_side_calls_work_while_crashed() {
+        assert!(callable_while_crashed("Page.reload"));
+        assert!(callable_while_crashed("Target.closeTarget"));
+        assert!(!callable_while_crashed("Runtime.evaluate"));
+        assert!(!callable_while_crashed("Page.captureScreenshot"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+use serde_json::Value as Json;
+
+use super::ConsoleMessage;
+use crate::browser::transport::{redact, DEFAULT_REDACTED_FIELDS};
+use crate::protocol::cdp::{
+    types::Event,
+    Fetch,
+    Network::{RequestId, ResourceType},
+    Page,
+};
+
+/// How many of the latest console messages a tab keeps for `Tab::debug_snapshot`.
+const CONSOLE_BACKLOG_SIZE: usize = 100;
+
+/// How many of the latest calls and events a tab keeps for `Tab::debug_snapshot`.
+const MESSAGE_HISTORY_SIZE: usize = 50;
+
+/// Strings in recorded messages are cut to this many characters, so that screenshots, response
+/// bodies and the like don't swamp a snapshot.
+const MAX_STRING_LENGTH: usize = 500;
+
+/// Everything about a tab which helps work out why an automation script failed on it, for
+/// attaching to bug reports. See `Tab::debug_snapshot`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugSnapshot {
+    pub target_id: String,
+    pub url: String,
+    pub title: String,
+    pub crashed: bool,
+    /// `None` if the frame tree couldn't be fetched, e.g. because the tab crashed.
+    pub frame_tree: Option<Page::FrameTree>,
+    pub pending_requests: Vec<PendingRequest>,
+    /// The latest console messages, oldest first.
+    pub console: Vec<ConsoleMessage>,
+    pub interceptors: Interceptors,
+    /// The latest calls made on the tab and events it received, oldest first.
+    pub recent_messages: Vec<ProtocolMessage>,
+}
+
+/// A request which the tab has sent and which hasn't finished or failed yet.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingRequest {
+    pub request_id: RequestId,
+    pub method: String,
+    pub url: String,
+    pub resource_type: Option<ResourceType>,
+}
+
+/// What is set up on the tab to handle its requests and events.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interceptors {
+    /// The requests paused for interception, if fetch is enabled.
+    pub fetch_patterns: Option<Vec<Fetch::RequestPattern>>,
+    /// How many middlewares the tab has, including its browser context's.
+    pub middlewares: usize,
+    pub response_handlers: Vec<String>,
+    pub loading_failed_handlers: Vec<String>,
+    pub bindings: Vec<String>,
+    pub event_listeners: usize,
+    pub navigation_policy: bool,
+}
+
+/// A call made on a tab, or an event it received.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ProtocolMessage {
+    #[serde(rename_all = "camelCase")]
+    Call {
+        method: String,
+        params: Json,
+        /// `None` if the call succeeded.
+        error: Option<String>,
+    },
+    Event {
+        method: String,
+        params: Json,
+    },
+}
+
+/// Cuts the strings in `value` down to `MAX_STRING_LENGTH` characters.
+fn abbreviate(value: &mut Json) {
+    match value {
+        Json::String(string) => {
+            if let Some((cut_at, _)) = string.char_indices().nth(MAX_STRING_LENGTH) {
+                let omitted = string[cut_at..].chars().count();
+                *string = format!("{}... ({omitted} more characters)", &string[..cut_at]);
+            }
+        }
+        Json::Array(values) => values.iter_mut().for_each(abbreviate),
+        Json::Object(map) => map.values_mut().for_each(abbreviate),
+        _ => {}
+    }
+}
+
+/// The tab's latest console messages, calls and events, and the requests it has in flight, as
+/// `Tab::debug_snapshot` reports them.
+#[derive(Debug, Default)]
+pub(crate) struct DebugHistory {
+    console: VecDeque<ConsoleMessage>,
+    messages: VecDeque<ProtocolMessage>,
+    pending_requests: HashMap<RequestId, PendingRequest>,
+}
+
+impl DebugHistory {
+    fn push_message(&mut self, mut message: ProtocolMessage) {
+        let (ProtocolMessage::Call { method, params, .. }
+        | ProtocolMessage::Event { method, params }) = &mut message;
+        redact(method, params, DEFAULT_REDACTED_FIELDS);
+        abbreviate(params);
+        if self.messages.len() == MESSAGE_HISTORY_SIZE {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    fn push_console_message(&mut self, message: ConsoleMessage) {
+        if self.console.len() == CONSOLE_BACKLOG_SIZE {
+            self.console.pop_front();
+        }
+        self.console.push_back(message);
+    }
+
+    pub(crate) fn call_made(&mut self, method: &str, params: Json, error: Option<String>) {
+        self.push_message(ProtocolMessage::Call {
+            method: method.to_string(),
+            params,
+            error,
+        });
+    }
+
+    pub(crate) fn event_received(&mut self, event: &Event) {
+        match event {
+            Event::RuntimeConsoleAPICalled(ev) => {
+                self.push_console_message(ConsoleMessage::from_console_api(&ev.params));
+            }
+            Event::LogEntryAdded(ev) => {
+                self.push_console_message(ConsoleMessage::from_log_entry(&ev.params.entry));
+            }
+            Event::NetworkRequestWillBeSent(ev) => {
+                let params = &ev.params;
+                self.pending_requests.insert(
+                    params.request_id.clone(),
+                    PendingRequest {
+                        request_id: params.request_id.clone(),
+                        method: params.request.method.clone(),
+                        url: params.request.url.clone(),
+                        resource_type: params.Type.clone(),
+                    },
+                );
+            }
+            Event::NetworkLoadingFinished(ev) => {
+                self.pending_requests.remove(&ev.params.request_id);
+            }
+            Event::NetworkLoadingFailed(ev) => {
+                self.pending_requests.remove(&ev.params.request_id);
+            }
+            _ => {}
+        }
+
+        let Ok(Json::Object(mut event)) = serde_json::to_value(event) else {
+            return;
+        };
+        let method = event
+            .remove("method")
+            .and_then(|method| method.as_str().map(String::from))
+            .unwrap_or_default();
+        let params = event.remove("params").unwrap_or(Json::Null);
+        self.push_message(ProtocolMessage::Event { method, params });
+    }
+
+    pub(crate) fn console(&self) -> Vec<ConsoleMessage> {
+        self.console.iter().cloned().collect()
+    }
+
+    pub(crate) fn messages(&self) -> Vec<ProtocolMessage> {
+        self.messages.iter().cloned().collect()
+    }
+
+    pub(crate) fn pending_requests(&self) -> Vec<PendingRequest> {
+        let mut requests: Vec<_> = self.pending_requests.values().cloned().collect();
+        requests.sort_by(|a, b| a.request_id.cmp(&b.request_id));
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redacts_credentials() {
+        let mut history = DebugHistory::default();
+        history.call_made(
+            "Fetch.continueWithAuth",
+            json!({
+                "requestId": "1",
+                "authChallengeResponse": {"response": "ProvideCredentials", "username": "alice", "password": "secret"},
+            }),
+            None,
+        );
+        history.call_made(
+            "Network.setExtraHTTPHeaders",
+            json!({ "headers": { "Authorization": "Bearer abc" } }),
+            None,
+        );
+        let messages = history.messages();
+        let (
+            ProtocolMessage::Call { params: auth, .. },
+            ProtocolMessage::Call {
+                params: headers, ..
+            },
+        ) = (&messages[0], &messages[1])
+        else {
+            panic!("expected calls");
+        };
+        assert_eq!("[REDACTED]", auth["authChallengeResponse"]["password"]);
+        assert_eq!("[REDACTED]", headers["headers"]["Authorization"]);
+    }
+
+    #[test]
+    fn keeps_the_latest_messages_abbreviated() {
+        let mut history = DebugHistory::default();
+        for i in 0..=MESSAGE_HISTORY_SIZE {
+            history.call_made("Page.navigate", json!({ "url": i.to_string() }), None);
+        }
+        let messages = history.messages();
+        assert_eq!(MESSAGE_HISTORY_SIZE, messages.len());
+        assert_eq!(
+            ProtocolMessage::Call {
+                method: "Page.navigate".to_string(),
+                params: json!({ "url": "1" }),
+                error: None,
+            },
+            messages[0]
+        );
+
+        history.call_made(
+            "Page.captureScreenshot",
+            json!({ "data": ["é".repeat(MAX_STRING_LENGTH + 3)] }),
+            Some("Timed out".to_string()),
+        );
+        let ProtocolMessage::Call { params, .. } = history.messages().pop().unwrap() else {
+            panic!("expected a call");
+        };
+        assert_eq!(
+            format!("{}... (3 more characters)", "é".repeat(MAX_STRING_LENGTH)),
+            params["data"][0]
+        );
+    }
+}
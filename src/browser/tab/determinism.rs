@@ -0,0 +1,53 @@
+/// Replaces `Date` with one whose current time is always `timestamp` (in milliseconds since the
+/// epoch), and stops `performance.now()` at 0. Dates made from explicit values work as usual.
+pub(crate) fn freeze_time_script(timestamp: f64) -> String {
+    format!(
+        r"(() => {{
+    const frozen = {timestamp};
+    const RealDate = Date;
+    function FrozenDate(...args) {{
+        // `Date()` called as a function returns the current time as a string
+        if (!new.target) {{
+            return new RealDate(frozen).toString();
+        }}
+        return args.length ? new RealDate(...args) : new RealDate(frozen);
+    }}
+    FrozenDate.prototype = RealDate.prototype;
+    FrozenDate.now = () => frozen;
+    FrozenDate.parse = RealDate.parse;
+    FrozenDate.UTC = RealDate.UTC;
+    Object.defineProperty(FrozenDate, 'name', {{ value: 'Date' }});
+    globalThis.Date = FrozenDate;
+    performance.now = () => 0;
+}})();"
+    )
+}
+
+/// Replaces `Math.random` with a mulberry32 generator seeded with `seed`, so that each document
+/// draws the same sequence of numbers.
+pub(crate) fn seed_random_script(seed: u32) -> String {
+    format!(
+        r"(() => {{
+    let state = {seed} >>> 0;
+    Math.random = () => {{
+        state = (state + 0x6D2B79F5) >>> 0;
+        let t = state;
+        t = Math.imul(t ^ (t >>> 15), t | 1);
+        t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+        return ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+    }};
+}})();"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_values_as_js_literals() {
+        assert!(freeze_time_script(1_700_000_000_000.0).contains("const frozen = 1700000000000;"));
+        assert!(freeze_time_script(1.5).contains("const frozen = 1.5;"));
+        assert!(seed_random_script(u32::MAX).contains("let state = 4294967295 >>> 0;"));
+    }
+}
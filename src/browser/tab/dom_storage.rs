@@ -0,0 +1,104 @@
+use anyhow::Result;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as Json;
+
+use crate::protocol::cdp::DOMStorage;
+
+/// A handle to the `localStorage` or `sessionStorage` of a tab's current origin.
+///
+/// Typically you get one of these via `Tab.local_storage` or `Tab.session_storage`. Values are
+/// stored as JSON, so anything that implements `Serialize` can be written and read back into a
+/// type that implements `Deserialize`. Plain strings written by page scripts can be read as
+/// `String`.
+///
+/// The origin is taken from the tab's URL when the handle is created, so get a new handle after
+/// navigating to a different origin.
+pub struct DomStorage<'a> {
+    storage_id: DOMStorage::StorageId,
+    parent: &'a super::Tab,
+}
+
+impl<'a> DomStorage<'a> {
+    pub fn new(parent: &'a super::Tab, is_local_storage: bool) -> Result<Self> {
+        let security_origin = url::Url::parse(&parent.get_url())?
+            .origin()
+            .ascii_serialization();
+
+        Ok(Self {
+            storage_id: DOMStorage::StorageId {
+                security_origin: Some(security_origin),
+                storage_key: None,
+                is_local_storage,
+            },
+            parent,
+        })
+    }
+
+    /// Returns the value stored under `key`, or `None` if there's no such item.
+    pub fn get<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let Some(raw) = self
+            .items()?
+            .into_iter()
+            .find_map(|(k, v)| if k == key { Some(v) } else { None })
+        else {
+            return Ok(None);
+        };
+
+        let value =
+            serde_json::from_str(&raw).or_else(|_| serde_json::from_value(Json::String(raw)))?;
+        Ok(Some(value))
+    }
+
+    /// Stores `value`, serialized as JSON, under `key`.
+    pub fn set<T>(&self, key: &str, value: T) -> Result<&Self>
+    where
+        T: Serialize,
+    {
+        self.parent.call_method(DOMStorage::SetDOMStorageItem {
+            storage_id: self.storage_id.clone(),
+            key: key.to_string(),
+            value: serde_json::to_string(&value)?,
+        })?;
+        Ok(self)
+    }
+
+    /// Removes the item stored under `key`, if any.
+    pub fn remove(&self, key: &str) -> Result<&Self> {
+        self.parent.call_method(DOMStorage::RemoveDOMStorageItem {
+            storage_id: self.storage_id.clone(),
+            key: key.to_string(),
+        })?;
+        Ok(self)
+    }
+
+    /// Removes every item.
+    pub fn clear(&self) -> Result<&Self> {
+        self.parent.call_method(DOMStorage::Clear {
+            storage_id: self.storage_id.clone(),
+        })?;
+        Ok(self)
+    }
+
+    /// Returns every item as a raw `(key, value)` pair.
+    pub fn items(&self) -> Result<Vec<(String, String)>> {
+        let entries = self
+            .parent
+            .call_method(DOMStorage::GetDOMStorageItems {
+                storage_id: self.storage_id.clone(),
+            })?
+            .entries;
+
+        Ok(entries
+            .into_iter()
+            .filter_map(|entry| match entry.as_slice() {
+                [key, value] => Some((key.clone(), value.clone())),
+                _ => None,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::logging::debug;
+use anyhow::Result;
+
+use crate::protocol::cdp::Fetch;
+
+/// A protocol domain which several of a tab's features can need enabled at once, e.g. `Network`
+/// for both `wait_for_network_idle` and `register_response_handling`. See `Tab::acquire_domain`.
+///
+/// Features which keep state from the domain's events for later calls to read (such as
+/// `Security` for `get_security_state`, or `Audits` for `take_issues`) hold it for as long as the
+/// tab is open; everything else releases it once it's done, or once its listener is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Domain {
+    Animation,
+    Audits,
+    Css,
+    Debugger,
+    Dom,
+    /// Only one set of request patterns can be paused at a time, so the patterns each holder
+    /// needs are merged, see `Tab::acquire_fetch`.
+    Fetch,
+    Inspector,
+    Log,
+    Media,
+    Network,
+    Page,
+    Performance,
+    Profiler,
+    Runtime,
+    Security,
+}
+
+/// Who has each domain enabled, so that a domain only gets disabled once nothing needs it any
+/// more, rather than one feature disabling it from under another (whose events then stop).
+#[derive(Debug, Default)]
+pub(crate) struct DomainStates {
+    holders: Mutex<HashMap<Domain, HashSet<String>>>,
+    /// The requests each holder of `Domain::Fetch` needs paused.
+    fetch_requirements: Mutex<BTreeMap<String, Fetch::Enable>>,
+}
+
+/// One `Fetch.enable` call pausing every request that any of `requirements` needs paused, or
+/// every request if one of them doesn't restrict it to patterns.
+fn merge_fetch_requirements<'a>(
+    requirements: impl IntoIterator<Item = &'a Fetch::Enable>,
+) -> Fetch::Enable {
+    let mut patterns = Some(Vec::new());
+    let mut handle_auth_requests = None;
+    for requirement in requirements {
+        match (&mut patterns, &requirement.patterns) {
+            (Some(merged), Some(required)) => {
+                for pattern in required {
+                    if !merged.contains(pattern) {
+                        merged.push(pattern.clone());
+                    }
+                }
+            }
+            _ => patterns = None,
+        }
+        if requirement.handle_auth_requests == Some(true) {
+            handle_auth_requests = Some(true);
+        }
+    }
+    Fetch::Enable {
+        // No requirements at all means `Domain::Fetch` was acquired directly
+        patterns: patterns.filter(|patterns| !patterns.is_empty()),
+        handle_auth_requests,
+    }
+}
+
+impl DomainStates {
+    /// Records that `holder` needs `domain`, calling `enable` if nothing else did. Acquiring a
+    /// domain again for the same holder does nothing.
+    pub(crate) fn acquire<F>(&self, domain: Domain, holder: &str, enable: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        // Held while enabling, so that concurrent acquirers don't both enable the domain
+        let mut holders = self.holders.lock().unwrap();
+        let domain_holders = holders.entry(domain).or_default();
+        if domain_holders.contains(holder) {
+            return Ok(());
+        }
+        if domain_holders.is_empty() {
+            debug!("Enabling {domain:?} for {holder}");
+            enable()?;
+        }
+        domain_holders.insert(holder.to_string());
+        Ok(())
+    }
+
+    /// Records that `holder` no longer needs `domain`, calling `disable` if nothing else does.
+    pub(crate) fn release<F>(&self, domain: Domain, holder: &str, disable: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let mut holders = self.holders.lock().unwrap();
+        let Some(domain_holders) = holders.get_mut(&domain) else {
+            return Ok(());
+        };
+        if !domain_holders.remove(holder) {
+            return Ok(());
+        }
+        if domain_holders.is_empty() {
+            debug!("Disabling {domain:?}, released by {holder}");
+            if let Err(err) = disable() {
+                // It's still enabled as far as we know
+                domain_holders.insert(holder.to_string());
+                return Err(err);
+            }
+        } else {
+            debug!("Keeping {domain:?} enabled for {domain_holders:?}");
+        }
+        Ok(())
+    }
+
+    /// Records the requests `holder` needs paused, or that it no longer needs any if
+    /// `requirement` is `None`. Returns what it needed before.
+    pub(crate) fn set_fetch_requirement(
+        &self,
+        holder: &str,
+        requirement: Option<Fetch::Enable>,
+    ) -> Option<Fetch::Enable> {
+        let mut requirements = self.fetch_requirements.lock().unwrap();
+        match requirement {
+            Some(requirement) => requirements.insert(holder.to_string(), requirement),
+            None => requirements.remove(holder),
+        }
+    }
+
+    /// The requests each holder of `Domain::Fetch` needs paused, by holder.
+    pub(crate) fn fetch_requirements(&self) -> Vec<(String, Fetch::Enable)> {
+        self.fetch_requirements
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(holder, requirement)| (holder.clone(), requirement.clone()))
+            .collect()
+    }
+
+    /// The `Fetch.enable` call which satisfies every holder of `Domain::Fetch`.
+    pub(crate) fn merged_fetch(&self) -> Fetch::Enable {
+        merge_fetch_requirements(self.fetch_requirements.lock().unwrap().values())
+    }
+
+    pub(crate) fn holders(&self, domain: Domain) -> Vec<String> {
+        let mut holders: Vec<_> = self
+            .holders
+            .lock()
+            .unwrap()
+            .get(&domain)
+            .map(|holders| holders.iter().cloned().collect())
+            .unwrap_or_default();
+        holders.sort();
+        holders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn only_disables_once_the_last_holder_releases() {
+        let states = DomainStates::default();
+        let enables = Cell::new(0);
+        let disables = Cell::new(0);
+        let enable = || {
+            enables.set(enables.get() + 1);
+            Ok(())
+        };
+        let disable = || {
+            disables.set(disables.get() + 1);
+            Ok(())
+        };
+
+        states.acquire(Domain::Network, "a", enable).unwrap();
+        states.acquire(Domain::Network, "b", enable).unwrap();
+        states.acquire(Domain::Network, "b", enable).unwrap();
+        assert_eq!(1, enables.get());
+        assert_eq!(vec!["a", "b"], states.holders(Domain::Network));
+
+        states.release(Domain::Network, "a", disable).unwrap();
+        states.release(Domain::Network, "a", disable).unwrap();
+        assert_eq!(0, disables.get());
+        states.release(Domain::Network, "b", disable).unwrap();
+        assert_eq!(1, disables.get());
+
+        states.acquire(Domain::Network, "a", enable).unwrap();
+        assert_eq!(2, enables.get());
+    }
+
+    #[test]
+    fn merges_fetch_patterns() {
+        let pattern = |url: &str| Fetch::RequestPattern {
+            url_pattern: Some(url.to_string()),
+            resource_Type: None,
+            request_stage: None,
+        };
+        let states = DomainStates::default();
+        states.set_fetch_requirement(
+            "a",
+            Some(Fetch::Enable {
+                patterns: Some(vec![pattern("*.png")]),
+                handle_auth_requests: None,
+            }),
+        );
+        states.set_fetch_requirement(
+            "b",
+            Some(Fetch::Enable {
+                patterns: Some(vec![pattern("*.png"), pattern("*/api/*")]),
+                handle_auth_requests: Some(true),
+            }),
+        );
+        let merged = states.merged_fetch();
+        assert_eq!(
+            Some(vec![pattern("*.png"), pattern("*/api/*")]),
+            merged.patterns
+        );
+        assert_eq!(Some(true), merged.handle_auth_requests);
+
+        states.set_fetch_requirement(
+            "c",
+            Some(Fetch::Enable {
+                patterns: None,
+                handle_auth_requests: None,
+            }),
+        );
+        assert_eq!(None, states.merged_fetch().patterns);
+
+        states.set_fetch_requirement("b", None);
+        states.set_fetch_requirement("c", None);
+        let merged = states.merged_fetch();
+        assert_eq!(Some(vec![pattern("*.png")]), merged.patterns);
+        assert_eq!(None, merged.handle_auth_requests);
+    }
+
+    #[test]
+    fn failed_enable_isnt_recorded() {
+        let states = DomainStates::default();
+        assert!(states
+            .acquire(Domain::Page, "a", || Err(anyhow::anyhow!("nope")))
+            .is_err());
+        assert!(states.holders(Domain::Page).is_empty());
+    }
+}
@@ -1,13 +1,14 @@
 use std::fmt::Debug;
+use std::ops::RangeInclusive;
 use std::time::Duration;
 
 use anyhow::{Error, Result};
 
 use thiserror::Error;
 
-use log::{debug, error};
+use crate::logging::{debug, error};
 
-use crate::browser::tab::NoElementFound;
+use crate::browser::tab::{Domain, NoElementFound};
 use crate::{browser::tab::point::Point, protocol::cdp::CSS::CSSComputedStyleProperty};
 
 mod box_model;
@@ -20,6 +21,10 @@ use crate::protocol::cdp::{Page, Runtime, CSS, DOM};
 #[derive(Debug, Error)]
 #[error("Couldnt get element quad")]
 pub struct NoQuadFound {}
+
+#[derive(Debug, Error)]
+#[error("Element has no value")]
+pub struct NoValueFound {}
 /// A handle to a [DOM Element](https://developer.mozilla.org/en-US/docs/Web/API/Element).
 ///
 /// Typically you get access to these by passing `Tab.wait_for_element` a CSS selector. Once
@@ -255,6 +260,12 @@ impl<'a> Element<'a> {
         Ok(self)
     }
 
+    /// Hovers the mouse over the middle of this element, triggering `mouseover`, `mouseenter`
+    /// and `:hover` styles. The same as `move_mouse_over`.
+    pub fn hover(&self) -> Result<&Self> {
+        self.move_mouse_over()
+    }
+
     pub fn click(&self) -> Result<&Self> {
         self.scroll_into_view()?;
         debug!("Clicking element {:?}", &self);
@@ -263,6 +274,25 @@ impl<'a> Element<'a> {
         Ok(self)
     }
 
+    /// Drags this element onto the middle of `target`, see `Tab::drag`.
+    pub fn drag_to(&self, target: &Element) -> Result<&Self> {
+        self.scroll_into_view()?;
+        let from = self.get_midpoint()?;
+        let to = target.get_midpoint()?;
+        debug!("Dragging element {:?} to {:?}", &self, target);
+        self.parent.drag(from, to)?;
+        Ok(self)
+    }
+
+    /// Taps the middle of this element, see `Tab::tap_point`.
+    pub fn tap(&self) -> Result<&Self> {
+        self.scroll_into_view()?;
+        debug!("Tapping element {:?}", &self);
+        let midpoint = self.get_midpoint()?;
+        self.parent.tap_point(midpoint)?;
+        Ok(self)
+    }
+
     pub fn type_into(&self, text: &str) -> Result<&Self> {
         self.click()?;
 
@@ -273,6 +303,116 @@ impl<'a> Element<'a> {
         Ok(self)
     }
 
+    /// Clicks this element and types `text` into it, pausing for a random time within `delay`
+    /// between keystrokes, see `Tab::type_with_delay`.
+    pub fn type_with_delay(&self, text: &str, delay: RangeInclusive<Duration>) -> Result<&Self> {
+        self.click()?;
+
+        debug!(
+            "Typing into element ( {:?} ) with delay {:?}: {}",
+            &self, delay, text
+        );
+
+        self.parent.type_with_delay(text, delay)?;
+
+        Ok(self)
+    }
+
+    /// Clears this input, textarea or contenteditable element the way a user would, by selecting
+    /// all of its text and pressing Backspace, which fires the usual key and `input` events.
+    pub fn clear(&self) -> Result<&Self> {
+        self.focus()?;
+        let result = self.call_js_fn(
+            "function() {
+                if (this instanceof HTMLInputElement || this instanceof HTMLTextAreaElement) {
+                    this.select();
+                    return this.value.length > 0;
+                }
+                if (this.isContentEditable) {
+                    const range = document.createRange();
+                    range.selectNodeContents(this);
+                    const selection = window.getSelection();
+                    selection.removeAllRanges();
+                    selection.addRange(range);
+                    return this.textContent.length > 0;
+                }
+                return 'Element is not an input, textarea or contenteditable element';
+            }",
+            vec![],
+            false,
+        )?;
+
+        match result.value {
+            Some(serde_json::Value::Bool(true)) => {
+                self.parent.press_key("Backspace")?;
+            }
+            Some(serde_json::Value::Bool(false)) => {}
+            Some(serde_json::Value::String(error_text)) => {
+                return Err(FillFailed { error_text }.into());
+            }
+            _ => {
+                return Err(FillFailed {
+                    error_text: "Couldn't select the element's text".to_string(),
+                }
+                .into())
+            }
+        }
+        Ok(self)
+    }
+
+    /// Replaces the value of this form field with `value`: clears it and types `value` key by key
+    /// for text fields, or selects the option with that value for `<select>` elements.
+    pub fn fill(&self, value: &str) -> Result<&Self> {
+        if self
+            .get_description()?
+            .node_name
+            .eq_ignore_ascii_case("select")
+        {
+            return self.select_option(value);
+        }
+
+        debug!("Filling element ( {:?} ): {}", &self, value);
+        self.clear()?;
+        self.parent.type_str(value)?;
+        Ok(self)
+    }
+
+    /// Submits the form this element is, or belongs to, as its submit button would: the form's
+    /// fields are validated and a `submit` event is fired first, either of which can stop it.
+    /// When called on a submit button, that button is the submitter.
+    ///
+    /// Use `submit_and_wait_for_navigation` to wait for the page the form leads to.
+    pub fn submit(&self) -> Result<&Self> {
+        let result = self.call_js_fn(
+            "function() {
+                const form = this instanceof HTMLFormElement ? this : this.form;
+                if (!form)
+                    return 'Element is not a form nor inside one';
+                const isSubmitter = (this instanceof HTMLButtonElement || this instanceof HTMLInputElement)
+                    && this.type === 'submit';
+                form.requestSubmit(isSubmitter ? this : undefined);
+                return false;
+            }",
+            vec![],
+            false,
+        )?;
+
+        if result.Type == Runtime::RemoteObjectType::String {
+            let error_text = result.value.unwrap().as_str().unwrap().to_string();
+            return Err(SubmitFailed { error_text }.into());
+        }
+
+        Ok(self)
+    }
+
+    /// Submits the form this element is or belongs to, see `submit`, then waits for the page
+    /// it leads to to load, see `Tab::wait_for_navigation`.
+    pub fn submit_and_wait_for_navigation(&self) -> Result<&Self> {
+        self.submit()?;
+        self.parent.wait_for_navigation()?;
+        Ok(self)
+    }
+
     pub fn call_js_fn(
         &self,
         function_declaration: &str,
@@ -364,7 +504,47 @@ impl<'a> Element<'a> {
         Ok(String::from(html.as_str().unwrap()))
     }
 
+    /// Get the serialized HTML of the element, including shadow roots attached to it or its
+    /// descendants.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/DOM#method-getOuterHTML>
+    pub fn get_outer_html(&self) -> Result<String> {
+        Ok(self
+            .parent
+            .call_method(DOM::GetOuterHTML {
+                node_id: None,
+                backend_node_id: Some(self.backend_node_id),
+                object_id: None,
+                include_shadow_dom: Some(true),
+            })?
+            .outer_html)
+    }
+
+    /// Get the current value of an `<input>`, `<textarea>` or `<select>` element, which (unlike
+    /// its `value` attribute) reflects what the user has typed or picked.
+    pub fn get_value(&self) -> Result<String> {
+        match self
+            .call_js_fn("function() { return this.value }", vec![], false)?
+            .value
+        {
+            Some(serde_json::Value::String(value)) => Ok(value),
+            _ => Err(NoValueFound {}.into()),
+        }
+    }
+
+    /// Get the value of the attribute called `name`, or `None` if the element doesn't have it.
+    pub fn get_attribute(&self, name: &str) -> Result<Option<String>> {
+        let attributes = self.get_attributes()?.unwrap_or_default();
+        Ok(attributes
+            .chunks_exact(2)
+            .find(|pair| pair[0] == name)
+            .map(|pair| pair[1].clone()))
+    }
+
+    /// Get every property of the element's computed style, i.e. the values the browser ended
+    /// up using for it after applying all of the stylesheets.
     pub fn get_computed_styles(&self) -> Result<Vec<CSSComputedStyleProperty>> {
+        self.enable_css()?;
         let styles = self
             .parent
             .call_method(CSS::GetComputedStyleForNode {
@@ -375,6 +555,53 @@ impl<'a> Element<'a> {
         Ok(styles)
     }
 
+    /// Get the computed value of the CSS property called `name` (e.g. `"display"`), or `None` if
+    /// there's no such property.
+    pub fn get_computed_style(&self, name: &str) -> Result<Option<String>> {
+        Ok(self
+            .get_computed_styles()?
+            .into_iter()
+            .find(|property| property.name == name)
+            .map(|property| property.value))
+    }
+
+    /// Get the CSS rules which match the element, in the order they cascade in (later ones win),
+    /// along with which of each rule's selectors matched. Inline and inherited styles aren't included; call
+    /// `CSS.getMatchedStylesForNode` for those.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let heading = tab.wait_for_element("h1")?;
+    /// for rule_match in heading.get_matched_css_rules()? {
+    ///     println!("{}", rule_match.rule.selector_list.text);
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_matched_css_rules(&self) -> Result<Vec<CSS::RuleMatch>> {
+        self.enable_css()?;
+        Ok(self
+            .parent
+            .call_method(CSS::GetMatchedStylesForNode {
+                node_id: self.node_id,
+            })?
+            .matched_css_rules
+            .unwrap_or_default())
+    }
+
+    /// The `CSS` domain is only enabled on the browser's initial tab, and needs `DOM` enabled.
+    fn enable_css(&self) -> Result<()> {
+        self.parent.acquire_domain(Domain::Dom, "css")?;
+        self.parent.acquire_domain(Domain::Css, "css")?;
+        Ok(())
+    }
+
     pub fn get_description(&self) -> Result<DOM::Node> {
         let node = self
             .parent
@@ -473,6 +700,80 @@ impl<'a> Element<'a> {
         Ok(self)
     }
 
+    /// Selects the option with the given `value` of this `<select>` element, firing `input` and
+    /// `change` events as if the user had picked it.
+    pub fn select_option(&self, value: &str) -> Result<&Self> {
+        let result = self.call_js_fn(
+            "function(value) {
+                if (!(this instanceof HTMLSelectElement))
+                    return 'Element is not a <select> element';
+                const option = Array.from(this.options).find(option => option.value === value);
+                if (!option)
+                    return `No option has the value ${value}`;
+                option.selected = true;
+                this.dispatchEvent(new Event('input', { bubbles: true }));
+                this.dispatchEvent(new Event('change', { bubbles: true }));
+                return false;
+            }",
+            vec![serde_json::json!(value)],
+            false,
+        )?;
+
+        if result.Type == Runtime::RemoteObjectType::String {
+            let error_text = result.value.unwrap().as_str().unwrap().to_string();
+            return Err(SelectFailed { error_text }.into());
+        }
+
+        Ok(self)
+    }
+
+    /// Checks this checkbox or radio button by clicking it, unless it's already checked.
+    pub fn check(&self) -> Result<&Self> {
+        self.set_checked(true)
+    }
+
+    /// Unchecks this checkbox by clicking it, unless it's already unchecked.
+    pub fn uncheck(&self) -> Result<&Self> {
+        self.set_checked(false)
+    }
+
+    fn set_checked(&self, checked: bool) -> Result<&Self> {
+        if self.is_checked()? != checked {
+            self.click()?;
+        }
+        if self.is_checked()? != checked {
+            return Err(CheckFailed {
+                error_text: format!(
+                    "Element is still {}",
+                    if checked { "unchecked" } else { "checked" }
+                ),
+            }
+            .into());
+        }
+        Ok(self)
+    }
+
+    fn is_checked(&self) -> Result<bool> {
+        let result = self.call_js_fn(
+            "function() {
+                if (!(this instanceof HTMLInputElement) || !['checkbox', 'radio'].includes(this.type))
+                    return 'Element is not a checkbox or radio button';
+                return this.checked;
+            }",
+            vec![],
+            false,
+        )?;
+
+        match result.value {
+            Some(serde_json::Value::Bool(checked)) => Ok(checked),
+            Some(serde_json::Value::String(error_text)) => Err(CheckFailed { error_text }.into()),
+            _ => Err(CheckFailed {
+                error_text: "Couldn't read the element's checked state".to_string(),
+            }
+            .into()),
+        }
+    }
+
     pub fn get_attributes(&self) -> Result<Option<Vec<String>>> {
         let description = self.get_description()?;
         Ok(description.attributes)
@@ -563,6 +864,30 @@ impl<'a> Element<'a> {
     }
 }
 
+#[derive(Debug, Error)]
+#[error("Selecting option failed: {}", error_text)]
+struct SelectFailed {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Filling element failed: {}", error_text)]
+struct FillFailed {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Submitting form failed: {}", error_text)]
+struct SubmitFailed {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Changing checked state failed: {}", error_text)]
+struct CheckFailed {
+    error_text: String,
+}
+
 #[derive(Debug, Error)]
 #[error("Scrolling element into view failed: {}", error_text)]
 struct ScrollFailed {
@@ -0,0 +1,140 @@
+use serde_json::{json, Value as Json};
+
+/// Pulls the values of `fields` out of `root` and every element matching `items` in it. Fields
+/// are `{selector, attribute, all}`, see `Field`; `items` is null to extract a single object.
+const EXTRACTOR: &str = r"function extract(items, fields) {
+    const read = (element, attribute) => {
+        if (attribute === null)
+            return element.textContent.trim();
+        // Properties give e.g. absolute URLs for `href` and current values for `value`, and
+        // keep their type, e.g. booleans for `checked`
+        const property = element[attribute];
+        if (['string', 'number', 'boolean'].includes(typeof property))
+            return property;
+        return element.getAttribute(attribute);
+    };
+    const extractFrom = (root) => {
+        const result = {};
+        for (const [name, field] of Object.entries(fields)) {
+            const elements = field.selector === ''
+                ? [root]
+                : Array.from(root.querySelectorAll(field.selector));
+            const values = elements.map((element) => read(element, field.attribute));
+            result[name] = field.all ? values : (values.length ? values[0] : null);
+        }
+        return result;
+    };
+    return JSON.stringify(items === null
+        ? extractFrom(document)
+        : Array.from(document.querySelectorAll(items)).map(extractFrom));
+}";
+
+/// A field of an extracted object, parsed from `Tab::extract`'s mini-syntax: a CSS selector,
+/// optionally followed by `@attribute` and then `[]`.
+#[derive(Debug, PartialEq)]
+struct Field<'a> {
+    /// Empty for the element being extracted from itself.
+    selector: &'a str,
+    /// None for the text content.
+    attribute: Option<&'a str>,
+    /// Whether every match is wanted rather than the first one.
+    all: bool,
+}
+
+impl<'a> Field<'a> {
+    fn parse(spec: &'a str) -> Self {
+        let spec = spec.trim();
+        let (spec, all) = match spec.strip_suffix("[]") {
+            Some(spec) => (spec, true),
+            None => (spec, false),
+        };
+        // An `@` can be part of a quoted attribute value in the selector, but not of a name
+        let attribute = spec.rsplit_once('@').filter(|(_, attribute)| {
+            !attribute.is_empty()
+                && attribute
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+        match attribute {
+            Some((selector, attribute)) => Self {
+                selector: selector.trim(),
+                attribute: Some(attribute),
+                all,
+            },
+            None => Self {
+                selector: spec,
+                attribute: None,
+                all,
+            },
+        }
+    }
+}
+
+/// The expression extracting `fields` from the document, or from each of the elements matching
+/// `items`, as a JSON string.
+pub(crate) fn extractor_expression(items: Option<&str>, fields: &[(&str, &str)]) -> String {
+    let fields: serde_json::Map<String, Json> = fields
+        .iter()
+        .map(|(name, spec)| {
+            let field = Field::parse(spec);
+            (
+                (*name).to_string(),
+                json!({
+                    "selector": field.selector,
+                    "attribute": field.attribute,
+                    "all": field.all,
+                }),
+            )
+        })
+        .collect();
+    format!("({EXTRACTOR})({}, {})", json!(items), Json::Object(fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_field_specs() {
+        assert_eq!(
+            Field {
+                selector: "h2.title",
+                attribute: None,
+                all: false
+            },
+            Field::parse("h2.title")
+        );
+        assert_eq!(
+            Field {
+                selector: "a.next",
+                attribute: Some("href"),
+                all: false
+            },
+            Field::parse("a.next @href")
+        );
+        assert_eq!(
+            Field {
+                selector: "",
+                attribute: Some("data-id"),
+                all: false
+            },
+            Field::parse("@data-id")
+        );
+        assert_eq!(
+            Field {
+                selector: "ul.tags li",
+                attribute: None,
+                all: true
+            },
+            Field::parse("ul.tags li[]")
+        );
+        assert_eq!(
+            Field {
+                selector: r#"a[href="mailto:me@example.com"]"#,
+                attribute: None,
+                all: false
+            },
+            Field::parse(r#"a[href="mailto:me@example.com"]"#)
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Result;
+
+use serde::de::DeserializeOwned;
+
+use crate::protocol::cdp::{IndexedDB, Runtime};
+
+pub use IndexedDB::{DatabaseWithObjectStores, Key, KeyRange};
+
+/// How many entries are fetched per `IndexedDB.requestData` call.
+const PAGE_SIZE: u32 = 100;
+
+/// The object group the browser puts the entries returned by `IndexedDB.requestData` in.
+const OBJECT_GROUP: &str = "indexeddb";
+
+/// A single record read from an IndexedDB object store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedDbEntry<T> {
+    pub key: serde_json::Value,
+    pub primary_key: serde_json::Value,
+    pub value: T,
+}
+
+/// A read-only handle to the IndexedDB databases of a single origin.
+///
+/// Typically you get one of these via `Tab.indexeddb`.
+pub struct IndexedDb<'a> {
+    security_origin: String,
+    parent: &'a super::Tab,
+}
+
+impl<'a> IndexedDb<'a> {
+    pub fn new(parent: &'a super::Tab, origin: &str) -> Result<Self> {
+        parent.call_method(IndexedDB::Enable(None))?;
+        Ok(Self {
+            security_origin: origin.to_string(),
+            parent,
+        })
+    }
+
+    /// Returns the names of every database belonging to the origin.
+    pub fn database_names(&self) -> Result<Vec<String>> {
+        Ok(self
+            .parent
+            .call_method(IndexedDB::RequestDatabaseNames {
+                security_origin: Some(self.security_origin.clone()),
+                storage_key: None,
+                storage_bucket: None,
+            })?
+            .database_names)
+    }
+
+    /// Returns the version and object stores (with their indexes) of the named database.
+    pub fn database(&self, database_name: &str) -> Result<DatabaseWithObjectStores> {
+        Ok(self
+            .parent
+            .call_method(IndexedDB::RequestDatabase {
+                security_origin: Some(self.security_origin.clone()),
+                storage_key: None,
+                storage_bucket: None,
+                database_name: database_name.to_string(),
+            })?
+            .database_with_object_stores)
+    }
+
+    /// Reads every entry of an object store whose key falls within `key_range` (or all of them if
+    /// `key_range` is `None`), deserializing values into `T`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// #[derive(serde::Deserialize)]
+    /// struct Todo {
+    ///     title: String,
+    /// }
+    ///
+    /// let todos = tab
+    ///     .indexeddb("https://example.com")?
+    ///     .request_data::<Todo>("todos-db", "todos", None)?;
+    /// for entry in todos {
+    ///     println!("{}: {}", entry.key, entry.value.title);
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_data<T>(
+        &self,
+        database_name: &str,
+        object_store_name: &str,
+        key_range: Option<KeyRange>,
+    ) -> Result<Vec<IndexedDbEntry<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        let entries = self.read_entries(database_name, object_store_name, key_range);
+        // The entries' remote objects stay alive in the page until their group is released
+        self.parent.call_method(Runtime::ReleaseObjectGroup {
+            object_group: OBJECT_GROUP.to_string(),
+        })?;
+        entries
+    }
+
+    fn read_entries<T>(
+        &self,
+        database_name: &str,
+        object_store_name: &str,
+        key_range: Option<KeyRange>,
+    ) -> Result<Vec<IndexedDbEntry<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        let mut entries = Vec::new();
+        loop {
+            let page = self.parent.call_method(IndexedDB::RequestData {
+                security_origin: Some(self.security_origin.clone()),
+                storage_key: None,
+                storage_bucket: None,
+                database_name: database_name.to_string(),
+                object_store_name: object_store_name.to_string(),
+                index_name: String::new(),
+                skip_count: entries.len() as u32,
+                page_size: PAGE_SIZE,
+                key_range: key_range.clone(),
+            })?;
+
+            let objects: Vec<_> = page
+                .object_store_data_entries
+                .iter()
+                .flat_map(|entry| [&entry.key, &entry.primary_key, &entry.value])
+                .collect();
+            let mut values = self.values_of(&objects)?.into_iter();
+            while let (Some(key), Some(primary_key), Some(value)) =
+                (values.next(), values.next(), values.next())
+            {
+                entries.push(IndexedDbEntry {
+                    key,
+                    primary_key,
+                    value: serde_json::from_value(value)?,
+                });
+            }
+
+            if !page.has_more {
+                return Ok(entries);
+            }
+        }
+    }
+
+    /// Returns the JSON values of `objects`, fetching the ones returned by reference from the page
+    /// in a single call.
+    fn values_of(&self, objects: &[&Runtime::RemoteObject]) -> Result<Vec<serde_json::Value>> {
+        let Some(this) = objects.iter().find_map(|object| object.object_id.clone()) else {
+            return Ok(objects
+                .iter()
+                .map(|object| object.value.clone().unwrap_or_default())
+                .collect());
+        };
+        let arguments = objects
+            .iter()
+            .map(|object| Runtime::CallArgument {
+                value: object.value.clone(),
+                unserializable_value: object.unserializable_value.clone(),
+                object_id: object.object_id.clone(),
+            })
+            .collect();
+        let result = self
+            .parent
+            .call_method(Runtime::CallFunctionOn {
+                object_id: Some(this),
+                function_declaration: "function(...values) { return values; }".to_string(),
+                arguments: Some(arguments),
+                return_by_value: Some(true),
+                generate_preview: None,
+                silent: Some(true),
+                await_promise: None,
+                user_gesture: None,
+                execution_context_id: None,
+                object_group: None,
+                throw_on_side_effect: None,
+            })?
+            .result;
+        Ok(serde_json::from_value(result.value.unwrap_or_default())?)
+    }
+}
@@ -0,0 +1,98 @@
+use std::io;
+
+use crate::logging::warn;
+use anyhow::Result;
+
+use crate::protocol::cdp::IO;
+
+/// How much to ask the browser for at once; a few MB keeps messages reasonably sized.
+const CHUNK_SIZE: u32 = 4 * 1024 * 1024;
+
+/// Reads a stream the browser handed out a handle to (e.g. a PDF printed with the
+/// `ReturnAsStream` transfer mode, a trace, or a response body taken as a stream), a chunk at a
+/// time via `IO.read`.
+///
+/// Implements `std::io::Read`, so the stream can be piped straight to a file with
+/// `std::io::copy`. The stream is closed with `IO.close` once the reader is dropped, or
+/// explicitly with `close`.
+pub struct StreamReader<'a> {
+    handle: IO::StreamHandle,
+    parent: &'a super::Tab,
+    chunk: Vec<u8>,
+    position: usize,
+    eof: bool,
+    closed: bool,
+}
+
+impl<'a> StreamReader<'a> {
+    pub fn new(parent: &'a super::Tab, handle: IO::StreamHandle) -> Self {
+        Self {
+            handle,
+            parent,
+            chunk: Vec::new(),
+            position: 0,
+            eof: false,
+            closed: false,
+        }
+    }
+
+    /// The handle of the stream being read.
+    pub fn handle(&self) -> &IO::StreamHandle {
+        &self.handle
+    }
+
+    /// Closes the stream, discarding whatever hasn't been read yet.
+    pub fn close(mut self) -> Result<()> {
+        self.closed = true;
+        self.parent.call_method(IO::Close {
+            handle: self.handle.clone(),
+        })?;
+        Ok(())
+    }
+
+    fn read_chunk(&mut self) -> Result<()> {
+        let chunk = self.parent.call_method(IO::Read {
+            handle: self.handle.clone(),
+            offset: None,
+            size: Some(CHUNK_SIZE),
+        })?;
+        self.chunk = if chunk.base_64_encoded == Some(true) {
+            base64::decode(chunk.data)?
+        } else {
+            chunk.data.into_bytes()
+        };
+        self.position = 0;
+        self.eof = chunk.eof;
+        Ok(())
+    }
+}
+
+impl io::Read for StreamReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The browser may hand out empty chunks before the end of the stream
+        while self.position == self.chunk.len() {
+            if self.eof || self.closed {
+                return Ok(0);
+            }
+            self.read_chunk().map_err(io::Error::other)?;
+        }
+
+        let read = buf.len().min(self.chunk.len() - self.position);
+        buf[..read].copy_from_slice(&self.chunk[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+impl Drop for StreamReader<'_> {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        if let Err(e) = self.parent.call_method(IO::Close {
+            handle: self.handle.clone(),
+        }) {
+            warn!("Failed to close stream {}: {e:?}", self.handle);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value as Json;
+
+use crate::protocol::cdp::types::Event;
+use crate::protocol::cdp::Media;
+
+/// Something a media player (a `<video>` or `<audio>` element) reported, as shown in the Media
+/// panel of DevTools. See `Tab.on_media_player_event`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaPlayerEvent {
+    Created(Media::Player),
+    /// Properties like the resolution, codecs or duration were set or changed.
+    PropertiesChanged {
+        player_id: Media::PlayerId,
+        properties: Vec<Media::PlayerProperty>,
+    },
+    /// State changes, like starting to play or pausing.
+    EventsAdded {
+        player_id: Media::PlayerId,
+        events: Vec<Media::PlayerEvent>,
+    },
+    MessagesLogged {
+        player_id: Media::PlayerId,
+        messages: Vec<Media::PlayerMessage>,
+    },
+    /// Errors decoding or playing the media.
+    ErrorsRaised {
+        player_id: Media::PlayerId,
+        errors: Vec<Media::PlayerError>,
+    },
+}
+
+impl MediaPlayerEvent {
+    pub(crate) fn from_event(event: &Event) -> Option<Self> {
+        Some(match event {
+            Event::MediaPlayerCreated(ev) => Self::Created(ev.params.player.clone()),
+            Event::MediaPlayerPropertiesChanged(ev) => Self::PropertiesChanged {
+                player_id: ev.params.player_id.clone(),
+                properties: ev.params.properties.clone(),
+            },
+            Event::MediaPlayerEventsAdded(ev) => Self::EventsAdded {
+                player_id: ev.params.player_id.clone(),
+                events: ev.params.events.clone(),
+            },
+            Event::MediaPlayerMessagesLogged(ev) => Self::MessagesLogged {
+                player_id: ev.params.player_id.clone(),
+                messages: ev.params.messages.clone(),
+            },
+            Event::MediaPlayerErrorsRaised(ev) => Self::ErrorsRaised {
+                player_id: ev.params.player_id.clone(),
+                errors: ev.params.errors.clone(),
+            },
+            _ => return None,
+        })
+    }
+
+    pub fn player_id(&self) -> &Media::PlayerId {
+        match self {
+            Self::Created(player) => &player.player_id,
+            Self::PropertiesChanged { player_id, .. }
+            | Self::EventsAdded { player_id, .. }
+            | Self::MessagesLogged { player_id, .. }
+            | Self::ErrorsRaised { player_id, .. } => player_id,
+        }
+    }
+}
+
+/// One of the statistics an `RTCPeerConnection` reports through `getStats()`, see
+/// `Tab.get_rtc_stats`.
+///
+/// The members most useful to assert on are typed; the rest, which depend on the `stats_type`,
+/// see <https://www.w3.org/TR/webrtc-stats/>, are in `other`. Bitrates are the
+/// difference in bytes between two reports divided by the difference in their timestamps.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RtcStats {
+    pub id: String,
+    /// E.g. `inbound-rtp`, `outbound-rtp`, `candidate-pair` or `transport`.
+    #[serde(rename = "type")]
+    pub stats_type: String,
+    /// Milliseconds since the epoch.
+    pub timestamp: f64,
+    /// `audio` or `video`, for RTP streams.
+    pub kind: Option<String>,
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub packets_sent: Option<u64>,
+    pub packets_received: Option<u64>,
+    pub packets_lost: Option<i64>,
+    /// In seconds.
+    pub jitter: Option<f64>,
+    /// In seconds.
+    pub current_round_trip_time: Option<f64>,
+    pub frame_width: Option<u32>,
+    pub frame_height: Option<u32>,
+    pub frames_per_second: Option<f64>,
+    #[serde(flatten)]
+    pub other: HashMap<String, Json>,
+}
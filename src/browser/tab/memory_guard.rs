@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::logging::{debug, warn};
+use anyhow::Result;
+
+use crate::browser::transport::{SessionId, Transport};
+use crate::protocol::cdp::{Page, Performance, Target};
+
+/// How often a tab's memory use is checked against its limit.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many checks are skipped after recycling a tab, to give the old page's memory time to be
+/// collected before holding the new page to the limit.
+const CHECKS_SKIPPED_AFTER_RECYCLING: u32 = 5;
+
+/// What to do with a tab whose page uses more memory than its limit, see
+/// `Tab::set_memory_limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLimitAction {
+    /// Closes the tab.
+    Close,
+    /// Reloads the page, which starts it over with a fresh heap, and keeps watching it.
+    Recycle,
+}
+
+/// The size of the page's JavaScript heap in bytes, from its performance metrics.
+fn js_heap_used(metrics: &[Performance::Metric]) -> Option<f64> {
+    metrics
+        .iter()
+        .find(|metric| metric.name == "JSHeapUsedSize")
+        .map(|metric| metric.value)
+}
+
+/// A thread checking a tab's memory use until `stop` is set, the tab is closed or it can't be
+/// checked any more (e.g. because the browser went away).
+pub(crate) struct MemoryGuard {
+    pub(crate) transport: Arc<Transport>,
+    pub(crate) session_id: SessionId,
+    pub(crate) target_id: String,
+    pub(crate) limit_bytes: u64,
+    pub(crate) action: MemoryLimitAction,
+    pub(crate) stop: Arc<AtomicBool>,
+}
+
+impl MemoryGuard {
+    /// Starts the thread, which calls `on_exit` once it stops, however it does, with whether it
+    /// closed the tab.
+    pub(crate) fn spawn<F>(self, on_exit: F)
+    where
+        F: FnOnce(bool) + Send + 'static,
+    {
+        thread::spawn(move || {
+            let mut checks_to_skip = 0;
+            let mut closed_tab = false;
+            while !self.stop.load(Ordering::SeqCst) {
+                thread::sleep(CHECK_INTERVAL);
+                if checks_to_skip > 0 {
+                    checks_to_skip -= 1;
+                    continue;
+                }
+                match self.check() {
+                    Ok(true) => {}
+                    Ok(false) if self.action == MemoryLimitAction::Close => {
+                        closed_tab = true;
+                        break;
+                    }
+                    Ok(false) => checks_to_skip = CHECKS_SKIPPED_AFTER_RECYCLING,
+                    Err(err) => {
+                        debug!("Stopped checking memory of tab {}: {err:?}", self.target_id);
+                        break;
+                    }
+                }
+            }
+            on_exit(closed_tab);
+        });
+    }
+
+    /// Checks the page's memory use, acting on it if it's over the limit. Returns whether the
+    /// page stayed as it was.
+    fn check(&self) -> Result<bool> {
+        let metrics = self
+            .transport
+            .call_method_on_target(self.session_id.clone(), Performance::GetMetrics(None))?
+            .metrics;
+        let Some(used) = js_heap_used(&metrics) else {
+            return Ok(true);
+        };
+        #[allow(clippy::cast_precision_loss)]
+        if used <= self.limit_bytes as f64 {
+            return Ok(true);
+        }
+        if self.stop.load(Ordering::SeqCst) {
+            return Ok(true);
+        }
+
+        warn!(
+            "Tab {} uses {used} bytes of JS heap, over its limit of {}, {:?}",
+            self.target_id, self.limit_bytes, self.action
+        );
+        match self.action {
+            MemoryLimitAction::Close => {
+                self.transport.call_method_on_browser(Target::CloseTarget {
+                    target_id: self.target_id.clone(),
+                })?;
+            }
+            MemoryLimitAction::Recycle => {
+                self.transport.call_method_on_target(
+                    self.session_id.clone(),
+                    Page::Reload {
+                        ignore_cache: None,
+                        script_to_evaluate_on_load: None,
+                    },
+                )?;
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_js_heap_size() {
+        let metric = |name: &str, value: f64| Performance::Metric {
+            name: name.to_string(),
+            value,
+        };
+        let metrics = vec![metric("Nodes", 10.0), metric("JSHeapUsedSize", 2048.0)];
+        assert_eq!(Some(2048.0), js_heap_used(&metrics));
+        assert_eq!(None, js_heap_used(&metrics[..1]));
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+
+use crate::browser::transport::{SessionId, Transport};
+use crate::protocol::cdp::{
+    Fetch::{self, events::RequestPausedEvent, ContinueRequest, FulfillRequest, HeaderEntry},
+    Network,
+};
+
+use super::{RequestIntercept, RequestPausedDecision};
+
+/// A request which is about to be sent, as seen by a [`Middleware`].
+///
+/// Changes to `url` and `headers` are applied to the request which is actually sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterceptedRequest {
+    pub url: String,
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+    pub resource_type: Network::ResourceType,
+}
+
+/// A response which has been received but not yet handed to the page, as seen by a
+/// [`Middleware`].
+///
+/// If any middleware changes it, the page gets the changed response instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterceptedResponse {
+    pub url: String,
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl InterceptedResponse {
+    /// Replaces the body with `body`, serialized as JSON, and sets a matching `Content-Type`.
+    pub fn set_json_body<T: serde::Serialize>(&mut self, body: &T) -> Result<()> {
+        self.body = serde_json::to_vec(body)?;
+        self.headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+        self.headers
+            .push(("Content-Type".to_string(), "application/json".to_string()));
+        Ok(())
+    }
+}
+
+/// One step of a tab's (or a context's) request handling, see `Tab::add_middleware`.
+///
+/// Middleware runs in the order it was added, each step seeing the changes made by the ones
+/// before it.
+pub trait Middleware {
+    /// Called for every request before it is sent.
+    fn on_request(&self, _request: &mut InterceptedRequest) {}
+
+    /// Whether `on_response` should be called for `request`. Intercepting a response means
+    /// fetching its whole body over the DevTools protocol, so only opt in for the requests you
+    /// need.
+    fn intercepts_response(&self, _request: &InterceptedRequest) -> bool {
+        false
+    }
+
+    /// Called for the response to every request that `intercepts_response` opted in to.
+    fn on_response(&self, _response: &mut InterceptedResponse) {}
+}
+
+pub type SyncMiddleware = dyn Middleware + Send + Sync;
+
+/// Identifies a middleware within the chain it was added to, so that it can be removed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MiddlewareId(u64);
+
+/// An ordered list of middleware.
+#[derive(Default)]
+pub struct MiddlewareChain {
+    next_id: AtomicU64,
+    middlewares: Mutex<Vec<(MiddlewareId, Arc<SyncMiddleware>)>>,
+}
+
+impl MiddlewareChain {
+    pub fn add(&self, middleware: Arc<SyncMiddleware>) -> MiddlewareId {
+        let id = MiddlewareId(self.next_id.fetch_add(1, Ordering::SeqCst));
+        self.middlewares.lock().unwrap().push((id, middleware));
+        id
+    }
+
+    /// Returns whether there was a middleware with the given ID to remove.
+    pub fn remove(&self, id: MiddlewareId) -> bool {
+        let mut middlewares = self.middlewares.lock().unwrap();
+        let len_before = middlewares.len();
+        middlewares.retain(|(middleware_id, _)| *middleware_id != id);
+        middlewares.len() != len_before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.middlewares.lock().unwrap().is_empty()
+    }
+
+    pub fn middlewares(&self) -> Vec<Arc<SyncMiddleware>> {
+        self.middlewares
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, middleware)| Arc::clone(middleware))
+            .collect()
+    }
+}
+
+fn headers_from_json(headers: &Network::Headers) -> Vec<(String, String)> {
+    let Some(serde_json::Value::Object(headers)) = &headers.0 else {
+        return Vec::new();
+    };
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .as_str()
+                .map_or_else(|| value.to_string(), str::to_string);
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+fn header_entries(headers: &[(String, String)]) -> Vec<HeaderEntry> {
+    headers
+        .iter()
+        .map(|(name, value)| HeaderEntry {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+/// The middleware which asked to see the response to each request, by the request's ID.
+///
+/// `intercepts_response` is asked when the request is paused, with the request as the page made
+/// it, and its answer is kept for when the response arrives.
+pub(crate) type ResponseInterceptions = Mutex<HashMap<Fetch::RequestId, Vec<Arc<SyncMiddleware>>>>;
+
+/// Fills in whatever `interceptor_request` leaves unchanged with the changes middleware made.
+fn merge_continue_requests(
+    interceptor_request: ContinueRequest,
+    middleware_request: ContinueRequest,
+) -> ContinueRequest {
+    ContinueRequest {
+        url: interceptor_request.url.or(middleware_request.url),
+        method: interceptor_request.method.or(middleware_request.method),
+        post_data: interceptor_request
+            .post_data
+            .or(middleware_request.post_data),
+        headers: interceptor_request.headers.or(middleware_request.headers),
+        intercept_response: interceptor_request
+            .intercept_response
+            .or(middleware_request.intercept_response),
+        ..interceptor_request
+    }
+}
+
+/// Runs `middlewares` over a paused request (or response), returning how to resume it.
+///
+/// The tab's request interceptor runs after the middleware, on the request as the page made it.
+/// If it fulfills or fails the request, that wins; if it continues the request, the changes it
+/// makes are applied on top of the middleware's. Responses which no middleware intercepts are
+/// left to the interceptor too.
+pub(crate) fn handle_request_paused(
+    middlewares: &[Arc<SyncMiddleware>],
+    interceptor: &RequestIntercept,
+    response_interceptions: &ResponseInterceptions,
+    transport: &Arc<Transport>,
+    session_id: &SessionId,
+    event: RequestPausedEvent,
+) -> Result<RequestPausedDecision> {
+    let intercept = |event| interceptor.intercept(Arc::clone(transport), session_id.clone(), event);
+    let params = &event.params;
+
+    let intercepting = response_interceptions
+        .lock()
+        .unwrap()
+        .remove(&params.request_id);
+    if params.response_error_reason.is_some() {
+        return Ok(match intercepting {
+            Some(_) => RequestPausedDecision::Continue(None),
+            None => intercept(event),
+        });
+    }
+
+    let Some(status) = params.response_status_code else {
+        let original = InterceptedRequest {
+            url: params.request.url.clone(),
+            method: params.request.method.clone(),
+            headers: headers_from_json(&params.request.headers),
+            resource_type: params.resource_Type.clone(),
+        };
+        let mut request = original.clone();
+        for middleware in middlewares {
+            middleware.on_request(&mut request);
+        }
+        let intercepting: Vec<_> = middlewares
+            .iter()
+            .filter(|middleware| middleware.intercepts_response(&original))
+            .cloned()
+            .collect();
+        let intercept_response = !intercepting.is_empty();
+        let request_id = params.request_id.clone();
+
+        let middleware_request =
+            (request != original || intercept_response).then(|| ContinueRequest {
+                request_id: request_id.clone(),
+                url: (request.url != original.url).then(|| request.url.clone()),
+                method: None,
+                post_data: None,
+                headers: (request.headers != original.headers)
+                    .then(|| header_entries(&request.headers)),
+                intercept_response: intercept_response.then_some(true),
+            });
+        let decision = match (intercept(event), middleware_request) {
+            (RequestPausedDecision::Continue(None), middleware_request) => {
+                RequestPausedDecision::Continue(middleware_request)
+            }
+            (
+                RequestPausedDecision::Continue(Some(interceptor_request)),
+                Some(middleware_request),
+            ) => RequestPausedDecision::Continue(Some(merge_continue_requests(
+                interceptor_request,
+                middleware_request,
+            ))),
+            (decision, _) => decision,
+        };
+        if intercept_response && matches!(decision, RequestPausedDecision::Continue(_)) {
+            response_interceptions
+                .lock()
+                .unwrap()
+                .insert(request_id, intercepting);
+        }
+        return Ok(decision);
+    };
+
+    let Some(intercepting) = intercepting else {
+        return Ok(intercept(event));
+    };
+
+    let body = transport.call_method_on_target(
+        session_id.clone(),
+        Fetch::GetResponseBody {
+            request_id: params.request_id.clone(),
+        },
+    )?;
+    let body = if body.base_64_encoded {
+        base64::decode(body.body)?
+    } else {
+        body.body.into_bytes()
+    };
+
+    let mut response = InterceptedResponse {
+        url: params.request.url.clone(),
+        status,
+        headers: params
+            .response_headers
+            .iter()
+            .flatten()
+            .map(|header| (header.name.clone(), header.value.clone()))
+            .collect(),
+        body,
+    };
+    let original = response.clone();
+    for middleware in &intercepting {
+        middleware.on_response(&mut response);
+    }
+    if response == original {
+        return Ok(RequestPausedDecision::Continue(None));
+    }
+
+    // The body is re-encoded for the page, so its original length no longer applies
+    response
+        .headers
+        .retain(|(name, _)| !name.eq_ignore_ascii_case("content-length"));
+
+    Ok(RequestPausedDecision::Fulfill(FulfillRequest {
+        request_id: params.request_id.clone(),
+        response_code: response.status,
+        response_headers: Some(header_entries(&response.headers)),
+        binary_response_headers: None,
+        body: Some(base64::encode(&response.body)),
+        response_phrase: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Noop;
+
+    impl Middleware for Noop {}
+
+    #[test]
+    fn removes_middleware_by_id() {
+        let chain = MiddlewareChain::default();
+        let first = chain.add(Arc::new(Noop));
+        let second = chain.add(Arc::new(Noop));
+        assert_ne!(first, second);
+
+        assert!(chain.remove(first));
+        assert!(!chain.remove(first));
+        assert_eq!(1, chain.middlewares().len());
+    }
+
+    #[test]
+    fn reads_headers_object() {
+        let headers = Network::Headers(Some(serde_json::json!({ "Accept": "*/*" })));
+        assert_eq!(
+            vec![("Accept".to_string(), "*/*".to_string())],
+            headers_from_json(&headers)
+        );
+    }
+
+    #[test]
+    fn interceptor_changes_override_middleware_changes() {
+        let continue_request =
+            |url: Option<&str>, headers: Option<Vec<HeaderEntry>>| ContinueRequest {
+                request_id: "1".to_string(),
+                url: url.map(str::to_string),
+                method: None,
+                post_data: None,
+                headers,
+                intercept_response: None,
+            };
+        let headers = header_entries(&[("X-Test".to_string(), "1".to_string())]);
+        let merged = merge_continue_requests(
+            continue_request(Some("https://interceptor.test/"), None),
+            continue_request(Some("https://middleware.test/"), Some(headers.clone())),
+        );
+        assert_eq!(
+            continue_request(Some("https://interceptor.test/"), Some(headers)),
+            merged
+        );
+    }
+}
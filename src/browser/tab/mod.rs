@@ -1,27 +1,50 @@
-use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::sync::{mpsc, Arc, Mutex, RwLock, Weak};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{
-    collections::HashMap,
-    sync::atomic::{AtomicBool, Ordering},
+    collections::{HashMap, HashSet, VecDeque},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
 };
 
 use anyhow::{Error, Result};
 
+use rand::Rng;
 use thiserror::Error;
 
-use log::{debug, error, info, trace, warn};
+use crate::logging::{debug, error, info, trace, warn};
 
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
 use serde_json::{json, Value as Json};
 
+use crash_recovery::{callable_while_crashed, CrashRecovery, TabCrashed, TabSetup};
+use debug_snapshot::{DebugHistory, DebugSnapshot, Interceptors};
+use dom_storage::DomStorage;
+pub use domains::Domain;
+use domains::DomainStates;
 use element::Element;
+use indexed_db::IndexedDb;
+use io::StreamReader;
+use media::{MediaPlayerEvent, RtcStats};
+use memory_guard::{MemoryGuard, MemoryLimitAction};
+use middleware::{MiddlewareChain, MiddlewareId, ResponseInterceptions, SyncMiddleware};
+use navigation_policy::NavigationPolicy;
+use network_activity::NetworkActivity;
+use page_stability::{PageSnapshot, STABILITY_PROBE};
 use point::Point;
+use service_worker::{ServiceWorkerRegistry, ServiceWorkers};
+use source_map::ScriptSourceMaps;
+use waterfall::{NavigationWaterfall, WaterfallRecorder};
+use worker::Worker;
 
 use crate::protocol::cdp::{
     types::{Event, Method},
-    Browser, Debugger, Emulation, Fetch, Input, Log, Network, Page, Profiler, Runtime, Target, DOM,
+    Animation, Audits, BackgroundService, BluetoothEmulation, Browser, DOMSnapshot, Debugger,
+    DeviceAccess, DeviceOrientation, Emulation, Fetch, Input, Inspector, Log, Media, Network, Page,
+    Performance, Profiler, Runtime, Schema, Security, Target, CSS, DOM, IO,
 };
 
 use Runtime::AddBinding;
@@ -53,12 +76,33 @@ use crate::util;
 use crate::types::{Bounds, CurrentBounds, PrintToPdfOptions, RemoteError};
 
 use super::transport::SessionId;
-use crate::browser::transport::Transport;
+use crate::browser::scheduler::{NavigationPermit, NavigationScheduler};
+use crate::browser::transport::{MethodDestination, RetryPolicy, Transport};
 use std::thread::sleep;
 
+pub mod crash_recovery;
+pub mod debug_snapshot;
+mod determinism;
+pub mod dom_storage;
+mod domains;
 pub mod element;
+mod extract;
+pub mod indexed_db;
+pub mod io;
 mod keys;
+pub mod media;
+pub mod memory_guard;
+pub mod middleware;
+pub mod navigation_policy;
+pub mod network_activity;
+mod page_stability;
 pub mod point;
+pub mod service_worker;
+pub mod source_map;
+pub mod waterfall;
+pub mod worker;
+
+pub use Emulation::SetEmulatedVisionDeficiencyTypeOption as VisionDeficiency;
 
 #[derive(Debug)]
 pub enum RequestPausedDecision {
@@ -67,6 +111,191 @@ pub enum RequestPausedDecision {
     Continue(Option<ContinueRequest>),
 }
 
+/// How far a document has to have loaded for `Tab.set_content` to return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitUntil {
+    /// The markup has been parsed (`DOMContentLoaded`), subresources may still be loading.
+    DomContentLoaded,
+    /// The markup has been parsed and every subresource (images, stylesheets...) has loaded.
+    Load,
+}
+
+/// A problem with a page the browser found, as listed in the DevTools Issues panel, see
+/// `Tab.take_issues`.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Issue {
+    Cookie(Audits::CookieIssueDetails),
+    MixedContent(Audits::MixedContentIssueDetails),
+    Cors(Audits::CorsIssueDetails),
+    /// Any other kind of issue, as the browser reported it.
+    Other(Audits::InspectorIssue),
+}
+
+impl From<Audits::InspectorIssue> for Issue {
+    fn from(issue: Audits::InspectorIssue) -> Self {
+        let details = issue.details.clone();
+        match issue.code {
+            Audits::InspectorIssueCode::CookieIssue if details.cookie_issue_details.is_some() => {
+                Issue::Cookie(details.cookie_issue_details.unwrap())
+            }
+            Audits::InspectorIssueCode::MixedContentIssue
+                if details.mixed_content_issue_details.is_some() =>
+            {
+                Issue::MixedContent(details.mixed_content_issue_details.unwrap())
+            }
+            Audits::InspectorIssueCode::CorsIssue if details.cors_issue_details.is_some() => {
+                Issue::Cors(details.cors_issue_details.unwrap())
+            }
+            _ => Issue::Other(issue),
+        }
+    }
+}
+
+/// Where a message seen by `Tab.on_console_message` came from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ConsoleMessageSource {
+    /// The page called the `console` API, e.g. `console.warn`.
+    ConsoleApi(Runtime::ConsoleAPICalledEventTypeOption),
+    /// The browser logged it itself, e.g. for blocked mixed content, a CORS failure, an
+    /// intervention or a deprecation.
+    Browser(Log::LogEntrySource),
+}
+
+/// A message logged to a page's console, by the page or by the browser.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsoleMessage {
+    pub source: ConsoleMessageSource,
+    pub level: Log::LogEntryLevel,
+    pub text: String,
+    /// The resource the message is about, for the browser's messages.
+    pub url: Option<String>,
+    pub line_number: Option<u32>,
+    /// Milliseconds since the epoch.
+    pub timestamp: f64,
+    pub stack_trace: Option<Runtime::StackTrace>,
+}
+
+impl ConsoleMessage {
+    fn from_console_api(params: &Runtime::events::ConsoleAPICalledEventParams) -> Self {
+        use Runtime::ConsoleAPICalledEventTypeOption as Type;
+
+        let level = match params.Type {
+            Type::Error | Type::Assert => Log::LogEntryLevel::Error,
+            Type::Warning => Log::LogEntryLevel::Warning,
+            Type::Debug => Log::LogEntryLevel::Verbose,
+            _ => Log::LogEntryLevel::Info,
+        };
+        // The way the console shows them: strings without quotes, objects by description
+        let text = params
+            .args
+            .iter()
+            .map(|arg| match &arg.value {
+                Some(Json::String(string)) => string.clone(),
+                Some(value) => value.to_string(),
+                None => arg
+                    .unserializable_value
+                    .clone()
+                    .or_else(|| arg.description.clone())
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let top_frame = params
+            .stack_trace
+            .as_ref()
+            .and_then(|trace| trace.call_frames.first());
+
+        Self {
+            source: ConsoleMessageSource::ConsoleApi(params.Type.clone()),
+            level,
+            text,
+            url: top_frame.map(|frame| frame.url.clone()),
+            line_number: top_frame.map(|frame| frame.line_number),
+            timestamp: params.timestamp,
+            stack_trace: params.stack_trace.clone(),
+        }
+    }
+
+    fn from_log_entry(entry: &Log::LogEntry) -> Self {
+        Self {
+            source: ConsoleMessageSource::Browser(entry.source.clone()),
+            level: entry.level.clone(),
+            text: entry.text.clone(),
+            url: entry.url.clone(),
+            line_number: entry.line_number,
+            timestamp: entry.timestamp,
+            stack_trace: entry.stack_trace.clone(),
+        }
+    }
+}
+
+/// A change to the part of a page watched with `Tab.on_dom_mutation`.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum DomMutation {
+    ChildNodeInserted {
+        parent_node_id: NodeId,
+        /// The node the new one was inserted after, 0 if it's the first child.
+        previous_node_id: NodeId,
+        node: Node,
+    },
+    ChildNodeRemoved {
+        parent_node_id: NodeId,
+        node_id: NodeId,
+    },
+    AttributeModified {
+        node_id: NodeId,
+        name: String,
+        value: String,
+    },
+    AttributeRemoved {
+        node_id: NodeId,
+        name: String,
+    },
+    CharacterDataModified {
+        node_id: NodeId,
+        character_data: String,
+    },
+}
+
+/// Which way a frame seen by `Tab.on_websocket_frame` was travelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketFrameDirection {
+    Sent,
+    Received,
+}
+
+/// A WebSocket frame sent or received by a page, see `Tab.on_websocket_frame`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketFrameEvent {
+    pub request_id: Network::RequestId,
+    /// The URL the WebSocket connected to, if it was created after the listener was added.
+    pub url: Option<String>,
+    pub direction: WebSocketFrameDirection,
+    /// 1 for text frames, 2 for binary ones.
+    pub opcode: u8,
+    /// The frame's payload; base64 encoded for binary frames.
+    pub payload_data: String,
+}
+
+/// A response returned by `Tab.wait_for_response`, along with its body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedResponse {
+    pub request_id: Network::RequestId,
+    /// The response's URL, status, headers and so on.
+    pub response: Network::Response,
+    pub body: Vec<u8>,
+}
+
+impl CapturedResponse {
+    /// The body, decoded as UTF-8.
+    pub fn body_text(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(&self.body)?)
+    }
+}
+
 #[rustfmt::skip]
 pub type ResponseHandler = Box<
     dyn Fn(
@@ -114,6 +343,72 @@ where
 
 type RequestIntercept = dyn RequestInterceptor + Send + Sync;
 
+/// Decides what happens to a request paused by the `Fetch` domain, and carries it out.
+fn handle_request_paused(
+    transport: &Arc<Transport>,
+    session_id: &SessionId,
+    policy: Option<&NavigationPolicy>,
+    middlewares: &[Arc<SyncMiddleware>],
+    interceptor: &RequestIntercept,
+    response_interceptions: &ResponseInterceptions,
+    event: RequestPausedEvent,
+) {
+    // The policy goes first, so that nothing can let through what it blocks
+    let blocked = policy.and_then(|policy| policy.check_paused_request(&event.params).err());
+    let decision = if let Some(blocked) = blocked {
+        warn!("{blocked}");
+        RequestPausedDecision::Fail(FailRequest {
+            request_id: event.params.request_id.clone(),
+            error_reason: Network::ErrorReason::BlockedByClient,
+        })
+    } else {
+        middleware::handle_request_paused(
+            middlewares,
+            interceptor,
+            response_interceptions,
+            transport,
+            session_id,
+            event.clone(),
+        )
+        .unwrap_or_else(|err| {
+            warn!("Middleware failed to handle paused request: {err:?}");
+            RequestPausedDecision::Continue(None)
+        })
+    };
+    let result = match decision {
+        RequestPausedDecision::Continue(continue_request) => {
+            if let Some(continue_request) = continue_request {
+                transport
+                    .call_method_on_target(session_id.clone(), continue_request)
+                    .map(|_| ())
+            } else {
+                transport
+                    .call_method_on_target(
+                        session_id.clone(),
+                        ContinueRequest {
+                            request_id: event.params.request_id,
+                            url: None,
+                            method: None,
+                            post_data: None,
+                            headers: None,
+                            intercept_response: None,
+                        },
+                    )
+                    .map(|_| ())
+            }
+        }
+        RequestPausedDecision::Fulfill(fulfill_request) => transport
+            .call_method_on_target(session_id.clone(), fulfill_request)
+            .map(|_| ()),
+        RequestPausedDecision::Fail(fail_request) => transport
+            .call_method_on_target(session_id.clone(), fail_request)
+            .map(|_| ()),
+    };
+    if result.is_err() {
+        warn!("Tried to handle request after connection was closed");
+    }
+}
+
 pub trait EventListener<T> {
     fn on_event(&self, event: &T);
 }
@@ -124,22 +419,55 @@ impl<T, F: Fn(&T) + Send + Sync> EventListener<T> for F {
     }
 }
 
+/// A Rust function which page JavaScript can call, see [`Tab::expose_function`].
+///
+/// It receives the JS call's arguments as a JSON array, and its return value is serialized and
+/// used to resolve the promise returned to the page. If it can't be serialized, the promise is
+/// rejected instead.
 pub trait Binding {
-    fn call_binding(&self, data: Json);
+    fn call_binding(&self, args: Json) -> Result<Json>;
 }
 
-impl<T: Fn(Json) + Send + Sync> Binding for T {
-    fn call_binding(&self, data: Json) {
-        self(data);
+impl<T, R> Binding for T
+where
+    T: Fn(Json) -> R + Send + Sync,
+    R: Serialize,
+{
+    fn call_binding(&self, args: Json) -> Result<Json> {
+        Ok(serde_json::to_value(self(args))?)
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct BindingPayload {
+    seq: u64,
+    args: Json,
+}
+
 pub type SafeBinding = dyn Binding + Send + Sync;
 
 pub type FunctionBinding = HashMap<String, Arc<SafeBinding>>;
 
+/// Processes every PDF the tab prints before it's returned, see `Tab::set_pdf_post_processor`.
+pub type PdfPostProcessor = dyn Fn(Vec<u8>) -> Result<Vec<u8>> + Send + Sync;
+
 // type SyncSendEvent = dyn EventListener<Event> + Send + Sync;
 
+/// A response matched by `Tab.wait_for_response`, and how loading its body ended (if it has).
+type MatchedResponse = (
+    Network::RequestId,
+    Network::Response,
+    Option<Result<(), String>>,
+);
+
+/// The URL of a tab's main frame and how many times it has navigated, as reported by
+/// `Page.frameNavigated` and `Page.navigatedWithinDocument` events.
+#[derive(Debug)]
+struct MainFrameNavigation {
+    url: String,
+    count: u64,
+}
+
 /// A handle to a single page. Exposes methods for simulating user actions (clicking,
 /// typing), and also for getting information about the DOM and other parts of the page.
 pub struct Tab {
@@ -156,6 +484,52 @@ pub struct Tab {
     page_bindings: Arc<Mutex<FunctionBinding>>,
     event_listeners: Arc<Mutex<Vec<Arc<SyncSendEvent>>>>,
     slow_motion_multiplier: Arc<RwLock<f64>>, // there's no AtomicF64, otherwise would use that
+    service_worker_registry: Arc<Mutex<ServiceWorkerRegistry>>,
+    workers: Arc<Mutex<Vec<Arc<Worker>>>>,
+    attaching_to_workers: AtomicBool,
+    main_frame_navigation: Arc<Mutex<MainFrameNavigation>>,
+    awaited_navigation_count: AtomicU64,
+    network_activity: Arc<Mutex<NetworkActivity>>,
+    waterfall: Arc<Mutex<WaterfallRecorder>>,
+    intercepted_drag: Arc<Mutex<Option<Input::DragData>>>,
+    emulated_media: Mutex<Emulation::SetEmulatedMedia>,
+    /// The sensors whose readings are currently overridden.
+    sensor_overrides: Mutex<Vec<Emulation::SensorType>>,
+    bluetooth_emulated: AtomicBool,
+    security_state: Arc<Mutex<Option<Security::VisibleSecurityState>>>,
+    /// The ids of the current document's animations, once the `Animation` domain is enabled.
+    animations: Arc<Mutex<Vec<String>>>,
+    /// Issues reported since the last `take_issues`, once the `Audits` domain is enabled.
+    issues: Arc<Mutex<Vec<Issue>>>,
+    domains: Arc<DomainStates>,
+    middleware: Arc<MiddlewareChain>,
+    context_middleware: Arc<Mutex<Option<Arc<MiddlewareChain>>>>,
+    response_interceptions: Arc<ResponseInterceptions>,
+    navigation_policy: Arc<RwLock<Option<NavigationPolicy>>>,
+    navigation_scheduler: Mutex<Option<Arc<NavigationScheduler>>>,
+    navigation_permit: Arc<Mutex<Option<NavigationPermit>>>,
+    protocol_domains: Mutex<Option<Vec<Schema::Domain>>>,
+    destroyed: AtomicBool,
+    pdf_post_processor: RwLock<Option<Arc<PdfPostProcessor>>>,
+    /// Popups this tab opened which haven't been returned by `wait_for_popup` yet.
+    popups: Mutex<VecDeque<Weak<Tab>>>,
+    /// Whether the renderer has crashed and the page hasn't been reloaded since.
+    crashed: Arc<AtomicBool>,
+    crash_recovery: RwLock<CrashRecovery>,
+    /// Whether `crash_recovery` was set on the tab itself, rather than following
+    /// `Browser::set_restore_crashed_tabs`.
+    crash_recovery_chosen: AtomicBool,
+    setup: Mutex<TabSetup>,
+    /// The tab opened in this one's place after it crashed, see `CrashRecovery::Recreate`.
+    replaced_by: Mutex<Option<Weak<Tab>>>,
+    /// Stops the thread enforcing the tab's memory limit, if it has one.
+    memory_guard: Arc<Mutex<Option<Arc<AtomicBool>>>>,
+    debug_history: Arc<Mutex<DebugHistory>>,
+    /// Whether calls and events go into `debug_history`, see `start_debug_recording`.
+    debug_recording: Arc<AtomicBool>,
+    /// The domains held for each event listener, see `add_event_listener_holding`.
+    listener_domains: Mutex<Vec<(Weak<SyncSendEvent>, Domain, String)>>,
+    next_listener_holder_id: AtomicU64,
 }
 
 #[derive(Debug, Error)]
@@ -172,10 +546,59 @@ pub struct NavigationFailed {
 #[error("No LocalStorage item was found")]
 pub struct NoLocalStorageItemFound {}
 
+#[derive(Debug, Error)]
+#[error("Function failed: {}", error_text)]
+pub struct FunctionFailed {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("The function returned a falsy value")]
+pub struct FunctionNotSatisfied {}
+
+#[derive(Debug, Error)]
+#[error("There is no history entry to navigate to")]
+pub struct NoHistoryEntry {}
+
+#[derive(Debug, Error)]
+#[error("The document hasn't finished loading")]
+pub struct DocumentNotReady {}
+
+#[derive(Debug, Error)]
+#[error("Loading the response failed: {}", error_text)]
+pub struct ResponseLoadingFailed {
+    error_text: String,
+}
+
 #[derive(Debug, Error)]
 #[error("No UserAgent evaluated")]
 pub struct NoUserAgentEvaluated {}
 
+#[derive(Debug, Error)]
+#[error("Clipboard access failed: {}", error_text)]
+pub struct ClipboardAccessFailed {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Couldn't get the WebRTC stats: {}", error_text)]
+pub struct RtcStatsUnavailable {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Extracting data from the page failed: {}", error_text)]
+pub struct ExtractionFailed {
+    error_text: String,
+}
+
+#[derive(Debug, Error)]
+#[error("The delay range {start:?}..={end:?} is empty, as it starts after it ends")]
+pub struct InvalidDelayRange {
+    pub start: Duration,
+    pub end: Duration,
+}
+
 impl NoElementFound {
     pub fn map(error: Error) -> Error {
         match error.downcast::<RemoteError>() {
@@ -209,6 +632,10 @@ impl Tab {
 
         debug!("New tab attached with session ID: {:?}", session_id);
 
+        let main_frame_navigation = MainFrameNavigation {
+            url: target_info.url.clone(),
+            count: 0,
+        };
         let target_info_mutex = Arc::new(Mutex::new(target_info));
 
         let tab = Self {
@@ -231,10 +658,50 @@ impl Tab {
             default_timeout: Arc::new(RwLock::new(Duration::from_secs(600))),
             event_listeners: Arc::new(Mutex::new(Vec::new())),
             slow_motion_multiplier: Arc::new(RwLock::new(0.0)),
+            service_worker_registry: Arc::new(Mutex::new(ServiceWorkerRegistry::default())),
+            workers: Arc::new(Mutex::new(Vec::new())),
+            attaching_to_workers: AtomicBool::new(false),
+            main_frame_navigation: Arc::new(Mutex::new(main_frame_navigation)),
+            awaited_navigation_count: AtomicU64::new(0),
+            network_activity: Arc::new(Mutex::new(NetworkActivity::default())),
+            waterfall: Arc::new(Mutex::new(WaterfallRecorder::default())),
+            intercepted_drag: Arc::new(Mutex::new(None)),
+            emulated_media: Mutex::new(Emulation::SetEmulatedMedia {
+                media: None,
+                features: None,
+            }),
+            sensor_overrides: Mutex::new(Vec::new()),
+            bluetooth_emulated: AtomicBool::new(false),
+            security_state: Arc::new(Mutex::new(None)),
+            animations: Arc::new(Mutex::new(Vec::new())),
+            issues: Arc::new(Mutex::new(Vec::new())),
+            domains: Arc::new(DomainStates::default()),
+            middleware: Arc::new(MiddlewareChain::default()),
+            context_middleware: Arc::new(Mutex::new(None)),
+            response_interceptions: Arc::new(Mutex::new(HashMap::new())),
+            navigation_policy: Arc::new(RwLock::new(None)),
+            navigation_scheduler: Mutex::new(None),
+            navigation_permit: Arc::new(Mutex::new(None)),
+            protocol_domains: Mutex::new(None),
+            destroyed: AtomicBool::new(false),
+            pdf_post_processor: RwLock::new(None),
+            popups: Mutex::new(VecDeque::new()),
+            crashed: Arc::new(AtomicBool::new(false)),
+            crash_recovery: RwLock::new(CrashRecovery::default()),
+            crash_recovery_chosen: AtomicBool::new(false),
+            setup: Mutex::new(TabSetup::default()),
+            replaced_by: Mutex::new(None),
+            memory_guard: Arc::new(Mutex::new(None)),
+            debug_history: Arc::new(Mutex::new(DebugHistory::default())),
+            debug_recording: Arc::new(AtomicBool::new(false)),
+            listener_domains: Mutex::new(Vec::new()),
+            next_listener_holder_id: AtomicU64::new(0),
         };
 
-        tab.call_method(Page::Enable(None))?;
+        tab.acquire_domain(Domain::Page, "tab")?;
         tab.call_method(Page::SetLifecycleEventsEnabled { enabled: true })?;
+        // For `Inspector.targetCrashed`
+        tab.acquire_domain(Domain::Inspector, "tab")?;
 
         tab.start_event_handler_thread();
 
@@ -250,6 +717,13 @@ impl Tab {
         &self.target_id
     }
 
+    /// Returns the info about this target (its URL, title...) as of the last
+    /// `Target.targetInfoChanged` event, without a round trip to the browser. See
+    /// `get_target_info` to fetch it instead.
+    pub fn target_info(&self) -> TargetInfo {
+        self.target_info.lock().unwrap().clone()
+    }
+
     /// Fetches the most recent info about this target
     pub fn get_target_info(&self) -> Result<TargetInfo> {
         Ok(self
@@ -263,6 +737,8 @@ impl Tab {
         Ok(self.get_target_info()?.browser_context_id)
     }
 
+    /// Returns the tab's URL as of the last `Target.targetInfoChanged` event, without a round trip
+    /// to the browser.
     pub fn get_url(&self) -> String {
         let info = self.target_info.lock().unwrap();
         info.url.clone()
@@ -275,13 +751,15 @@ impl Tab {
         accept_language: Option<&str>,
         platform: Option<&str>,
     ) -> Result<()> {
-        self.call_method(SetUserAgentOverride {
+        let user_agent = SetUserAgentOverride {
             user_agent: user_agent.to_string(),
             accept_language: accept_language.map(std::string::ToString::to_string),
             platform: platform.map(std::string::ToString::to_string),
             user_agent_metadata: None,
-        })
-        .map(|_| ())
+        };
+        self.call_method(user_agent.clone())?;
+        self.setup.lock().unwrap().user_agent = Some(user_agent);
+        Ok(())
     }
 
     fn start_event_handler_thread(&self) {
@@ -296,6 +774,24 @@ impl Tab {
         let auth_handler_mutex = self.auth_handler.clone();
         let session_id = self.session_id.clone();
         let listeners_mutex = Arc::clone(&self.event_listeners);
+        let service_worker_registry_mutex = Arc::clone(&self.service_worker_registry);
+        let workers_mutex = Arc::clone(&self.workers);
+        let main_frame_navigation_mutex = Arc::clone(&self.main_frame_navigation);
+        let target_id = self.target_id.clone();
+        let network_activity_mutex = Arc::clone(&self.network_activity);
+        let waterfall_mutex = Arc::clone(&self.waterfall);
+        let intercepted_drag_mutex = Arc::clone(&self.intercepted_drag);
+        let security_state_mutex = Arc::clone(&self.security_state);
+        let animations_mutex = Arc::clone(&self.animations);
+        let issues_mutex = Arc::clone(&self.issues);
+        let middleware_chain = Arc::clone(&self.middleware);
+        let context_middleware_mutex = Arc::clone(&self.context_middleware);
+        let response_interceptions_mutex = Arc::clone(&self.response_interceptions);
+        let navigation_policy_lock = Arc::clone(&self.navigation_policy);
+        let crashed = Arc::clone(&self.crashed);
+        let navigation_permit_mutex = Arc::clone(&self.navigation_permit);
+        let debug_history_mutex = Arc::clone(&self.debug_history);
+        let debug_recording = Arc::clone(&self.debug_recording);
 
         let bindings_mutex = Arc::clone(&self.page_bindings);
         let received_event_params = Arc::new(Mutex::new(HashMap::new()));
@@ -306,6 +802,9 @@ impl Tab {
                 listeners.iter().for_each(|listener| {
                     listener.on_event(&event);
                 });
+                if debug_recording.load(Ordering::SeqCst) {
+                    debug_history_mutex.lock().unwrap().event_received(&event);
+                }
 
                 match event {
                     Event::PageLifecycleEvent(lifecycle_event) => {
@@ -314,6 +813,7 @@ impl Tab {
                         match event_name {
                             "networkAlmostIdle" => {
                                 navigating.store(false, Ordering::SeqCst);
+                                navigation_permit_mutex.lock().unwrap().take();
                             }
                             "init" => {
                                 navigating.store(true, Ordering::SeqCst);
@@ -321,54 +821,123 @@ impl Tab {
                             _ => {}
                         }
                     }
+                    Event::PageFrameNavigated(ev) if ev.params.frame.parent_id.is_none() => {
+                        let frame = ev.params.frame;
+                        // Set this before the count goes up, so that anyone waiting on the
+                        // count then waits for the new document to load
+                        navigating.store(true, Ordering::SeqCst);
+                        crashed.store(false, Ordering::SeqCst);
+                        let mut main_frame_navigation = main_frame_navigation_mutex.lock().unwrap();
+                        main_frame_navigation.url =
+                            frame.url + &frame.url_fragment.unwrap_or_default();
+                        main_frame_navigation.count += 1;
+                        animations_mutex.lock().unwrap().clear();
+                    }
+                    Event::PageNavigatedWithinDocument(ev) if ev.params.frame_id == target_id => {
+                        let mut main_frame_navigation = main_frame_navigation_mutex.lock().unwrap();
+                        main_frame_navigation.url = ev.params.url;
+                        main_frame_navigation.count += 1;
+                    }
                     Event::RuntimeBindingCalled(binding) => {
                         let bindings = bindings_mutex.lock().unwrap().clone();
 
                         let name = binding.params.name;
-                        let payload = binding.params.payload;
+                        let Some(func) = bindings.get(&name) else {
+                            warn!("Page called a binding that isn't exposed: {name}");
+                            continue;
+                        };
+
+                        let payload: BindingPayload =
+                            match serde_json::from_str(&binding.params.payload) {
+                                Ok(payload) => payload,
+                                Err(e) => {
+                                    warn!("Couldn't parse payload of binding {name}: {e}");
+                                    continue;
+                                }
+                            };
 
-                        let func = &Arc::clone(bindings.get(&name).unwrap());
+                        let (result, error) = match func.call_binding(payload.args) {
+                            Ok(result) => (result, Json::Null),
+                            Err(err) => {
+                                warn!("Binding {name} returned a value which can't be serialized: {err}");
+                                (Json::Null, json!(err.to_string()))
+                            }
+                        };
 
-                        func.call_binding(json!(payload));
+                        let expression = format!(
+                            "(function deliverResult(name, seq, result, error) {{
+                                const callbacks = window[name]['callbacks'];
+                                const callback = callbacks.get(seq);
+                                callbacks.delete(seq);
+                                if (error === null) {{
+                                    callback.resolve(result);
+                                }} else {{
+                                    callback.reject(new Error(error));
+                                }}
+                            }})({}, {}, {}, {})",
+                            json!(name),
+                            payload.seq,
+                            result,
+                            error
+                        );
+                        let method = Runtime::Evaluate {
+                            expression,
+                            object_group: None,
+                            include_command_line_api: None,
+                            silent: Some(true),
+                            context_id: Some(binding.params.execution_context_id),
+                            return_by_value: None,
+                            generate_preview: None,
+                            user_gesture: None,
+                            await_promise: None,
+                            throw_on_side_effect: None,
+                            timeout: None,
+                            disable_breaks: None,
+                            repl_mode: None,
+                            allow_unsafe_eval_blocked_by_csp: None,
+                            unique_context_id: None,
+                        };
+                        if transport
+                            .call_method_on_target(session_id.clone(), method)
+                            .is_err()
+                        {
+                            warn!("Tried to deliver binding result after connection was closed");
+                        }
                     }
                     Event::FetchRequestPaused(event) => {
-                        let interceptor = interceptor_mutex.lock().unwrap();
-                        let decision = interceptor.intercept(
-                            Arc::clone(&transport),
-                            session_id.clone(),
-                            event.clone(),
-                        );
-                        let result = match decision {
-                            RequestPausedDecision::Continue(continue_request) => {
-                                if let Some(continue_request) = continue_request {
-                                    transport
-                                        .call_method_on_target(session_id.clone(), continue_request)
-                                        .map(|_| ())
-                                } else {
-                                    transport
-                                        .call_method_on_target(
-                                            session_id.clone(),
-                                            ContinueRequest {
-                                                request_id: event.params.request_id,
-                                                url: None,
-                                                method: None,
-                                                post_data: None,
-                                                headers: None,
-                                                intercept_response: None,
-                                            },
-                                        )
-                                        .map(|_| ())
-                                }
-                            }
-                            RequestPausedDecision::Fulfill(fulfill_request) => transport
-                                .call_method_on_target(session_id.clone(), fulfill_request)
-                                .map(|_| ()),
-                            RequestPausedDecision::Fail(fail_request) => transport
-                                .call_method_on_target(session_id.clone(), fail_request)
-                                .map(|_| ()),
+                        let mut middlewares = context_middleware_mutex
+                            .lock()
+                            .unwrap()
+                            .as_ref()
+                            .map(|chain| chain.middlewares())
+                            .unwrap_or_default();
+                        middlewares.extend(middleware_chain.middlewares());
+                        let interceptor = Arc::clone(&*interceptor_mutex.lock().unwrap());
+                        let policy = navigation_policy_lock.read().unwrap().clone();
+                        // Looking up the request's host can take a while, which mustn't hold up
+                        // the tab's other events
+                        let looks_up_host = policy
+                            .as_ref()
+                            .is_some_and(|policy| policy.looks_up_host(&event.params));
+
+                        let transport = Arc::clone(&transport);
+                        let session_id = session_id.clone();
+                        let response_interceptions = Arc::clone(&response_interceptions_mutex);
+                        let handle = move || {
+                            handle_request_paused(
+                                &transport,
+                                &session_id,
+                                policy.as_ref(),
+                                &middlewares,
+                                interceptor.as_ref(),
+                                &response_interceptions,
+                                event,
+                            );
                         };
-                        if result.is_err() {
-                            warn!("Tried to handle request after connection was closed");
+                        if looks_up_host {
+                            thread::spawn(handle);
+                        } else {
+                            handle();
                         }
                     }
                     Event::FetchAuthRequired(event) => {
@@ -384,7 +953,55 @@ impl Tab {
                             warn!("Tried to handle request after connection was closed");
                         }
                     }
+                    Event::VisibleSecurityStateChanged(ev) => {
+                        *security_state_mutex.lock().unwrap() =
+                            Some(ev.params.visible_security_state);
+                    }
+                    Event::AnimationCreated(ev) => {
+                        animations_mutex.lock().unwrap().push(ev.params.id);
+                    }
+                    Event::AnimationCanceled(ev) => {
+                        animations_mutex
+                            .lock()
+                            .unwrap()
+                            .retain(|id| *id != ev.params.id);
+                    }
+                    Event::AuditsIssueAdded(ev) => {
+                        issues_mutex.lock().unwrap().push(ev.params.issue.into());
+                    }
+                    Event::InspectorTargetCrashed(_) => {
+                        warn!("Renderer of tab {target_id} crashed");
+                        crashed.store(true, Ordering::SeqCst);
+                    }
+                    Event::InspectorTargetReloadedAfterCrash(_) => {
+                        crashed.store(false, Ordering::SeqCst);
+                    }
+                    Event::InputDragIntercepted(ev) => {
+                        *intercepted_drag_mutex.lock().unwrap() = Some(ev.params.data);
+                    }
+                    Event::NetworkRequestWillBeSent(ev) => {
+                        let params = ev.params;
+                        let navigation = params.request_id == params.loader_id
+                            && params.Type == Some(Network::ResourceType::Document)
+                            && params.frame_id.as_ref() == Some(&target_id);
+                        waterfall_mutex.lock().unwrap().request_started(
+                            params.request_id.clone(),
+                            params.request.url,
+                            params.Type,
+                            params.timestamp,
+                            navigation,
+                        );
+                        network_activity_mutex
+                            .lock()
+                            .unwrap()
+                            .request_started(params.request_id);
+                    }
                     Event::NetworkResponseReceived(ev) => {
+                        waterfall_mutex.lock().unwrap().response_received(
+                            &ev.params.request_id,
+                            ev.params.response.status,
+                            ev.params.response.timing.as_ref(),
+                        );
                         let request_id = ev.params.request_id.clone();
                         received_event_params
                             .lock()
@@ -392,6 +1009,15 @@ impl Tab {
                             .insert(request_id, ev.params);
                     }
                     Event::NetworkLoadingFinished(ev) => {
+                        waterfall_mutex.lock().unwrap().request_finished(
+                            &ev.params.request_id,
+                            ev.params.timestamp,
+                            Some(ev.params.encoded_data_length),
+                        );
+                        network_activity_mutex
+                            .lock()
+                            .unwrap()
+                            .request_finished(&ev.params.request_id);
                         response_handler_mutex.lock().unwrap().iter().for_each(
                             |(_name, handler)| {
                                 let request_id = ev.params.request_id.clone();
@@ -411,21 +1037,65 @@ impl Tab {
                             },
                         );
                     }
-                    Event::NetworkLoadingFailed(ev) => loading_failed_handler_mutex
-                        .lock()
-                        .unwrap()
-                        .iter()
-                        .for_each(|(_name, handler)| {
-                            let request_id = ev.params.request_id.clone();
-
-                            if let Some(params) =
-                                received_event_params.lock().unwrap().get(&request_id)
-                            {
-                                handler(params.clone(), ev.params.clone());
-                            } else {
-                                warn!("Request id does not exist");
-                            }
-                        }),
+                    Event::NetworkLoadingFailed(ev) => {
+                        waterfall_mutex.lock().unwrap().request_finished(
+                            &ev.params.request_id,
+                            ev.params.timestamp,
+                            None,
+                        );
+                        network_activity_mutex
+                            .lock()
+                            .unwrap()
+                            .request_finished(&ev.params.request_id);
+                        loading_failed_handler_mutex
+                            .lock()
+                            .unwrap()
+                            .iter()
+                            .for_each(|(_name, handler)| {
+                                let request_id = ev.params.request_id.clone();
+
+                                if let Some(params) =
+                                    received_event_params.lock().unwrap().get(&request_id)
+                                {
+                                    handler(params.clone(), ev.params.clone());
+                                } else {
+                                    warn!("Request id does not exist");
+                                }
+                            });
+                    }
+                    Event::ServiceWorkerWorkerRegistrationUpdated(ev) => {
+                        service_worker_registry_mutex
+                            .lock()
+                            .unwrap()
+                            .update_registrations(ev.params.registrations);
+                    }
+                    Event::ServiceWorkerWorkerVersionUpdated(ev) => {
+                        service_worker_registry_mutex
+                            .lock()
+                            .unwrap()
+                            .update_versions(ev.params.versions);
+                    }
+                    Event::AttachedToTarget(ev) if ev.params.target_info.Type == "worker" => {
+                        match Worker::new(
+                            ev.params.target_info,
+                            session_id.clone(),
+                            ev.params.session_id.into(),
+                            Arc::clone(&transport),
+                        ) {
+                            Ok(worker) => workers_mutex.lock().unwrap().push(Arc::new(worker)),
+                            Err(err) => warn!("Failed to set up attached worker: {err:?}"),
+                        }
+                    }
+                    Event::DetachedFromTarget(ev) => {
+                        let detached_session_id: SessionId = ev.params.session_id.into();
+                        let mut workers = workers_mutex.lock().unwrap();
+                        let count = workers.len();
+                        workers.retain(|worker| *worker.get_session_id() != detached_session_id);
+                        if workers.len() < count {
+                            drop(workers);
+                            transport.stop_listening_to_target_events(&detached_session_id);
+                        }
+                    }
                     _ => {
                         let raw_event = format!("{event:?}");
                         trace!(
@@ -439,31 +1109,59 @@ impl Tab {
         });
     }
 
+    /// Makes `func` callable from page JavaScript as `window[name](...args)`, in the current
+    /// document and in every document loaded afterwards.
+    ///
+    /// The call returns a promise on the page side, which resolves to whatever `func` returns
+    /// (serialized with serde). `func` receives the call's arguments as a JSON array.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use std::sync::Arc;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.expose_function(
+    ///     "add",
+    ///     Arc::new(|args: serde_json::Value| {
+    ///         args[0].as_i64().unwrap_or(0) + args[1].as_i64().unwrap_or(0)
+    ///     }),
+    /// )?;
+    /// let sum = tab.evaluate("window.add(1, 2)", true)?;
+    /// assert_eq!(sum.value, Some(serde_json::json!(3)));
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn expose_function(&self, name: &str, func: Arc<SafeBinding>) -> Result<()> {
-        let bindings_mutex = Arc::clone(&self.page_bindings);
-
-        let mut bindings = bindings_mutex.lock().unwrap();
-
-        bindings.insert(name.to_string(), func);
+        self.page_bindings
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), func);
 
-        let expression = r#"
-        (function addPageBinding(bindingName) {
+        let expression = format!(
+            r"
+        (function addPageBinding(bindingName) {{
             const binding = window[bindingName];
-            window[bindingName] = (...args) => {
+            window[bindingName] = (...args) => {{
               const me = window[bindingName];
               let callbacks = me['callbacks'];
-              if (!callbacks) {
+              if (!callbacks) {{
                 callbacks = new Map();
                 me['callbacks'] = callbacks;
-              }
+              }}
               const seq = (me['lastSeq'] || 0) + 1;
               me['lastSeq'] = seq;
-              const promise = new Promise((resolve, reject) => callbacks.set(seq, {resolve, reject}));
-              binding(JSON.stringify({name: bindingName, seq, args}));
+              const promise = new Promise((resolve, reject) => callbacks.set(seq, {{resolve, reject}}));
+              binding(JSON.stringify({{name: bindingName, seq, args}}));
               return promise;
-            };
-          })()
-        "#; // https://github.com/puppeteer/puppeteer/blob/97c9fe2520723d45a5a86da06b888ae888d400be/src/common/helper.ts#L183
+            }};
+          }})({})
+        ",
+            json!(name)
+        ); // https://github.com/puppeteer/puppeteer/blob/97c9fe2520723d45a5a86da06b888ae888d400be/src/common/helper.ts#L183
 
         self.call_method(AddBinding {
             name: name.to_string(),
@@ -471,21 +1169,19 @@ impl Tab {
             execution_context_name: None,
         })?;
 
-        self.call_method(AddScriptToEvaluateOnNewDocument {
-            source: expression.to_string(),
-            world_name: None,
-            include_command_line_api: None,
-        })?;
+        self.evaluate_on_new_document(&expression)?;
+        self.evaluate(&expression, false)?;
 
         Ok(())
     }
 
+    /// Removes a function previously exposed with `expose_function`.
     pub fn remove_function(&self, name: &str) -> Result<()> {
-        let bindings_mutex = Arc::clone(&self.page_bindings);
-
-        let mut bindings = bindings_mutex.lock().unwrap();
+        self.page_bindings.lock().unwrap().remove(name);
 
-        bindings.remove(name).unwrap();
+        self.call_method(Runtime::RemoveBinding {
+            name: name.to_string(),
+        })?;
 
         Ok(())
     }
@@ -494,15 +1190,138 @@ impl Tab {
     where
         C: Method + serde::Serialize + std::fmt::Debug,
     {
+        if self.crashed.load(Ordering::SeqCst) && !callable_while_crashed(C::NAME) {
+            return Err(TabCrashed {
+                target_id: self.target_id.clone(),
+            }
+            .into());
+        }
         trace!("Calling method: {:?}", method);
+        let params = self
+            .debug_recording
+            .load(Ordering::SeqCst)
+            .then(|| serde_json::to_value(&method).unwrap_or_default());
         let result = self
             .transport
             .call_method_on_target(self.session_id.clone(), method);
         let result_string = format!("{result:?}");
         trace!("Got result: {:?}", result_string.chars().take(70));
+        if let Some(params) = params {
+            self.debug_history.lock().unwrap().call_made(
+                C::NAME,
+                params,
+                result.as_ref().err().map(ToString::to_string),
+            );
+        }
         result
     }
 
+    /// Like `call_method`, but retries the call according to `policy` while it fails with a
+    /// transient error, such as the target being detached or the call timing out.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::transport::RetryPolicy;
+    /// use headless_chrome::protocol::cdp::DOM;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let document = tab.call_method_with_retry(
+    ///     DOM::GetDocument { depth: Some(0), pierce: None },
+    ///     &RetryPolicy::default(),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_method_with_retry<C>(
+        &self,
+        method: C,
+        policy: &RetryPolicy,
+    ) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize + std::fmt::Debug + Clone,
+    {
+        policy.run(|| self.call_method(method.clone()))
+    }
+
+    /// Makes all of the `methods` calls on this tab before waiting for any of their responses,
+    /// rather than one round trip per call. Returns each call's result in the order of `methods`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::DOM;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let node_ids = tab.find_elements("a")?.iter().map(|link| link.node_id).collect::<Vec<_>>();
+    /// let attributes = tab.call_batch(
+    ///     node_ids
+    ///         .into_iter()
+    ///         .map(|node_id| DOM::GetAttributes { node_id })
+    ///         .collect(),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_batch<C>(&self, methods: Vec<C>) -> Result<Vec<Result<C::ReturnObject>>>
+    where
+        C: Method + serde::Serialize + std::fmt::Debug,
+    {
+        trace!("Calling {} methods in a batch", methods.len());
+        self.transport
+            .call_batch(methods, &MethodDestination::Target(self.session_id.clone()))
+    }
+
+    /// Like `call_method`, but returns the result's JSON as it arrived, without parsing it.
+    pub fn call_method_raw<C>(&self, method: C) -> Result<Box<RawValue>>
+    where
+        C: Method + serde::Serialize + std::fmt::Debug,
+    {
+        trace!("Calling method raw: {method:?}");
+        self.transport
+            .call_method_raw(method, MethodDestination::Target(self.session_id.clone()))
+    }
+
+    /// Calls a protocol method by name with the given JSON params and returns its result's
+    /// JSON, for methods newer than this crate's protocol definitions or only some browsers
+    /// have.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::Browser;
+    /// use serde_json::json;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let result = tab.call_raw("Page.getLayoutMetrics", json!({}))?;
+    /// let width = result["cssVisualViewport"]["clientWidth"].as_f64();
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_raw(
+        &self,
+        method_name: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        trace!("Calling method by name: {method_name} {params}");
+        self.transport.call_raw(
+            method_name,
+            params,
+            MethodDestination::Target(self.session_id.clone()),
+        )
+    }
+
     pub fn wait_until_navigated(&self) -> Result<&Self> {
         let navigating = Arc::clone(&self.navigating);
         let timeout = *self.default_timeout.read().unwrap();
@@ -515,16 +1334,217 @@ impl Tab {
             }
         })?;
         debug!("A tab finished navigating");
+        self.navigation_permit.lock().unwrap().take();
+
+        let navigation_count = self.main_frame_navigation.lock().unwrap().count;
+        self.awaited_navigation_count
+            .store(navigation_count, Ordering::SeqCst);
 
         Ok(self)
     }
 
-    // Pulls focus to this tab
+    /// Waits for the main frame to navigate, then for the new page to finish loading.
+    ///
+    /// Navigations which have already been waited for by this method or by `wait_until_navigated`
+    /// don't count, so this is for navigations the page starts on its own, e.g. after a click:
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com/login")?.wait_until_navigated()?;
+    /// tab.wait_for_element("button[type=submit]")?.click()?;
+    /// tab.wait_for_navigation()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Uses the tab's default timeout.
+    pub fn wait_for_navigation(&self) -> Result<&Self> {
+        let awaited_navigation_count = self.awaited_navigation_count.load(Ordering::SeqCst);
+        util::Wait::with_timeout(*self.default_timeout.read().unwrap()).until(|| {
+            let navigation_count = self.main_frame_navigation.lock().unwrap().count;
+            (navigation_count > awaited_navigation_count).then_some(())
+        })?;
+        self.wait_until_navigated()
+    }
+
+    /// Waits until no more than `max_in_flight` network requests have been in flight for
+    /// `idle_time`, e.g. until a single-page app has finished its XHR calls.
+    ///
+    /// Requests are only tracked once the Network domain is enabled, which the first call to this
+    /// method does and which lasts for the tab's lifetime; call it once before navigating so that
+    /// requests the page starts early aren't missed.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::time::Duration;
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.wait_for_network_idle(Duration::ZERO, usize::MAX)?;
+    /// tab.navigate_to("https://example.com/app")?
+    ///     .wait_for_network_idle(Duration::from_millis(500), 0)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Uses the tab's default timeout.
+    pub fn wait_for_network_idle(
+        &self,
+        idle_time: Duration,
+        max_in_flight: usize,
+    ) -> Result<&Self> {
+        self.acquire_domain(Domain::Network, "wait_for_network_idle")?;
+        util::Wait::new(
+            *self.default_timeout.read().unwrap(),
+            Duration::from_millis(50),
+        )
+        .until(|| {
+            let idle_for = self
+                .network_activity
+                .lock()
+                .unwrap()
+                .idle_for(max_in_flight)?;
+            (idle_for >= idle_time).then_some(())
+        })?;
+        Ok(self)
+    }
+
+    /// Waits until a page (typically a single-page app built with React, Vue and the like) has
+    /// settled: its DOM hasn't changed, no network requests have been in flight for
+    /// `quiet_period`, and it's rendering frames without long gaps. Unlike the load event, this
+    /// catches the content apps render after fetching their data, so it's a better point to take
+    /// a screenshot at.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::time::Duration;
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com/app")?
+    ///     .wait_for_stable_dom(Duration::from_millis(500))?;
+    /// let png = tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Uses the tab's default timeout.
+    pub fn wait_for_stable_dom(&self, quiet_period: Duration) -> Result<&Self> {
+        // Requests already in flight are missed if Network only gets enabled now, but then
+        // there'll usually be DOM changes once they complete
+        self.acquire_domain(Domain::Network, "wait_for_stable_dom")?;
+        let stable = util::Wait::new(
+            *self.default_timeout.read().unwrap(),
+            Duration::from_millis(100),
+        )
+        .until(|| {
+            let probe = self.evaluate(STABILITY_PROBE, false).ok()?;
+            let snapshot: PageSnapshot = serde_json::from_str(probe.value?.as_str()?).ok()?;
+            let network_idle_for = self.network_activity.lock().unwrap().idle_for(0);
+            trace!(
+                "{} DOM mutations so far, the last {:.0}ms ago, network idle for {network_idle_for:?}",
+                snapshot.mutations,
+                snapshot.since_last_mutation
+            );
+            snapshot
+                .is_settled(quiet_period, network_idle_for)
+                .then_some(())
+        });
+        self.release_domain(Domain::Network, "wait_for_stable_dom")?;
+        stable?;
+        Ok(self)
+    }
+
+    /// The timings of the requests made by the tab's latest navigation and the page it loaded
+    /// (DNS, connecting, TLS, time to first byte, download), or `None` if it hasn't navigated
+    /// since this was first called. Requests still in flight are included as far as they've got.
+    ///
+    /// Requests are only recorded once the Network domain is enabled, which the first call to
+    /// this method does; call it once before navigating.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.last_navigation_waterfall()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// if let Some(waterfall) = tab.last_navigation_waterfall()? {
+    ///     for request in waterfall.requests {
+    ///         println!("{} ttfb: {:?}ms", request.url, request.ttfb);
+    ///     }
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last_navigation_waterfall(&self) -> Result<Option<NavigationWaterfall>> {
+        self.acquire_domain(Domain::Network, "last_navigation_waterfall")?;
+        Ok(self.waterfall.lock().unwrap().waterfall())
+    }
+
+    /// Waits until the main frame's URL satisfies `predicate`, including after navigations
+    /// within the document such as `history.pushState` or fragment changes.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.wait_for_element("button[type=submit]")?.click()?;
+    /// tab.wait_for_url(|url| url.contains("/dashboard"))?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Uses the tab's default timeout.
+    pub fn wait_for_url<F>(&self, predicate: F) -> Result<&Self>
+    where
+        F: Fn(&str) -> bool,
+    {
+        util::Wait::with_timeout(*self.default_timeout.read().unwrap()).until(|| {
+            let main_frame_navigation = self.main_frame_navigation.lock().unwrap();
+            predicate(&main_frame_navigation.url).then_some(())
+        })?;
+        Ok(self)
+    }
+
+    /// Pulls focus to this tab, making it the visible one in its window
     pub fn bring_to_front(&self) -> Result<Page::BringToFrontReturnObject> {
         self.call_method(Page::BringToFront(None))
     }
 
     pub fn navigate_to(&self, url: &str) -> Result<&Self> {
+        if let Some(policy) = self.navigation_policy.read().unwrap().as_ref() {
+            policy.check(url)?;
+        }
+        // A navigation started before the last one finished replaces it, so gives up its slot
+        self.navigation_permit.lock().unwrap().take();
+        let scheduler = self.navigation_scheduler.lock().unwrap().clone();
+        let permit = match scheduler {
+            Some(scheduler) => Some(scheduler.acquire(*self.default_timeout.read().unwrap())?),
+            None => None,
+        };
+
         let return_object = self.call_method(Navigate {
             url: url.to_string(),
             referrer: None,
@@ -538,12 +1558,111 @@ impl Tab {
 
         let navigating = Arc::clone(&self.navigating);
         navigating.store(true, Ordering::SeqCst);
+        *self.navigation_permit.lock().unwrap() = permit;
 
         info!("Navigating a tab to {}", url);
 
         Ok(self)
     }
 
+    /// Returns the tab's session history, along with the index of the current entry.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-getNavigationHistory>
+    pub fn navigation_history(&self) -> Result<Page::GetNavigationHistoryReturnObject> {
+        self.call_method(Page::GetNavigationHistory(None))
+    }
+
+    /// Navigates to the previous entry of the session history, like the browser's back button,
+    /// and waits for the navigation to finish.
+    ///
+    /// Fails with `NoHistoryEntry` if the tab is already at its first entry.
+    pub fn go_back(&self) -> Result<&Self> {
+        self.navigate_history_by(-1)
+    }
+
+    /// Navigates to the next entry of the session history, like the browser's forward button,
+    /// and waits for the navigation to finish.
+    ///
+    /// Fails with `NoHistoryEntry` if the tab is already at its last entry.
+    pub fn go_forward(&self) -> Result<&Self> {
+        self.navigate_history_by(1)
+    }
+
+    fn navigate_history_by(&self, offset: i64) -> Result<&Self> {
+        let history = self.navigation_history()?;
+        let target_index = i64::from(history.current_index) + offset;
+        let Some(entry) = usize::try_from(target_index)
+            .ok()
+            .and_then(|index| history.entries.get(index))
+        else {
+            return Err(NoHistoryEntry {}.into());
+        };
+
+        self.call_method(Page::NavigateToHistoryEntry { entry_id: entry.id })?;
+        info!("Navigating a tab through its history to {}", entry.url);
+
+        // Wait for the navigation to commit before waiting for it to finish loading
+        util::Wait::with_timeout(*self.default_timeout.read().unwrap()).until(|| {
+            self.navigation_history()
+                .ok()
+                .filter(|history| i64::from(history.current_index) == target_index)
+        })?;
+
+        self.wait_until_navigated()
+    }
+
+    /// Replaces the tab's document with `html`, without needing a web server or a `data:` URL.
+    ///
+    /// The tab is first navigated to `about:blank`, so relative URLs in the markup can't be
+    /// resolved; use absolute URLs or a `<base>` element.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::WaitUntil;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let pdf = tab
+    ///     .set_content("<h1>Invoice #42</h1>", WaitUntil::Load)?
+    ///     .print_to_pdf(None)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_content(&self, html: &str, wait_until: WaitUntil) -> Result<&Self> {
+        self.navigate_to("about:blank")?.wait_until_navigated()?;
+
+        let frame_id = self
+            .call_method(Page::GetFrameTree(None))?
+            .frame_tree
+            .frame
+            .id;
+        self.call_method(Page::SetDocumentContent {
+            frame_id,
+            html: html.to_string(),
+        })?;
+
+        let ready_states: &[&str] = match wait_until {
+            WaitUntil::DomContentLoaded => &["interactive", "complete"],
+            WaitUntil::Load => &["complete"],
+        };
+        util::Wait::with_timeout(*self.default_timeout.read().unwrap()).strict_until(
+            || {
+                let ready_state = self.evaluate("document.readyState", false)?.value;
+                match ready_state.as_ref().and_then(Json::as_str) {
+                    Some(state) if ready_states.contains(&state) => Ok(()),
+                    _ => Err(DocumentNotReady {}.into()),
+                }
+            },
+            Error::downcast::<DocumentNotReady>,
+        )?;
+
+        Ok(self)
+    }
+
     /// Set default timeout for the tab
     ///
     /// This will be applied to all [wait_for_element](Tab::wait_for_element) and [wait_for_elements](Tab::wait_for_elements) calls for this tab
@@ -754,19 +1873,163 @@ impl Tab {
             .root)
     }
 
-    /// Get the full HTML contents of the page.
+    /// Calls `callback` for every change to the element matching `selector_scope` and to
+    /// everything inside it: children being inserted or removed, attributes changing and text
+    /// changing. Returns the underlying event listener, which can be passed to
+    /// `remove_event_listener` to stop.
+    ///
+    /// This relies on the browser's node ids, which are reset whenever the document is fetched
+    /// again (e.g. by `find_element` or `get_document`) or the page navigates, so call this again
+    /// afterwards to keep watching.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::DomMutation;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com/dashboard")?.wait_until_navigated()?;
+    /// tab.on_dom_mutation("#prices", |mutation| {
+    ///     if let DomMutation::CharacterDataModified { character_data, .. } = mutation {
+    ///         println!("Price changed to {character_data}");
+    ///     }
+    /// })?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_dom_mutation<F>(
+        &self,
+        selector_scope: &str,
+        callback: F,
+    ) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&DomMutation) + Send + Sync + 'static,
+    {
+        fn add_subtree(scope: &mut HashSet<NodeId>, node: &Node) {
+            scope.insert(node.node_id);
+            for child in node.children.iter().flatten() {
+                add_subtree(scope, child);
+            }
+        }
+
+        let scope_node_id = self.find_element(selector_scope)?.node_id;
+        let scope = Mutex::new(HashSet::from([scope_node_id]));
+        let listener = self.add_event_listener(Arc::new(move |event: &Event| {
+            let mut scope = scope.lock().unwrap();
+            let mutation = match event {
+                // The subtree's nodes, as requested below
+                Event::DOMSetChildNodes(ev) => {
+                    if scope.contains(&ev.params.parent_id) {
+                        for node in &ev.params.nodes {
+                            add_subtree(&mut scope, node);
+                        }
+                    }
+                    return;
+                }
+                Event::DOMChildNodeInserted(ev) if scope.contains(&ev.params.parent_node_id) => {
+                    add_subtree(&mut scope, &ev.params.node);
+                    DomMutation::ChildNodeInserted {
+                        parent_node_id: ev.params.parent_node_id,
+                        previous_node_id: ev.params.previous_node_id,
+                        node: ev.params.node.clone(),
+                    }
+                }
+                Event::DOMChildNodeRemoved(ev) if scope.contains(&ev.params.parent_node_id) => {
+                    scope.remove(&ev.params.node_id);
+                    DomMutation::ChildNodeRemoved {
+                        parent_node_id: ev.params.parent_node_id,
+                        node_id: ev.params.node_id,
+                    }
+                }
+                Event::DOMAttributeModified(ev) if scope.contains(&ev.params.node_id) => {
+                    DomMutation::AttributeModified {
+                        node_id: ev.params.node_id,
+                        name: ev.params.name.clone(),
+                        value: ev.params.value.clone(),
+                    }
+                }
+                Event::DOMAttributeRemoved(ev) if scope.contains(&ev.params.node_id) => {
+                    DomMutation::AttributeRemoved {
+                        node_id: ev.params.node_id,
+                        name: ev.params.name.clone(),
+                    }
+                }
+                Event::DOMCharacterDataModified(ev) if scope.contains(&ev.params.node_id) => {
+                    DomMutation::CharacterDataModified {
+                        node_id: ev.params.node_id,
+                        character_data: ev.params.character_data.clone(),
+                    }
+                }
+                Event::DOMDocumentUpdated(_) => {
+                    scope.clear();
+                    return;
+                }
+                _ => return,
+            };
+            drop(scope);
+            callback(&mutation);
+        }))?;
+
+        // The browser only reports changes to nodes it has sent us, so have it send the whole
+        // subtree. (-1, meaning "all of it", doesn't fit the generated type.)
+        if let Err(err) = self.call_method(DOM::RequestChildNodes {
+            node_id: scope_node_id,
+            depth: Some(i32::MAX as u32),
+            pierce: None,
+        }) {
+            self.remove_event_listener(&listener)?;
+            return Err(err);
+        }
+        Ok(listener)
+    }
+
+    /// Get the full HTML contents of the page, including its doctype.
     pub fn get_content(&self) -> Result<String> {
-        let func = "
-            (function () { 
-                let retVal = '';
-                if (document.doctype)
-                    retVal = new XMLSerializer().serializeToString(document.doctype);
-                if (document.documentElement)
-                    retVal += document.documentElement.outerHTML;
-                return retVal;
-            })();";
-        let html = self.evaluate(func, false)?.value.unwrap();
-        Ok(String::from(html.as_str().unwrap()))
+        let document_node_id = self.get_document()?.node_id;
+        Ok(self
+            .call_method(DOM::GetOuterHTML {
+                node_id: Some(document_node_id),
+                backend_node_id: None,
+                object_id: None,
+                include_shadow_dom: None,
+            })?
+            .outer_html)
+    }
+
+    /// Captures a flattened snapshot of the whole DOM (including iframes and pseudo-elements),
+    /// with layout information and the values of `computed_styles` for every rendered node.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// let snapshot = tab.capture_dom_snapshot(&["display", "color"])?;
+    /// println!("{} documents captured", snapshot.documents.len());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/DOMSnapshot#method-captureSnapshot>
+    pub fn capture_dom_snapshot(
+        &self,
+        computed_styles: &[&str],
+    ) -> Result<DOMSnapshot::CaptureSnapshotReturnObject> {
+        self.call_method(DOMSnapshot::CaptureSnapshot {
+            computed_styles: computed_styles.iter().map(ToString::to_string).collect(),
+            include_paint_order: None,
+            include_dom_rects: Some(true),
+            include_blended_background_colors: None,
+            include_text_color_opacities: None,
+        })
     }
 
     pub fn find_elements(&self, selector: &str) -> Result<Vec<Element<'_>>> {
@@ -826,59 +2089,182 @@ impl Tab {
         Ok(node)
     }
 
+    /// Fills in form fields, given as `(selector, value)` pairs, in order: each field is waited
+    /// for and filled with `Element::fill`, which types text with key events and picks options
+    /// of `<select>` elements.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com/login")?.wait_until_navigated()?;
+    /// tab.fill_form(&[("#email", "a@b.c"), ("#password", "hunter2")])?
+    ///     .wait_for_element("#password")?
+    ///     .submit_and_wait_for_navigation()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fill_form(&self, fields: &[(&str, &str)]) -> Result<&Self> {
+        for (selector, value) in fields {
+            self.wait_for_element(selector)?.fill(value)?;
+        }
+        Ok(self)
+    }
+
     pub fn type_str(&self, string_to_type: &str) -> Result<&Self> {
         for c in string_to_type.split("") {
             // split call above will have empty string at start and end which we won't type
             if c.is_empty() {
                 continue;
             }
-            let definition = keys::get_key_definition(c);
-            // https://github.com/puppeteer/puppeteer/blob/b8806d5625ca7835abbaf2e997b0bf35a5679e29/src/common/Input.ts#L239-L245
-            match definition {
-                Ok(key) => {
-                    let v: DispatchKeyEvent = key.into();
-
-                    self.call_method(v.clone())?;
-                    self.call_method(DispatchKeyEvent {
-                        Type: Input::DispatchKeyEventTypeOption::KeyUp,
-                        ..v
-                    })?;
-                }
-                Err(_) => {
-                    self.send_character(c)?;
-                }
+            self.type_char(c)?;
+        }
+        Ok(self)
+    }
+
+    /// Like `type_str`, but pauses for a random time within `delay` between keystrokes, as
+    /// people typing do. Some bot detection looks at how evenly spaced keystrokes are. Fails with
+    /// `InvalidDelayRange`, before typing anything, if `delay` starts after it ends.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use std::time::Duration;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.wait_for_element("input#search")?.click()?;
+    /// tab.type_with_delay(
+    ///     "headless chrome",
+    ///     Duration::from_millis(50)..=Duration::from_millis(200),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn type_with_delay(&self, text: &str, delay: RangeInclusive<Duration>) -> Result<&Self> {
+        if delay.start() > delay.end() {
+            return Err(InvalidDelayRange {
+                start: *delay.start(),
+                end: *delay.end(),
             }
+            .into());
+        }
+        let mut rng = rand::thread_rng();
+        for (i, c) in text.split("").filter(|c| !c.is_empty()).enumerate() {
+            if i > 0 {
+                sleep(rng.gen_range(delay.clone()));
+            }
+            self.type_char(c)?;
         }
         Ok(self)
     }
 
+    fn type_char(&self, c: &str) -> Result<()> {
+        let definition = keys::get_key_definition(c);
+        // https://github.com/puppeteer/puppeteer/blob/b8806d5625ca7835abbaf2e997b0bf35a5679e29/src/common/Input.ts#L239-L245
+        match definition {
+            Ok(key) => {
+                let v: DispatchKeyEvent = key.into();
+
+                self.call_method(v.clone())?;
+                self.call_method(DispatchKeyEvent {
+                    Type: Input::DispatchKeyEventTypeOption::KeyUp,
+                    ..v
+                })?;
+            }
+            Err(_) => {
+                self.send_character(c)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Does the same as `type_str` but it only dispatches a `keypress` and `input` event.
     /// It does not send a `keydown` or `keyup` event.
     ///
     /// What this means is that it is much faster.
     /// It is especially useful when you have a lot of text as input.
     pub fn send_character(&self, char_to_send: &str) -> Result<&Self> {
+        self.insert_text(char_to_send)
+    }
+
+    /// Inserts `text` into the focused element at once, the way an input method or a paste
+    /// would, firing `beforeinput` and `input` events but no key events.
+    ///
+    /// Unlike `type_str`, this works for any text, including CJK characters and emoji which
+    /// have no key definition. It also commits any composition started with
+    /// `ime_set_composition`.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Input#method-insertText>
+    pub fn insert_text(&self, text: &str) -> Result<&Self> {
         self.call_method(Input::InsertText {
-            text: char_to_send.to_string(),
+            text: text.to_string(),
         })?;
         Ok(self)
     }
 
-    pub fn press_key(&self, key: &str) -> Result<&Self> {
-        // See https://github.com/GoogleChrome/puppeteer/blob/62da2366c65b335751896afbb0206f23c61436f1/lib/Input.js#L114-L115
-        let definiton = keys::get_key_definition(key)?;
-
-        let text = definiton
-            .text
-            .or({
-                if definiton.key.len() == 1 {
-                    Some(definiton.key)
-                } else {
-                    None
-                }
-            })
-            .map(std::string::ToString::to_string);
-
+    /// Sets the text of the input method's pending composition in the focused element, firing
+    /// `compositionstart`/`compositionupdate` events, as an IME does while the user is
+    /// converting keystrokes into e.g. kanji. `selection_start` and `selection_end` are the
+    /// caret or selection within `text`.
+    ///
+    /// Call `insert_text` with the final text to commit the composition:
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.wait_for_element("input#search")?.click()?;
+    /// tab.ime_set_composition("にほん", 3, 3)?
+    ///     .ime_set_composition("日本", 2, 2)?
+    ///     .insert_text("日本")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Input#method-imeSetComposition>
+    pub fn ime_set_composition(
+        &self,
+        text: &str,
+        selection_start: u32,
+        selection_end: u32,
+    ) -> Result<&Self> {
+        self.call_method(Input::ImeSetComposition {
+            text: text.to_string(),
+            selection_start,
+            selection_end,
+            replacement_start: None,
+            replacement_end: None,
+        })?;
+        Ok(self)
+    }
+
+    pub fn press_key(&self, key: &str) -> Result<&Self> {
+        // See https://github.com/GoogleChrome/puppeteer/blob/62da2366c65b335751896afbb0206f23c61436f1/lib/Input.js#L114-L115
+        let definiton = keys::get_key_definition(key)?;
+
+        let text = definiton
+            .text
+            .or({
+                if definiton.key.len() == 1 {
+                    Some(definiton.key)
+                } else {
+                    None
+                }
+            })
+            .map(std::string::ToString::to_string);
+
         // See https://github.com/GoogleChrome/puppeteer/blob/62da2366c65b335751896afbb0206f23c61436f1/lib/Input.js#L52
         let key_down_event_type = if text.is_some() {
             Input::DispatchKeyEventTypeOption::KeyDown
@@ -958,6 +2344,124 @@ impl Tab {
         Ok(self)
     }
 
+    /// Scrolls by turning the mouse wheel over the middle of the viewport, which fires `wheel`
+    /// events like a user scrolling does. Positive deltas scroll right and down, in CSS pixels.
+    pub fn scroll_by(&self, delta_x: f64, delta_y: f64) -> Result<&Self> {
+        let viewport = self
+            .call_method(Page::GetLayoutMetrics(None))?
+            .css_visual_viewport;
+
+        self.call_method(Input::DispatchMouseEvent {
+            Type: Input::DispatchMouseEventTypeOption::MouseWheel,
+            x: viewport.client_width / 2.0,
+            y: viewport.client_height / 2.0,
+            modifiers: None,
+            timestamp: None,
+            button: None,
+            buttons: None,
+            click_count: None,
+            force: None,
+            tangential_pressure: None,
+            tilt_x: None,
+            tilt_y: None,
+            twist: None,
+            delta_x: Some(delta_x),
+            delta_y: Some(delta_y),
+            pointer_Type: None,
+        })?;
+
+        Ok(self)
+    }
+
+    /// Scrolls down by `step` pixels at a time until the bottom of the page, waiting
+    /// `settle_time` there for an infinite-scroll page to load more content, and carrying on
+    /// if it did. Stops after `max_iterations` steps at most.
+    ///
+    /// Returns whether the bottom was reached with no more content loading.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use std::time::Duration;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com/feed")?.wait_until_navigated()?;
+    /// tab.scroll_to_bottom(800.0, Duration::from_secs(1), 100)?;
+    /// let posts = tab.find_elements("article")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scroll_to_bottom(
+        &self,
+        step: f64,
+        settle_time: Duration,
+        max_iterations: usize,
+    ) -> Result<bool> {
+        let is_at_bottom = |metrics: &Page::GetLayoutMetricsReturnObject| {
+            let viewport = &metrics.css_visual_viewport;
+            // Allow for the viewport's offset not quite adding up due to rounding
+            viewport.page_y + viewport.client_height >= metrics.css_content_size.height - 1.0
+        };
+
+        for _ in 0..max_iterations {
+            let metrics = self.call_method(Page::GetLayoutMetrics(None))?;
+            if is_at_bottom(&metrics) {
+                sleep(settle_time);
+                let settled = self.call_method(Page::GetLayoutMetrics(None))?;
+                if is_at_bottom(&settled) {
+                    return Ok(true);
+                }
+                debug!(
+                    "Page grew from {} to {} pixels while at the bottom",
+                    metrics.css_content_size.height, settled.css_content_size.height
+                );
+            }
+            self.scroll_by(0.0, step)?;
+        }
+
+        Ok(false)
+    }
+
+    /// Scrolls `element` to the middle of the viewport with the mouse wheel, see `scroll_by`,
+    /// unlike `Element::scroll_into_view`, which scrolls from script.
+    pub fn scroll_element_into_view_center(&self, element: &Element) -> Result<&Self> {
+        // Sticky headers and smooth scrolling can keep a single step from getting there
+        for _ in 0..5 {
+            let midpoint = element.get_midpoint()?;
+            let viewport = self
+                .call_method(Page::GetLayoutMetrics(None))?
+                .css_visual_viewport;
+            let delta_x = midpoint.x - viewport.client_width / 2.0;
+            let delta_y = midpoint.y - viewport.client_height / 2.0;
+            if delta_x.abs() < 1.0 && delta_y.abs() < 1.0 {
+                break;
+            }
+            self.scroll_by(delta_x, delta_y)?;
+
+            // Wheel scrolling may be animated, so wait for the element to stop moving. Stop
+            // once the page can't scroll any further, e.g. for elements near its end.
+            let mut previous = midpoint;
+            let scrolled_to = util::Wait::new(Duration::from_secs(1), Duration::from_millis(50))
+                .until(|| {
+                    let current = element.get_midpoint().ok()?;
+                    if current == previous && current != midpoint {
+                        Some(current)
+                    } else {
+                        previous = current;
+                        None
+                    }
+                });
+            if scrolled_to.is_err() {
+                break;
+            }
+        }
+        Ok(self)
+    }
+
     pub fn click_point(&self, point: Point) -> Result<&Self> {
         trace!("Clicking point: {:?}", point);
         if point.x == 0.0 && point.y == 0.0 {
@@ -1006,204 +2510,1434 @@ impl Tab {
         Ok(self)
     }
 
-    /// Capture a screenshot of the current page.
-    ///
-    /// If `clip` is given, the screenshot is taken of the specified region only.
-    /// `Element::get_box_model()` can be used to get regions of certains elements
-    /// on the page; there is also `Element::capture_screenhot()` as a shorthand.
+    /// Drags with the left mouse button from `from` to `to`.
     ///
-    /// If `from_surface` is true, the screenshot is taken from the surface rather than
-    /// the view.
+    /// HTML5 drag and drop (`draggable` elements) can't be simulated with mouse events alone, so
+    /// drags the page starts are intercepted via `Input.setInterceptDrags` and replayed as
+    /// `dragenter`, `dragover` and `drop` events at `to`. Pages which implement dragging with
+    /// mouse events receive those as usual.
     ///
     /// ```rust,no_run
     /// # use anyhow::Result;
     /// # fn main() -> Result<()> {
     /// #
-    /// use headless_chrome::{protocol::page::ScreenshotFormat, Browser, LaunchOptions};
-    /// let browser = Browser::new(LaunchOptions::default_builder().build().unwrap())?;
-    /// let tab = browser.wait_for_initial_tab()?;
-    /// let viewport = tab.navigate_to("https://en.wikipedia.org/wiki/WebKit")?
-    ///     .wait_for_element("#mw-content-text > div > table.infobox.vevent")?
-    ///     .get_box_model()?
-    ///     .margin_viewport();
-    ///  let png_data = tab.capture_screenshot(ScreenshotFormat::PNG, Some(viewport), true)?;
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let card = tab.wait_for_element("#todo .card")?;
+    /// let done_column = tab.wait_for_element("#done")?;
+    /// card.drag_to(&done_column)?;
     /// #
     /// # Ok(())
     /// # }
     /// ```
-    pub fn capture_screenshot(
-        &self,
-        format: Page::CaptureScreenshotFormatOption,
-        quality: Option<u32>,
-        clip: Option<Page::Viewport>,
-        from_surface: bool,
-    ) -> Result<Vec<u8>> {
-        let data = self
-            .call_method(Page::CaptureScreenshot {
-                format: Some(format),
-                clip,
-                quality,
-                from_surface: Some(from_surface),
-                capture_beyond_viewport: None,
-            })?
-            .data;
-        base64::decode(data).map_err(Into::into)
-    }
+    pub fn drag(&self, from: Point, to: Point) -> Result<&Self> {
+        trace!("Dragging from {from:?} to {to:?}");
+        self.call_method(Input::SetInterceptDrags { enabled: true })?;
+        *self.intercepted_drag.lock().unwrap() = None;
 
-    pub fn print_to_pdf(&self, options: Option<PrintToPdfOptions>) -> Result<Vec<u8>> {
-        if let Some(options) = options {
-            let transfer_mode: Option<Page::PrintToPDFTransfer_modeOption> =
-                options.transfer_mode.and_then(std::convert::Into::into);
-            let data = self
-                .call_method(Page::PrintToPDF {
-                    landscape: options.landscape,
-                    display_header_footer: options.display_header_footer,
-                    print_background: options.print_background,
-                    scale: options.scale,
-                    paper_width: options.paper_width,
-                    paper_height: options.paper_height,
-                    margin_top: options.margin_top,
-                    margin_bottom: options.margin_bottom,
-                    margin_left: options.margin_left,
-                    margin_right: options.margin_right,
-                    page_ranges: options.page_ranges,
-                    ignore_invalid_page_ranges: options.ignore_invalid_page_ranges,
-                    header_template: options.header_template,
-                    footer_template: options.footer_template,
-                    prefer_css_page_size: options.prefer_css_page_size,
-                    transfer_mode,
-                })?
-                .data;
-            base64::decode(data).map_err(Into::into)
-        } else {
-            let data = self
-                .call_method(Page::PrintToPDF {
-                    ..Default::default()
-                })?
-                .data;
+        let result = self.drag_with_interception(from, to);
 
-            base64::decode(data).map_err(Into::into)
+        self.call_method(Input::SetInterceptDrags { enabled: false })?;
+        result?;
+        Ok(self)
+    }
+
+    fn drag_with_interception(&self, from: Point, to: Point) -> Result<()> {
+        self.move_mouse_to_point(from)?;
+        self.dispatch_left_mouse_button(Input::DispatchMouseEventTypeOption::MousePressed, from)?;
+        self.optional_slow_motion_sleep(100);
+        self.dispatch_left_mouse_button(Input::DispatchMouseEventTypeOption::MouseMoved, to)?;
+
+        // Chrome reports the drag (if the page started one) asynchronously
+        let drag_data = util::Wait::new(Duration::from_millis(500), Duration::from_millis(20))
+            .until(|| self.intercepted_drag.lock().unwrap().take())
+            .ok();
+
+        if let Some(data) = drag_data {
+            for event_type in [
+                Input::DispatchDragEventTypeOption::DragEnter,
+                Input::DispatchDragEventTypeOption::DragOver,
+                Input::DispatchDragEventTypeOption::Drop,
+            ] {
+                self.call_method(Input::DispatchDragEvent {
+                    Type: event_type,
+                    x: to.x,
+                    y: to.y,
+                    data: data.clone(),
+                    modifiers: None,
+                })?;
+            }
         }
+
+        self.dispatch_left_mouse_button(Input::DispatchMouseEventTypeOption::MouseReleased, to)?;
+        Ok(())
     }
 
-    /// Reloads given page optionally ignoring the cache
+    fn dispatch_left_mouse_button(
+        &self,
+        event_type: Input::DispatchMouseEventTypeOption,
+        point: Point,
+    ) -> Result<()> {
+        let buttons = match event_type {
+            Input::DispatchMouseEventTypeOption::MouseReleased => 0,
+            _ => 1,
+        };
+        self.call_method(Input::DispatchMouseEvent {
+            Type: event_type,
+            x: point.x,
+            y: point.y,
+            button: Some(Input::MouseButton::Left),
+            buttons: Some(buttons),
+            click_count: Some(1),
+            modifiers: None,
+            timestamp: None,
+            force: None,
+            tangential_pressure: None,
+            tilt_x: None,
+            tilt_y: None,
+            twist: None,
+            delta_x: None,
+            delta_y: None,
+            pointer_Type: None,
+        })?;
+        Ok(())
+    }
+
+    /// Makes the page report touch support (`navigator.maxTouchPoints`, touch events, `(pointer:
+    /// coarse)`...), as mobile-emulated pages often expect.
     ///
-    /// If `ignore_cache` is true, the browser cache is ignored (as if the user pressed Shift+F5).
-    /// If `script_to_evaluate` is given, the script will be injected into all frames of the
-    /// inspected page after reload. Argument will be ignored if reloading dataURL origin.
-    pub fn reload(
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setTouchEmulationEnabled>
+    pub fn set_touch_emulation_enabled(
         &self,
-        ignore_cache: bool,
-        script_to_evaluate_on_load: Option<&str>,
+        enabled: bool,
+        max_touch_points: Option<u32>,
     ) -> Result<&Self> {
-        self.optional_slow_motion_sleep(100);
-        self.call_method(Page::Reload {
-            ignore_cache: Some(ignore_cache),
-            script_to_evaluate_on_load: script_to_evaluate_on_load
-                .map(std::string::ToString::to_string),
+        self.call_method(Emulation::SetTouchEmulationEnabled {
+            enabled,
+            max_touch_points,
         })?;
         Ok(self)
     }
 
-    /// Set the background color of the dom to transparent.
-    ///
-    /// Useful when you want capture a .png
+    /// Overrides the size of the page's viewport, independently of the window's size, until
+    /// `clear_viewport` is called. `mobile` emulates a mobile device's viewport (e.g. honouring
+    /// `<meta name="viewport">` and overlay scrollbars).
     ///
     /// ```rust,no_run
     /// # use anyhow::Result;
     /// # fn main() -> Result<()> {
     /// #
-    /// use headless_chrome::{protocol::page::ScreenshotFormat, Browser, LaunchOptions};
-    /// let browser = Browser::new(LaunchOptions::default_builder().build().unwrap())?;
-    /// let tab = browser.wait_for_initial_tab()?;
-    /// tab.set_transparent_background_color()?;
-    ///
+    /// # use headless_chrome::Browser;
+    /// # use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.set_viewport(390, 844, 3.0, true)?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// let png = tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)?;
+    /// tab.clear_viewport()?;
     /// #
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_transparent_background_color(&self) -> Result<&Self> {
-        self.call_method(Emulation::SetDefaultBackgroundColorOverride {
-            color: Some(DOM::RGBA {
-                r: 0,
-                g: 0,
-                b: 0,
-                a: Some(0.0),
-            }),
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setDeviceMetricsOverride>
+    pub fn set_viewport(
+        &self,
+        width: u32,
+        height: u32,
+        device_scale_factor: f64,
+        mobile: bool,
+    ) -> Result<&Self> {
+        self.call_method(Emulation::SetDeviceMetricsOverride {
+            width,
+            height,
+            device_scale_factor,
+            mobile,
+            scale: None,
+            screen_width: None,
+            screen_height: None,
+            position_x: None,
+            position_y: None,
+            dont_set_visible_size: None,
+            screen_orientation: None,
+            viewport: None,
+            display_feature: None,
+            device_posture: None,
         })?;
         Ok(self)
     }
 
-    /// Set the default background color of the dom.
-    ///
-    /// Pass a RGBA to override the backrgound color of the dom.
+    /// Removes the viewport override set by `set_viewport`, so the viewport follows the window's
+    /// size again.
+    pub fn clear_viewport(&self) -> Result<&Self> {
+        self.call_method(Emulation::ClearDeviceMetricsOverride(None))?;
+        Ok(self)
+    }
+
+    /// Sets the fonts the page's generic font families (`serif`, `monospace` and so on) resolve
+    /// to, so that pages render with the same fonts whatever is installed where they're
+    /// rendered.
     ///
     /// ```rust,no_run
     /// # use anyhow::Result;
     /// # fn main() -> Result<()> {
     /// #
-    /// use headless_chrome::{protocol::page::ScreenshotFormat, Browser, LaunchOptions};
-    /// let browser = Browser::new(LaunchOptions::default_builder().build().unwrap())?;
-    /// let tab = browser.wait_for_initial_tab()?;
-    /// tab.set_background_color( color: RGBA { r: 255, g: 0, b: 0, a: 1.,})?;
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Page::FontFamilies;
     ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.set_font_families(FontFamilies {
+    ///     standard: Some("DejaVu Serif".to_string()),
+    ///     fixed: Some("DejaVu Sans Mono".to_string()),
+    ///     serif: Some("DejaVu Serif".to_string()),
+    ///     sans_serif: Some("DejaVu Sans".to_string()),
+    ///     cursive: None,
+    ///     fantasy: None,
+    ///     math: None,
+    /// })?;
     /// #
     /// # Ok(())
     /// # }
     /// ```
-    pub fn set_background_color(&self, color: DOM::RGBA) -> Result<&Self> {
-        self.call_method(Emulation::SetDefaultBackgroundColorOverride { color: Some(color) })?;
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-setFontFamilies>
+    pub fn set_font_families(&self, font_families: Page::FontFamilies) -> Result<&Self> {
+        self.call_method(Page::SetFontFamilies {
+            font_families,
+            for_scripts: None,
+        })?;
         Ok(self)
     }
 
-    /// Enables the profiler
-    pub fn enable_profiler(&self) -> Result<&Self> {
-        self.call_method(Profiler::Enable(None))?;
+    /// Sets the default font sizes, in pixels, of proportional (`standard`) and monospace
+    /// (`fixed`) text.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-setFontSizes>
+    pub fn set_font_sizes(&self, standard: Option<u32>, fixed: Option<u32>) -> Result<&Self> {
+        self.call_method(Page::SetFontSizes {
+            font_sizes: Page::FontSizes { standard, fixed },
+        })?;
+        Ok(self)
+    }
 
+    /// Taps the screen with one finger at `point`.
+    ///
+    /// The page only receives touch events if touch emulation is enabled, see
+    /// `set_touch_emulation_enabled`.
+    pub fn tap_point(&self, point: Point) -> Result<&Self> {
+        trace!("Tapping point: {point:?}");
+        self.optional_slow_motion_sleep(100);
+        self.dispatch_touch(Input::DispatchTouchEventTypeOption::TouchStart, vec![point])?;
+        self.dispatch_touch(Input::DispatchTouchEventTypeOption::TouchEnd, vec![])?;
         Ok(self)
     }
 
-    /// Disables the profiler
-    pub fn disable_profiler(&self) -> Result<&Self> {
-        self.call_method(Profiler::Disable(None))?;
+    /// Drags one finger from `from` to `to`, e.g. to flip through a carousel or scroll a list.
+    ///
+    /// The page only receives touch events if touch emulation is enabled, see
+    /// `set_touch_emulation_enabled`.
+    pub fn swipe(&self, from: Point, to: Point) -> Result<&Self> {
+        const STEPS: u32 = 10;
 
+        trace!("Swiping from {from:?} to {to:?}");
+        self.optional_slow_motion_sleep(100);
+        self.dispatch_touch(Input::DispatchTouchEventTypeOption::TouchStart, vec![from])?;
+        for step in 1..=STEPS {
+            let progress = f64::from(step) / f64::from(STEPS);
+            let point = Point {
+                x: from.x + (to.x - from.x) * progress,
+                y: from.y + (to.y - from.y) * progress,
+            };
+            self.dispatch_touch(Input::DispatchTouchEventTypeOption::TouchMove, vec![point])?;
+        }
+        self.dispatch_touch(Input::DispatchTouchEventTypeOption::TouchEnd, vec![])?;
         Ok(self)
     }
 
-    /// Starts tracking which lines of JS have been executed
-    ///
-    /// Will return error unless `enable_profiler` has been called.
-    ///
-    /// Equivalent to hitting the record button in the "coverage" tab in Chrome DevTools.
-    /// See the file `tests/coverage.rs` for an example.
-    ///
-    /// By default we enable the 'detailed' flag on StartPreciseCoverage, which enables block-level
-    /// granularity, and also enable 'call_count' (which when disabled always sets count to 1 or 0).
+    /// Pinches around `center` with two fingers, zooming in if `scale_factor` is greater than 1
+    /// and out if it's less.
     ///
-    pub fn start_js_coverage(&self) -> Result<&Self> {
-        self.call_method(Profiler::StartPreciseCoverage {
-            call_count: Some(true),
-            detailed: Some(true),
-            allow_triggered_updates: None,
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Input#method-synthesizePinchGesture>
+    pub fn pinch(&self, center: Point, scale_factor: f64) -> Result<&Self> {
+        self.call_method(Input::SynthesizePinchGesture {
+            x: center.x,
+            y: center.y,
+            scale_factor,
+            relative_speed: None,
+            gesture_source_Type: Some(Input::GestureSourceType::Touch),
         })?;
         Ok(self)
     }
 
-    /// Stops tracking which lines of JS have been executed
-    /// If you're finished with the profiler, don't forget to call `disable_profiler`.
-    pub fn stop_js_coverage(&self) -> Result<&Self> {
-        self.call_method(Profiler::StopPreciseCoverage(None))?;
-        Ok(self)
+    fn dispatch_touch(
+        &self,
+        event_type: Input::DispatchTouchEventTypeOption,
+        points: Vec<Point>,
+    ) -> Result<()> {
+        self.call_method(Input::DispatchTouchEvent {
+            Type: event_type,
+            touch_points: points
+                .into_iter()
+                .map(|point| Input::TouchPoint {
+                    x: point.x,
+                    y: point.y,
+                    radius_x: None,
+                    radius_y: None,
+                    rotation_angle: None,
+                    force: None,
+                    tangential_pressure: None,
+                    tilt_x: None,
+                    tilt_y: None,
+                    twist: None,
+                    id: None,
+                })
+                .collect(),
+            modifiers: None,
+            timestamp: None,
+        })?;
+        Ok(())
     }
 
-    /// Collect coverage data for the current isolate, and resets execution counters.
+    /// Capture a screenshot of the current page.
     ///
-    /// Precise code coverage needs to have started (see `start_js_coverage`).
+    /// If `clip` is given, the screenshot is taken of the specified region only.
+    /// `Element::get_box_model()` can be used to get regions of certains elements
+    /// on the page; there is also `Element::capture_screenhot()` as a shorthand.
     ///
-    /// Will only send information about code that's been executed since this method was last
-    /// called, or (if this is the first time) since calling `start_js_coverage`.
-    /// Another way of thinking about it is: every time you call this, the call counts for
-    /// FunctionRanges are reset after returning.
+    /// If `from_surface` is true, the screenshot is taken from the surface rather than
+    /// the view.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::{protocol::page::ScreenshotFormat, Browser, LaunchOptions};
+    /// let browser = Browser::new(LaunchOptions::default_builder().build().unwrap())?;
+    /// let tab = browser.wait_for_initial_tab()?;
+    /// let viewport = tab.navigate_to("https://en.wikipedia.org/wiki/WebKit")?
+    ///     .wait_for_element("#mw-content-text > div > table.infobox.vevent")?
+    ///     .get_box_model()?
+    ///     .margin_viewport();
+    ///  let png_data = tab.capture_screenshot(ScreenshotFormat::PNG, Some(viewport), true)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capture_screenshot(
+        &self,
+        format: Page::CaptureScreenshotFormatOption,
+        quality: Option<u32>,
+        clip: Option<Page::Viewport>,
+        from_surface: bool,
+    ) -> Result<Vec<u8>> {
+        let data = self
+            .call_method(Page::CaptureScreenshot {
+                format: Some(format),
+                clip,
+                quality,
+                from_surface: Some(from_surface),
+                capture_beyond_viewport: None,
+            })?
+            .data;
+        base64::decode(data).map_err(Into::into)
+    }
+
+    /// Takes a PNG screenshot and compares it to the one at `baseline_path`, failing with a
+    /// `ScreenshotMismatch` if any pixel differs by more than anti-aliasing noise. The first run
+    /// (when there's no baseline yet) saves the screenshot as the baseline. On failure, the
+    /// screenshot and an image highlighting the differences are saved next to the baseline, as
+    /// `<name>.actual.png` and `<name>.diff.png`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// tab.disable_css_animations()?;
+    /// tab.assert_screenshot_matches("tests/screenshots/example.png")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "image")]
+    pub fn assert_screenshot_matches<P: AsRef<std::path::Path>>(
+        &self,
+        baseline_path: P,
+    ) -> Result<()> {
+        let baseline_path = baseline_path.as_ref();
+        let screenshot =
+            self.capture_screenshot(Page::CaptureScreenshotFormatOption::Png, None, None, true)?;
+        if !baseline_path.exists() {
+            info!(
+                "Saving new screenshot baseline: {}",
+                baseline_path.display()
+            );
+            std::fs::write(baseline_path, screenshot)?;
+            return Ok(());
+        }
+
+        let diff = crate::screenshots::diff(&std::fs::read(baseline_path)?, &screenshot, 0.1)?;
+        if diff.mismatched_pixels == 0 {
+            return Ok(());
+        }
+        let diff_path = baseline_path.with_extension("diff.png");
+        std::fs::write(baseline_path.with_extension("actual.png"), screenshot)?;
+        std::fs::write(&diff_path, diff.diff_png)?;
+        Err(crate::screenshots::ScreenshotMismatch {
+            baseline: baseline_path.display().to_string(),
+            diff: diff_path.display().to_string(),
+            mismatch_percentage: diff.mismatch_percentage,
+        }
+        .into())
+    }
+
+    /// Saves the page and its subresources (images, stylesheets, frames...) as a single
+    /// self-contained MHTML archive.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// std::fs::write("example.mhtml", tab.capture_mhtml()?)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-captureSnapshot>
+    pub fn capture_mhtml(&self) -> Result<String> {
+        Ok(self
+            .call_method(Page::CaptureSnapshot {
+                format: Some(Page::CaptureSnapshotFormatOption::Mhtml),
+            })?
+            .data)
+    }
+
+    pub fn print_to_pdf(&self, options: Option<PrintToPdfOptions>) -> Result<Vec<u8>> {
+        let pdf = self.call_print_to_pdf(options, None)?;
+        let data = if let Some(handle) = pdf.stream {
+            let mut data = Vec::new();
+            self.read_stream(handle, &mut data)?;
+            data
+        } else {
+            base64::decode(pdf.data)?
+        };
+        self.post_process_pdf(data)
+    }
+
+    /// Prints the page to PDF like `print_to_pdf`, but has the browser hand the PDF over as a
+    /// stream, which is written to `writer` a chunk at a time; so large documents are never held
+    /// in memory whole (unless there's a `set_pdf_post_processor`, which needs the whole PDF).
+    /// Returns the number of bytes written.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let mut file = std::fs::File::create("report.pdf")?;
+    /// tab.print_to_pdf_to_writer(None, &mut file)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn print_to_pdf_to_writer<W: Write>(
+        &self,
+        options: Option<PrintToPdfOptions>,
+        writer: &mut W,
+    ) -> Result<u64> {
+        if self.pdf_post_processor.read().unwrap().is_some() {
+            let data = self.print_to_pdf(options)?;
+            writer.write_all(&data)?;
+            return Ok(data.len() as u64);
+        }
+
+        let pdf = self.call_print_to_pdf(
+            options,
+            Some(Page::PrintToPDFTransfer_modeOption::ReturnAsStream),
+        )?;
+        if let Some(handle) = pdf.stream {
+            self.read_stream(handle, writer)
+        } else {
+            let data = base64::decode(pdf.data)?;
+            writer.write_all(&data)?;
+            Ok(data.len() as u64)
+        }
+    }
+
+    fn call_print_to_pdf(
+        &self,
+        options: Option<PrintToPdfOptions>,
+        transfer_mode: Option<Page::PrintToPDFTransfer_modeOption>,
+    ) -> Result<Page::PrintToPDFReturnObject> {
+        let options = options.unwrap_or_default();
+        let newer_params = [
+            ("generateTaggedPDF", options.generate_tagged_pdf),
+            ("generateDocumentOutline", options.generate_document_outline),
+        ];
+        let mut method = Page::PrintToPDF::from(options);
+        if transfer_mode.is_some() {
+            method.transfer_mode = transfer_mode;
+        }
+        if newer_params.iter().all(|(_, value)| value.is_none()) {
+            return self.call_method(method);
+        }
+
+        // The protocol definitions this crate is generated from predate these parameters
+        let mut params = serde_json::to_value(&method)?;
+        for (name, value) in newer_params {
+            if let Some(value) = value {
+                params[name] = json!(value);
+            }
+        }
+        let result = self.call_raw(Page::PrintToPDF::NAME, params)?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Has every PDF the tab prints from now on go through `post_processor` before it's
+    /// returned, e.g. to attach metadata (title, author, XMP) or to convert it to PDF/A, which
+    /// Chrome can't produce itself. `None` removes it.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::sync::Arc;
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::types::PrintToPdfOptions;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// # fn attach_metadata(pdf: Vec<u8>) -> Result<Vec<u8>> { Ok(pdf) }
+    /// tab.set_pdf_post_processor(Some(Arc::new(|pdf| attach_metadata(pdf))));
+    /// let pdf = tab.print_to_pdf(Some(PrintToPdfOptions {
+    ///     generate_tagged_pdf: Some(true),
+    ///     generate_document_outline: Some(true),
+    ///     ..PrintToPdfOptions::default()
+    /// }))?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_pdf_post_processor(&self, post_processor: Option<Arc<PdfPostProcessor>>) -> &Self {
+        *self.pdf_post_processor.write().unwrap() = post_processor;
+        self
+    }
+
+    fn post_process_pdf(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        let post_processor = self.pdf_post_processor.read().unwrap().clone();
+        match post_processor {
+            Some(post_processor) => post_processor(data),
+            None => Ok(data),
+        }
+    }
+
+    /// Reads the stream with the given handle (e.g. a PDF printed with the `ReturnAsStream`
+    /// transfer mode) to its end, writing it to `writer` a chunk at a time, then closes it.
+    /// Returns the number of bytes written.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/IO#method-read>
+    pub fn read_stream<W: Write>(&self, handle: IO::StreamHandle, writer: &mut W) -> Result<u64> {
+        let mut reader = self.stream_reader(handle);
+        let copied = std::io::copy(&mut reader, writer)?;
+        reader.close()?;
+        Ok(copied)
+    }
+
+    /// Returns a `std::io::Read` over the stream with the given handle, for piping large blobs
+    /// (PDFs, traces, response bodies) somewhere without holding them in memory whole.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Page;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let pdf = tab.call_method(Page::PrintToPDF {
+    ///     transfer_mode: Some(Page::PrintToPDFTransfer_modeOption::ReturnAsStream),
+    ///     ..Default::default()
+    /// })?;
+    /// let mut reader = tab.stream_reader(pdf.stream.unwrap());
+    /// std::io::copy(&mut reader, &mut std::fs::File::create("page.pdf")?)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream_reader(&self, handle: IO::StreamHandle) -> StreamReader<'_> {
+        StreamReader::new(self, handle)
+    }
+
+    /// Reloads given page optionally ignoring the cache
+    ///
+    /// If `ignore_cache` is true, the browser cache is ignored (as if the user pressed Shift+F5).
+    /// If `script_to_evaluate` is given, the script will be injected into all frames of the
+    /// inspected page after reload. Argument will be ignored if reloading dataURL origin.
+    ///
+    /// Like `navigate_to`, this doesn't wait for the page to load; chain `wait_until_navigated`
+    /// for that.
+    pub fn reload(
+        &self,
+        ignore_cache: bool,
+        script_to_evaluate_on_load: Option<&str>,
+    ) -> Result<&Self> {
+        self.optional_slow_motion_sleep(100);
+        self.call_method(Page::Reload {
+            ignore_cache: Some(ignore_cache),
+            script_to_evaluate_on_load: script_to_evaluate_on_load
+                .map(std::string::ToString::to_string),
+        })?;
+        self.navigating.store(true, Ordering::SeqCst);
+        Ok(self)
+    }
+
+    /// Set the background color of the dom to transparent.
+    ///
+    /// Useful when you want capture a .png
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::{protocol::page::ScreenshotFormat, Browser, LaunchOptions};
+    /// let browser = Browser::new(LaunchOptions::default_builder().build().unwrap())?;
+    /// let tab = browser.wait_for_initial_tab()?;
+    /// tab.set_transparent_background_color()?;
+    ///
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_transparent_background_color(&self) -> Result<&Self> {
+        self.call_method(Emulation::SetDefaultBackgroundColorOverride {
+            color: Some(DOM::RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: Some(0.0),
+            }),
+        })?;
+        Ok(self)
+    }
+
+    /// Set the default background color of the dom.
+    ///
+    /// Pass a RGBA to override the backrgound color of the dom.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::{protocol::page::ScreenshotFormat, Browser, LaunchOptions};
+    /// let browser = Browser::new(LaunchOptions::default_builder().build().unwrap())?;
+    /// let tab = browser.wait_for_initial_tab()?;
+    /// tab.set_background_color( color: RGBA { r: 255, g: 0, b: 0, a: 1.,})?;
+    ///
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_background_color(&self, color: DOM::RGBA) -> Result<&Self> {
+        self.call_method(Emulation::SetDefaultBackgroundColorOverride { color: Some(color) })?;
+        Ok(self)
+    }
+
+    /// Makes CSS media queries match `media_type` (e.g. `"print"` or `"screen"`) rather than the
+    /// actual media type; `None` stops overriding it. Any emulated media features are kept.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setEmulatedMedia>
+    pub fn emulate_media_type(&self, media_type: Option<&str>) -> Result<&Self> {
+        let mut emulated_media = self.emulated_media.lock().unwrap();
+        let mut updated = emulated_media.clone();
+        updated.media = media_type.map(ToString::to_string);
+        // Only kept once it's in effect, so that a failed call leaves the state as it was
+        self.call_method(updated.clone())?;
+        *emulated_media = updated;
+        Ok(self)
+    }
+
+    /// Makes CSS media queries (and `matchMedia`) see the given `(name, value)` media features,
+    /// replacing any emulated previously. Pass an empty slice to stop emulating them. The
+    /// emulated media type is kept.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.emulate_media_features(&[
+    ///     ("prefers-color-scheme", "dark"),
+    ///     ("prefers-reduced-motion", "reduce"),
+    /// ])?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setEmulatedMedia>
+    pub fn emulate_media_features(&self, features: &[(&str, &str)]) -> Result<&Self> {
+        let mut emulated_media = self.emulated_media.lock().unwrap();
+        let mut updated = emulated_media.clone();
+        updated.features = Some(
+            features
+                .iter()
+                .map(|(name, value)| Emulation::MediaFeature {
+                    name: (*name).to_string(),
+                    value: (*value).to_string(),
+                })
+                .collect(),
+        );
+        self.call_method(updated.clone())?;
+        *emulated_media = updated;
+        Ok(self)
+    }
+
+    /// Renders the page as someone with the given vision deficiency would see it, e.g. to take
+    /// screenshots for accessibility reviews. `VisionDeficiency::None` turns this off.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::VisionDeficiency;
+    /// use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// tab.emulate_vision_deficiency(VisionDeficiency::Deuteranopia)?;
+    /// let png = tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setEmulatedVisionDeficiency>
+    pub fn emulate_vision_deficiency(&self, deficiency: VisionDeficiency) -> Result<&Self> {
+        self.call_method(Emulation::SetEmulatedVisionDeficiency { Type: deficiency })?;
+        Ok(self)
+    }
+
+    /// Emulates (or stops emulating) a forced colors mode such as Windows High Contrast, by
+    /// setting the `forced-colors` media feature. Other emulated media features are kept.
+    pub fn emulate_forced_colors(&self, active: bool) -> Result<&Self> {
+        let mut emulated_media = self.emulated_media.lock().unwrap();
+        let mut updated = emulated_media.clone();
+        let features = updated.features.get_or_insert_with(Vec::new);
+        features.retain(|feature| feature.name != "forced-colors");
+        if active {
+            features.push(Emulation::MediaFeature {
+                name: "forced-colors".to_string(),
+                value: "active".to_string(),
+            });
+        }
+        self.call_method(updated.clone())?;
+        *emulated_media = updated;
+        Ok(self)
+    }
+
+    /// Makes the page behave as if its window had focus (or stops doing so), which a headless
+    /// tab's never does otherwise: `document.hasFocus()` returns true, focus and blur events
+    /// fire, and APIs requiring focus such as the Clipboard API work.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setFocusEmulationEnabled>
+    pub fn emulate_focus(&self, enabled: bool) -> Result<&Self> {
+        self.call_method(Emulation::SetFocusEmulationEnabled { enabled })?;
+        Ok(self)
+    }
+
+    /// Overrides the state the Idle Detection API reports to the page: whether the user is idle
+    /// and whether the screen is locked.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setIdleOverride>
+    pub fn emulate_idle_state(&self, user_idle: bool, screen_locked: bool) -> Result<&Self> {
+        self.call_method(Emulation::SetIdleOverride {
+            is_user_active: !user_idle,
+            is_screen_unlocked: !screen_locked,
+        })?;
+        Ok(self)
+    }
+
+    /// Stops overriding the idle state, see `emulate_idle_state`.
+    pub fn clear_idle_state(&self) -> Result<&Self> {
+        self.call_method(Emulation::ClearIdleOverride(None))?;
+        Ok(self)
+    }
+
+    /// Makes documents loaded afterwards see `timestamp` as the current time, for as long as
+    /// they're open: `Date.now()` and `new Date()` return it and `performance.now()` returns 0.
+    /// Together with `seed_random`, this makes pages which show the time or random content
+    /// render the same way every time.
+    ///
+    /// Timers still fire in real time; see `set_virtual_time_policy` to control those.
+    /// Returns an identifier which can be passed to `remove_script_on_new_document`.
+    pub fn freeze_time(&self, timestamp: SystemTime) -> Result<Page::ScriptIdentifier> {
+        let millis = timestamp.duration_since(UNIX_EPOCH)?.as_secs_f64() * 1000.0;
+        self.evaluate_on_new_document(&determinism::freeze_time_script(millis))
+    }
+
+    /// Makes `Math.random()` in documents loaded afterwards return the same sequence of
+    /// numbers for the same `seed`. Each document starts the sequence afresh.
+    ///
+    /// Returns an identifier which can be passed to `remove_script_on_new_document`.
+    pub fn seed_random(&self, seed: u32) -> Result<Page::ScriptIdentifier> {
+        self.evaluate_on_new_document(&determinism::seed_random_script(seed))
+    }
+
+    /// Sets how the page's virtual time advances: with `VirtualTimePolicy::Pause`, timers and
+    /// animations stop until the policy changes; with `PauseIfNetworkFetchesPending`, time only
+    /// advances while no requests are in flight, so that what a page shows doesn't depend on
+    /// how fast its resources load.
+    ///
+    /// `budget` is how much virtual time may pass before it pauses again, and
+    /// `initial_virtual_time` is the time virtual time starts at, which `Date` then reports.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setVirtualTimePolicy>
+    pub fn set_virtual_time_policy(
+        &self,
+        policy: Emulation::VirtualTimePolicy,
+        budget: Option<Duration>,
+        initial_virtual_time: Option<SystemTime>,
+    ) -> Result<&Self> {
+        let initial_virtual_time = initial_virtual_time
+            .map(|time| time.duration_since(UNIX_EPOCH))
+            .transpose()?
+            .map(|since_epoch| since_epoch.as_secs_f64());
+        self.call_method(Emulation::SetVirtualTimePolicy {
+            policy,
+            budget: budget.map(|budget| budget.as_secs_f64() * 1000.0),
+            max_virtual_time_task_starvation_count: None,
+            initial_virtual_time,
+        })?;
+        Ok(self)
+    }
+
+    /// Lets `budget` of virtual time pass on the page, running its timers and animations as
+    /// fast as it can rather than in real time, and returns once it has. Time doesn't advance
+    /// while requests are in flight, so the page ends up as it would after `budget` on any
+    /// network. Virtual time is paused afterwards, until the policy changes again, including
+    /// when the budget doesn't run out within the tab's default timeout plus `budget` itself.
+    ///
+    /// Handy for rendering pages which animate in or poll on timers: a page whose chart
+    /// animates for five seconds can be captured in a fraction of that.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use std::time::Duration;
+    ///
+    /// use headless_chrome::protocol::cdp::{
+    ///     Emulation::VirtualTimePolicy, Page::CaptureScreenshotFormatOption,
+    /// };
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.new_tab()?;
+    /// tab.set_virtual_time_policy(VirtualTimePolicy::Pause, None, None)?;
+    /// tab.navigate_to("https://example.com/dashboard")?;
+    /// tab.run_with_virtual_time(Duration::from_secs(5))?;
+    /// let png = tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)?;
+    /// std::fs::write("dashboard.png", png)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#event-virtualTimeBudgetExpired>
+    pub fn run_with_virtual_time(&self, budget: Duration) -> Result<&Self> {
+        let expired = Arc::new(AtomicBool::new(false));
+        let expired_clone = Arc::clone(&expired);
+        let listener = self.add_event_listener(Arc::new(move |event: &Event| {
+            if let Event::EmulationVirtualTimeBudgetExpired(_) = event {
+                expired_clone.store(true, Ordering::SeqCst);
+            }
+        }))?;
+
+        let result = self
+            .set_virtual_time_policy(
+                Emulation::VirtualTimePolicy::PauseIfNetworkFetchesPending,
+                Some(budget),
+                None,
+            )
+            .and_then(|_| {
+                // Virtual time usually runs faster than real time, but not while waiting on the
+                // network, so allow for the whole budget on top of the usual timeout
+                util::Wait::with_timeout(*self.default_timeout.read().unwrap() + budget)
+                    .until(|| expired.load(Ordering::SeqCst).then_some(()))
+                    .map_err(Into::into)
+            });
+        self.remove_event_listener(&listener)?;
+        if let Err(err) = result {
+            // Rather than leaving the page to use up the rest of the budget
+            if let Err(reset_err) =
+                self.set_virtual_time_policy(Emulation::VirtualTimePolicy::Pause, None, None)
+            {
+                debug!("Couldn't pause virtual time after running it failed: {reset_err:?}");
+            }
+            return Err(err);
+        }
+        Ok(self)
+    }
+
+    /// Overrides the device orientation the page sees through `deviceorientation` events, in
+    /// degrees: `alpha` around the z axis (0 to 360), `beta` front to back (-180 to 180) and
+    /// `gamma` left to right (-90 to 90).
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/DeviceOrientation#method-setDeviceOrientationOverride>
+    pub fn set_device_orientation(&self, alpha: f64, beta: f64, gamma: f64) -> Result<&Self> {
+        self.call_method(DeviceOrientation::SetDeviceOrientationOverride { alpha, beta, gamma })?;
+        Ok(self)
+    }
+
+    /// Stops overriding the device orientation, see `set_device_orientation`.
+    pub fn clear_device_orientation(&self) -> Result<&Self> {
+        self.call_method(DeviceOrientation::ClearDeviceOrientationOverride(None))?;
+        Ok(self)
+    }
+
+    /// Overrides the readings of a sensor the page accesses through the Generic Sensor API
+    /// (e.g. `Accelerometer` or `AmbientLightSensor`), emulating the sensor if the device has
+    /// none. The first call for a sensor replaces the real one; later calls just change the
+    /// reading.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Emulation::{SensorReading, SensorReadingSingle, SensorType};
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.set_sensor_reading(
+    ///     SensorType::AmbientLight,
+    ///     SensorReading {
+    ///         single: Some(SensorReadingSingle { value: 50.0 }),
+    ///         xyz: None,
+    ///         quaternion: None,
+    ///     },
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Emulation#method-setSensorOverrideReadings>
+    pub fn set_sensor_reading(
+        &self,
+        sensor: Emulation::SensorType,
+        reading: Emulation::SensorReading,
+    ) -> Result<&Self> {
+        let mut sensor_overrides = self.sensor_overrides.lock().unwrap();
+        if !sensor_overrides.contains(&sensor) {
+            self.call_method(Emulation::SetSensorOverrideEnabled {
+                enabled: true,
+                Type: sensor.clone(),
+                metadata: Some(Emulation::SensorMetadata {
+                    available: Some(true),
+                    minimum_frequency: None,
+                    maximum_frequency: None,
+                }),
+            })?;
+            sensor_overrides.push(sensor.clone());
+        }
+        self.call_method(Emulation::SetSensorOverrideReadings {
+            Type: sensor,
+            reading,
+        })?;
+        Ok(self)
+    }
+
+    /// Stops overriding a sensor, see `set_sensor_reading`.
+    pub fn clear_sensor_override(&self, sensor: Emulation::SensorType) -> Result<&Self> {
+        let mut sensor_overrides = self.sensor_overrides.lock().unwrap();
+        self.call_method(Emulation::SetSensorOverrideEnabled {
+            enabled: false,
+            Type: sensor.clone(),
+            metadata: None,
+        })?;
+        sensor_overrides.retain(|overridden| *overridden != sensor);
+        Ok(self)
+    }
+
+    /// Replaces the device's Bluetooth adapter with a simulated one (or changes the state of the
+    /// simulated one), so Web Bluetooth can be used without real hardware. Peripherals are then
+    /// simulated with `simulate_bluetooth_advertisement` or `simulate_bluetooth_peripheral`, and
+    /// the page's device chooser answered with `auto_select_device_prompts`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::BluetoothEmulation::CentralState;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.emulate_bluetooth_adapter(CentralState::PoweredOn)?;
+    /// tab.simulate_bluetooth_peripheral("09:09:09:09:09:09", "Heart Rate", &["heart_rate"])?;
+    /// tab.accept_bluetooth_gatt_operations()?;
+    /// tab.auto_select_device_prompts(|devices| {
+    ///     devices
+    ///         .iter()
+    ///         .find(|device| device.name == "Heart Rate")
+    ///         .map(|device| device.id.clone())
+    /// })?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/BluetoothEmulation>
+    pub fn emulate_bluetooth_adapter(
+        &self,
+        state: BluetoothEmulation::CentralState,
+    ) -> Result<&Self> {
+        if self.bluetooth_emulated.swap(true, Ordering::SeqCst) {
+            self.call_method(BluetoothEmulation::SetSimulatedCentralState { state })?;
+        } else if let Err(err) = self.call_method(BluetoothEmulation::Enable {
+            state,
+            le_supported: true,
+        }) {
+            self.bluetooth_emulated.store(false, Ordering::SeqCst);
+            return Err(err);
+        }
+        Ok(self)
+    }
+
+    /// Stops simulating the Bluetooth adapter, forgetting the simulated peripherals.
+    pub fn stop_bluetooth_emulation(&self) -> Result<&Self> {
+        self.call_method(BluetoothEmulation::Disable(None))?;
+        self.bluetooth_emulated.store(false, Ordering::SeqCst);
+        Ok(self)
+    }
+
+    /// Has the simulated Bluetooth adapter see an advertisement packet, i.e. a peripheral the
+    /// page can find by scanning or with `requestDevice`.
+    pub fn simulate_bluetooth_advertisement(
+        &self,
+        entry: BluetoothEmulation::ScanEntry,
+    ) -> Result<&Self> {
+        self.call_method(BluetoothEmulation::SimulateAdvertisement { entry })?;
+        Ok(self)
+    }
+
+    /// Simulates a peripheral already connected to the simulated Bluetooth adapter, offering the
+    /// given GATT services (by UUID or by name, e.g. `heart_rate`).
+    pub fn simulate_bluetooth_peripheral(
+        &self,
+        address: &str,
+        name: &str,
+        service_uuids: &[&str],
+    ) -> Result<&Self> {
+        self.call_method(BluetoothEmulation::SimulatePreconnectedPeripheral {
+            address: address.to_string(),
+            name: name.to_string(),
+            manufacturer_data: Vec::new(),
+            known_service_uuids: service_uuids.iter().map(ToString::to_string).collect(),
+        })?;
+        Ok(self)
+    }
+
+    /// Adds a GATT service to a simulated peripheral, returning its id.
+    pub fn add_bluetooth_service(&self, address: &str, service_uuid: &str) -> Result<String> {
+        Ok(self
+            .call_method(BluetoothEmulation::AddService {
+                address: address.to_string(),
+                service_uuid: service_uuid.to_string(),
+            })?
+            .service_id)
+    }
+
+    /// Adds a characteristic to a service added with `add_bluetooth_service`, returning its id.
+    pub fn add_bluetooth_characteristic(
+        &self,
+        service_id: &str,
+        characteristic_uuid: &str,
+        properties: BluetoothEmulation::CharacteristicProperties,
+    ) -> Result<String> {
+        Ok(self
+            .call_method(BluetoothEmulation::AddCharacteristic {
+                service_id: service_id.to_string(),
+                characteristic_uuid: characteristic_uuid.to_string(),
+                properties,
+            })?
+            .characteristic_id)
+    }
+
+    /// Has the simulated peripherals accept every GATT connection and service discovery the page
+    /// attempts, which otherwise wait for a `BluetoothEmulation.simulateGATTOperationResponse`.
+    /// Returns the underlying event listener, which can be passed to `remove_event_listener` to
+    /// stop.
+    pub fn accept_bluetooth_gatt_operations(&self) -> Result<Weak<SyncSendEvent>> {
+        let transport = Arc::clone(&self.transport);
+        let session_id = self.session_id.clone();
+        self.add_event_listener(Arc::new(move |event: &Event| {
+            if let Event::BluetoothEmulationGattOperationReceived(ev) = event {
+                // 0 is GATT_SUCCESS
+                if let Err(err) = transport.call_method_on_target(
+                    session_id.clone(),
+                    BluetoothEmulation::SimulateGATTOperationResponse {
+                        address: ev.params.address.clone(),
+                        Type: ev.params.Type.clone(),
+                        code: 0,
+                    },
+                ) {
+                    warn!("Failed to accept GATT operation: {err:?}");
+                }
+            }
+        }))
+    }
+
+    /// Answers the device choosers the page opens (e.g. with `navigator.bluetooth.requestDevice`)
+    /// with the device `choose` picks among the ones found so far. The chooser stays open while
+    /// `choose` returns `None`, and `choose` is called again as more devices are found. Returns
+    /// the underlying event listener, which can be passed to `remove_event_listener` to stop.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/DeviceAccess>
+    pub fn auto_select_device_prompts<F>(&self, choose: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&[DeviceAccess::PromptDevice]) -> Option<DeviceAccess::DeviceId>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let transport = Arc::clone(&self.transport);
+        let session_id = self.session_id.clone();
+        let listener = self.add_event_listener(Arc::new(move |event: &Event| {
+            if let Event::DeviceAccessDeviceRequestPrompted(ev) = event {
+                if let Some(device_id) = choose(&ev.params.devices) {
+                    if let Err(err) = transport.call_method_on_target(
+                        session_id.clone(),
+                        DeviceAccess::SelectPrompt {
+                            id: ev.params.id.clone(),
+                            device_id,
+                        },
+                    ) {
+                        warn!("Failed to select device: {err:?}");
+                    }
+                }
+            }
+        }))?;
+        self.call_method(DeviceAccess::Enable(None))?;
+        Ok(listener)
+    }
+
+    /// Returns the page's runtime metrics, such as `JSHeapUsedSize`, `Nodes` or `TaskDuration`,
+    /// by name.
+    ///
+    /// The first call enables the Performance domain for the tab's lifetime, since disabling it
+    /// resets cumulative metrics such as `TaskDuration`.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Performance#method-getMetrics>
+    pub fn get_performance_metrics(&self) -> Result<HashMap<String, f64>> {
+        self.acquire_domain(Domain::Performance, "get_performance_metrics")?;
+        Ok(self
+            .call_method(Performance::GetMetrics(None))?
+            .metrics
+            .into_iter()
+            .map(|metric| (metric.name, metric.value))
+            .collect())
+    }
+
+    /// Checks the page's JavaScript heap size (its `JSHeapUsedSize` metric) every second, and
+    /// closes the tab or reloads its page once it grows beyond `limit_bytes`, so that a page
+    /// which leaks memory can't take a long-running service down with it. Replaces any limit
+    /// set before.
+    ///
+    /// The heap size comes from `Performance.getMetrics`. `Memory.getBrowserSamplingProfile`
+    /// isn't used: it samples the allocations of the whole browser rather than measuring one
+    /// page, so it can't tell which tab is over its limit.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::memory_guard::MemoryLimitAction;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.new_tab()?;
+    /// tab.set_memory_limit(512 * 1024 * 1024, MemoryLimitAction::Recycle)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_memory_limit(&self, limit_bytes: u64, action: MemoryLimitAction) -> Result<&Self> {
+        self.acquire_domain(Domain::Performance, "memory_limit")?;
+        let stop = Arc::new(AtomicBool::new(false));
+        if let Some(previous) = self.memory_guard.lock().unwrap().replace(Arc::clone(&stop)) {
+            previous.store(true, Ordering::SeqCst);
+        }
+
+        // Once the guard stops by itself (having closed the tab, or failed to check it), it gives
+        // up its slot and its claim on the domain, unless it's been replaced or cleared already
+        let on_exit = {
+            let memory_guard = Arc::clone(&self.memory_guard);
+            let domains = Arc::clone(&self.domains);
+            let transport = Arc::clone(&self.transport);
+            let session_id = self.session_id.clone();
+            let stop = Arc::clone(&stop);
+            move |closed_tab: bool| {
+                let mut current = memory_guard.lock().unwrap();
+                if !current
+                    .as_ref()
+                    .is_some_and(|current| Arc::ptr_eq(current, &stop))
+                {
+                    return;
+                }
+                current.take();
+                drop(current);
+                // A closed tab has nothing left to disable
+                let released = domains.release(Domain::Performance, "memory_limit", || {
+                    if closed_tab {
+                        return Ok(());
+                    }
+                    transport
+                        .call_method_on_target(session_id, Performance::Disable(None))
+                        .map(|_| ())
+                });
+                if let Err(err) = released {
+                    debug!("Couldn't disable Performance once the memory guard stopped: {err:?}");
+                }
+            }
+        };
+        MemoryGuard {
+            transport: Arc::clone(&self.transport),
+            session_id: self.session_id.clone(),
+            target_id: self.target_id.clone(),
+            limit_bytes,
+            action,
+            stop,
+        }
+        .spawn(on_exit);
+        Ok(self)
+    }
+
+    /// Stops enforcing the limit set with `set_memory_limit`.
+    pub fn clear_memory_limit(&self) -> Result<&Self> {
+        if let Some(stop) = self.memory_guard.lock().unwrap().take() {
+            stop.store(true, Ordering::SeqCst);
+            self.release_domain(Domain::Performance, "memory_limit")?;
+        }
+        Ok(self)
+    }
+
+    /// Starts recording the calls made on the tab and the events it receives, with their
+    /// credentials and cookies redacted, for `debug_snapshot`. Also enables the `Runtime`, `Log`
+    /// and `Network` domains, so that it reports the page's console messages and the requests it
+    /// has in flight from then on.
+    pub fn start_debug_recording(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Runtime, "debug_snapshot")?
+            .acquire_domain(Domain::Log, "debug_snapshot")?
+            .acquire_domain(Domain::Network, "debug_snapshot")?;
+        self.debug_recording.store(true, Ordering::SeqCst);
+        Ok(self)
+    }
+
+    /// Stops recording, keeping what was recorded for `debug_snapshot`.
+    pub fn stop_debug_recording(&self) -> Result<&Self> {
+        self.debug_recording.store(false, Ordering::SeqCst);
+        self.release_domain(Domain::Runtime, "debug_snapshot")?
+            .release_domain(Domain::Log, "debug_snapshot")?
+            .release_domain(Domain::Network, "debug_snapshot")
+    }
+
+    /// Gathers the tab's current URL and frame tree, the requests it has in flight, its latest
+    /// console messages, what is set up to intercept its requests and events, and the latest
+    /// calls made on it and events it received, into one struct which serializes to JSON for
+    /// bug reports. Only what happened while recording is reported, see
+    /// `start_debug_recording`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.new_tab()?;
+    /// tab.start_debug_recording()?;
+    /// if tab.navigate_to("https://example.com")?.wait_for_element("#login").is_err() {
+    ///     let snapshot = tab.debug_snapshot()?;
+    ///     std::fs::write("snapshot.json", serde_json::to_string_pretty(&snapshot)?)?;
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn debug_snapshot(&self) -> Result<DebugSnapshot> {
+        let frame_tree = self
+            .call_method(Page::GetFrameTree(None))
+            .map(|tree| tree.frame_tree)
+            .ok();
+        let fetch_patterns = self
+            .setup
+            .lock()
+            .unwrap()
+            .fetch
+            .as_ref()
+            .map(|fetch| fetch.patterns.clone().unwrap_or_default());
+        let context_middlewares = self
+            .context_middleware
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |chain| chain.middlewares().len());
+        let mut response_handlers: Vec<_> = self
+            .response_handler
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        response_handlers.sort();
+        let mut loading_failed_handlers: Vec<_> = self
+            .loading_failed_handler
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        loading_failed_handlers.sort();
+        let mut bindings: Vec<_> = self.page_bindings.lock().unwrap().keys().cloned().collect();
+        bindings.sort();
+
+        let interceptors = Interceptors {
+            fetch_patterns,
+            middlewares: self.middleware.middlewares().len() + context_middlewares,
+            response_handlers,
+            loading_failed_handlers,
+            bindings,
+            event_listeners: self.event_listeners.lock().unwrap().len(),
+            navigation_policy: self.navigation_policy.read().unwrap().is_some(),
+        };
+
+        let history = self.debug_history.lock().unwrap();
+        Ok(DebugSnapshot {
+            target_id: self.target_id.clone(),
+            url: self.get_url(),
+            title: self.target_info.lock().unwrap().title.clone(),
+            crashed: self.is_crashed(),
+            frame_tree,
+            pending_requests: history.pending_requests(),
+            console: history.console(),
+            interceptors,
+            recent_messages: history.messages(),
+        })
+    }
+
+    /// Returns the protocol domains (such as `Page` or `Network`) the connected browser build
+    /// supports, with their versions. They're only fetched once per tab.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Schema#method-getDomains>
+    pub fn get_protocol_domains(&self) -> Result<Vec<Schema::Domain>> {
+        let mut protocol_domains = self.protocol_domains.lock().unwrap();
+        if protocol_domains.is_none() {
+            *protocol_domains = Some(self.call_method(Schema::GetDomains(None))?.domains);
+        }
+        Ok(protocol_domains.clone().unwrap_or_default())
+    }
+
+    /// Returns whether the connected browser build has the domain of the command `C` (e.g.
+    /// `Audits` for `Audits::Enable`), logging a warning if it doesn't, so that code relying on
+    /// newer (or removed) parts of the protocol can fall back gracefully.
+    ///
+    /// Only the domain is checked, not the command itself: the browser only reports its domains,
+    /// through the deprecated `Schema.getDomains` (see [`Tab::get_protocol_domains`]). So this
+    /// returns true for a command the browser's build has dropped or doesn't have yet, as long as
+    /// the rest of its domain is there, and calling it then fails with a "method not found"
+    /// error.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Audits;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// if tab.supports::<Audits::Enable>()? {
+    ///     tab.call_method(Audits::Enable(None))?;
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn supports<C: Method>(&self) -> Result<bool> {
+        let domain = C::NAME.split('.').next().unwrap_or_default();
+        let supported = self
+            .get_protocol_domains()?
+            .iter()
+            .any(|supported_domain| supported_domain.name == domain);
+        if !supported {
+            warn!(
+                "{} isn't supported by the connected browser: it has no {domain} domain",
+                C::NAME
+            );
+        }
+        Ok(supported)
+    }
+
+    /// Enables the profiler
+    pub fn enable_profiler(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Profiler, "user")
+    }
+
+    /// Disables the profiler
+    pub fn disable_profiler(&self) -> Result<&Self> {
+        self.release_domain(Domain::Profiler, "user")
+    }
+
+    /// Starts tracking which lines of JS have been executed, enabling the profiler if need be.
+    ///
+    /// Equivalent to hitting the record button in the "coverage" tab in Chrome DevTools.
+    /// See the file `tests/coverage.rs` for an example.
+    ///
+    /// By default we enable the 'detailed' flag on StartPreciseCoverage, which enables block-level
+    /// granularity, and also enable 'call_count' (which when disabled always sets count to 1 or 0).
+    ///
+    pub fn start_js_coverage(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Profiler, "js_coverage")?;
+        self.call_method(Profiler::StartPreciseCoverage {
+            call_count: Some(true),
+            detailed: Some(true),
+            allow_triggered_updates: None,
+        })?;
+        Ok(self)
+    }
+
+    /// Stops tracking which lines of JS have been executed
+    /// If you're finished with the profiler, don't forget to call `disable_profiler`.
+    pub fn stop_js_coverage(&self) -> Result<&Self> {
+        self.call_method(Profiler::StopPreciseCoverage(None))?;
+        self.release_domain(Domain::Profiler, "js_coverage")
+    }
+
+    /// Collect coverage data for the current isolate, and resets execution counters.
+    ///
+    /// Precise code coverage needs to have started (see `start_js_coverage`).
+    ///
+    /// Will only send information about code that's been executed since this method was last
+    /// called, or (if this is the first time) since calling `start_js_coverage`.
+    /// Another way of thinking about it is: every time you call this, the call counts for
+    /// FunctionRanges are reset after returning.
     ///
     /// The format of the data is a little unintuitive, see here for details:
     /// <https://chromedevtools.github.io/devtools-protocol/tot/Profiler#type-ScriptCoverage>
@@ -1214,211 +3948,1492 @@ impl Tab {
         Ok(script_coverages)
     }
 
-    /// Enables fetch domain.
-    pub fn enable_fetch(
+    /// Enables fetch domain, pausing the requests matching `patterns` (every request if
+    /// `None`) on top of those the tab's own features need paused.
+    pub fn enable_fetch(
+        &self,
+        patterns: Option<&[Fetch::RequestPattern]>,
+        handle_auth_requests: Option<bool>,
+    ) -> Result<&Self> {
+        self.acquire_fetch("user", patterns, handle_auth_requests.unwrap_or(false))
+    }
+
+    /// Disables fetch domain, unless another of the tab's features still needs it
+    pub fn disable_fetch(&self) -> Result<&Self> {
+        self.release_fetch("user")
+    }
+
+    /// Like `acquire_domain(Domain::Fetch, holder)`, for pausing the requests matching
+    /// `patterns` (every request if `None`), and auth challenges if `handle_auth_requests` is
+    /// set. The tab pauses what any of `Fetch`'s holders needs paused, so acquiring it again
+    /// for the same holder replaces what that holder needs.
+    pub fn acquire_fetch(
+        &self,
+        holder: &str,
+        patterns: Option<&[Fetch::RequestPattern]>,
+        handle_auth_requests: bool,
+    ) -> Result<&Self> {
+        let previous = self.domains.set_fetch_requirement(
+            holder,
+            Some(Fetch::Enable {
+                patterns: patterns.map(Vec::from),
+                handle_auth_requests: handle_auth_requests.then_some(true),
+            }),
+        );
+        let already_enabled = !self.domains.holders(Domain::Fetch).is_empty();
+        let result = if already_enabled {
+            self.acquire_domain(Domain::Fetch, holder)
+                .and_then(|_| self.call_method(self.domains.merged_fetch()))
+                .map(|_| ())
+        } else {
+            self.acquire_domain(Domain::Fetch, holder).map(|_| ())
+        };
+        if let Err(err) = result {
+            self.domains.set_fetch_requirement(holder, previous);
+            return Err(err);
+        }
+        self.setup.lock().unwrap().fetch = Some(self.domains.merged_fetch());
+        Ok(self)
+    }
+
+    /// Releases `Fetch` for `holder`, see `acquire_fetch`; it stays enabled, pausing only what
+    /// the other holders need paused, if anything still holds it.
+    pub fn release_fetch(&self, holder: &str) -> Result<&Self> {
+        let previous = self.domains.set_fetch_requirement(holder, None);
+        if previous.is_none()
+            && !self
+                .domains
+                .holders(Domain::Fetch)
+                .contains(&holder.to_string())
+        {
+            return Ok(self);
+        }
+        if let Err(err) = self.release_domain(Domain::Fetch, holder) {
+            self.domains.set_fetch_requirement(holder, previous);
+            return Err(err);
+        }
+        let mut setup = self.setup.lock().unwrap();
+        if self.domains.holders(Domain::Fetch).is_empty() {
+            setup.fetch = None;
+        } else {
+            let merged = self.domains.merged_fetch();
+            self.call_method(merged.clone())?;
+            setup.fetch = Some(merged);
+        }
+        Ok(self)
+    }
+
+    /// Allows you to inspect outgoing network requests from the tab, and optionally return
+    /// your own responses to them
+    ///
+    /// The `interceptor` argument is a closure which takes this tab's `Transport` and its SessionID
+    /// so that you can call methods from within the closure using `transport.call_method_on_target`.
+    ///
+    /// The closure needs to return a variant of `RequestPausedDecision`.
+    pub fn enable_request_interception(&self, interceptor: Arc<RequestIntercept>) -> Result<()> {
+        let mut current_interceptor = self.request_interceptor.lock().unwrap();
+        *current_interceptor = interceptor;
+        Ok(())
+    }
+
+    /// Adds a step to this tab's request handling, which can rewrite request URLs and headers,
+    /// and transform response bodies (e.g. to stub a JSON API in a test). Middleware runs in the
+    /// order it was added, after any middleware added to the tab's context.
+    ///
+    /// The interceptor set with `enable_request_interception` still sees every paused request,
+    /// as the page made it, after the middleware has run. A fulfilled or failed request is left
+    /// that way; changes the interceptor makes to a continued request override the middleware's.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::sync::Arc;
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::middleware::{
+    ///     InterceptedRequest, InterceptedResponse, Middleware,
+    /// };
+    ///
+    /// struct StubUsers;
+    ///
+    /// impl Middleware for StubUsers {
+    ///     fn on_request(&self, request: &mut InterceptedRequest) {
+    ///         request.headers.push(("X-Test".to_string(), "1".to_string()));
+    ///     }
+    ///
+    ///     fn intercepts_response(&self, request: &InterceptedRequest) -> bool {
+    ///         request.url.ends_with("/api/users")
+    ///     }
+    ///
+    ///     fn on_response(&self, response: &mut InterceptedResponse) {
+    ///         response.status = 200;
+    ///         response.set_json_body(&["alice", "bob"]).unwrap();
+    ///     }
+    /// }
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let stub = tab.add_middleware(Arc::new(StubUsers))?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// tab.remove_middleware(stub)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_middleware(&self, middleware: Arc<SyncMiddleware>) -> Result<MiddlewareId> {
+        let id = self.middleware.add(middleware);
+        self.acquire_fetch("middleware", None, true)?;
+        Ok(id)
+    }
+
+    /// Removes a middleware added with `add_middleware`, returning whether it was still
+    /// registered.
+    pub fn remove_middleware(&self, id: MiddlewareId) -> Result<bool> {
+        let removed = self.middleware.remove(id);
+        if removed && self.middleware.is_empty() {
+            self.release_fetch("middleware")?;
+        }
+        Ok(removed)
+    }
+
+    /// Restricts which URLs the tab may load, for tabs loading URLs from people you don't
+    /// trust: `navigate_to` fails with `NavigationBlocked` for URLs the policy doesn't allow,
+    /// and any other request for one (a redirect, a subresource, a navigation the page starts)
+    /// fails with `net::ERR_BLOCKED_BY_CLIENT`. Pass `None` to stop restricting.
+    ///
+    /// The policy is checked before any middleware or request interceptor sees a request.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::navigation_policy::NavigationPolicy;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.new_tab()?;
+    /// // Blocks file:// URLs and anything on localhost or the local network
+    /// tab.set_navigation_policy(Some(NavigationPolicy::default()))?;
+    /// # let user_supplied_url = "https://example.com";
+    /// tab.navigate_to(user_supplied_url)?.wait_until_navigated()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_navigation_policy(&self, policy: Option<NavigationPolicy>) -> Result<&Self> {
+        let enabled = policy.is_some();
+        *self.navigation_policy.write().unwrap() = policy;
+        if enabled {
+            self.acquire_fetch("navigation_policy", None, false)
+        } else {
+            self.release_fetch("navigation_policy")
+        }
+    }
+
+    pub(crate) fn set_navigation_scheduler(&self, scheduler: Arc<NavigationScheduler>) {
+        *self.navigation_scheduler.lock().unwrap() = Some(scheduler);
+    }
+
+    pub(crate) fn set_context_middleware(&self, chain: Arc<MiddlewareChain>) -> Result<()> {
+        let empty = chain.is_empty();
+        *self.context_middleware.lock().unwrap() = Some(chain);
+        if empty {
+            self.release_fetch("context_middleware")?;
+        } else {
+            self.acquire_fetch("context_middleware", None, true)?;
+        }
+        Ok(())
+    }
+
+    /// Blocks every request whose URL matches one of `patterns`, which may contain `*`
+    /// wildcards. Blocked requests fail with `net::ERR_BLOCKED_BY_CLIENT`. Pass an empty slice
+    /// to stop blocking.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.block_urls(&["*.png", "*googletagmanager*"])?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Network#method-setBlockedURLs>
+    pub fn block_urls(&self, patterns: &[&str]) -> Result<&Self> {
+        if !patterns.is_empty() {
+            self.acquire_domain(Domain::Network, "block_urls")?;
+        } else if !self
+            .domain_holders(Domain::Network)
+            .iter()
+            .any(|holder| holder == "block_urls")
+        {
+            // Nothing is blocked
+            return Ok(self);
+        }
+        self.call_method(Network::SetBlockedURLs {
+            urls: patterns
+                .iter()
+                .map(|pattern| (*pattern).to_string())
+                .collect(),
+        })?;
+        if patterns.is_empty() {
+            self.release_domain(Domain::Network, "block_urls")?;
+        }
+        Ok(self)
+    }
+
+    /// Blocks every request for the given kinds of resources, which can make scraping a lot
+    /// faster when images, fonts or media aren't needed.
+    ///
+    /// This pauses matching requests via the `Fetch` domain and replaces the tab's request
+    /// interceptor, so it can't be combined with `enable_request_interception`.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Network::ResourceType;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.block_resource_types(&[ResourceType::Image, ResourceType::Font, ResourceType::Media])?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_resource_types(&self, resource_types: &[Network::ResourceType]) -> Result<&Self> {
+        let blocked = resource_types.to_vec();
+        self.enable_request_interception(Arc::new(
+            move |_transport, _session_id, intercepted: RequestPausedEvent| {
+                if blocked.contains(&intercepted.params.resource_Type) {
+                    RequestPausedDecision::Fail(FailRequest {
+                        request_id: intercepted.params.request_id,
+                        error_reason: Network::ErrorReason::BlockedByClient,
+                    })
+                } else {
+                    RequestPausedDecision::Continue(None)
+                }
+            },
+        ))?;
+
+        let patterns: Vec<Fetch::RequestPattern> = resource_types
+            .iter()
+            .map(|resource_type| Fetch::RequestPattern {
+                url_pattern: None,
+                resource_Type: Some(resource_type.clone()),
+                request_stage: Some(Fetch::RequestStage::Request),
+            })
+            .collect();
+        if patterns.is_empty() {
+            self.release_fetch("block_resource_types")
+        } else {
+            self.acquire_fetch("block_resource_types", Some(&patterns), false)
+        }
+    }
+
+    pub fn authenticate(
+        &self,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<&Self> {
+        let mut current_auth_handler = self.auth_handler.lock().unwrap();
+        *current_auth_handler = AuthChallengeResponse {
+            response: Fetch::AuthChallengeResponseResponse::ProvideCredentials,
+            username,
+            password,
+        };
+        Ok(self)
+    }
+
+    /// Lets you register a listener for responses, and gives you a way to get the response body too.
+    ///
+    /// Please note that the 'response' does not include the *body* of the response -- Chrome tells
+    /// us about them seperately (because you might quickly get the status code and headers from a
+    /// server well before you receive the entire response body which could, after all, be gigabytes
+    /// long).
+    ///
+    /// Currently we leave it up to the caller to decide when to call `fetch_body` (the second
+    /// argument to the response handler), although ideally it wouldn't be possible until Chrome has
+    /// sent the `Network.loadingFinished` event.
+    ///
+    /// Return a option for ResponseHander for existing handler with same name if existed.
+    pub fn register_response_handling<S: ToString>(
+        &self,
+        handler_name: S,
+        handler: ResponseHandler,
+    ) -> Result<Option<ResponseHandler>> {
+        self.acquire_domain(Domain::Network, "register_response_handling")?;
+        Ok(self
+            .response_handler
+            .lock()
+            .unwrap()
+            .insert(handler_name.to_string(), handler))
+    }
+
+    pub fn register_loading_failed_handling<S: ToString>(
+        &self,
+        handler_name: S,
+        handler: LoadingFailedHandler,
+    ) -> Result<Option<LoadingFailedHandler>> {
+        self.acquire_domain(Domain::Network, "register_loading_failed_handling")?;
+        Ok(self
+            .loading_failed_handler
+            .lock()
+            .unwrap()
+            .insert(handler_name.to_string(), handler))
+    }
+
+    /// Deregister a reponse handler based on its name.
+    ///
+    /// Return a option for ResponseHandler for removed handler if existed.
+    pub fn deregister_response_handling(
+        &self,
+        handler_name: &str,
+    ) -> Result<Option<ResponseHandler>> {
+        let mut response_handlers = self.response_handler.lock().unwrap();
+        let removed = response_handlers.remove(handler_name);
+        if response_handlers.is_empty() {
+            self.release_domain(Domain::Network, "register_response_handling")?;
+        }
+        Ok(removed)
+    }
+
+    /// Deregister all registered handlers.
+    pub fn deregister_response_handling_all(&self) -> Result<()> {
+        self.response_handler.lock().unwrap().clear();
+        self.release_domain(Domain::Network, "register_response_handling")?;
+        Ok(())
+    }
+
+    /// Waits for the page to receive a response for which `predicate` returns true, and for its
+    /// body to finish loading. Only responses received after this is called are considered.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?;
+    /// let captured = tab.wait_for_response(|response| response.url.ends_with("/api/data"))?;
+    /// println!("{:?}: {}", captured.response.headers, captured.body_text()?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait_for_response<F>(&self, predicate: F) -> Result<CapturedResponse>
+    where
+        F: Fn(&Network::Response) -> bool + Send + Sync + 'static,
+    {
+        self.acquire_domain(Domain::Network, "wait_for_response")?;
+
+        let matched: Arc<Mutex<Option<MatchedResponse>>> = Arc::new(Mutex::new(None));
+        let matched_clone = Arc::clone(&matched);
+        let listener = self.add_event_listener(Arc::new(move |event: &Event| {
+            let mut matched = matched_clone.lock().unwrap();
+            match (event, matched.as_mut()) {
+                (Event::NetworkResponseReceived(ev), None) if predicate(&ev.params.response) => {
+                    *matched = Some((
+                        ev.params.request_id.clone(),
+                        ev.params.response.clone(),
+                        None,
+                    ));
+                }
+                (Event::NetworkLoadingFinished(ev), Some((request_id, _, outcome)))
+                    if ev.params.request_id == *request_id =>
+                {
+                    *outcome = Some(Ok(()));
+                }
+                (Event::NetworkLoadingFailed(ev), Some((request_id, _, outcome)))
+                    if ev.params.request_id == *request_id =>
+                {
+                    *outcome = Some(Err(ev.params.error_text.clone()));
+                }
+                _ => {}
+            }
+        }))?;
+
+        let result =
+            util::Wait::with_timeout(*self.default_timeout.read().unwrap()).until(|| match matched
+                .lock()
+                .unwrap()
+                .as_ref()
+            {
+                Some((request_id, response, Some(outcome))) => {
+                    Some((request_id.clone(), response.clone(), outcome.clone()))
+                }
+                _ => None,
+            });
+        self.remove_event_listener(&listener)?;
+
+        let body = result
+            .map_err(Error::from)
+            .and_then(|(request_id, response, outcome)| {
+                if let Err(error_text) = outcome {
+                    return Err(ResponseLoadingFailed { error_text }.into());
+                }
+                let body = self.call_method(GetResponseBody {
+                    request_id: request_id.clone(),
+                })?;
+                Ok((request_id, response, body))
+            });
+        self.release_domain(Domain::Network, "wait_for_response")?;
+        let (request_id, response, body) = body?;
+        let body = if body.base_64_encoded {
+            base64::decode(body.body)?
+        } else {
+            body.body.into_bytes()
+        };
+
+        Ok(CapturedResponse {
+            request_id,
+            response,
+            body,
+        })
+    }
+
+    /// Calls `callback` for every message the page receives over an `EventSource` (i.e. from a
+    /// server-sent events endpoint). Returns the underlying event listener, which can be passed
+    /// to `remove_event_listener` to stop.
+    pub fn on_event_source_message<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&Network::events::EventSourceMessageReceivedEventParams) + Send + Sync + 'static,
+    {
+        self.add_event_listener_holding(
+            &[Domain::Network],
+            "on_event_source_message",
+            Arc::new(move |event: &Event| {
+                if let Event::NetworkEventSourceMessageReceived(ev) = event {
+                    callback(&ev.params);
+                }
+            }),
+        )
+    }
+
+    /// Starts streaming the body of a response which is still loading (e.g. a streamed `fetch`),
+    /// returning the part of it which has already been received. The rest arrives in chunks
+    /// through `on_response_data`.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Network#method-streamResourceContent>
+    pub fn stream_response_content(&self, request_id: &str) -> Result<Vec<u8>> {
+        self.acquire_domain(Domain::Network, "stream_response_content")?;
+        let streamed = self.call_method(Network::StreamResourceContent {
+            request_id: request_id.to_string(),
+        });
+        self.release_domain(Domain::Network, "stream_response_content")?;
+        let buffered_data = streamed?.buffered_data;
+        base64::decode(buffered_data).map_err(Into::into)
+    }
+
+    /// Calls `callback` with every chunk of data received for a response being streamed with
+    /// `stream_response_content`. Returns the underlying event listener, which can be passed to
+    /// `remove_event_listener` to stop.
+    pub fn on_response_data<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&Network::RequestId, Vec<u8>) + Send + Sync + 'static,
+    {
+        self.add_event_listener_holding(
+            &[Domain::Network],
+            "on_response_data",
+            Arc::new(move |event: &Event| {
+                if let Event::NetworkDataReceived(ev) = event {
+                    let Some(data) = &ev.params.data else {
+                        return;
+                    };
+                    match base64::decode(data) {
+                        Ok(data) => callback(&ev.params.request_id, data),
+                        Err(err) => warn!("Received undecodable response data: {err:?}"),
+                    }
+                }
+            }),
+        )
+    }
+
+    /// Calls `callback` for every frame sent or received over the page's WebSockets, so that
+    /// the traffic of apps like chats or trading UIs can be asserted on. Returns the underlying
+    /// event listener, which can be passed to `remove_event_listener` to stop.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::sync::{Arc, Mutex};
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::WebSocketFrameDirection;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let received = Arc::new(Mutex::new(Vec::new()));
+    /// let received_clone = Arc::clone(&received);
+    /// tab.on_websocket_frame(move |frame| {
+    ///     if frame.direction == WebSocketFrameDirection::Received {
+    ///         received_clone.lock().unwrap().push(frame.payload_data.clone());
+    ///     }
+    /// })?;
+    /// tab.navigate_to("https://example.com/chat")?.wait_until_navigated()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_websocket_frame<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&WebSocketFrameEvent) + Send + Sync + 'static,
+    {
+        let urls: Mutex<HashMap<Network::RequestId, String>> = Mutex::new(HashMap::new());
+        let listener = Arc::new(move |event: &Event| {
+            let (params, direction) = match event {
+                Event::NetworkWebSocketCreated(ev) => {
+                    let params = &ev.params;
+                    urls.lock()
+                        .unwrap()
+                        .insert(params.request_id.clone(), params.url.clone());
+                    return;
+                }
+                Event::NetworkWebSocketClosed(ev) => {
+                    urls.lock().unwrap().remove(&ev.params.request_id);
+                    return;
+                }
+                Event::NetworkWebSocketFrameSent(ev) => {
+                    let params = &ev.params;
+                    (
+                        (&params.request_id, &params.response),
+                        WebSocketFrameDirection::Sent,
+                    )
+                }
+                Event::NetworkWebSocketFrameReceived(ev) => {
+                    let params = &ev.params;
+                    (
+                        (&params.request_id, &params.response),
+                        WebSocketFrameDirection::Received,
+                    )
+                }
+                _ => return,
+            };
+            let (request_id, frame) = params;
+            callback(&WebSocketFrameEvent {
+                request_id: request_id.clone(),
+                url: urls.lock().unwrap().get(request_id).cloned(),
+                direction,
+                opcode: frame.opcode as u8,
+                payload_data: frame.payload_data.clone(),
+            });
+        });
+        self.add_event_listener_holding(&[Domain::Network], "on_websocket_frame", listener)
+    }
+
+    /// Enables `domain` on behalf of `holder` (any name for the feature needing it), unless it's
+    /// already enabled. The tab's own features acquire the domains they need like this too, so
+    /// that releasing one with `release_domain` only disables it once nothing else holds it.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// use headless_chrome::browser::tab::Domain;
+    /// use headless_chrome::Browser;
+    ///
+    /// let browser = Browser::default()?;
+    /// let tab = browser.new_tab()?;
+    /// tab.register_response_handling("log", Box::new(|_, _| {}))?;
+    /// tab.acquire_domain(Domain::Network, "my_crawler")?;
+    /// // Network events keep coming, as the response handler still needs them
+    /// tab.release_domain(Domain::Network, "my_crawler")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn acquire_domain(&self, domain: Domain, holder: &str) -> Result<&Self> {
+        self.domains
+            .acquire(domain, holder, || self.set_domain_enabled(domain, true))?;
+        Ok(self)
+    }
+
+    /// Gives up `holder`'s claim on `domain`, disabling it if nothing else holds it.
+    pub fn release_domain(&self, domain: Domain, holder: &str) -> Result<&Self> {
+        self.domains
+            .release(domain, holder, || self.set_domain_enabled(domain, false))?;
+        Ok(self)
+    }
+
+    /// The names of whatever currently holds `domain` enabled, see `acquire_domain`.
+    pub fn domain_holders(&self, domain: Domain) -> Vec<String> {
+        self.domains.holders(domain)
+    }
+
+    fn set_domain_enabled(&self, domain: Domain, enabled: bool) -> Result<()> {
+        match (domain, enabled) {
+            (Domain::Animation, true) => self.call_method(Animation::Enable(None)).map(|_| ()),
+            (Domain::Animation, false) => self.call_method(Animation::Disable(None)).map(|_| ()),
+            (Domain::Audits, true) => self.call_method(Audits::Enable(None)).map(|_| ()),
+            (Domain::Audits, false) => self.call_method(Audits::Disable(None)).map(|_| ()),
+            (Domain::Css, true) => self.call_method(CSS::Enable(None)).map(|_| ()),
+            (Domain::Css, false) => self.call_method(CSS::Disable(None)).map(|_| ()),
+            (Domain::Debugger, true) => self
+                .call_method(Debugger::Enable {
+                    max_scripts_cache_size: None,
+                })
+                .map(|_| ()),
+            (Domain::Debugger, false) => self.call_method(Debugger::Disable(None)).map(|_| ()),
+            (Domain::Dom, true) => self.call_method(DOM::Enable(None)).map(|_| ()),
+            (Domain::Dom, false) => self.call_method(DOM::Disable(None)).map(|_| ()),
+            (Domain::Fetch, true) => self.call_method(self.domains.merged_fetch()).map(|_| ()),
+            (Domain::Fetch, false) => self.call_method(Fetch::Disable(None)).map(|_| ()),
+            (Domain::Inspector, true) => self.call_method(Inspector::Enable(None)).map(|_| ()),
+            (Domain::Inspector, false) => self.call_method(Inspector::Disable(None)).map(|_| ()),
+            (Domain::Log, true) => self.call_method(Log::Enable(None)).map(|_| ()),
+            (Domain::Log, false) => self.call_method(Log::Disable(None)).map(|_| ()),
+            (Domain::Media, true) => self.call_method(Media::Enable(None)).map(|_| ()),
+            (Domain::Media, false) => self.call_method(Media::Disable(None)).map(|_| ()),
+            (Domain::Network, true) => self
+                .call_method(Network::Enable {
+                    max_total_buffer_size: None,
+                    max_resource_buffer_size: None,
+                    max_post_data_size: None,
+                })
+                .map(|_| ()),
+            (Domain::Network, false) => {
+                self.call_method(Network::Disable(None))?;
+                // Requests in flight now never get reported finished
+                *self.network_activity.lock().unwrap() = NetworkActivity::default();
+                Ok(())
+            }
+            (Domain::Page, true) => self.call_method(Page::Enable(None)).map(|_| ()),
+            (Domain::Page, false) => self.call_method(Page::Disable(None)).map(|_| ()),
+            (Domain::Performance, true) => self
+                .call_method(Performance::Enable { time_domain: None })
+                .map(|_| ()),
+            (Domain::Performance, false) => {
+                self.call_method(Performance::Disable(None)).map(|_| ())
+            }
+            (Domain::Profiler, true) => self.call_method(Profiler::Enable(None)).map(|_| ()),
+            (Domain::Profiler, false) => self.call_method(Profiler::Disable(None)).map(|_| ()),
+            (Domain::Runtime, true) => self.call_method(Runtime::Enable(None)).map(|_| ()),
+            (Domain::Runtime, false) => self.call_method(Runtime::Disable(None)).map(|_| ()),
+            (Domain::Security, true) => self.call_method(Security::Enable(None)).map(|_| ()),
+            (Domain::Security, false) => self.call_method(Security::Disable(None)).map(|_| ()),
+        }
+    }
+
+    /// Enables runtime domain.
+    pub fn enable_runtime(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Runtime, "user")
+    }
+
+    /// Disables runtime domain, unless another of the tab's features still needs it
+    pub fn disable_runtime(&self) -> Result<&Self> {
+        self.release_domain(Domain::Runtime, "user")
+    }
+
+    /// Enables Debugger
+    pub fn enable_debugger(&self) -> Result<()> {
+        self.acquire_domain(Domain::Debugger, "user")?;
+        Ok(())
+    }
+
+    /// Disables Debugger
+    pub fn disable_debugger(&self) -> Result<()> {
+        self.release_domain(Domain::Debugger, "user")?;
+        Ok(())
+    }
+
+    /// Returns source for the script with given id, enabling the debugger if need be.
+    pub fn get_script_source(&self, script_id: &str) -> Result<String> {
+        self.acquire_domain(Domain::Debugger, "debugger")?;
+        Ok(self
+            .call_method(Debugger::GetScriptSource {
+                script_id: script_id.to_string(),
+            })?
+            .script_source)
+    }
+
+    /// Calls `callback` with the details of every uncaught exception the page throws from now
+    /// on. Returns the underlying event listener, which can be passed to
+    /// `remove_event_listener` to stop.
+    ///
+    /// With `apply_source_maps`, the positions in the exception and its stack trace are mapped
+    /// back to the original sources (e.g. TypeScript or JSX files) of scripts which have source
+    /// maps, so that errors point at code you can act on. This enables the Debugger, which
+    /// reports the scripts' source maps, and fetches each map from the page the first time it's
+    /// needed. `callback` is then called on a thread of its own rather than the tab's event
+    /// thread, so that fetching maps doesn't hold up other events.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.on_exception_thrown(true, |details| {
+    ///     for frame in details.stack_trace.iter().flat_map(|trace| &trace.call_frames) {
+    ///         eprintln!("  at {} ({}:{})", frame.function_name, frame.url, frame.line_number + 1);
+    ///     }
+    /// })?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_exception_thrown<F>(
         &self,
-        patterns: Option<&[Fetch::RequestPattern]>,
-        handle_auth_requests: Option<bool>,
+        apply_source_maps: bool,
+        callback: F,
+    ) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&Runtime::ExceptionDetails) + Send + Sync + 'static,
+    {
+        let transport = Arc::clone(&self.transport);
+        let session_id = self.session_id.clone();
+        let fetch = move |url: &str| -> Result<String> {
+            let result = transport.call_method_on_target(
+                session_id.clone(),
+                Runtime::Evaluate {
+                    expression: format!("fetch({}).then(response => response.text())", json!(url)),
+                    object_group: None,
+                    include_command_line_api: None,
+                    silent: Some(true),
+                    context_id: None,
+                    return_by_value: Some(true),
+                    generate_preview: None,
+                    user_gesture: None,
+                    await_promise: Some(true),
+                    throw_on_side_effect: None,
+                    timeout: None,
+                    disable_breaks: None,
+                    repl_mode: None,
+                    allow_unsafe_eval_blocked_by_csp: None,
+                    unique_context_id: None,
+                },
+            )?;
+            if let Some(exception_details) = result.exception_details {
+                return Err(anyhow::anyhow!(
+                    "Fetching {url} failed: {}",
+                    exception_details.text
+                ));
+            }
+            Ok(result
+                .result
+                .value
+                .as_ref()
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string())
+        };
+
+        if !apply_source_maps {
+            let listener: Arc<SyncSendEvent> = Arc::new(move |event: &Event| {
+                if let Event::RuntimeExceptionThrown(ev) = event {
+                    callback(&ev.params.exception_details);
+                }
+            });
+            return self.add_event_listener_holding(
+                &[Domain::Runtime],
+                "on_exception_thrown",
+                listener,
+            );
+        }
+
+        // Fetching a map means a round trip to the page, which mustn't hold up the event thread,
+        // so the scripts and exceptions are handed, in order, to a thread of their own. It stops
+        // once the listener (and so the sender) is dropped.
+        let (events_tx, events_rx) = mpsc::channel::<Event>();
+        thread::spawn(move || {
+            let mut source_maps = ScriptSourceMaps::default();
+            for event in events_rx {
+                match event {
+                    Event::DebuggerScriptParsed(ev) => source_maps.script_parsed(&ev.params),
+                    Event::RuntimeExceptionThrown(ev) => {
+                        let mut details = ev.params.exception_details;
+                        source_maps.apply(&mut details, &fetch);
+                        callback(&details);
+                    }
+                    _ => {}
+                }
+            }
+        });
+        let listener: Arc<SyncSendEvent> = Arc::new(move |event: &Event| {
+            if matches!(
+                event,
+                Event::DebuggerScriptParsed(_) | Event::RuntimeExceptionThrown(_)
+            ) {
+                let _ = events_tx.send(event.clone());
+            }
+        });
+        self.add_event_listener_holding(
+            &[Domain::Runtime, Domain::Debugger],
+            "on_exception_thrown",
+            listener,
+        )
+    }
+
+    /// Sets a breakpoint on `line_number` (0-based) of every script whose URL (or
+    /// `//# sourceURL`) is `url`, including scripts which haven't loaded yet. If `condition` is
+    /// given, execution only pauses when that expression is true.
+    ///
+    /// Returns the breakpoint's id, for `remove_breakpoint`, along with where it has resolved to
+    /// in the scripts loaded so far. Enables the debugger if need be.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Debugger#method-setBreakpointByUrl>
+    pub fn set_breakpoint_by_url(
+        &self,
+        url: &str,
+        line_number: u32,
+        condition: Option<&str>,
+    ) -> Result<Debugger::SetBreakpointByUrlReturnObject> {
+        self.acquire_domain(Domain::Debugger, "debugger")?;
+        self.call_method(Debugger::SetBreakpointByUrl {
+            line_number,
+            url: Some(url.to_string()),
+            url_regex: None,
+            script_hash: None,
+            column_number: None,
+            condition: condition.map(ToString::to_string),
+        })
+    }
+
+    /// Removes a breakpoint set with `set_breakpoint_by_url`.
+    pub fn remove_breakpoint(&self, breakpoint_id: &str) -> Result<&Self> {
+        self.call_method(Debugger::RemoveBreakpoint {
+            breakpoint_id: breakpoint_id.to_string(),
+        })?;
+        Ok(self)
+    }
+
+    /// Pauses the page's JavaScript on its next statement, enabling the debugger if need be.
+    pub fn pause_debugger(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Debugger, "debugger")?;
+        self.call_method(Debugger::Pause(None))?;
+        Ok(self)
+    }
+
+    /// Resumes the page's JavaScript after it paused.
+    pub fn resume_debugger(&self) -> Result<&Self> {
+        self.call_method(Debugger::Resume {
+            terminate_on_resume: None,
+        })?;
+        Ok(self)
+    }
+
+    /// Runs the paused JavaScript up to its next statement, stepping over function calls.
+    pub fn step_over(&self) -> Result<&Self> {
+        self.call_method(Debugger::StepOver { skip_list: None })?;
+        Ok(self)
+    }
+
+    /// Runs the paused JavaScript up to its next statement, stepping into function calls.
+    pub fn step_into(&self) -> Result<&Self> {
+        self.call_method(Debugger::StepInto {
+            break_on_async_call: None,
+            skip_list: None,
+        })?;
+        Ok(self)
+    }
+
+    /// Runs the paused JavaScript until the current function returns.
+    pub fn step_out(&self) -> Result<&Self> {
+        self.call_method(Debugger::StepOut(None))?;
+        Ok(self)
+    }
+
+    /// Calls `callback` every time the page's JavaScript pauses (on a breakpoint, a `debugger`
+    /// statement or after stepping), with the reason and the call stack. The JavaScript stays
+    /// paused until `resume_debugger` or one of the step methods is called, which can be done
+    /// from the callback. Returns the underlying event listener, which can be passed to
+    /// `remove_event_listener` to stop.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// // A weak reference, as the tab holds on to the callback
+    /// let paused_tab = std::sync::Arc::downgrade(&tab);
+    /// tab.on_debugger_paused(move |paused| {
+    ///     for frame in &paused.call_frames {
+    ///         println!("at {} ({}:{})", frame.function_name, frame.url, frame.location.line_number);
+    ///     }
+    ///     if let Some(tab) = paused_tab.upgrade() {
+    ///         tab.resume_debugger().unwrap();
+    ///     }
+    /// })?;
+    /// tab.set_breakpoint_by_url("https://example.com/app.js", 41, None)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_debugger_paused<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&Debugger::events::PausedEventParams) + Send + Sync + 'static,
+    {
+        self.add_event_listener_holding(
+            &[Domain::Debugger],
+            "on_debugger_paused",
+            Arc::new(move |event: &Event| {
+                if let Event::DebuggerPaused(ev) = event {
+                    callback(&ev.params);
+                }
+            }),
+        )
+    }
+
+    /// Starts recording the events of a background service (push messages, notifications,
+    /// background sync...) and calls `callback` with each of them: first the ones recorded
+    /// earlier, as the Application panel of DevTools lists them, then new ones as they happen.
+    /// Returns the underlying event listener, which can be passed to `remove_event_listener`
+    /// to stop getting called; `stop_observing_background_service` stops the recording.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::BackgroundService::ServiceName;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.observe_background_service(ServiceName::PushMessaging, |event| {
+    ///     println!("{}: {} ({})", event.origin, event.event_name, event.instance_id);
+    /// })?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn observe_background_service<F>(
+        &self,
+        service: BackgroundService::ServiceName,
+        callback: F,
+    ) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&BackgroundService::BackgroundServiceEvent) + Send + Sync + 'static,
+    {
+        let observed_service = service.clone();
+        let listener = self.add_event_listener(Arc::new(move |event: &Event| {
+            if let Event::BackgroundServiceEventReceived(ev) = event {
+                let background_service_event = &ev.params.background_service_event;
+                if background_service_event.service == observed_service {
+                    callback(background_service_event);
+                }
+            }
+        }))?;
+        self.call_method(BackgroundService::StartObserving {
+            service: service.clone(),
+        })?;
+        self.call_method(BackgroundService::SetRecording {
+            should_record: true,
+            service,
+        })?;
+        Ok(listener)
+    }
+
+    /// Stops recording and observing the events of a background service, see
+    /// `observe_background_service`.
+    pub fn stop_observing_background_service(
+        &self,
+        service: BackgroundService::ServiceName,
     ) -> Result<&Self> {
-        self.call_method(Fetch::Enable {
-            patterns: patterns.map(Vec::from),
-            handle_auth_requests,
+        self.call_method(BackgroundService::SetRecording {
+            should_record: false,
+            service: service.clone(),
         })?;
+        self.call_method(BackgroundService::StopObserving { service })?;
         Ok(self)
     }
 
-    /// Disables fetch domain
-    pub fn disable_fetch(&self) -> Result<&Self> {
-        self.call_method(Fetch::Disable(None))?;
+    /// Enables log domain.
+    ///
+    /// Sends the entries collected so far to the client by means of the entryAdded notification.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-enable>
+    pub fn enable_log(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Log, "user")
+    }
+
+    /// Disables log domain
+    ///
+    /// Prevents further log entries from being reported to the client
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-disable>
+    pub fn disable_log(&self) -> Result<&Self> {
+        self.release_domain(Domain::Log, "user")
+    }
+
+    /// Starts violation reporting
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-startViolationsReport>
+    pub fn start_violations_report(&self, config: Vec<ViolationSetting>) -> Result<&Self> {
+        self.acquire_domain(Domain::Log, "violations_report")?;
+        self.call_method(Log::StartViolationsReport { config })?;
         Ok(self)
     }
 
-    /// Allows you to inspect outgoing network requests from the tab, and optionally return
-    /// your own responses to them
-    ///
-    /// The `interceptor` argument is a closure which takes this tab's `Transport` and its SessionID
-    /// so that you can call methods from within the closure using `transport.call_method_on_target`.
+    /// Stop violation reporting
     ///
-    /// The closure needs to return a variant of `RequestPausedDecision`.
-    pub fn enable_request_interception(&self, interceptor: Arc<RequestIntercept>) -> Result<()> {
-        let mut current_interceptor = self.request_interceptor.lock().unwrap();
-        *current_interceptor = interceptor;
-        Ok(())
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-stopViolationsReport>
+    pub fn stop_violations_report(&self) -> Result<&Self> {
+        self.call_method(Log::StopViolationsReport(None))?;
+        self.release_domain(Domain::Log, "violations_report")
     }
 
-    pub fn authenticate(
-        &self,
-        username: Option<String>,
-        password: Option<String>,
-    ) -> Result<&Self> {
-        let mut current_auth_handler = self.auth_handler.lock().unwrap();
-        *current_auth_handler = AuthChallengeResponse {
-            response: Fetch::AuthChallengeResponseResponse::ProvideCredentials,
-            username,
-            password,
-        };
-        Ok(self)
+    /// Enables the `Audits` domain, after which the browser reports the issues it has found
+    /// with the page (including the ones found so far) for `take_issues` to return. Does
+    /// nothing if it's already enabled.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Audits#method-enable>
+    pub fn enable_audits(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Audits, "issues")
     }
 
-    /// Lets you register a listener for responses, and gives you a way to get the response body too.
+    /// Returns the issues (cookie problems, mixed content, CORS failures...) the browser has
+    /// reported since the last call, like the DevTools Issues panel lists them.
     ///
-    /// Please note that the 'response' does not include the *body* of the response -- Chrome tells
-    /// us about them seperately (because you might quickly get the status code and headers from a
-    /// server well before you receive the entire response body which could, after all, be gigabytes
-    /// long).
+    /// Issues are only collected once `enable_audits` has been called, which this does if
+    /// needed; as the browser reports them asynchronously, enable auditing before loading the
+    /// page to be sure to get them all.
     ///
-    /// Currently we leave it up to the caller to decide when to call `fetch_body` (the second
-    /// argument to the response handler), although ideally it wouldn't be possible until Chrome has
-    /// sent the `Network.loadingFinished` event.
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::Issue;
     ///
-    /// Return a option for ResponseHander for existing handler with same name if existed.
-    pub fn register_response_handling<S: ToString>(
-        &self,
-        handler_name: S,
-        handler: ResponseHandler,
-    ) -> Result<Option<ResponseHandler>> {
-        self.call_method(Network::Enable {
-            max_total_buffer_size: None,
-            max_resource_buffer_size: None,
-            max_post_data_size: None,
-        })?;
-        Ok(self
-            .response_handler
-            .lock()
-            .unwrap()
-            .insert(handler_name.to_string(), handler))
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.enable_audits()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// for issue in tab.take_issues()? {
+    ///     if let Issue::MixedContent(details) = issue {
+    ///         println!("Insecure content: {}", details.insecure_url);
+    ///     }
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn take_issues(&self) -> Result<Vec<Issue>> {
+        self.enable_audits()?;
+        Ok(std::mem::take(&mut *self.issues.lock().unwrap()))
     }
 
-    pub fn register_loading_failed_handling<S: ToString>(
-        &self,
-        handler_name: S,
-        handler: LoadingFailedHandler,
-    ) -> Result<Option<LoadingFailedHandler>> {
-        self.call_method(Network::Enable {
-            max_total_buffer_size: None,
-            max_resource_buffer_size: None,
-            max_post_data_size: None,
-        })?;
-        Ok(self
-            .loading_failed_handler
-            .lock()
-            .unwrap()
-            .insert(handler_name.to_string(), handler))
+    /// Calls `callback` for every message logged to the page's console: both the page's own
+    /// `console` calls and the messages the browser logs itself (blocked mixed content, CORS
+    /// failures, interventions, deprecations and so on), which `Runtime.consoleAPICalled`
+    /// doesn't see. Messages logged before this was called are reported too. Returns the
+    /// underlying event listener, which can be passed to `remove_event_listener` to stop.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Log::LogEntryLevel;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.on_console_message(|message| {
+    ///     if message.level == LogEntryLevel::Error {
+    ///         eprintln!("{:?}: {}", message.source, message.text);
+    ///     }
+    /// })?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_console_message<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&ConsoleMessage) + Send + Sync + 'static,
+    {
+        self.add_event_listener_holding(
+            &[Domain::Runtime, Domain::Log],
+            "on_console_message",
+            Arc::new(move |event: &Event| match event {
+                Event::RuntimeConsoleAPICalled(ev) => {
+                    callback(&ConsoleMessage::from_console_api(&ev.params));
+                }
+                Event::LogEntryAdded(ev) => {
+                    callback(&ConsoleMessage::from_log_entry(&ev.params.entry));
+                }
+                _ => {}
+            }),
+        )
     }
 
-    /// Deregister a reponse handler based on its name.
+    /// Enables the `Media` domain, which reports the events of the page's media players.
     ///
-    /// Return a option for ResponseHandler for removed handler if existed.
-    pub fn deregister_response_handling(
-        &self,
-        handler_name: &str,
-    ) -> Result<Option<ResponseHandler>> {
-        Ok(self.response_handler.lock().unwrap().remove(handler_name))
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Media#method-enable>
+    pub fn enable_media(&self) -> Result<&Self> {
+        self.acquire_domain(Domain::Media, "user")
     }
 
-    /// Deregister all registered handlers.
-    pub fn deregister_response_handling_all(&self) -> Result<()> {
-        self.response_handler.lock().unwrap().clear();
-        Ok(())
+    /// Calls `callback` for everything the page's media players (`<video>` and `<audio>`
+    /// elements) report: their creation, properties like the resolution, state changes and
+    /// errors. Returns the underlying event listener, which can be passed to
+    /// `remove_event_listener` to stop.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::media::MediaPlayerEvent;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.on_media_player_event(|event| {
+    ///     if let MediaPlayerEvent::ErrorsRaised { player_id, errors } = event {
+    ///         eprintln!("Player {player_id} failed: {errors:?}");
+    ///     }
+    /// })?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_media_player_event<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&MediaPlayerEvent) + Send + Sync + 'static,
+    {
+        self.add_event_listener_holding(
+            &[Domain::Media],
+            "on_media_player_event",
+            Arc::new(move |event: &Event| {
+                if let Some(media_event) = MediaPlayerEvent::from_event(event) {
+                    callback(&media_event);
+                }
+            }),
+        )
     }
 
-    /// Enables runtime domain.
-    pub fn enable_runtime(&self) -> Result<&Self> {
-        self.call_method(Runtime::Enable(None))?;
-        Ok(self)
+    /// Returns the statistics of a WebRTC connection, as its `getStats()` reports them.
+    /// `peer_connection` is a JavaScript expression evaluating to the page's
+    /// `RTCPeerConnection`, e.g. a global variable it's stored in.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// for stats in tab.get_rtc_stats("window.peerConnection")? {
+    ///     if stats.stats_type == "inbound-rtp" && stats.kind.as_deref() == Some("video") {
+    ///         println!("{:?}x{:?}", stats.frame_width, stats.frame_height);
+    ///     }
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_rtc_stats(&self, peer_connection: &str) -> Result<Vec<RtcStats>> {
+        let result = self.evaluate(
+            &format!(
+                "(async () => JSON.stringify([...(await ({peer_connection}).getStats()).values()]))()"
+            ),
+            true,
+        )?;
+        match result.value {
+            Some(Json::String(stats)) => Ok(serde_json::from_str(&stats)?),
+            _ => Err(RtcStatsUnavailable {
+                error_text: result.description.unwrap_or_default(),
+            }
+            .into()),
+        }
     }
 
-    /// Disables runtime domain
-    pub fn disable_runtime(&self) -> Result<&Self> {
-        self.call_method(Runtime::Disable(None))?;
-        Ok(self)
+    /// Extracts data from the page into `T`, from `fields` given as `(name, field)` pairs. Each
+    /// field is a CSS selector for the trimmed text content of the first element matching it,
+    /// or `null` if none does. Append `@attribute` for the value of an attribute instead (or of
+    /// the property with that name, so e.g. `a@href` gives absolute URLs), and `[]` for an
+    /// array of every match's.
+    ///
+    /// Text content and attributes are strings, but properties keep their JavaScript type, so
+    /// e.g. `input@checked` is a boolean and `progress@value` a number. `T`'s fields should
+    /// match (or be arrays of them for `[]`), and be `Option`s for fields which may not match.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// #[derive(serde::Deserialize)]
+    /// struct Article {
+    ///     title: String,
+    ///     author: Option<String>,
+    ///     tags: Vec<String>,
+    ///     next_page: Option<String>,
+    /// }
+    ///
+    /// let article: Article = tab.extract(&[
+    ///     ("title", "h1"),
+    ///     ("author", ".byline a"),
+    ///     ("tags", "ul.tags li[]"),
+    ///     ("next_page", "a[rel=next]@href"),
+    /// ])?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract<T>(&self, fields: &[(&str, &str)]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.evaluate_extractor(&extract::extractor_expression(None, fields))
     }
 
-    /// Enables Debugger
-    pub fn enable_debugger(&self) -> Result<()> {
-        self.call_method(Debugger::Enable {
-            max_scripts_cache_size: None,
-        })?;
-        Ok(())
+    /// Like `extract`, but extracts a `T` from each element matching `item_selector`, such as
+    /// the rows of a table, with the selectors of `fields` applied within it. An empty selector
+    /// is the element itself, e.g. `@data-id` for its `data-id` attribute.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// #[derive(serde::Deserialize)]
+    /// struct Product {
+    ///     id: String,
+    ///     name: String,
+    ///     price: String,
+    /// }
+    ///
+    /// let products: Vec<Product> = tab.extract_all(
+    ///     "table#products tbody tr",
+    ///     &[("id", "@data-id"), ("name", "td.name"), ("price", "td.price")],
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_all<T>(&self, item_selector: &str, fields: &[(&str, &str)]) -> Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.evaluate_extractor(&extract::extractor_expression(Some(item_selector), fields))
     }
 
-    /// Disables Debugger
-    pub fn disable_debugger(&self) -> Result<()> {
-        self.call_method(Debugger::Disable(None))?;
-        Ok(())
+    fn evaluate_extractor<T>(&self, expression: &str) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let result = self.evaluate(expression, false)?;
+        match result.value {
+            Some(Json::String(extracted)) => Ok(serde_json::from_str(&extracted)?),
+            _ => Err(ExtractionFailed {
+                error_text: result.description.unwrap_or_default(),
+            }
+            .into()),
+        }
     }
 
-    /// Returns source for the script with given id.
-    ///
-    /// Debugger must be enabled.
-    pub fn get_script_source(&self, script_id: &str) -> Result<String> {
-        Ok(self
-            .call_method(Debugger::GetScriptSource {
-                script_id: script_id.to_string(),
+    /// Evaluates expression on global object.
+    pub fn evaluate(&self, expression: &str, await_promise: bool) -> Result<Runtime::RemoteObject> {
+        let result = self
+            .call_method(Runtime::Evaluate {
+                expression: expression.to_string(),
+                return_by_value: Some(false),
+                generate_preview: Some(true),
+                silent: Some(false),
+                await_promise: Some(await_promise),
+                include_command_line_api: Some(false),
+                user_gesture: Some(false),
+                object_group: None,
+                context_id: None,
+                throw_on_side_effect: None,
+                timeout: None,
+                disable_breaks: None,
+                repl_mode: None,
+                allow_unsafe_eval_blocked_by_csp: None,
+                unique_context_id: None,
             })?
-            .script_source)
+            .result;
+        Ok(result)
     }
 
-    /// Enables log domain.
+    /// Repeatedly calls the JavaScript `function` in the page, every `polling_interval`, until it
+    /// returns (or resolves to) a truthy value, which is then returned.
     ///
-    /// Sends the entries collected so far to the client by means of the entryAdded notification.
+    /// Fails with `FunctionFailed` if the function throws, or `Timeout` if it doesn't return a
+    /// truthy value within `timeout`.
     ///
-    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-enable>
-    pub fn enable_log(&self) -> Result<&Self> {
-        self.call_method(Log::Enable(None))?;
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::time::Duration;
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com/app")?;
+    /// tab.wait_for_function(
+    ///     "() => window.appReady === true",
+    ///     Duration::from_secs(10),
+    ///     Duration::from_millis(100),
+    /// )?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait_for_function(
+        &self,
+        function: &str,
+        timeout: Duration,
+        polling_interval: Duration,
+    ) -> Result<Runtime::RemoteObject> {
+        let expression = format!("({function})()");
+        util::Wait::new(timeout, polling_interval).strict_until(
+            || {
+                let result = self.evaluate(&expression, true)?;
+                if result.subtype == Some(Runtime::RemoteObjectSubtype::Error) {
+                    return Err(FunctionFailed {
+                        error_text: result.description.unwrap_or_default(),
+                    }
+                    .into());
+                }
+                if util::is_truthy(&result) {
+                    Ok(result)
+                } else {
+                    Err(FunctionNotSatisfied {}.into())
+                }
+            },
+            Error::downcast::<FunctionNotSatisfied>,
+        )
+    }
 
-        Ok(self)
+    /// Enables the ServiceWorker domain and returns a handle for inspecting and controlling the
+    /// service workers of this tab's pages.
+    ///
+    /// Service workers often serve cached responses or intercept requests, which can get in the
+    /// way of scraping or make tests nondeterministic:
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// let service_workers = tab.service_workers()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// for registration in service_workers.registrations() {
+    ///     service_workers.unregister(&registration.scope_url)?;
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn service_workers(&self) -> Result<ServiceWorkers<'_>> {
+        ServiceWorkers::new(self)
     }
 
-    /// Disables log domain
+    /// Returns the dedicated workers currently running in this tab's pages.
     ///
-    /// Prevents further log entries from being reported to the client
+    /// The first call makes Chrome auto-attach to this tab's workers (including ones which are
+    /// already running) via
+    /// [`Target.setAutoAttach`](https://chromedevtools.github.io/devtools-protocol/tot/Target#method-setAutoAttach).
+    /// Workers are reported asynchronously, so call this before navigating and poll it
+    /// afterwards:
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::time::Duration;
+    /// # use headless_chrome::Browser;
+    /// # use headless_chrome::util::Wait;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.workers()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
     ///
-    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-disable>
-    pub fn disable_log(&self) -> Result<&Self> {
-        self.call_method(Log::Disable(None))?;
-
-        Ok(self)
+    /// let worker = Wait::with_timeout(Duration::from_secs(5))
+    ///     .until(|| tab.workers().ok()?.into_iter().next())?;
+    /// let href = worker.evaluate("self.location.href", false)?.value;
+    /// println!("worker running at {href:?}");
+    /// for exception in worker.get_exceptions() {
+    ///     println!("{} threw {}", worker.get_url(), exception.text);
+    /// }
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn workers(&self) -> Result<Vec<Arc<Worker>>> {
+        if !self.attaching_to_workers.swap(true, Ordering::SeqCst) {
+            if let Err(err) = self.call_method(Target::SetAutoAttach {
+                auto_attach: true,
+                wait_for_debugger_on_start: false,
+                flatten: Some(false),
+                filter: None,
+            }) {
+                self.attaching_to_workers.store(false, Ordering::SeqCst);
+                return Err(err);
+            }
+        }
+        Ok(self.workers.lock().unwrap().clone())
     }
 
-    /// Starts violation reporting
-    ///
-    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-startViolationsReport>
-    pub fn start_violations_report(&self, config: Vec<ViolationSetting>) -> Result<&Self> {
-        self.call_method(Log::StartViolationsReport { config })?;
-        Ok(self)
+    fn enable_security(&self) -> Result<()> {
+        self.acquire_domain(Domain::Security, "security_state")?;
+        Ok(())
     }
 
-    /// Stop violation reporting
+    /// Returns the security state Chrome would show in the address bar for the current page,
+    /// including certificate details and the issues (e.g. mixed content) which downgraded it.
     ///
-    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Log#method-stopViolationsReport>
-    pub fn stop_violations_report(&self) -> Result<&Self> {
-        self.call_method(Log::StopViolationsReport(None))?;
-        Ok(self)
+    /// The first call enables the
+    /// [`Security`](https://chromedevtools.github.io/devtools-protocol/tot/Security) domain and
+    /// waits (up to the default timeout) for Chrome to report the state.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::protocol::cdp::Security::SecurityState;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// let state = tab.get_security_state()?;
+    /// assert_eq!(SecurityState::Secure, state.security_state);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_security_state(&self) -> Result<Security::VisibleSecurityState> {
+        self.enable_security()?;
+        Ok(
+            util::Wait::with_timeout(*self.default_timeout.read().unwrap())
+                .until(|| self.security_state.lock().unwrap().clone())?,
+        )
     }
 
-    /// Evaluates expression on global object.
-    pub fn evaluate(&self, expression: &str, await_promise: bool) -> Result<Runtime::RemoteObject> {
-        let result = self
-            .call_method(Runtime::Evaluate {
-                expression: expression.to_string(),
-                return_by_value: Some(false),
-                generate_preview: Some(true),
-                silent: Some(false),
-                await_promise: Some(await_promise),
-                include_command_line_api: Some(false),
-                user_gesture: Some(false),
-                object_group: None,
-                context_id: None,
-                throw_on_side_effect: None,
-                timeout: None,
-                disable_breaks: None,
-                repl_mode: None,
-                allow_unsafe_eval_blocked_by_csp: None,
-                unique_context_id: None,
-            })?
-            .result;
-        Ok(result)
+    /// Calls `callback` every time the page's security state changes, e.g. when a secure page
+    /// loads an insecure image. Returns the underlying event listener, which can be passed to
+    /// `remove_event_listener` to stop.
+    pub fn on_security_state_changed<F>(&self, callback: F) -> Result<Weak<SyncSendEvent>>
+    where
+        F: Fn(&Security::VisibleSecurityState) + Send + Sync + 'static,
+    {
+        self.enable_security()?;
+        self.add_event_listener(Arc::new(move |event: &Event| {
+            if let Event::VisibleSecurityStateChanged(ev) = event {
+                callback(&ev.params.visible_security_state);
+            }
+        }))
     }
 
     /// Adds event listener to Event
@@ -1457,6 +5472,18 @@ impl Tab {
     }
 
     pub fn remove_event_listener(&self, listener: &Weak<SyncSendEvent>) -> Result<()> {
+        let held: Vec<_> = {
+            let mut listener_domains = self.listener_domains.lock().unwrap();
+            let (held, kept) = listener_domains
+                .drain(..)
+                .partition(|(holding, _, _)| Weak::ptr_eq(holding, listener));
+            *listener_domains = kept;
+            held
+        };
+        for (_, domain, holder) in held {
+            self.release_domain(domain, &holder)?;
+        }
+
         let listener = listener.upgrade();
         if listener.is_none() {
             return Ok(());
@@ -1471,6 +5498,34 @@ impl Tab {
         Ok(())
     }
 
+    /// Adds `listener`, then holds `domains` enabled for it until it's removed with
+    /// `remove_event_listener`. Adding it first means it also sees the events the domains
+    /// report as soon as they're enabled, such as the console messages logged so far.
+    fn add_event_listener_holding(
+        &self,
+        domains: &[Domain],
+        holder: &str,
+        listener: Arc<SyncSendEvent>,
+    ) -> Result<Weak<SyncSendEvent>> {
+        let holder = format!(
+            "{holder}#{}",
+            self.next_listener_holder_id.fetch_add(1, Ordering::SeqCst)
+        );
+        let listener = self.add_event_listener(listener)?;
+        for domain in domains {
+            if let Err(err) = self.acquire_domain(*domain, &holder) {
+                self.remove_event_listener(&listener)?;
+                return Err(err);
+            }
+            self.listener_domains.lock().unwrap().push((
+                Weak::clone(&listener),
+                *domain,
+                holder.clone(),
+            ));
+        }
+        Ok(listener)
+    }
+
     /// Closes the target Page
     pub fn close_target(&self) -> Result<bool> {
         self.call_method(Target::CloseTarget {
@@ -1494,6 +5549,239 @@ impl Tab {
         self.close_target()
     }
 
+    /// Closes the tab like `close`, but only returns once the browser has reported the target
+    /// destroyed, so that its resources have really been released.
+    ///
+    /// If `fire_unload` is set and the page asks for confirmation before unloading (a
+    /// `beforeunload` dialog), the dialog is answered with `accept_beforeunload`. If the dialog
+    /// is dismissed the page stays open, and this fails once the default timeout elapses.
+    pub fn close_and_wait(&self, fire_unload: bool, accept_beforeunload: bool) -> Result<()> {
+        let dialog_listener = if fire_unload {
+            let transport = Arc::clone(&self.transport);
+            let session_id = self.session_id.clone();
+            Some(self.add_event_listener(Arc::new(move |event: &Event| {
+                if let Event::PageJavascriptDialogOpening(ev) = event {
+                    if ev.params.Type == Page::DialogType::Beforeunload {
+                        if let Err(err) = transport.call_method_on_target(
+                            session_id.clone(),
+                            Page::HandleJavaScriptDialog {
+                                accept: accept_beforeunload,
+                                prompt_text: None,
+                            },
+                        ) {
+                            warn!("Failed to answer beforeunload dialog: {err:?}");
+                        }
+                    }
+                }
+            }))?)
+        } else {
+            None
+        };
+
+        let closed = self.close(fire_unload).and_then(|_| {
+            Ok(
+                util::Wait::with_timeout(*self.default_timeout.read().unwrap())
+                    .until(|| self.destroyed.load(Ordering::SeqCst).then_some(()))?,
+            )
+        });
+        if let Some(dialog_listener) = dialog_listener {
+            self.remove_event_listener(&dialog_listener)?;
+        }
+        closed
+    }
+
+    /// Waits for the next popup this tab opens (e.g. with `window.open` or a link with
+    /// `target="_blank"`) and returns a handle to it, for flows spanning several windows such as
+    /// OAuth logins. Popups opened since the last call are returned first, so it's fine to open the
+    /// popup before calling this. Popups opened with `noopener` aren't linked to this tab.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.find_element("button#login-with-provider")?.click()?;
+    /// let popup = tab.wait_for_popup()?;
+    /// popup.wait_until_navigated()?;
+    /// popup.find_element("input[name=username]")?.type_into("alice")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wait_for_popup(&self) -> Result<Arc<Tab>> {
+        Ok(
+            util::Wait::with_timeout(*self.default_timeout.read().unwrap()).until(|| {
+                let mut popups = self.popups.lock().unwrap();
+                // Skip the popups which have been closed in the meantime
+                while let Some(popup) = popups.pop_front() {
+                    if let Some(popup) = popup.upgrade() {
+                        return Some(popup);
+                    }
+                }
+                None
+            })?,
+        )
+    }
+
+    pub(crate) fn add_popup(&self, popup: &Arc<Tab>) {
+        self.popups.lock().unwrap().push_back(Arc::downgrade(popup));
+    }
+
+    pub(crate) fn mark_destroyed(&self) {
+        self.destroyed.store(true, Ordering::SeqCst);
+    }
+
+    /// Sets what happens when the tab's renderer crashes, see `CrashRecovery`. Defaults to
+    /// `CrashRecovery::Error`, or `Reload` if `Browser::set_restore_crashed_tabs` is on.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use std::collections::HashMap;
+    /// # use headless_chrome::Browser;
+    /// use headless_chrome::browser::tab::crash_recovery::CrashRecovery;
+    ///
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.new_tab()?;
+    /// tab.set_crash_recovery(CrashRecovery::Reload);
+    /// // Sent again when the page is reloaded after a crash
+    /// tab.set_extra_http_headers(HashMap::from([("Authorization", "Bearer secret")]))?;
+    /// tab.navigate_to("https://example.com/huge-page")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_crash_recovery(&self, recovery: CrashRecovery) {
+        self.crash_recovery_chosen.store(true, Ordering::SeqCst);
+        *self.crash_recovery.write().unwrap() = recovery;
+    }
+
+    /// Sets the crash recovery the browser defaults to, unless one was set on the tab itself.
+    pub(crate) fn set_default_crash_recovery(&self, recovery: CrashRecovery) {
+        if !self.crash_recovery_chosen.load(Ordering::SeqCst) {
+            *self.crash_recovery.write().unwrap() = recovery;
+        }
+    }
+
+    pub fn crash_recovery(&self) -> CrashRecovery {
+        *self.crash_recovery.read().unwrap()
+    }
+
+    /// Whether the tab's renderer has crashed and the page hasn't been loaded again since.
+    pub fn is_crashed(&self) -> bool {
+        self.crashed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn mark_crashed(&self) {
+        self.crashed.store(true, Ordering::SeqCst);
+    }
+
+    /// The URL of the main frame's last committed navigation, which unlike `get_url` isn't
+    /// that of a navigation which crashed the renderer.
+    pub(crate) fn committed_url(&self) -> String {
+        self.main_frame_navigation.lock().unwrap().url.clone()
+    }
+
+    /// Reloads a crashed tab, first applying its setup again: the extra HTTP headers, user
+    /// agent and request interception set on it. `CrashRecovery::Reload` does this
+    /// automatically.
+    pub fn recover_from_crash(&self) -> Result<&Self> {
+        let setup = self.setup.lock().unwrap().clone();
+        self.crashed.store(false, Ordering::SeqCst);
+        if let Err(err) = self.apply_setup(&setup, false) {
+            warn!("Failed to set up tab {} again: {err:?}", self.target_id);
+        }
+        self.reload(false, None)
+    }
+
+    /// Makes the calls in `setup` on this tab, including those adding scripts to evaluate on
+    /// new documents if `with_scripts` is set (they survive crashes, so needn't be added again).
+    fn apply_setup(&self, setup: &TabSetup, with_scripts: bool) -> Result<()> {
+        if let Some(extra_http_headers) = &setup.extra_http_headers {
+            self.acquire_domain(Domain::Network, "set_extra_http_headers")?;
+            self.call_method(extra_http_headers.clone())?;
+        }
+        if let Some(user_agent) = &setup.user_agent {
+            self.call_method(user_agent.clone())?;
+        }
+        // A new tab acquires `Fetch` for the crashed one's holders instead, see `take_over_from`
+        if setup.fetch.is_some() && !with_scripts {
+            self.call_method(self.domains.merged_fetch())?;
+        }
+        let mut scripts_on_new_document = Vec::new();
+        if with_scripts {
+            for (_, script) in &setup.scripts_on_new_document {
+                let identifier = self
+                    .call_method(AddScriptToEvaluateOnNewDocument {
+                        source: script.clone(),
+                        world_name: None,
+                        include_command_line_api: None,
+                    })?
+                    .identifier;
+                scripts_on_new_document.push((identifier, script.clone()));
+            }
+        } else {
+            scripts_on_new_document.clone_from(&setup.scripts_on_new_document);
+        }
+        *self.setup.lock().unwrap() = TabSetup {
+            scripts_on_new_document,
+            ..setup.clone()
+        };
+        Ok(())
+    }
+
+    /// Sets this (new) tab up like `crashed`, to take its place: its setup, request interceptor,
+    /// middleware, navigation policy, timeout and crash recovery.
+    pub(crate) fn take_over_from(&self, crashed: &Tab) -> Result<()> {
+        *self.request_interceptor.lock().unwrap() =
+            Arc::clone(&crashed.request_interceptor.lock().unwrap());
+        self.auth_handler
+            .lock()
+            .unwrap()
+            .clone_from(&crashed.auth_handler.lock().unwrap());
+        for middleware in crashed.middleware.middlewares() {
+            self.middleware.add(middleware);
+        }
+        self.navigation_policy
+            .write()
+            .unwrap()
+            .clone_from(&crashed.navigation_policy.read().unwrap());
+        self.set_default_timeout(*crashed.default_timeout.read().unwrap());
+        if crashed.crash_recovery_chosen.load(Ordering::SeqCst) {
+            self.set_crash_recovery(crashed.crash_recovery());
+        }
+        let fetch_requirements = crashed.domains.fetch_requirements();
+        for holder in crashed.domains.holders(Domain::Fetch) {
+            match fetch_requirements
+                .iter()
+                .find(|(required_by, _)| *required_by == holder)
+            {
+                Some((_, requirement)) => self.acquire_fetch(
+                    &holder,
+                    requirement.patterns.as_deref(),
+                    requirement.handle_auth_requests == Some(true),
+                )?,
+                None => self.acquire_domain(Domain::Fetch, &holder)?,
+            };
+        }
+
+        let setup = crashed.setup.lock().unwrap().clone();
+        self.apply_setup(&setup, true)
+    }
+
+    pub(crate) fn set_replaced_by(&self, replacement: &Arc<Tab>) {
+        *self.replaced_by.lock().unwrap() = Some(Arc::downgrade(replacement));
+    }
+
+    /// The tab opened in this one's place after it crashed, if it had
+    /// `CrashRecovery::Recreate` and it's still open.
+    pub fn replacement(&self) -> Option<Arc<Tab>> {
+        self.replaced_by.lock().unwrap().as_ref()?.upgrade()
+    }
+
     /// Activates (focuses) the target.
     pub fn activate(&self) -> Result<&Self> {
         self.call_method(Target::ActivateTarget {
@@ -1610,26 +5898,29 @@ impl Tab {
         Ok(())
     }
 
-    /// Returns the title of the document.
+    /// Returns the title of the document as of the last `Target.targetInfoChanged` event, without
+    /// a round trip to the browser. The event can trail a navigation or a change to
+    /// `document.title` slightly, and for a document without a title the browser reports its URL.
     ///
     /// ```rust
     /// # use anyhow::Result;
+    /// # use std::time::Duration;
     /// # use headless_chrome::Browser;
+    /// # use headless_chrome::util::Wait;
     /// # fn main() -> Result<()> {
     /// #
     /// # let browser = Browser::default()?;
     /// # let tab = browser.wait_for_initial_tab()?;
     /// tab.navigate_to("https://google.com")?;
     /// tab.wait_until_navigated()?;
-    /// let title = tab.get_title()?;
-    /// assert_eq!(title, "Google");
+    /// Wait::with_timeout(Duration::from_secs(5))
+    ///     .until(|| (tab.get_title().ok()? == "Google").then_some(()))?;
     /// #
     /// # Ok(())
     /// # }
     /// ```
     pub fn get_title(&self) -> Result<String> {
-        let remote_object = self.evaluate("document.title", false)?;
-        Ok(serde_json::from_value(remote_object.value.unwrap())?)
+        Ok(self.target_info.lock().unwrap().title.clone())
     }
 
     /// If enabled, instead of using the GUI to select files, the browser will
@@ -1658,14 +5949,12 @@ impl Tab {
     }
 
     pub fn set_extra_http_headers(&self, headers: HashMap<&str, &str>) -> Result<()> {
-        self.call_method(Network::Enable {
-            max_total_buffer_size: None,
-            max_resource_buffer_size: None,
-            max_post_data_size: None,
-        })?;
-        self.call_method(SetExtraHTTPHeaders {
+        self.acquire_domain(Domain::Network, "set_extra_http_headers")?;
+        let extra_http_headers = SetExtraHTTPHeaders {
             headers: Network::Headers(Some(json!(headers))),
-        })?;
+        };
+        self.call_method(extra_http_headers.clone())?;
+        self.setup.lock().unwrap().extra_http_headers = Some(extra_http_headers);
         Ok(())
     }
 
@@ -1714,27 +6003,306 @@ impl Tab {
         Ok(())
     }
 
+    /// Returns a handle to the `localStorage` of the current origin, backed by the `DOMStorage`
+    /// domain.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// let storage = tab.local_storage()?;
+    /// storage.set("visits", 3)?;
+    /// assert_eq!(Some(3), storage.get::<u32>("visits")?);
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn local_storage(&self) -> Result<DomStorage<'_>> {
+        DomStorage::new(self, true)
+    }
+
+    /// Returns a handle to the `sessionStorage` of the current origin, backed by the
+    /// `DOMStorage` domain.
+    pub fn session_storage(&self) -> Result<DomStorage<'_>> {
+        DomStorage::new(self, false)
+    }
+
+    /// Returns a read-only handle to the IndexedDB databases of `origin`.
+    pub fn indexeddb(&self, origin: &str) -> Result<IndexedDb<'_>> {
+        IndexedDb::new(self, origin)
+    }
+
+    /// Returns the names of the IndexedDB databases belonging to `origin`.
+    pub fn indexeddb_databases(&self, origin: &str) -> Result<Vec<String>> {
+        self.indexeddb(origin)?.database_names()
+    }
+
+    /// Writes `text` to the system clipboard via the page's async Clipboard API.
+    ///
+    /// Clipboard permissions are granted to the current origin first, so the tab must have
+    /// navigated to a page served over http(s) (or localhost).
+    pub fn set_clipboard_text(&self, text: &str) -> Result<&Self> {
+        self.grant_clipboard_permissions()?;
+        let result = self.evaluate(
+            &format!("navigator.clipboard.writeText({})", json!(text)),
+            true,
+        )?;
+        if result.subtype == Some(Runtime::RemoteObjectSubtype::Error) {
+            return Err(ClipboardAccessFailed {
+                error_text: result.description.unwrap_or_default(),
+            }
+            .into());
+        }
+        Ok(self)
+    }
+
+    /// Reads the text currently on the system clipboard via the page's async Clipboard API.
+    ///
+    /// See `set_clipboard_text` for the requirements on the current page.
+    pub fn get_clipboard_text(&self) -> Result<String> {
+        self.grant_clipboard_permissions()?;
+        let result = self.evaluate("navigator.clipboard.readText()", true)?;
+        if result.subtype == Some(Runtime::RemoteObjectSubtype::Error) {
+            return Err(ClipboardAccessFailed {
+                error_text: result.description.unwrap_or_default(),
+            }
+            .into());
+        }
+        Ok(serde_json::from_value(result.value.unwrap_or_default())?)
+    }
+
+    fn grant_clipboard_permissions(&self) -> Result<()> {
+        let (origin, browser_context_id) = {
+            let info = self.target_info.lock().unwrap();
+            let origin = url::Url::parse(&info.url)?.origin().ascii_serialization();
+            (origin, info.browser_context_id.clone())
+        };
+        self.transport
+            .call_method_on_browser(Browser::GrantPermissions {
+                permissions: vec![
+                    Browser::PermissionType::ClipboardReadWrite,
+                    Browser::PermissionType::ClipboardSanitizedWrite,
+                ],
+                origin: Some(origin),
+                browser_context_id,
+            })?;
+        // The Clipboard API rejects calls from documents which don't have focus, which
+        // headless tabs never do unless we emulate it
+        self.emulate_focus(true)?;
+        Ok(())
+    }
+
+    /// Stops loading the page (and all its pending subresources), like the browser's stop button.
+    ///
+    /// Any `wait_until_navigated` call made afterwards returns immediately rather than waiting for
+    /// a load which will never finish.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-stopLoading>
     pub fn stop_loading(&self) -> Result<bool> {
-        self.call_method(Page::StopLoading(None)).map(|_| true)
+        self.call_method(Page::StopLoading(None))?;
+        self.navigating.store(false, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Enables or disables the page's Content Security Policy by-passing.
+    ///
+    /// Useful when a page's CSP would otherwise block scripts injected with
+    /// `evaluate_on_new_document` or `evaluate`. Takes effect on the next navigation.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-setBypassCSP>
+    pub fn set_bypass_csp(&self, enabled: bool) -> Result<&Self> {
+        self.call_method(Page::SetBypassCSP { enabled })?;
+        Ok(self)
+    }
+
+    /// Evaluates the given script in every frame upon creation, before any of the frame's own
+    /// scripts run.
+    ///
+    /// Returns an identifier which can be passed to `remove_script_on_new_document`.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Page#method-addScriptToEvaluateOnNewDocument>
+    pub fn evaluate_on_new_document(&self, script: &str) -> Result<Page::ScriptIdentifier> {
+        let identifier = self
+            .call_method(AddScriptToEvaluateOnNewDocument {
+                source: script.to_string(),
+                world_name: None,
+                include_command_line_api: None,
+            })?
+            .identifier;
+        self.setup
+            .lock()
+            .unwrap()
+            .scripts_on_new_document
+            .push((identifier.clone(), script.to_string()));
+        Ok(identifier)
+    }
+
+    /// Stops a script added with `evaluate_on_new_document` from running in new documents.
+    pub fn remove_script_on_new_document(
+        &self,
+        identifier: Page::ScriptIdentifier,
+    ) -> Result<&Self> {
+        self.setup
+            .lock()
+            .unwrap()
+            .scripts_on_new_document
+            .retain(|(script_identifier, _)| *script_identifier != identifier);
+        self.call_method(Page::RemoveScriptToEvaluateOnNewDocument { identifier })?;
+        Ok(self)
+    }
+
+    fn enable_animation(&self) -> Result<()> {
+        self.acquire_domain(Domain::Animation, "animations")?;
+        Ok(())
+    }
+
+    /// Sets how fast the page's animations play, relative to normal speed; 0 pauses all of them,
+    /// so that screenshots of animated pages come out the same every time.
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Animation#method-setPlaybackRate>
+    pub fn set_animation_playback_rate(&self, playback_rate: f64) -> Result<&Self> {
+        self.enable_animation()?;
+        self.call_method(Animation::SetPlaybackRate { playback_rate })?;
+        Ok(self)
+    }
+
+    /// Moves every animation created since the `Animation` domain was enabled (by this or
+    /// `set_animation_playback_rate`) to `current_time` milliseconds into it.
+    ///
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # fn main() -> Result<()> {
+    /// #
+    /// # use headless_chrome::Browser;
+    /// # use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
+    /// # let browser = Browser::default()?;
+    /// # let tab = browser.wait_for_initial_tab()?;
+    /// tab.set_animation_playback_rate(0.0)?;
+    /// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+    /// // Every animation frozen 500ms in
+    /// tab.seek_animations(500.0)?;
+    /// let png = tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// See <https://chromedevtools.github.io/devtools-protocol/tot/Animation#method-seekAnimations>
+    pub fn seek_animations(&self, current_time: f64) -> Result<&Self> {
+        self.enable_animation()?;
+        let animations = self.animations.lock().unwrap().clone();
+        if !animations.is_empty() {
+            self.call_method(Animation::SeekAnimations {
+                animations,
+                current_time,
+            })?;
+        }
+        Ok(self)
+    }
+
+    /// Makes CSS animations and transitions finish instantly, in the current document and in
+    /// every one loaded afterwards, by injecting a style override. Also hides the text cursor,
+    /// which blinks.
+    pub fn disable_css_animations(&self) -> Result<&Self> {
+        const DISABLE_ANIMATIONS_CSS: &str = "*, *::before, *::after {
+            animation-delay: 0s !important;
+            animation-duration: 0s !important;
+            animation-iteration-count: 1 !important;
+            transition-delay: 0s !important;
+            transition-duration: 0s !important;
+            scroll-behavior: auto !important;
+            caret-color: transparent !important;
+        }";
+
+        let script = format!(
+            "(function disableCssAnimations() {{
+                const addStyle = () => {{
+                    const style = document.createElement('style');
+                    style.textContent = {};
+                    (document.head || document.documentElement).appendChild(style);
+                }};
+                if (document.documentElement) {{
+                    addStyle();
+                }} else {{
+                    document.addEventListener('DOMContentLoaded', addStyle, {{ once: true }});
+                }}
+            }})()",
+            json!(DISABLE_ANIMATIONS_CSS)
+        );
+        self.evaluate_on_new_document(&script)?;
+        self.evaluate(&script, false)?;
+        Ok(self)
     }
 
     fn bypass_user_agent(&self) -> Result<()> {
         let object = self.evaluate("window.navigator.userAgent", true)?;
 
-        match object.value.map(|x| x.to_string()) {
-            Some(mut ua) => {
-                ua = ua.replace("HeadlessChrome/", "Chrome/");
+        match object.value.as_ref().and_then(Json::as_str) {
+            Some(ua) => {
+                let mut ua = ua.replace("HeadlessChrome/", "Chrome/");
 
                 let re = regex::Regex::new(r"\(([^)]+)\)").unwrap();
                 ua = re.replace(&ua, "(Windows NT 10.0; Win64; x64)").to_string();
 
-                self.set_user_agent(&ua, None, None)?;
+                // Client hints (Sec-CH-UA headers and navigator.userAgentData) have to tell the
+                // same story as the User-Agent header, or the mismatch gives us away
+                let full_version = regex::Regex::new(r"Chrome/([\d.]+)")
+                    .unwrap()
+                    .captures(&ua)
+                    .map_or_else(String::new, |captures| captures[1].to_string());
+                let major_version = full_version.split('.').next().unwrap_or_default();
+                let brands = |version: &str| {
+                    [
+                        ("Not)A;Brand", "99"),
+                        ("Google Chrome", version),
+                        ("Chromium", version),
+                    ]
+                    .into_iter()
+                    .map(|(brand, version)| Emulation::UserAgentBrandVersion {
+                        brand: brand.to_string(),
+                        version: version.to_string(),
+                    })
+                    .collect()
+                };
+
+                self.call_method(SetUserAgentOverride {
+                    user_agent: ua.clone(),
+                    accept_language: Some("en-US,en".to_string()),
+                    platform: Some("Win32".to_string()),
+                    user_agent_metadata: Some(Emulation::UserAgentMetadata {
+                        brands: Some(brands(major_version)),
+                        full_version_list: Some(brands(&full_version)),
+                        full_version: Some(full_version.clone()),
+                        platform: "Windows".to_string(),
+                        platform_version: "10.0.0".to_string(),
+                        architecture: "x86".to_string(),
+                        model: String::new(),
+                        mobile: false,
+                        bitness: Some("64".to_string()),
+                        wow_64: Some(false),
+                        form_factors: None,
+                    }),
+                })?;
                 Ok(())
             }
             None => Err(NoUserAgentEvaluated {}.into()),
         }
     }
 
+    fn bypass_languages(&self) -> Result<()> {
+        self.call_method(Page::AddScriptToEvaluateOnNewDocument {
+            source: "Object.defineProperty(navigator, 'languages', {get: () => ['en-US', 'en']});"
+                .to_string(),
+            world_name: None,
+            include_command_line_api: None,
+        })?;
+        Ok(())
+    }
+
     fn bypass_wedriver(&self) -> Result<()> {
         self.call_method(Page::AddScriptToEvaluateOnNewDocument {
             source: "Object.defineProperty(navigator, 'webdriver', {get: () => undefined});"
@@ -1806,9 +6374,16 @@ impl Tab {
         Ok(())
     }
 
+    /// Makes the tab harder to tell apart from one in a regular desktop Chrome: hides
+    /// `navigator.webdriver`, fakes `window.chrome.runtime`, plugins, languages and the WebGL
+    /// vendor, and sends a User-Agent (and matching client hints) without "Headless" in it.
+    ///
+    /// The scripts only apply to documents loaded afterwards. See
+    /// `Browser::set_stealth_mode` to apply this to every tab as it's created.
     pub fn enable_stealth_mode(&self) -> Result<()> {
         self.bypass_user_agent()?;
         self.bypass_wedriver()?;
+        self.bypass_languages()?;
         self.bypass_chrome()?;
         self.bypass_permissions()?;
         self.bypass_plugins()?;
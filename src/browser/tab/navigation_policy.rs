@@ -0,0 +1,219 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+
+use thiserror::Error;
+use url::{Host, Url};
+
+use crate::protocol::cdp::Fetch::events::RequestPausedEventParams;
+
+#[derive(Debug, Error)]
+#[error("Request to {url} was blocked by the tab's navigation policy: {reason}")]
+pub struct NavigationBlocked {
+    pub url: String,
+    pub reason: String,
+}
+
+/// Which URLs a tab may load, for tabs which load URLs from people you don't trust (e.g. a
+/// service rendering user-supplied pages). See `Tab::set_navigation_policy`.
+///
+/// Hosts match themselves and their subdomains, so `example.com` also matches
+/// `www.example.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavigationPolicy {
+    /// The URL schemes which may be loaded. `file`, `chrome` and the like aren't allowed by
+    /// default.
+    pub allowed_schemes: Vec<String>,
+    /// If not empty, only these hosts may be loaded.
+    pub allowed_hosts: Vec<String>,
+    pub denied_hosts: Vec<String>,
+    /// Blocks loopback, private, link-local and shared (carrier-grade NAT) addresses, whether
+    /// given as IP addresses or as hostnames which resolve to them, so that pages can't reach
+    /// services on the machine or network the browser runs on.
+    ///
+    /// Hostnames are resolved separately from the browser's own lookup, so a DNS server
+    /// answering differently the second time can get past this.
+    pub block_private_networks: bool,
+    /// Blocks requests which are redirects, rather than only checking where they go.
+    pub block_redirects: bool,
+}
+
+impl Default for NavigationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_schemes: ["http", "https", "data", "blob", "about"]
+                .map(String::from)
+                .to_vec(),
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+            block_private_networks: true,
+            block_redirects: false,
+        }
+    }
+}
+
+fn host_matches(host: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_start_matches('.').to_ascii_lowercase();
+        host == pattern
+            || host
+                .strip_suffix(&pattern)
+                .is_some_and(|subdomain| subdomain.ends_with('.'))
+    })
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // 100.64.0.0/10
+        || (first == 100 && (second & 0xc0) == 64)
+}
+
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_private_ipv4(ipv4);
+    }
+    let first_segment = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local, fc00::/7
+        || (first_segment & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first_segment & 0xffc0) == 0xfe80
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => is_private_ipv6(ip),
+    }
+}
+
+impl NavigationPolicy {
+    /// Checks whether `url` may be loaded.
+    pub fn check(&self, url: &str) -> Result<(), NavigationBlocked> {
+        let blocked = |reason: String| NavigationBlocked {
+            url: url.to_string(),
+            reason,
+        };
+        let parsed = Url::parse(url).map_err(|err| blocked(format!("invalid URL: {err}")))?;
+
+        if !self
+            .allowed_schemes
+            .iter()
+            .any(|scheme| scheme.eq_ignore_ascii_case(parsed.scheme()))
+        {
+            return Err(blocked(format!(
+                "the {} scheme isn't allowed",
+                parsed.scheme()
+            )));
+        }
+        let Some(host) = parsed.host() else {
+            return Ok(());
+        };
+
+        let host_name = match &host {
+            Host::Domain(domain) => domain.to_ascii_lowercase(),
+            Host::Ipv4(ip) => ip.to_string(),
+            Host::Ipv6(ip) => ip.to_string(),
+        };
+        if host_matches(&host_name, &self.denied_hosts) {
+            return Err(blocked(format!("{host_name} is denied")));
+        }
+        if !self.allowed_hosts.is_empty() && !host_matches(&host_name, &self.allowed_hosts) {
+            return Err(blocked(format!("{host_name} isn't allowed")));
+        }
+
+        if self.block_private_networks {
+            let private = match host {
+                Host::Ipv4(ip) => is_private_ipv4(ip),
+                Host::Ipv6(ip) => is_private_ipv6(ip),
+                Host::Domain(_)
+                    if host_name == "localhost" || host_name.ends_with(".localhost") =>
+                {
+                    true
+                }
+                // If it doesn't resolve, the browser won't be able to load it either
+                Host::Domain(domain) => (domain, parsed.port_or_known_default().unwrap_or(80))
+                    .to_socket_addrs()
+                    .is_ok_and(|mut addrs| addrs.any(|addr| is_private_ip(addr.ip()))),
+            };
+            if private {
+                return Err(blocked(format!("{host_name} is on a private network")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether checking a request paused by the `Fetch` domain looks up its host, as hosts given
+    /// by name have to be resolved to tell whether they're on a private network.
+    pub(crate) fn looks_up_host(&self, params: &RequestPausedEventParams) -> bool {
+        if !self.block_private_networks
+            || params.response_status_code.is_some()
+            || params.response_error_reason.is_some()
+        {
+            return false;
+        }
+        Url::parse(&params.request.url).is_ok_and(|url| {
+            matches!(url.host(), Some(Host::Domain(domain))
+                if domain != "localhost" && !domain.ends_with(".localhost"))
+        })
+    }
+
+    /// Checks a request paused by the `Fetch` domain, before it's sent. Responses aren't
+    /// checked, as their requests already were. This can look up the request's host, see
+    /// `looks_up_host`.
+    pub(crate) fn check_paused_request(
+        &self,
+        params: &RequestPausedEventParams,
+    ) -> Result<(), NavigationBlocked> {
+        if params.response_status_code.is_some() || params.response_error_reason.is_some() {
+            return Ok(());
+        }
+        if self.block_redirects && params.redirected_request_id.is_some() {
+            return Err(NavigationBlocked {
+                url: params.request.url.clone(),
+                reason: "redirects aren't allowed".to_string(),
+            });
+        }
+        self.check(&params.request.url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_schemes_hosts_and_private_addresses() {
+        let policy = NavigationPolicy::default();
+        assert!(policy.check("file:///etc/passwd").is_err());
+        assert!(policy.check("chrome://settings").is_err());
+        assert!(policy.check("about:blank").is_ok());
+        assert!(policy.check("http://127.0.0.1:8080/").is_err());
+        assert!(policy.check("http://10.1.2.3/").is_err());
+        assert!(policy
+            .check("http://169.254.169.254/latest/meta-data/")
+            .is_err());
+        assert!(policy.check("http://100.100.0.1/").is_err());
+        assert!(policy.check("http://[::1]/").is_err());
+        assert!(policy.check("http://[::ffff:192.168.0.1]/").is_err());
+        assert!(policy.check("http://[fd00::1]/").is_err());
+        assert!(policy.check("http://app.localhost/").is_err());
+        assert!(policy.check("http://93.184.215.14/").is_ok());
+
+        let policy = NavigationPolicy {
+            allowed_hosts: vec!["example.com".to_string()],
+            denied_hosts: vec!["admin.example.com".to_string()],
+            block_private_networks: false,
+            ..NavigationPolicy::default()
+        };
+        assert!(policy.check("https://example.com/").is_ok());
+        assert!(policy.check("https://WWW.Example.com/").is_ok());
+        assert!(policy.check("https://notexample.com/").is_err());
+        assert!(policy.check("https://admin.example.com/").is_err());
+        assert!(policy.check("https://a.admin.example.com/").is_err());
+    }
+}
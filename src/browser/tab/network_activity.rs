@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::protocol::cdp::Network::RequestId;
+
+/// The requests a tab has in flight, as reported by `Network.requestWillBeSent`,
+/// `Network.loadingFinished` and `Network.loadingFailed` events.
+#[derive(Debug)]
+pub struct NetworkActivity {
+    in_flight: HashSet<RequestId>,
+    last_change: Instant,
+}
+
+impl Default for NetworkActivity {
+    fn default() -> Self {
+        Self {
+            in_flight: HashSet::new(),
+            last_change: Instant::now(),
+        }
+    }
+}
+
+impl NetworkActivity {
+    pub fn request_started(&mut self, request_id: RequestId) {
+        // Redirects are reported as further requests with the same ID
+        if self.in_flight.insert(request_id) {
+            self.last_change = Instant::now();
+        }
+    }
+
+    pub fn request_finished(&mut self, request_id: &RequestId) {
+        if self.in_flight.remove(request_id) {
+            self.last_change = Instant::now();
+        }
+    }
+
+    /// How long there have been no more than `max_in_flight` requests in flight, or `None` if
+    /// there currently are more.
+    pub fn idle_for(&self, max_in_flight: usize) -> Option<Duration> {
+        if self.in_flight.len() > max_in_flight {
+            None
+        } else {
+            Some(self.last_change.elapsed())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_each_request_once() {
+        let mut activity = NetworkActivity::default();
+
+        activity.request_started("1".to_string());
+        activity.request_started("1".to_string());
+        activity.request_started("2".to_string());
+        assert!(activity.idle_for(1).is_none());
+        assert!(activity.idle_for(2).is_some());
+
+        activity.request_finished(&"1".to_string());
+        assert!(activity.idle_for(1).is_some());
+
+        activity.request_finished(&"2".to_string());
+        activity.request_finished(&"2".to_string());
+        assert!(activity.idle_for(0).is_some());
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Installs a `MutationObserver` and a `requestAnimationFrame` loop on the page the first time
+/// it's evaluated (and again after each navigation, as they go with the old page), then reports
+/// what they've seen so far as JSON.
+pub(crate) const STABILITY_PROBE: &str = r"(() => {
+    let probe = window.__headlessChromeStability;
+    if (!probe) {
+        probe = window.__headlessChromeStability = {
+            mutations: 0,
+            lastMutation: performance.now(),
+            lastFrame: performance.now(),
+            frameGap: 0,
+        };
+        new MutationObserver(records => {
+            probe.mutations += records.length;
+            probe.lastMutation = performance.now();
+        }).observe(document, { childList: true, subtree: true, attributes: true, characterData: true });
+        const frame = now => {
+            probe.frameGap = now - probe.lastFrame;
+            probe.lastFrame = now;
+            requestAnimationFrame(frame);
+        };
+        requestAnimationFrame(frame);
+    }
+    return JSON.stringify({
+        mutations: probe.mutations,
+        sinceLastMutation: performance.now() - probe.lastMutation,
+        frameGap: probe.frameGap,
+    });
+})()";
+
+/// Frames further apart than this mean the page's main thread is still busy, e.g. rendering.
+const MAX_FRAME_GAP: Duration = Duration::from_millis(100);
+
+/// What `STABILITY_PROBE` reports, with times in milliseconds.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PageSnapshot {
+    pub mutations: u64,
+    pub since_last_mutation: f64,
+    pub frame_gap: f64,
+}
+
+impl PageSnapshot {
+    /// Whether the page looks settled: the DOM hasn't changed and the network has been idle
+    /// (`network_idle_for` is `None` while requests are in flight) for `quiet_period`, and
+    /// frames are being rendered without long gaps. Pages which aren't rendering frames at all
+    /// (e.g. background tabs) only go by the first two.
+    pub(crate) fn is_settled(
+        &self,
+        quiet_period: Duration,
+        network_idle_for: Option<Duration>,
+    ) -> bool {
+        let quiet_ms = quiet_period.as_secs_f64() * 1000.0;
+        self.since_last_mutation >= quiet_ms
+            && network_idle_for.is_some_and(|idle_for| idle_for >= quiet_period)
+            && self.frame_gap <= MAX_FRAME_GAP.as_secs_f64() * 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_every_signal_to_be_quiet() {
+        let quiet_period = Duration::from_millis(500);
+        let snapshot = PageSnapshot {
+            mutations: 10,
+            since_last_mutation: 600.0,
+            frame_gap: 16.7,
+        };
+        assert!(snapshot.is_settled(quiet_period, Some(Duration::from_millis(700))));
+        assert!(!snapshot.is_settled(quiet_period, Some(Duration::from_millis(100))));
+        assert!(!snapshot.is_settled(quiet_period, None));
+
+        let mutated = PageSnapshot {
+            since_last_mutation: 100.0,
+            ..snapshot.clone()
+        };
+        assert!(!mutated.is_settled(quiet_period, Some(Duration::from_secs(1))));
+
+        let busy = PageSnapshot {
+            frame_gap: 250.0,
+            ..snapshot
+        };
+        assert!(!busy.is_settled(quiet_period, Some(Duration::from_secs(1))));
+    }
+}
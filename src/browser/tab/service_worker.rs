@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::protocol::cdp::ServiceWorker;
+
+pub use ServiceWorker::{ServiceWorkerRegistration, ServiceWorkerVersion};
+
+/// The service worker registrations and versions a tab has been told about via
+/// `ServiceWorker.workerRegistrationUpdated` and `ServiceWorker.workerVersionUpdated` events.
+#[derive(Debug, Default)]
+pub struct ServiceWorkerRegistry {
+    registrations: HashMap<ServiceWorker::RegistrationID, ServiceWorkerRegistration>,
+    versions: HashMap<String, ServiceWorkerVersion>,
+}
+
+impl ServiceWorkerRegistry {
+    pub fn update_registrations(&mut self, registrations: Vec<ServiceWorkerRegistration>) {
+        for registration in registrations {
+            if registration.is_deleted {
+                self.registrations.remove(&registration.registration_id);
+                self.versions
+                    .retain(|_, version| version.registration_id != registration.registration_id);
+            } else {
+                self.registrations
+                    .insert(registration.registration_id.clone(), registration);
+            }
+        }
+    }
+
+    pub fn update_versions(&mut self, versions: Vec<ServiceWorkerVersion>) {
+        for version in versions {
+            if version.status == ServiceWorker::ServiceWorkerVersionStatus::Redundant {
+                self.versions.remove(&version.version_id);
+            } else {
+                self.versions.insert(version.version_id.clone(), version);
+            }
+        }
+    }
+}
+
+/// A handle to the service workers which can control a tab's pages.
+///
+/// Typically you get one of these via `Tab.service_workers`, which also starts tracking
+/// registrations. Because Chrome reports registrations asynchronously, `registrations` and
+/// `versions` may be empty immediately after the first call.
+pub struct ServiceWorkers<'a> {
+    parent: &'a super::Tab,
+}
+
+impl<'a> ServiceWorkers<'a> {
+    pub fn new(parent: &'a super::Tab) -> Result<Self> {
+        parent.call_method(ServiceWorker::Enable(None))?;
+        Ok(Self { parent })
+    }
+
+    /// The currently known (non-deleted) registrations.
+    pub fn registrations(&self) -> Vec<ServiceWorkerRegistration> {
+        let registry = self.parent.service_worker_registry.lock().unwrap();
+        registry.registrations.values().cloned().collect()
+    }
+
+    /// The currently known (non-redundant) worker versions, across all registrations.
+    pub fn versions(&self) -> Vec<ServiceWorkerVersion> {
+        let registry = self.parent.service_worker_registry.lock().unwrap();
+        registry.versions.values().cloned().collect()
+    }
+
+    /// Unregisters the service worker registered for `scope_url`.
+    pub fn unregister(&self, scope_url: &str) -> Result<&Self> {
+        self.parent.call_method(ServiceWorker::Unregister {
+            scope_url: scope_url.to_string(),
+        })?;
+        Ok(self)
+    }
+
+    /// Makes the waiting worker registered for `scope_url` (if any) become active immediately.
+    pub fn skip_waiting(&self, scope_url: &str) -> Result<&Self> {
+        self.parent.call_method(ServiceWorker::SkipWaiting {
+            scope_url: scope_url.to_string(),
+        })?;
+        Ok(self)
+    }
+
+    /// Stops every running service worker.
+    pub fn stop_all_workers(&self) -> Result<&Self> {
+        self.parent
+            .call_method(ServiceWorker::StopAllWorkers(None))?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(id: &str, is_deleted: bool) -> ServiceWorkerRegistration {
+        ServiceWorkerRegistration {
+            registration_id: id.to_string(),
+            scope_url: format!("http://127.0.0.1/{id}/"),
+            is_deleted,
+        }
+    }
+
+    #[test]
+    fn tracks_and_forgets_registrations() {
+        let mut registry = ServiceWorkerRegistry::default();
+
+        registry.update_registrations(vec![registration("1", false), registration("2", false)]);
+        assert_eq!(2, registry.registrations.len());
+
+        registry.update_registrations(vec![registration("1", true)]);
+        assert_eq!(1, registry.registrations.len());
+        assert!(registry.registrations.contains_key("2"));
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::logging::warn;
+use anyhow::Result;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::protocol::cdp::{Debugger, Runtime};
+
+#[derive(Debug, Error)]
+#[error("Invalid source map: {reason}")]
+pub struct InvalidSourceMap {
+    pub reason: String,
+}
+
+/// Where a position in generated (e.g. bundled or minified) JavaScript came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalPosition {
+    /// The original file, e.g. `src/App.tsx`, resolved against the map's `sourceRoot`.
+    pub source: String,
+    /// 0-based, like the protocol's line numbers.
+    pub line: u32,
+    /// 0-based, like the protocol's column numbers.
+    pub column: u32,
+    /// The original name of the symbol at the position, if the map records it.
+    pub name: Option<String>,
+}
+
+/// One segment of the `mappings`: a generated column and where it came from.
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    generated_column: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+    name: Option<u32>,
+}
+
+/// A parsed [source map](https://sourcemaps.info/spec.html) (version 3, without sections),
+/// mapping positions in generated JavaScript back to the sources it was built from.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    names: Vec<String>,
+    /// The mappings of each generated line, sorted by generated column.
+    lines: Vec<Vec<Mapping>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSourceMap {
+    #[serde(default)]
+    source_root: Option<String>,
+    #[serde(default)]
+    sources: Vec<Option<String>>,
+    #[serde(default)]
+    names: Vec<String>,
+    mappings: String,
+}
+
+impl SourceMap {
+    pub fn parse(json: &str) -> Result<Self> {
+        let raw: RawSourceMap = serde_json::from_str(json)?;
+        let source_root = raw
+            .source_root
+            .filter(|root| !root.is_empty())
+            .map(|root| root.trim_end_matches('/').to_string() + "/");
+        let sources = raw
+            .sources
+            .into_iter()
+            .map(|source| {
+                let source = source.unwrap_or_default();
+                match &source_root {
+                    Some(root) => root.clone() + &source,
+                    None => source,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            sources,
+            names: raw.names,
+            lines: parse_mappings(&raw.mappings)?,
+        })
+    }
+
+    /// Finds where the generated position (0-based) came from, i.e. the closest mapping at or
+    /// before it on its line.
+    pub fn lookup(&self, line: u32, column: u32) -> Option<OriginalPosition> {
+        let mappings = self.lines.get(line as usize)?;
+        let index = mappings.partition_point(|mapping| mapping.generated_column <= column);
+        let mapping = mappings.get(index.checked_sub(1)?)?;
+        Some(OriginalPosition {
+            source: self.sources.get(mapping.source as usize)?.clone(),
+            line: mapping.line,
+            column: mapping.column,
+            name: mapping
+                .name
+                .and_then(|name| self.names.get(name as usize).cloned()),
+        })
+    }
+}
+
+/// Keeps track of the source maps of the scripts a page has parsed, to map the positions in
+/// exceptions' stack traces back to the original sources.
+#[derive(Default)]
+pub(crate) struct ScriptSourceMaps {
+    /// The resolved URL of each parsed script's source map, by script id.
+    map_urls: HashMap<Runtime::ScriptId, String>,
+    /// Source maps by URL, fetched when first needed; None for ones which couldn't be loaded.
+    maps: HashMap<String, Option<Arc<SourceMap>>>,
+}
+
+impl ScriptSourceMaps {
+    pub(crate) fn script_parsed(&mut self, script: &Debugger::events::ScriptParsedEventParams) {
+        let Some(map_url) = script.source_map_url.as_ref().filter(|url| !url.is_empty()) else {
+            return;
+        };
+        // Relative to the script
+        let map_url = url::Url::parse(&script.url)
+            .and_then(|script_url| script_url.join(map_url))
+            .map_or_else(|_| map_url.clone(), String::from);
+        self.map_urls.insert(script.script_id.clone(), map_url);
+    }
+
+    /// Rewrites the positions in `details` which are in scripts with source maps to the
+    /// original sources' positions, fetching the maps with `fetch` as needed.
+    pub(crate) fn apply<F>(&mut self, details: &mut Runtime::ExceptionDetails, mut fetch: F)
+    where
+        F: FnMut(&str) -> Result<String>,
+    {
+        if let Some(script_id) = &details.script_id {
+            if let Some(position) = self
+                .map_for(script_id, &mut fetch)
+                .and_then(|map| map.lookup(details.line_number, details.column_number))
+            {
+                details.url = Some(position.source);
+                details.line_number = position.line;
+                details.column_number = position.column;
+            }
+        }
+
+        let mut stack_trace = details.stack_trace.as_mut();
+        while let Some(trace) = stack_trace {
+            for frame in &mut trace.call_frames {
+                if let Some(position) = self
+                    .map_for(&frame.script_id, &mut fetch)
+                    .and_then(|map| map.lookup(frame.line_number, frame.column_number))
+                {
+                    frame.url = position.source;
+                    frame.line_number = position.line;
+                    frame.column_number = position.column;
+                }
+            }
+            stack_trace = trace.parent.as_deref_mut();
+        }
+    }
+
+    fn map_for<F>(&mut self, script_id: &Runtime::ScriptId, fetch: &mut F) -> Option<Arc<SourceMap>>
+    where
+        F: FnMut(&str) -> Result<String>,
+    {
+        let map_url = self.map_urls.get(script_id)?;
+        self.maps
+            .entry(map_url.clone())
+            .or_insert_with(|| {
+                fetch(map_url)
+                    .and_then(|json| SourceMap::parse(&json))
+                    .map(Arc::new)
+                    .map_err(|err| warn!("Couldn't load source map {map_url}: {err:?}"))
+                    .ok()
+            })
+            .clone()
+    }
+}
+
+fn parse_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>> {
+    // Everything but the generated column is relative to the previous segment in the whole map
+    let (mut source, mut line, mut column, mut name) = (0i64, 0i64, 0i64, 0i64);
+    let mut lines = Vec::new();
+    for encoded_line in mappings.split(';') {
+        let mut generated_column = 0i64;
+        let mut line_mappings = Vec::new();
+        for segment in encoded_line
+            .split(',')
+            .filter(|segment| !segment.is_empty())
+        {
+            let fields = decode_vlq(segment)?;
+            generated_column += fields[0];
+            // Segments with just a generated column don't map to anything
+            if fields.len() < 4 {
+                continue;
+            }
+            source += fields[1];
+            line += fields[2];
+            column += fields[3];
+            let segment_name = if let Some(name_delta) = fields.get(4) {
+                name += name_delta;
+                Some(to_u32(name)?)
+            } else {
+                None
+            };
+            line_mappings.push(Mapping {
+                generated_column: to_u32(generated_column)?,
+                source: to_u32(source)?,
+                line: to_u32(line)?,
+                column: to_u32(column)?,
+                name: segment_name,
+            });
+        }
+        line_mappings.sort_by_key(|mapping| mapping.generated_column);
+        lines.push(line_mappings);
+    }
+    Ok(lines)
+}
+
+fn to_u32(value: i64) -> Result<u32> {
+    u32::try_from(value).map_err(|_| {
+        InvalidSourceMap {
+            reason: format!("mapping out of range: {value}"),
+        }
+        .into()
+    })
+}
+
+/// Decodes a segment's base64 VLQ fields.
+fn decode_vlq(segment: &str) -> Result<Vec<i64>> {
+    let mut fields = Vec::with_capacity(5);
+    let mut value = 0i64;
+    let mut shift = 0;
+    for byte in segment.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => {
+                return Err(InvalidSourceMap {
+                    reason: format!("unexpected character in mappings: {:?}", byte as char),
+                }
+                .into())
+            }
+        };
+        if shift > 60 {
+            return Err(InvalidSourceMap {
+                reason: format!("mapping too long: {segment}"),
+            }
+            .into());
+        }
+        value += i64::from(digit & 0b1_1111) << shift;
+        if digit & 0b10_0000 == 0 {
+            // The lowest bit is the sign
+            let magnitude = value >> 1;
+            fields.push(if value & 1 == 1 {
+                -magnitude
+            } else {
+                magnitude
+            });
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 5;
+        }
+    }
+    if shift != 0 {
+        return Err(InvalidSourceMap {
+            reason: format!("truncated mapping: {segment}"),
+        }
+        .into());
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_vlq() {
+        assert_eq!(vec![0, 0, 16, 1], decode_vlq("AAgBC").unwrap());
+        assert_eq!(vec![-1, 123], decode_vlq("D2H").unwrap());
+        assert!(decode_vlq("g").is_err());
+    }
+
+    #[test]
+    fn looks_up_original_positions() {
+        // Generated by TypeScript for:
+        //   function greet(name: string) {
+        //     throw new Error(name);
+        //   }
+        let map = SourceMap::parse(
+            r#"{
+                "version": 3,
+                "sourceRoot": "src",
+                "sources": ["greet.ts"],
+                "names": ["greet", "name", "Error"],
+                "mappings": "AAAA,SAASA,MAAMC;IACb,MAAM,IAAIC,MAAMD,IAAI,CAAC,CAAC;AACxB,CAAC"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(OriginalPosition {
+                source: "src/greet.ts".to_string(),
+                line: 1,
+                column: 12,
+                name: Some("Error".to_string()),
+            }),
+            map.lookup(1, 15)
+        );
+        assert_eq!(Some(0), map.lookup(0, 9).map(|position| position.line));
+        assert_eq!(None, map.lookup(10, 0));
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use crate::protocol::cdp::Network::{RequestId, ResourceTiming, ResourceType};
+
+/// How one request made while loading a page went, as DevTools' network waterfall shows it.
+/// Times are in milliseconds. Phases which didn't happen for the request, like DNS and
+/// connecting for a request reusing a connection, or which haven't happened yet, are `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestTiming {
+    pub request_id: RequestId,
+    pub url: String,
+    pub resource_type: Option<ResourceType>,
+    pub status: Option<u32>,
+    /// When the request started, relative to the start of the navigation.
+    pub start: f64,
+    pub dns: Option<f64>,
+    /// Setting up the connection, including `tls`.
+    pub connect: Option<f64>,
+    pub tls: Option<f64>,
+    /// From sending the request to the response headers starting to arrive.
+    pub ttfb: Option<f64>,
+    /// From the response headers arriving to the last of the body.
+    pub download: Option<f64>,
+    /// From the start of the request to its end.
+    pub total: Option<f64>,
+    /// How many bytes were received, including headers, once the request has finished.
+    pub encoded_data_length: Option<f64>,
+    pub failed: bool,
+}
+
+/// The requests made by a navigation of a tab's main frame and the page it loaded, in the
+/// order they started. See `Tab::last_navigation_waterfall`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavigationWaterfall {
+    pub url: String,
+    pub requests: Vec<RequestTiming>,
+}
+
+struct Navigation {
+    /// When the navigation's request started, in seconds.
+    started_at: f64,
+    waterfall: NavigationWaterfall,
+    /// Index of each request in `waterfall.requests`, and when its response headers arrived.
+    requests: HashMap<RequestId, (usize, Option<f64>)>,
+}
+
+/// Builds the waterfall of the latest navigation from `Network` events.
+#[derive(Default)]
+pub(crate) struct WaterfallRecorder {
+    navigation: Option<Navigation>,
+}
+
+/// The duration between two of a `ResourceTiming`'s offsets, which are -1 for phases which
+/// didn't happen.
+fn phase(start: f64, end: f64) -> Option<f64> {
+    (start >= 0.0 && end >= start).then_some(end - start)
+}
+
+impl WaterfallRecorder {
+    /// Records a request starting, at `timestamp` (in seconds). A `navigation` request starts a
+    /// new waterfall; requests made before the first navigation are ignored.
+    pub(crate) fn request_started(
+        &mut self,
+        request_id: RequestId,
+        url: String,
+        resource_type: Option<ResourceType>,
+        timestamp: f64,
+        navigation: bool,
+    ) {
+        if navigation {
+            // A redirect is the same request carrying on
+            let redirected = self
+                .navigation
+                .as_ref()
+                .is_some_and(|navigation| navigation.requests.contains_key(&request_id));
+            if !redirected {
+                self.navigation = Some(Navigation {
+                    started_at: timestamp,
+                    waterfall: NavigationWaterfall {
+                        url: url.clone(),
+                        requests: Vec::new(),
+                    },
+                    requests: HashMap::new(),
+                });
+            }
+        }
+        let Some(navigation) = &mut self.navigation else {
+            return;
+        };
+        if let Some((index, _)) = navigation.requests.get(&request_id) {
+            navigation.waterfall.requests[*index].url = url;
+            return;
+        }
+
+        navigation.requests.insert(
+            request_id.clone(),
+            (navigation.waterfall.requests.len(), None),
+        );
+        navigation.waterfall.requests.push(RequestTiming {
+            request_id,
+            url,
+            resource_type,
+            status: None,
+            start: (timestamp - navigation.started_at) * 1000.0,
+            dns: None,
+            connect: None,
+            tls: None,
+            ttfb: None,
+            download: None,
+            total: None,
+            encoded_data_length: None,
+            failed: false,
+        });
+    }
+
+    pub(crate) fn response_received(
+        &mut self,
+        request_id: &RequestId,
+        status: u32,
+        timing: Option<&ResourceTiming>,
+    ) {
+        let Some(navigation) = &mut self.navigation else {
+            return;
+        };
+        let Some((index, headers_received_at)) = navigation.requests.get_mut(request_id) else {
+            return;
+        };
+        let request = &mut navigation.waterfall.requests[*index];
+        request.status = Some(status);
+        let Some(timing) = timing else {
+            return;
+        };
+
+        request.dns = phase(timing.dns_start, timing.dns_end);
+        request.connect = phase(timing.connect_start, timing.connect_end);
+        request.tls = phase(timing.ssl_start, timing.ssl_end);
+        let headers_start = if timing.receive_headers_start > 0.0 {
+            timing.receive_headers_start
+        } else {
+            timing.receive_headers_end
+        };
+        request.ttfb = phase(timing.send_end, headers_start);
+        *headers_received_at = Some(timing.request_time + timing.receive_headers_end / 1000.0);
+    }
+
+    pub(crate) fn request_finished(
+        &mut self,
+        request_id: &RequestId,
+        timestamp: f64,
+        encoded_data_length: Option<f64>,
+    ) {
+        let Some(navigation) = &mut self.navigation else {
+            return;
+        };
+        let Some((index, headers_received_at)) = navigation.requests.get(request_id) else {
+            return;
+        };
+        let request = &mut navigation.waterfall.requests[*index];
+        let end = (timestamp - navigation.started_at) * 1000.0;
+        request.total = Some(end - request.start);
+        request.download = headers_received_at.map(|at| (timestamp - at).max(0.0) * 1000.0);
+        request.encoded_data_length = encoded_data_length;
+        request.failed = encoded_data_length.is_none();
+    }
+
+    pub(crate) fn waterfall(&self) -> Option<NavigationWaterfall> {
+        self.navigation
+            .as_ref()
+            .map(|navigation| navigation.waterfall.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn breaks_requests_down_into_phases() {
+        let mut recorder = WaterfallRecorder::default();
+        recorder.request_started("0".into(), "https://a.test/".into(), None, 99.0, false);
+        assert!(recorder.waterfall().is_none());
+
+        recorder.request_started(
+            "1".into(),
+            "http://a.test/".into(),
+            Some(ResourceType::Document),
+            100.0,
+            true,
+        );
+        // Redirected to HTTPS
+        recorder.request_started(
+            "1".into(),
+            "https://a.test/".into(),
+            Some(ResourceType::Document),
+            100.05,
+            true,
+        );
+        let timing: ResourceTiming = serde_json::from_value(json!({
+            "requestTime": 100.1,
+            "dnsStart": 0.0,
+            "dnsEnd": 10.0,
+            "connectStart": 10.0,
+            "connectEnd": 50.0,
+            "sslStart": 20.0,
+            "sslEnd": 50.0,
+            "sendStart": 50.0,
+            "sendEnd": 51.0,
+            "receiveHeadersStart": 150.0,
+            "receiveHeadersEnd": 151.0,
+        }))
+        .unwrap();
+        recorder.response_received(&"1".into(), 200, Some(&timing));
+        recorder.request_started(
+            "2".into(),
+            "https://a.test/app.js".into(),
+            Some(ResourceType::Script),
+            100.5,
+            false,
+        );
+        recorder.request_finished(&"1".into(), 100.351, Some(2048.0));
+        recorder.request_finished(&"2".into(), 100.6, None);
+
+        let waterfall = recorder.waterfall().unwrap();
+        assert_eq!("http://a.test/", waterfall.url);
+        let [document, script] = &waterfall.requests[..] else {
+            panic!("expected 2 requests, got {:?}", waterfall.requests);
+        };
+        assert_eq!("https://a.test/", document.url);
+        assert_eq!(Some(200), document.status);
+        assert_eq!(Some(10.0), document.dns);
+        assert_eq!(Some(40.0), document.connect);
+        assert_eq!(Some(30.0), document.tls);
+        assert_eq!(Some(99.0), document.ttfb);
+        assert!((document.download.unwrap() - 100.0).abs() < 1e-6);
+        assert!((script.start - 500.0).abs() < 1e-6);
+        assert!(script.failed);
+        assert_eq!(None, script.ttfb);
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::Result;
+
+use crate::logging::{info, trace};
+
+use crate::browser::transport::{SessionId, Transport};
+use crate::protocol::cdp::{
+    types::{Event, Method},
+    Runtime, Target,
+};
+
+pub use Runtime::{events::ConsoleAPICalledEventParams, ExceptionDetails};
+
+/// A dedicated worker started by a tab's page.
+///
+/// Workers are attached to through their parent tab's session, so you get them via
+/// `Tab.workers` rather than from the browser. Console messages and uncaught exceptions are
+/// recorded from the moment the worker is attached.
+pub struct Worker {
+    target_info: Target::TargetInfo,
+    parent_session_id: SessionId,
+    session_id: SessionId,
+    transport: Arc<Transport>,
+    console_messages: Arc<Mutex<Vec<ConsoleAPICalledEventParams>>>,
+    exceptions: Arc<Mutex<Vec<ExceptionDetails>>>,
+}
+
+impl Worker {
+    pub fn new(
+        target_info: Target::TargetInfo,
+        parent_session_id: SessionId,
+        session_id: SessionId,
+        transport: Arc<Transport>,
+    ) -> Result<Self> {
+        let worker = Self {
+            target_info,
+            parent_session_id,
+            session_id,
+            transport,
+            console_messages: Arc::new(Mutex::new(Vec::new())),
+            exceptions: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        worker.start_event_handler_thread();
+        worker.call_method(Runtime::Enable(None))?;
+
+        Ok(worker)
+    }
+
+    fn start_event_handler_thread(&self) {
+        let incoming_events_rx = self
+            .transport
+            .listen_to_target_events(self.session_id.clone());
+        let console_messages_mutex = Arc::clone(&self.console_messages);
+        let exceptions_mutex = Arc::clone(&self.exceptions);
+
+        thread::spawn(move || {
+            for event in incoming_events_rx {
+                match event {
+                    Event::RuntimeConsoleAPICalled(ev) => {
+                        console_messages_mutex.lock().unwrap().push(ev.params);
+                    }
+                    Event::RuntimeExceptionThrown(ev) => {
+                        exceptions_mutex
+                            .lock()
+                            .unwrap()
+                            .push(ev.params.exception_details);
+                    }
+                    _ => {
+                        let raw_event = format!("{event:?}");
+                        trace!(
+                            "Unhandled worker event: {}",
+                            raw_event.chars().take(50).collect::<String>()
+                        );
+                    }
+                }
+            }
+            info!("finished worker's event handling loop");
+        });
+    }
+
+    pub fn get_target_info(&self) -> &Target::TargetInfo {
+        &self.target_info
+    }
+
+    pub fn get_url(&self) -> &str {
+        &self.target_info.url
+    }
+
+    pub(crate) fn get_session_id(&self) -> &SessionId {
+        &self.session_id
+    }
+
+    pub fn call_method<C>(&self, method: C) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize + std::fmt::Debug,
+    {
+        trace!("Calling method on worker: {method:?}");
+        self.transport.call_method_on_child_target(
+            self.parent_session_id.clone(),
+            self.session_id.clone(),
+            method,
+        )
+    }
+
+    /// Evaluates expression on the worker's global scope.
+    pub fn evaluate(&self, expression: &str, await_promise: bool) -> Result<Runtime::RemoteObject> {
+        let result = self
+            .call_method(Runtime::Evaluate {
+                expression: expression.to_string(),
+                return_by_value: Some(false),
+                generate_preview: Some(true),
+                silent: Some(false),
+                await_promise: Some(await_promise),
+                include_command_line_api: Some(false),
+                user_gesture: Some(false),
+                object_group: None,
+                context_id: None,
+                throw_on_side_effect: None,
+                timeout: None,
+                disable_breaks: None,
+                repl_mode: None,
+                allow_unsafe_eval_blocked_by_csp: None,
+                unique_context_id: None,
+            })?
+            .result;
+        Ok(result)
+    }
+
+    /// Every `console.*` call the worker has made since it was attached.
+    pub fn get_console_messages(&self) -> Vec<ConsoleAPICalledEventParams> {
+        self.console_messages.lock().unwrap().clone()
+    }
+
+    /// Every uncaught exception the worker has thrown since it was attached.
+    pub fn get_exceptions(&self) -> Vec<ExceptionDetails> {
+        self.exceptions.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::mpsc;
+
+use crate::logging::{info, trace, warn};
+use anyhow::Result;
+
+use super::protocol_logger::SharedProtocolLogger;
+use super::recording::SharedProtocolRecorder;
+use crate::types::{parse_raw_message, Message};
+
+/// The channel a `Transport` talks to the browser over.
+///
+/// By default that's a WebSocket, but tests can give `Transport::with_connection` a fake which
+/// answers with canned responses instead of launching Chrome.
+pub trait Connection: std::fmt::Debug + Send + Sync {
+    /// Sends a (JSON-serialized) protocol message to the browser.
+    fn send_message(&self, message_text: &str) -> Result<()>;
+
+    /// Closes the connection. Implementations should call `IncomingMessages::closed` once it's
+    /// closed, whether that was asked for or not.
+    fn shutdown(&self);
+
+    /// Whether the connection translates the DevTools protocol into another one the browser
+    /// speaks, in which case the browser needn't be Chromium-based.
+    fn translates_protocol(&self) -> bool {
+        false
+    }
+}
+
+/// What a WebSocket connection hands the messages it reads to: a `Transport`'s
+/// [`IncomingMessages`], or the client of another protocol spoken over the same kind of
+/// connection, such as a `BidiSession`.
+pub trait MessageHandler: Send + 'static {
+    /// Handles a text message. Returns false if the connection should stop reading.
+    fn text(&self, message_text: &str) -> bool;
+
+    /// Handles a binary message. Returns false if the connection should stop reading.
+    fn binary(&self, payload: Vec<u8>) -> bool;
+
+    /// Called once the connection has closed.
+    fn closed(&self);
+}
+
+/// Where a [`Connection`] hands the messages it receives from the browser to its `Transport`.
+#[derive(Debug, Clone)]
+pub struct IncomingMessages {
+    messages_tx: mpsc::Sender<Message>,
+    protocol_logger: SharedProtocolLogger,
+    protocol_recorder: SharedProtocolRecorder,
+}
+
+impl IncomingMessages {
+    pub(crate) fn new(
+        messages_tx: mpsc::Sender<Message>,
+        protocol_logger: SharedProtocolLogger,
+        protocol_recorder: SharedProtocolRecorder,
+    ) -> Self {
+        Self {
+            messages_tx,
+            protocol_logger,
+            protocol_recorder,
+        }
+    }
+
+    /// Hands over a text message. Returns false once the transport has stopped listening, in
+    /// which case the connection should stop reading.
+    pub fn text(&self, message_text: &str) -> bool {
+        self.observe(message_text);
+        if let Ok(message) = parse_raw_message(message_text) {
+            self.messages_tx.send(message).is_ok()
+        } else {
+            trace!("Incoming message isn't recognised as event or method response: {message_text}");
+            true
+        }
+    }
+
+    /// Hands over a binary message. Some proxies re-frame protocol messages as binary, so those
+    /// are treated like text messages; anything else goes to `Transport::listen_to_binary_messages`.
+    pub fn binary(&self, payload: Vec<u8>) -> bool {
+        let message = std::str::from_utf8(&payload)
+            .ok()
+            .and_then(|text| Some((text, parse_raw_message(text).ok()?)));
+        match message {
+            Some((message_text, message)) => {
+                self.observe(message_text);
+                self.messages_tx.send(message).is_ok()
+            }
+            None => self.messages_tx.send(Message::Binary(payload)).is_ok(),
+        }
+    }
+
+    /// Tells the transport the connection has closed.
+    pub fn closed(&self) {
+        info!("Sending shutdown message to message handling loop");
+        if self.messages_tx.send(Message::ConnectionShutdown).is_err() {
+            warn!("Couldn't send message to transport loop telling it to shut down");
+        }
+    }
+
+    fn observe(&self, message_text: &str) {
+        if let Some(logger) = self.protocol_logger.read().unwrap().as_ref() {
+            logger.log_incoming(message_text);
+        }
+        if let Some(recorder) = self.protocol_recorder.read().unwrap().as_ref() {
+            recorder.record_received(message_text);
+        }
+    }
+}
+
+impl MessageHandler for IncomingMessages {
+    fn text(&self, message_text: &str) -> bool {
+        IncomingMessages::text(self, message_text)
+    }
+
+    fn binary(&self, payload: Vec<u8>) -> bool {
+        IncomingMessages::binary(self, payload)
+    }
+
+    fn closed(&self) {
+        IncomingMessages::closed(self);
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::logging::{error, warn};
+use thiserror::Error;
+
+use crate::browser::metrics::CallCounters;
+use crate::protocol::cdp::types::Event;
+
+/// Events are grouped into classes so that the noisy ones can be capped without risking the
+/// ones the `Tab` API relies on, like lifecycle events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventClass {
+    /// The events Chrome sends for every request a page makes, which there can be thousands of.
+    /// `Fetch.requestPaused` isn't one of them, as the request stays paused until it's handled.
+    Network,
+    /// `Runtime.consoleAPICalled` and `Log.entryAdded`.
+    Console,
+    Other,
+}
+
+impl EventClass {
+    pub fn of(event: &Event) -> Self {
+        match event {
+            Event::NetworkRequestWillBeSent(_)
+            | Event::NetworkRequestWillBeSentExtraInfo(_)
+            | Event::NetworkResponseReceived(_)
+            | Event::NetworkResponseReceivedExtraInfo(_)
+            | Event::NetworkDataReceived(_)
+            | Event::NetworkLoadingFinished(_)
+            | Event::NetworkLoadingFailed(_)
+            | Event::NetworkRequestServedFromCache(_)
+            | Event::NetworkResourceChangedPriority(_) => Self::Network,
+            Event::RuntimeConsoleAPICalled(_) | Event::LogEntryAdded(_) => Self::Console,
+            _ => Self::Other,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Self::Network => 0,
+            Self::Console => 1,
+            Self::Other => 2,
+        }
+    }
+}
+
+/// What happens to an event which arrives while its listener already has as many events of its
+/// class waiting as it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drops the oldest waiting event of the same class to make room.
+    DropOldest,
+    /// Holds up the connection until the listener makes room. As no responses to calls get
+    /// through in the meantime, this gives up (and drops the event) after the transport's idle
+    /// timeout, rather than deadlocking a listener which makes calls as it handles events.
+    Block,
+    /// Rejects the new event, logging an error.
+    Error,
+}
+
+/// How many events of a class may be waiting for a listener, and what happens when there are more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLimit {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+/// Limits on the events waiting for each listener (i.e. each tab, and the browser itself), per
+/// class. Classes without a limit are unbounded, which is the default.
+///
+/// ```rust
+/// use headless_chrome::browser::transport::{ChannelLimit, EventBackpressure, OverflowPolicy};
+///
+/// let backpressure = EventBackpressure {
+///     network: Some(ChannelLimit {
+///         capacity: 1000,
+///         overflow: OverflowPolicy::DropOldest,
+///     }),
+///     ..EventBackpressure::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventBackpressure {
+    pub network: Option<ChannelLimit>,
+    pub console: Option<ChannelLimit>,
+    pub other: Option<ChannelLimit>,
+}
+
+impl EventBackpressure {
+    fn limit(&self, class: EventClass) -> Option<ChannelLimit> {
+        match class {
+            EventClass::Network => self.network,
+            EventClass::Console => self.console,
+            EventClass::Other => self.other,
+        }
+    }
+}
+
+pub(crate) type SharedBackpressure = Arc<RwLock<EventBackpressure>>;
+
+#[derive(Default)]
+struct Queue {
+    events: VecDeque<(EventClass, Event)>,
+    /// How many of `events` are of each class, by `EventClass::index`.
+    counts: [usize; 3],
+    dropped: u64,
+    sender_closed: bool,
+    receiver_closed: bool,
+}
+
+impl Queue {
+    fn pop(&mut self) -> Option<Event> {
+        let (class, event) = self.events.pop_front()?;
+        self.counts[class.index()] -= 1;
+        Some(event)
+    }
+
+    fn drop_oldest(&mut self, class: EventClass) {
+        if let Some(pos) = self.events.iter().position(|(c, _)| *c == class) {
+            self.events.remove(pos);
+            self.counts[class.index()] -= 1;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Channel {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+/// Creates a channel whose capacity is governed by `backpressure` as it is when each event is
+/// sent, so that changing it affects existing listeners too.
+pub(crate) fn event_channel(
+    backpressure: SharedBackpressure,
+    call_counters: Arc<CallCounters>,
+    block_timeout: Duration,
+) -> (EventSender, EventReceiver) {
+    let channel = Arc::new(Channel::default());
+    (
+        EventSender {
+            channel: Arc::clone(&channel),
+            backpressure,
+            call_counters,
+            block_timeout,
+        },
+        EventReceiver { channel },
+    )
+}
+
+#[derive(Debug, Error)]
+#[error("The listener for this event has gone away")]
+pub(crate) struct ListenerGone {}
+
+/// The transport's end of a listener's channel.
+pub(crate) struct EventSender {
+    channel: Arc<Channel>,
+    backpressure: SharedBackpressure,
+    call_counters: Arc<CallCounters>,
+    block_timeout: Duration,
+}
+
+impl std::fmt::Debug for EventSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSender").finish_non_exhaustive()
+    }
+}
+
+impl EventSender {
+    /// Queues `event` for the listener, unless it's gone away.
+    pub(crate) fn send(&self, event: Event) -> Result<(), ListenerGone> {
+        let class = EventClass::of(&event);
+        let limit = self.backpressure.read().unwrap().limit(class);
+        let mut queue = self.channel.queue.lock().unwrap();
+        if queue.receiver_closed {
+            return Err(ListenerGone {});
+        }
+
+        if let Some(limit) = limit {
+            if queue.counts[class.index()] >= limit.capacity {
+                match limit.overflow {
+                    OverflowPolicy::DropOldest => {
+                        queue.drop_oldest(class);
+                        self.dropped(&mut queue);
+                    }
+                    OverflowPolicy::Block => {
+                        let deadline = Instant::now() + self.block_timeout;
+                        while queue.counts[class.index()] >= limit.capacity
+                            && !queue.receiver_closed
+                        {
+                            let now = Instant::now();
+                            if now >= deadline {
+                                warn!("Listener didn't make room for {class:?} event in time, dropping it");
+                                self.dropped(&mut queue);
+                                return Ok(());
+                            }
+                            queue = self
+                                .channel
+                                .changed
+                                .wait_timeout(queue, deadline - now)
+                                .unwrap()
+                                .0;
+                        }
+                        if queue.receiver_closed {
+                            return Err(ListenerGone {});
+                        }
+                    }
+                    OverflowPolicy::Error => {
+                        error!(
+                            "Listener already has {} {class:?} events waiting, rejecting another",
+                            limit.capacity
+                        );
+                        self.dropped(&mut queue);
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        queue.counts[class.index()] += 1;
+        queue.events.push_back((class, event));
+        self.channel.changed.notify_all();
+        Ok(())
+    }
+
+    fn dropped(&self, queue: &mut Queue) {
+        queue.dropped += 1;
+        self.call_counters.event_dropped();
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.channel.queue.lock().unwrap().sender_closed = true;
+        self.channel.changed.notify_all();
+    }
+}
+
+/// Receives the events sent to a tab or the browser, much like a `std::sync::mpsc::Receiver`,
+/// but bounded according to the transport's `EventBackpressure`.
+pub struct EventReceiver {
+    channel: Arc<Channel>,
+}
+
+impl std::fmt::Debug for EventReceiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventReceiver").finish_non_exhaustive()
+    }
+}
+
+impl EventReceiver {
+    pub fn recv(&self) -> Result<Event, RecvError> {
+        let mut queue = self.channel.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.pop() {
+                self.channel.changed.notify_all();
+                return Ok(event);
+            }
+            if queue.sender_closed {
+                return Err(RecvError);
+            }
+            queue = self.channel.changed.wait(queue).unwrap();
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Event, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.channel.queue.lock().unwrap();
+        loop {
+            if let Some(event) = queue.pop() {
+                self.channel.changed.notify_all();
+                return Ok(event);
+            }
+            if queue.sender_closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            queue = self
+                .channel
+                .changed
+                .wait_timeout(queue, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<Event, TryRecvError> {
+        let mut queue = self.channel.queue.lock().unwrap();
+        match queue.pop() {
+            Some(event) => {
+                self.channel.changed.notify_all();
+                Ok(event)
+            }
+            None if queue.sender_closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Blocks waiting for each event in turn, until the transport goes away.
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// How many events meant for this receiver were dropped because of the `EventBackpressure`.
+    pub fn dropped(&self) -> u64 {
+        self.channel.queue.lock().unwrap().dropped
+    }
+}
+
+impl IntoIterator for EventReceiver {
+    type Item = Event;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter { receiver: self }
+    }
+}
+
+pub struct IntoIter {
+    receiver: EventReceiver,
+}
+
+impl Iterator for IntoIter {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        self.channel.queue.lock().unwrap().receiver_closed = true;
+        self.channel.changed.notify_all();
+    }
+}
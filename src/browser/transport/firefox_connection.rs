@@ -0,0 +1,932 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::logging::{trace, warn};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::{json, Map, Value as Json};
+
+use super::connection::{Connection, IncomingMessages};
+use crate::browser::bidi::{BidiError, BidiEvent, BidiSession};
+
+/// The protocol version reported for Firefox, which passes `Browser`'s compatibility check.
+const TRANSLATED_PROTOCOL_VERSION: &str = "1.3";
+
+/// CDP's error codes for unknown methods and for methods which failed.
+const METHOD_NOT_FOUND: i64 = -32601;
+const SERVER_ERROR: i64 = -32000;
+
+const CM_PER_INCH: f64 = 2.54;
+
+/// The DevTools methods sent to Firefox as their WebDriver BiDi equivalents.
+const TRANSLATED_METHODS: [&str; 20] = [
+    "Browser.getVersion",
+    "Emulation.clearDeviceMetricsOverride",
+    "Emulation.setDeviceMetricsOverride",
+    "Page.bringToFront",
+    "Page.captureScreenshot",
+    "Page.handleJavaScriptDialog",
+    "Page.navigate",
+    "Page.printToPDF",
+    "Page.reload",
+    "Runtime.evaluate",
+    "Target.activateTarget",
+    "Target.attachToTarget",
+    "Target.closeTarget",
+    "Target.createBrowserContext",
+    "Target.createTarget",
+    "Target.disposeBrowserContext",
+    "Target.getTargetInfo",
+    "Target.getTargets",
+    "Target.sendMessageToTarget",
+    "Target.setDiscoverTargets",
+];
+
+/// The methods acknowledged without doing anything, besides enabling and disabling the domains
+/// in `ACKNOWLEDGED_DOMAINS`.
+const ACKNOWLEDGED_METHODS: [&str; 2] =
+    ["Page.setLifecycleEventsEnabled", "Target.detachFromTarget"];
+
+/// The domains a `Tab` enables when it's created, whose enable and disable methods are
+/// acknowledged without doing anything.
+const ACKNOWLEDGED_DOMAINS: [&str; 5] = ["CSS", "DOM", "Inspector", "Page", "Runtime"];
+
+/// What [`FirefoxConnection`] does with a DevTools method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Sent to Firefox as the equivalent WebDriver BiDi commands.
+    Translated,
+    /// Answered straight away without doing anything, e.g. enabling a domain whose events are
+    /// either translated anyway (`Page.enable`) or never sent (`DOM.enable`).
+    Acknowledged,
+    /// Answered with a "method not found" error.
+    Unsupported,
+}
+
+#[derive(Deserialize)]
+struct Command {
+    id: u64,
+    method: String,
+    #[serde(default)]
+    params: Json,
+}
+
+/// A top-level browsing context, i.e. what the DevTools protocol calls a page target.
+#[derive(Debug, Clone, Default)]
+struct Page {
+    url: String,
+    title: String,
+    user_context: Option<String>,
+    /// Whether `Target.targetCreated` has been sent for it.
+    announced: bool,
+}
+
+/// **Experimental**: talks to Firefox over [WebDriver BiDi](https://w3c.github.io/webdriver-bidi/),
+/// translating the DevTools methods and events it can so that `Browser` and `Tab` work on top of
+/// it, e.g. for running the same smoke tests against Firefox. See `Browser::connect_firefox`.
+///
+/// Only a core set of methods is translated: discovering, creating and closing tabs, navigating
+/// (with the `Page` lifecycle events `wait_until_navigated` relies on), evaluating JavaScript,
+/// screenshots, printing to PDF, dialogs and the viewport size. See `capability` for which
+/// methods are supported; the others fail with a "method not found" `RemoteError`, so anything
+/// which needs them (e.g. finding elements, which uses the `DOM` domain) doesn't work yet.
+pub struct FirefoxConnection {
+    translator: Arc<Translator>,
+}
+
+impl std::fmt::Debug for FirefoxConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "FirefoxConnection {{}}")
+    }
+}
+
+impl FirefoxConnection {
+    /// Connects to Firefox's BiDi endpoint at `ws_url` (`ws://127.0.0.1:9222/session` when started
+    /// with `--remote-debugging-port=9222`) and starts a session. `timeout` is how long to wait
+    /// for the response to each BiDi command.
+    pub fn connect(ws_url: &str, timeout: Duration, incoming: IncomingMessages) -> Result<Self> {
+        let closed_incoming = incoming.clone();
+        let session = BidiSession::connect_with_close_handler(ws_url, timeout, move || {
+            closed_incoming.closed();
+        })?;
+        let capabilities = session.new_session()?["capabilities"].clone();
+        let mut browser_name = capabilities["browserName"]
+            .as_str()
+            .unwrap_or("firefox")
+            .chars();
+        let mut product: String = browser_name
+            .next()
+            .into_iter()
+            .flat_map(char::to_uppercase)
+            .chain(browser_name)
+            .collect();
+        if let Some(version) = capabilities["browserVersion"].as_str() {
+            product = format!("{product}/{version}");
+        }
+
+        let translator = Arc::new(Translator {
+            session,
+            incoming,
+            product,
+            user_agent: capabilities["userAgent"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            discovering: AtomicBool::new(false),
+            pages: Mutex::new(HashMap::new()),
+        });
+        // Weak, as the session would otherwise keep its own listener alive
+        let listening_translator = Arc::downgrade(&translator);
+        translator
+            .session
+            .add_event_listener(Arc::new(move |event: &BidiEvent| {
+                if let Some(translator) = Weak::upgrade(&listening_translator) {
+                    translator.handle_event(event);
+                }
+            }));
+        translator.session.subscribe(&[
+            "browsingContext.contextCreated",
+            "browsingContext.contextDestroyed",
+            "browsingContext.navigationStarted",
+            "browsingContext.domContentLoaded",
+            "browsingContext.load",
+            "browsingContext.userPromptOpened",
+        ])?;
+
+        Ok(Self { translator })
+    }
+
+    /// Whether `method` (e.g. `Page.navigate`) is translated, acknowledged without doing
+    /// anything, or unsupported.
+    pub fn capability(method: &str) -> Capability {
+        if TRANSLATED_METHODS.contains(&method) {
+            return Capability::Translated;
+        }
+        if ACKNOWLEDGED_METHODS.contains(&method) {
+            return Capability::Acknowledged;
+        }
+        match method.split_once('.') {
+            Some((domain, "enable" | "disable")) if ACKNOWLEDGED_DOMAINS.contains(&domain) => {
+                Capability::Acknowledged
+            }
+            _ => Capability::Unsupported,
+        }
+    }
+}
+
+impl Connection for FirefoxConnection {
+    fn send_message(&self, message_text: &str) -> Result<()> {
+        let command: Command = serde_json::from_str(message_text)?;
+        let translator = Arc::clone(&self.translator);
+        // Some commands take a while (e.g. awaiting a promise), so each gets its own thread
+        // rather than holding up the others
+        std::thread::spawn(move || translator.handle_command(command, None));
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        self.translator.session.shutdown();
+    }
+
+    fn translates_protocol(&self) -> bool {
+        true
+    }
+}
+
+struct Translator {
+    session: BidiSession,
+    incoming: IncomingMessages,
+    product: String,
+    user_agent: String,
+    /// Whether `Target.setDiscoverTargets` has been called, after which pages are announced.
+    discovering: AtomicBool,
+    /// By BiDi context id, which doubles as the page's target and session id.
+    pages: Mutex<HashMap<String, Page>>,
+}
+
+impl Translator {
+    /// Answers a command sent to the browser, or to the page with `session_id`.
+    fn handle_command(&self, command: Command, session_id: Option<&str>) {
+        if command.method == "Target.sendMessageToTarget" {
+            self.respond(command.id, session_id, Ok(json!({})));
+            let Some(session_id) = command.params["sessionId"].as_str() else {
+                warn!("Got a message for a target without its session id");
+                return;
+            };
+            match serde_json::from_str(command.params["message"].as_str().unwrap_or_default()) {
+                Ok(target_command) => self.handle_command(target_command, Some(session_id)),
+                Err(err) => warn!("Couldn't parse a message for a target: {err}"),
+            }
+            return;
+        }
+
+        let result = match FirefoxConnection::capability(&command.method) {
+            Capability::Translated => self
+                .translate(&command.method, &command.params, session_id)
+                .map_err(|err| json!({ "code": SERVER_ERROR, "message": err.to_string() })),
+            Capability::Acknowledged => Ok(json!({})),
+            Capability::Unsupported => Err(json!({
+                "code": METHOD_NOT_FOUND,
+                "message": format!("'{}' isn't supported by the Firefox backend", command.method),
+            })),
+        };
+        self.respond(command.id, session_id, result);
+    }
+
+    fn translate(&self, method: &str, params: &Json, session_id: Option<&str>) -> Result<Json> {
+        let target_id = || {
+            params["targetId"]
+                .as_str()
+                .or(session_id)
+                .ok_or_else(|| anyhow!("'{method}' needs a target"))
+        };
+        match method {
+            "Browser.getVersion" => Ok(json!({
+                "protocolVersion": TRANSLATED_PROTOCOL_VERSION,
+                "product": self.product,
+                "revision": "",
+                "userAgent": self.user_agent,
+                "jsVersion": "",
+            })),
+            "Target.setDiscoverTargets" => {
+                let discover = params["discover"].as_bool().unwrap_or(false);
+                self.discovering.store(discover, Ordering::SeqCst);
+                if discover {
+                    let tree = self
+                        .session
+                        .call("browsingContext.getTree", json!({ "maxDepth": 0 }))?;
+                    for context in tree["contexts"].as_array().into_iter().flatten() {
+                        self.context_created(context);
+                    }
+                }
+                Ok(json!({}))
+            }
+            "Target.getTargets" => {
+                let pages = self.pages.lock().unwrap();
+                let target_infos: Vec<Json> = pages
+                    .iter()
+                    .map(|(context, page)| target_info(context, page))
+                    .collect();
+                Ok(json!({ "targetInfos": target_infos }))
+            }
+            "Target.getTargetInfo" => {
+                let context = target_id()?;
+                let pages = self.pages.lock().unwrap();
+                let page = pages
+                    .get(context)
+                    .ok_or_else(|| anyhow!("No target with id {context}"))?;
+                Ok(json!({ "targetInfo": target_info(context, page) }))
+            }
+            "Target.attachToTarget" => {
+                let context = target_id()?;
+                if !self.pages.lock().unwrap().contains_key(context) {
+                    return Err(anyhow!("No target with id {context}"));
+                }
+                Ok(json!({ "sessionId": context }))
+            }
+            "Target.createTarget" => {
+                let mut create = json!({
+                    "type": if params["newWindow"] == true { "window" } else { "tab" },
+                    "background": params["background"].as_bool().unwrap_or(false),
+                });
+                if let Some(user_context) = params["browserContextId"].as_str() {
+                    create["userContext"] = json!(user_context);
+                }
+                let created = self.session.call("browsingContext.create", create)?;
+                let context = created["context"].clone();
+                let url = params["url"].as_str().unwrap_or("about:blank");
+                if url != "about:blank" {
+                    self.session.call(
+                        "browsingContext.navigate",
+                        json!({ "context": context, "url": url, "wait": "none" }),
+                    )?;
+                }
+                Ok(json!({ "targetId": context }))
+            }
+            "Target.closeTarget" => {
+                self.session
+                    .call("browsingContext.close", json!({ "context": target_id()? }))?;
+                Ok(json!({ "success": true }))
+            }
+            "Target.activateTarget" | "Page.bringToFront" => {
+                self.session.call(
+                    "browsingContext.activate",
+                    json!({ "context": target_id()? }),
+                )?;
+                Ok(json!({}))
+            }
+            "Target.createBrowserContext" => {
+                let created = self.session.call("browser.createUserContext", json!({}))?;
+                Ok(json!({ "browserContextId": created["userContext"] }))
+            }
+            "Target.disposeBrowserContext" => {
+                self.session.call(
+                    "browser.removeUserContext",
+                    json!({ "userContext": params["browserContextId"] }),
+                )?;
+                Ok(json!({}))
+            }
+            "Page.navigate" => {
+                let context = target_id()?;
+                let navigated = self.session.call(
+                    "browsingContext.navigate",
+                    json!({ "context": context, "url": params["url"], "wait": "none" }),
+                );
+                match navigated {
+                    Ok(navigated) => {
+                        Ok(json!({ "frameId": context, "loaderId": navigated["navigation"] }))
+                    }
+                    Err(err) => match err.downcast::<BidiError>() {
+                        // Like Chrome, report navigations which failed (e.g. to resolve the
+                        // host) in the result rather than as an error
+                        Ok(err) => Ok(json!({ "frameId": context, "errorText": err.message })),
+                        Err(err) => Err(err),
+                    },
+                }
+            }
+            "Page.reload" => {
+                self.session.call(
+                    "browsingContext.reload",
+                    json!({
+                        "context": target_id()?,
+                        "ignoreCache": params["ignoreCache"].as_bool().unwrap_or(false),
+                        "wait": "none",
+                    }),
+                )?;
+                Ok(json!({}))
+            }
+            "Page.captureScreenshot" => {
+                let format = params["format"].as_str().unwrap_or("png");
+                let mut image_format = json!({ "type": format!("image/{format}") });
+                if let Some(quality) = params["quality"].as_f64() {
+                    image_format["quality"] = json!(quality / 100.0);
+                }
+                let origin = if params["captureBeyondViewport"] == true {
+                    "document"
+                } else {
+                    "viewport"
+                };
+                let mut screenshot =
+                    json!({ "context": target_id()?, "format": image_format, "origin": origin });
+                if params["clip"].is_object() {
+                    let clip = &params["clip"];
+                    screenshot["clip"] = json!({
+                        "type": "box",
+                        "x": clip["x"],
+                        "y": clip["y"],
+                        "width": clip["width"],
+                        "height": clip["height"],
+                    });
+                }
+                let captured = self
+                    .session
+                    .call("browsingContext.captureScreenshot", screenshot)?;
+                Ok(json!({ "data": captured["data"] }))
+            }
+            "Page.printToPDF" => {
+                if params["transferMode"] == "ReturnAsStream" {
+                    return Err(anyhow!("Firefox can't stream PDFs"));
+                }
+                self.print_to_pdf(target_id()?, params)
+            }
+            "Page.handleJavaScriptDialog" => {
+                let mut handle = json!({ "context": target_id()?, "accept": params["accept"] });
+                if let Some(prompt_text) = params["promptText"].as_str() {
+                    handle["userText"] = json!(prompt_text);
+                }
+                self.session
+                    .call("browsingContext.handleUserPrompt", handle)?;
+                Ok(json!({}))
+            }
+            "Emulation.setDeviceMetricsOverride" | "Emulation.clearDeviceMetricsOverride" => {
+                let width = params["width"].as_u64().unwrap_or(0);
+                let height = params["height"].as_u64().unwrap_or(0);
+                // Zeroes (or clearing the override) restore the window's own size and ratio
+                let viewport = if width == 0 || height == 0 {
+                    Json::Null
+                } else {
+                    json!({ "width": width, "height": height })
+                };
+                let device_pixel_ratio = params["deviceScaleFactor"]
+                    .as_f64()
+                    .filter(|ratio| *ratio > 0.0);
+                self.session.call(
+                    "browsingContext.setViewport",
+                    json!({
+                        "context": target_id()?,
+                        "viewport": viewport,
+                        "devicePixelRatio": device_pixel_ratio,
+                    }),
+                )?;
+                Ok(json!({}))
+            }
+            "Runtime.evaluate" => self.evaluate(target_id()?, params),
+            _ => Err(anyhow!("'{method}' isn't translated")),
+        }
+    }
+
+    fn evaluate(&self, context: &str, params: &Json) -> Result<Json> {
+        let evaluated = self.session.call(
+            "script.evaluate",
+            json!({
+                "expression": params["expression"],
+                "target": { "context": context },
+                "awaitPromise": params["awaitPromise"].as_bool().unwrap_or(false),
+                "resultOwnership": "none",
+                "userActivation": params["userGesture"].as_bool().unwrap_or(false),
+            }),
+        )?;
+        let return_by_value = params["returnByValue"].as_bool().unwrap_or(false);
+
+        if evaluated["type"] == "exception" {
+            let details = &evaluated["exceptionDetails"];
+            let mut exception = remote_object(&details["exception"], return_by_value);
+            exception["description"] = details["text"].clone();
+            return Ok(json!({
+                "result": exception,
+                "exceptionDetails": {
+                    "exceptionId": 1,
+                    "text": "Uncaught",
+                    "lineNumber": details["lineNumber"].as_u64().unwrap_or(0),
+                    "columnNumber": details["columnNumber"].as_u64().unwrap_or(0),
+                    "exception": exception,
+                },
+            }));
+        }
+        Ok(json!({ "result": remote_object(&evaluated["result"], return_by_value) }))
+    }
+
+    fn print_to_pdf(&self, context: &str, params: &Json) -> Result<Json> {
+        let centimeters = |name: &str| params[name].as_f64().map(|inches| inches * CM_PER_INCH);
+        let mut print = json!({
+            "context": context,
+            "background": params["printBackground"].as_bool().unwrap_or(false),
+            "orientation": if params["landscape"] == true { "landscape" } else { "portrait" },
+        });
+        if let Some(scale) = params["scale"].as_f64() {
+            print["scale"] = json!(scale);
+        }
+        let mut page = Map::new();
+        for (cdp_name, bidi_name) in [("paperWidth", "width"), ("paperHeight", "height")] {
+            if let Some(length) = centimeters(cdp_name) {
+                page.insert(bidi_name.to_string(), json!(length));
+            }
+        }
+        print["page"] = Json::Object(page);
+        let mut margin = Map::new();
+        for (cdp_name, bidi_name) in [
+            ("marginTop", "top"),
+            ("marginBottom", "bottom"),
+            ("marginLeft", "left"),
+            ("marginRight", "right"),
+        ] {
+            if let Some(length) = centimeters(cdp_name) {
+                margin.insert(bidi_name.to_string(), json!(length));
+            }
+        }
+        print["margin"] = Json::Object(margin);
+        if let Some(page_ranges) = params["pageRanges"].as_str() {
+            let page_ranges: Vec<&str> = page_ranges
+                .split(',')
+                .map(str::trim)
+                .filter(|range| !range.is_empty())
+                .collect();
+            print["pageRanges"] = json!(page_ranges);
+        }
+
+        let printed = self.session.call("browsingContext.print", print)?;
+        Ok(json!({ "data": printed["data"] }))
+    }
+
+    /// Translates the BiDi events which have DevTools equivalents.
+    fn handle_event(&self, event: &BidiEvent) {
+        let params = &event.params;
+        let context = params["context"].as_str().unwrap_or_default();
+        match event.method.as_str() {
+            "browsingContext.contextCreated" => self.context_created(params),
+            "browsingContext.contextDestroyed" => {
+                let page = self.pages.lock().unwrap().remove(context);
+                if page.is_some_and(|page| page.announced) {
+                    self.send(
+                        None,
+                        json!({
+                            "method": "Target.targetDestroyed",
+                            "params": { "targetId": context },
+                        }),
+                    );
+                }
+            }
+            "browsingContext.navigationStarted" => {
+                self.lifecycle_events(context, params, &["init"]);
+            }
+            "browsingContext.domContentLoaded" => {
+                self.frame_navigated(context, params);
+                self.lifecycle_events(context, params, &["DOMContentLoaded"]);
+            }
+            "browsingContext.load" => {
+                self.update_title(context);
+                self.lifecycle_events(
+                    context,
+                    params,
+                    &["load", "networkAlmostIdle", "networkIdle"],
+                );
+            }
+            "browsingContext.userPromptOpened" => {
+                let Some(url) = self.page(context).map(|page| page.url) else {
+                    return;
+                };
+                self.send(
+                    Some(context),
+                    json!({
+                        "method": "Page.javascriptDialogOpening",
+                        "params": {
+                            "url": url,
+                            "frameId": context,
+                            "message": params["message"],
+                            "type": params["type"],
+                            "hasBrowserHandler": false,
+                            "defaultPrompt": params["defaultValue"],
+                        },
+                    }),
+                );
+            }
+            _ => trace!("Not translating BiDi event {}", event.method),
+        }
+    }
+
+    /// Records a new browsing context, announcing it if it's a page and targets are being
+    /// discovered. `info` is a BiDi `browsingContext.Info`.
+    fn context_created(&self, info: &Json) {
+        // Frames are part of their page, as far as the DevTools protocol is concerned
+        if !info["parent"].is_null() {
+            return;
+        }
+        let Some(context) = info["context"].as_str() else {
+            return;
+        };
+        let mut pages = self.pages.lock().unwrap();
+        let page = pages.entry(context.to_string()).or_insert_with(|| Page {
+            url: info["url"].as_str().unwrap_or("about:blank").to_string(),
+            user_context: info["userContext"]
+                .as_str()
+                .filter(|user_context| *user_context != "default")
+                .map(String::from),
+            ..Page::default()
+        });
+        if self.discovering.load(Ordering::SeqCst) && !page.announced {
+            page.announced = true;
+            self.send(
+                None,
+                json!({
+                    "method": "Target.targetCreated",
+                    "params": { "targetInfo": target_info(context, page) },
+                }),
+            );
+        }
+    }
+
+    fn frame_navigated(&self, context: &str, params: &Json) {
+        let url = params["url"].as_str().unwrap_or_default();
+        if !self.update_page(context, |page| page.url = url.to_string()) {
+            return;
+        }
+        let security_origin = url::Url::parse(url)
+            .map(|url| url.origin().ascii_serialization())
+            .unwrap_or_default();
+        let secure_context_type = if url.starts_with("https:") {
+            "Secure"
+        } else {
+            "InsecureScheme"
+        };
+        self.send(
+            Some(context),
+            json!({
+                "method": "Page.frameNavigated",
+                "params": {
+                    "frame": {
+                        "id": context,
+                        "loaderId": params["navigation"].as_str().unwrap_or_default(),
+                        "url": url,
+                        "domainAndRegistry": "",
+                        "securityOrigin": security_origin,
+                        "mimeType": "text/html",
+                        "secureContextType": secure_context_type,
+                        "crossOriginIsolatedContextType": "NotIsolated",
+                        "gatedAPIFeatures": [],
+                    },
+                    "type": "Navigation",
+                },
+            }),
+        );
+    }
+
+    /// Looks the page's title up, as BiDi has no event for it changing.
+    fn update_title(&self, context: &str) {
+        let title = match self.session.call(
+            "script.evaluate",
+            json!({
+                "expression": "document.title",
+                "target": { "context": context },
+                "awaitPromise": false,
+            }),
+        ) {
+            Ok(evaluated) => evaluated["result"]["value"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            Err(err) => {
+                trace!("Couldn't get the title of {context}: {err}");
+                return;
+            }
+        };
+        self.update_page(context, |page| page.title = title);
+    }
+
+    /// Changes the page with the id `context`, announcing the change with
+    /// `Target.targetInfoChanged` if the page has been announced. Returns whether it's a page.
+    fn update_page<F>(&self, context: &str, update: F) -> bool
+    where
+        F: FnOnce(&mut Page),
+    {
+        let mut pages = self.pages.lock().unwrap();
+        let Some(page) = pages.get_mut(context) else {
+            return false;
+        };
+        update(page);
+        if page.announced {
+            self.send(
+                None,
+                json!({
+                    "method": "Target.targetInfoChanged",
+                    "params": { "targetInfo": target_info(context, page) },
+                }),
+            );
+        }
+        true
+    }
+
+    fn page(&self, context: &str) -> Option<Page> {
+        self.pages.lock().unwrap().get(context).cloned()
+    }
+
+    fn lifecycle_events(&self, context: &str, params: &Json, names: &[&str]) {
+        if self.page(context).is_none() {
+            return;
+        }
+        // BiDi timestamps are in milliseconds, CDP's in seconds
+        let timestamp = params["timestamp"].as_f64().unwrap_or_default() / 1000.0;
+        for name in names {
+            self.send(
+                Some(context),
+                json!({
+                    "method": "Page.lifecycleEvent",
+                    "params": {
+                        "frameId": context,
+                        "loaderId": params["navigation"].as_str().unwrap_or_default(),
+                        "name": name,
+                        "timestamp": timestamp,
+                    },
+                }),
+            );
+        }
+    }
+
+    fn respond(&self, id: u64, session_id: Option<&str>, result: Result<Json, Json>) {
+        let response = match result {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(error) => json!({ "id": id, "error": error }),
+        };
+        self.send(session_id, response);
+    }
+
+    /// Hands a message to the transport, wrapped in `Target.receivedMessageFromTarget` if it's
+    /// from a page.
+    fn send(&self, session_id: Option<&str>, message: Json) {
+        let message = match session_id {
+            None => message,
+            Some(session_id) => json!({
+                "method": "Target.receivedMessageFromTarget",
+                "params": {
+                    "sessionId": session_id,
+                    "message": message.to_string(),
+                    "targetId": session_id,
+                },
+            }),
+        };
+        self.incoming.text(&message.to_string());
+    }
+}
+
+fn target_info(context: &str, page: &Page) -> Json {
+    json!({
+        "targetId": context,
+        "type": "page",
+        "title": page.title,
+        "url": page.url,
+        "attached": false,
+        "canAccessOpener": false,
+        "browserContextId": page.user_context,
+    })
+}
+
+/// Translates a BiDi `script.RemoteValue` into a CDP `Runtime.RemoteObject`. Objects only get a
+/// `value` if `return_by_value`, as they'd get an `objectId` instead from Chrome, which BiDi has
+/// no equivalent of here.
+fn remote_object(value: &Json, return_by_value: bool) -> Json {
+    let kind = value["type"].as_str().unwrap_or("undefined");
+    match kind {
+        "undefined" => json!({ "type": "undefined" }),
+        "null" => json!({ "type": "object", "subtype": "null", "value": null }),
+        "string" | "boolean" => json!({ "type": kind, "value": value["value"] }),
+        "number" => match &value["value"] {
+            // NaN, -0 and the infinities, which JSON can't represent
+            Json::String(special) => json!({
+                "type": "number",
+                "unserializableValue": special,
+                "description": special,
+            }),
+            number => {
+                json!({ "type": "number", "value": number, "description": number.to_string() })
+            }
+        },
+        "bigint" => {
+            let digits = value["value"].as_str().unwrap_or("0");
+            json!({
+                "type": "bigint",
+                "unserializableValue": format!("{digits}n"),
+                "description": format!("{digits}n"),
+            })
+        }
+        "symbol" | "function" => json!({ "type": kind }),
+        _ => {
+            let (subtype, class_name) = match kind {
+                "array" => (Some("array"), "Array"),
+                "nodelist" => (Some("array"), "NodeList"),
+                "htmlcollection" => (Some("array"), "HTMLCollection"),
+                "node" => (Some("node"), "Node"),
+                "window" => (None, "Window"),
+                "regexp" => (Some("regexp"), "RegExp"),
+                "date" => (Some("date"), "Date"),
+                "map" => (Some("map"), "Map"),
+                "set" => (Some("set"), "Set"),
+                "weakmap" => (Some("weakmap"), "WeakMap"),
+                "weakset" => (Some("weakset"), "WeakSet"),
+                "generator" => (Some("generator"), "Generator"),
+                "error" => (Some("error"), "Error"),
+                "proxy" => (Some("proxy"), "Proxy"),
+                "promise" => (Some("promise"), "Promise"),
+                "typedarray" => (Some("typedarray"), "TypedArray"),
+                "arraybuffer" => (Some("arraybuffer"), "ArrayBuffer"),
+                _ => (None, "Object"),
+            };
+            let mut object = json!({ "type": "object", "className": class_name });
+            if let Some(subtype) = subtype {
+                object["subtype"] = json!(subtype);
+            }
+            if return_by_value {
+                object["value"] = json_value(value);
+            }
+            object
+        }
+    }
+}
+
+/// The JSON a BiDi `script.RemoteValue` stands for, like Chrome's `returnByValue` gives.
+fn json_value(value: &Json) -> Json {
+    let entries = || value["value"].as_array().into_iter().flatten();
+    match value["type"].as_str() {
+        Some("string" | "boolean") => value["value"].clone(),
+        Some("number") if value["value"].is_number() => value["value"].clone(),
+        Some("array" | "set" | "nodelist" | "htmlcollection") => {
+            Json::Array(entries().map(json_value).collect())
+        }
+        Some("object" | "map") => Json::Object(
+            entries()
+                .map(|entry| {
+                    let key = match &entry[0] {
+                        Json::String(key) => key.clone(),
+                        key => json_value(key).to_string(),
+                    };
+                    (key, json_value(&entry[1]))
+                })
+                .collect(),
+        ),
+        Some("date" | "regexp" | "error" | "weakmap" | "weakset" | "promise") => {
+            Json::Object(Map::new())
+        }
+        _ => Json::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::protocol::cdp::DOM;
+    use crate::Browser;
+
+    /// A Firefox-like BiDi endpoint with a single page, which loads whatever it's navigated to
+    /// and evaluates `document.title` to "Fake" and anything else to 2.
+    fn fake_firefox() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            while let Ok(tungstenite::protocol::Message::Text(text)) = socket.read_message() {
+                let command: Json = serde_json::from_str(&text).unwrap();
+                let params = &command["params"];
+                let event = |method: &str| {
+                    json!({"type": "event", "method": method, "params": {
+                        "context": "page", "navigation": "nav", "url": params["url"], "timestamp": 1000,
+                    }})
+                };
+                let success = |result: Json| json!({"type": "success", "id": command["id"], "result": result});
+                let replies = match command["method"].as_str().unwrap() {
+                    "session.new" => vec![success(json!({"sessionId": "s", "capabilities": {
+                        "browserName": "firefox", "browserVersion": "130.0", "userAgent": "Fake Firefox",
+                    }}))],
+                    "browsingContext.getTree" => vec![success(json!({"contexts": [
+                        {"context": "page", "url": "about:blank", "children": [], "parent": null, "userContext": "default"},
+                    ]}))],
+                    "browsingContext.navigate" => vec![
+                        event("browsingContext.navigationStarted"),
+                        success(json!({"navigation": "nav", "url": params["url"]})),
+                        event("browsingContext.domContentLoaded"),
+                        event("browsingContext.load"),
+                    ],
+                    "script.evaluate" => {
+                        let result = if params["expression"] == "document.title" {
+                            json!({"type": "string", "value": "Fake"})
+                        } else {
+                            json!({"type": "number", "value": 2})
+                        };
+                        vec![success(
+                            json!({"type": "success", "result": result, "realm": "r"}),
+                        )]
+                    }
+                    _ => vec![success(json!({}))],
+                };
+                for reply in replies {
+                    let frame = tungstenite::protocol::Message::Text(reply.to_string());
+                    socket.write_message(frame).unwrap();
+                }
+            }
+        });
+        format!("ws://127.0.0.1:{port}/session")
+    }
+
+    #[test]
+    fn drives_tabs_over_bidi() {
+        let browser = Browser::connect_firefox(fake_firefox(), Default::default()).unwrap();
+        assert_eq!("Firefox/130.0", browser.get_version().unwrap().product);
+
+        let tab = browser.wait_for_initial_tab().unwrap();
+        tab.navigate_to("http://example.test/")
+            .unwrap()
+            .wait_until_navigated()
+            .unwrap();
+        assert_eq!(Some(json!(2)), tab.evaluate("1 + 1", false).unwrap().value);
+        crate::util::Wait::with_timeout(Duration::from_secs(5))
+            .until(|| (tab.get_title().unwrap() == "Fake").then_some(()))
+            .unwrap();
+        assert_eq!("http://example.test/", tab.get_url());
+
+        let error = tab.call_method(DOM::GetDocument {
+            depth: None,
+            pierce: None,
+        });
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn translates_remote_values() {
+        let object = json!({"type": "object", "value": [
+            ["answer", {"type": "number", "value": 42}],
+            ["items", {"type": "array", "value": [{"type": "string", "value": "a"}, {"type": "null"}]}],
+        ]});
+        assert_eq!(
+            json!({"type": "object", "className": "Object", "value": {"answer": 42, "items": ["a", null]}}),
+            remote_object(&object, true)
+        );
+        assert_eq!(
+            json!({"type": "object", "className": "Object"}),
+            remote_object(&object, false)
+        );
+        assert_eq!(
+            json!({"type": "number", "unserializableValue": "NaN", "description": "NaN"}),
+            remote_object(&json!({"type": "number", "value": "NaN"}), false)
+        );
+        assert_eq!(
+            Capability::Acknowledged,
+            FirefoxConnection::capability("DOM.enable")
+        );
+        assert_eq!(
+            Capability::Unsupported,
+            FirefoxConnection::capability("DOM.getDocument")
+        );
+    }
+}
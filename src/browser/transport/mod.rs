@@ -1,36 +1,67 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc;
-use std::sync::mpsc::Sender;
 use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
 use std::sync::Arc;
-use std::sync::Mutex;
-use std::time::Duration;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use serde_json::value::RawValue;
 
 use thiserror::Error;
 
-use log::{error, info, trace, warn};
-
+use crate::logging::{error, info, trace, warn};
+
+pub use connection::{Connection, IncomingMessages, MessageHandler};
+use event_channel::{event_channel, EventSender, SharedBackpressure};
+pub use event_channel::{
+    ChannelLimit, EventBackpressure, EventClass, EventReceiver, OverflowPolicy,
+};
+#[cfg(feature = "firefox")]
+pub use firefox_connection::{Capability, FirefoxConnection};
+pub use pipe_connection::PipeConnection;
+pub use protocol_logger::ProtocolLogger;
+use protocol_logger::SharedProtocolLogger;
+pub(crate) use protocol_logger::{redact, DEFAULT_REDACTED_FIELDS};
+use recording::SharedProtocolRecorder;
+pub use recording::{ProtocolRecorder, ReplayServer};
+pub use retry::{RetriesExhausted, RetryPolicy};
 use url::Url;
 use waiting_call_registry::WaitingCallRegistry;
-use web_socket_connection::WebSocketConnection;
+pub(crate) use web_socket_connection::WebSocketConnection;
 
+use crate::browser::metrics::{CallCounters, TransportMetrics};
 use crate::protocol::cdp::{types::Event, types::Method, Target};
 
-use crate::types::{parse_raw_message, parse_response, CallId, Message};
+use crate::types::{parse_raw_message, parse_response, CallId, Message, Response};
 
 use crate::util;
 
+mod connection;
+mod event_channel;
+#[cfg(feature = "firefox")]
+mod firefox_connection;
+mod pipe_connection;
+mod protocol_logger;
+mod recording;
+mod retry;
 mod waiting_call_registry;
 mod web_socket_connection;
 
+/// The highest call id, after which they wrap around: Chrome reads them as signed 32-bit ints.
+pub const MAX_CALL_ID: CallId = i32::MAX as CallId;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SessionId(String);
 
+#[derive(Debug, Clone)]
 pub enum MethodDestination {
     Target(SessionId),
+    /// A target attached to another target rather than to the browser (e.g. a dedicated worker
+    /// auto-attached to its page), given as `(parent, child)`.
+    ChildTarget(SessionId, SessionId),
     Browser,
 }
 
@@ -52,36 +83,211 @@ enum ListenerId {
     Browser,
 }
 
-type Listeners = Arc<Mutex<HashMap<ListenerId, Sender<Event>>>>;
+type Listeners = Arc<Mutex<HashMap<ListenerId, EventSender>>>;
 
+/// With the `tracing` feature, each call made through a transport is a `cdp_call` span with its
+/// method, call id, session id, and once it's answered, its latency and outcome (`ok`, `error`,
+/// `timeout`...). Each event received is a `trace` level event with its method and session id.
 #[derive(Debug)]
 pub struct Transport {
-    web_socket_connection: Arc<WebSocketConnection>,
+    connection: Arc<dyn Connection>,
     waiting_call_registry: Arc<WaitingCallRegistry>,
     listeners: Listeners,
     open: Arc<AtomicBool>,
+    /// Cleared by `shutdown_gracefully`, so that the calls already made can finish but no new
+    /// ones are.
+    accepting_calls: AtomicBool,
+    dispatcher: Mutex<Option<std::thread::JoinHandle<()>>>,
     call_id_counter: Arc<AtomicU32>,
     loop_shutdown_tx: Mutex<mpsc::SyncSender<()>>,
     idle_browser_timeout: Duration,
+    binary_listeners: BinaryListeners,
+    protocol_logger: SharedProtocolLogger,
+    protocol_recorder: SharedProtocolRecorder,
+    /// Held while recording a message and sending it, so that recordings are in the order the
+    /// messages were actually sent.
+    send_lock: Mutex<()>,
+    call_counters: Arc<CallCounters>,
+    event_backpressure: SharedBackpressure,
+}
+
+type BinaryListeners = Arc<Mutex<Vec<mpsc::Sender<Vec<u8>>>>>;
+
+/// A call which has been sent, and the response to which hasn't been waited for yet.
+struct PendingCall {
+    call_id: CallId,
+    /// Borrowed for typed calls, so that only calls made by name allocate it.
+    method_name: Cow<'static, str>,
+    response_rx: Receiver<Result<Response>>,
+    sent_at: Instant,
+    /// The call passing this call on to its target, if it's for one.
+    wrapper: Option<Box<PendingCall>>,
+    /// Covers the call from being sent to its response arriving, see `trace_call_finished`.
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 #[derive(Debug, Error)]
 #[error("Unable to make method calls because underlying connection is closed")]
 pub struct ConnectionClosed {}
 
+#[derive(Debug, Error)]
+#[error("Shut down with {abandoned_calls} calls still waiting for their responses")]
+pub struct CallsAbandoned {
+    pub abandoned_calls: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("Couldn't find the browser's WebSocket URL at {endpoint}: {reason}")]
+pub struct EndpointDiscoveryFailed {
+    pub endpoint: String,
+    pub reason: String,
+}
+
+/// Asks the browser listening on `endpoint` (`host:port`, e.g. a port forwarded to a remote
+/// browser's debugging port) for its WebSocket URL via its `/json/version` HTTP endpoint, and
+/// points it at `endpoint`: the browser only knows its own address, which isn't reachable
+/// when connecting through a forwarded port.
+pub fn forwarded_ws_url(endpoint: &str) -> Result<Url> {
+    use std::io::{Read, Write};
+
+    let failed = |reason: String| EndpointDiscoveryFailed {
+        endpoint: endpoint.to_string(),
+        reason,
+    };
+
+    let mut stream = std::net::TcpStream::connect(endpoint)?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    // Chrome only answers requests whose Host is an IP address or localhost
+    let host = if endpoint.starts_with("localhost:") {
+        endpoint.to_string()
+    } else {
+        stream.peer_addr()?.to_string()
+    };
+    write!(
+        stream,
+        "GET /json/version HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body)
+        .ok_or_else(|| failed(format!("unexpected response: {response:?}")))?;
+    let version: serde_json::Value = serde_json::from_str(body)?;
+    let ws_url = version["webSocketDebuggerUrl"]
+        .as_str()
+        .ok_or_else(|| failed(format!("no webSocketDebuggerUrl in {body}")))?;
+
+    // Keep the path, which has the browser's id, but go through the forwarded port
+    let forwarded = Url::parse(&format!("ws://{endpoint}"))?;
+    let mut forwarded_ws_url = Url::parse(ws_url)?;
+    forwarded_ws_url.set_host(forwarded.host_str())?;
+    forwarded_ws_url
+        .set_port(forwarded.port())
+        .map_err(|()| failed(format!("can't point {ws_url} at it")))?;
+    Ok(forwarded_ws_url)
+}
+
 impl Transport {
     pub fn new(
         ws_url: Url,
         process_id: Option<u32>,
         idle_browser_timeout: Duration,
     ) -> Result<Self> {
+        Self::with_ping_interval(ws_url, process_id, idle_browser_timeout, None)
+    }
+
+    /// Like `new`, but also sends a WebSocket Ping frame every `ping_interval` to keep the
+    /// connection from being closed while idle.
+    pub fn with_ping_interval(
+        ws_url: Url,
+        process_id: Option<u32>,
+        idle_browser_timeout: Duration,
+        ping_interval: Option<Duration>,
+    ) -> Result<Self> {
+        Self::with_connection(process_id, idle_browser_timeout, |incoming| {
+            WebSocketConnection::new(&ws_url, process_id, incoming, ping_interval)
+        })
+    }
+
+    /// Connects to the browser's WebSocket at `ws_url` over an already connected `stream`, e.g.
+    /// one bound to a particular local interface, or opened to the local end of an SSH tunnel
+    /// while `ws_url` names the browser's own address.
+    pub fn connect_via(
+        stream: std::net::TcpStream,
+        ws_url: &Url,
+        idle_browser_timeout: Duration,
+        ping_interval: Option<Duration>,
+    ) -> Result<Self> {
+        Self::with_connection(None, idle_browser_timeout, |incoming| {
+            WebSocketConnection::over_stream(stream, ws_url, None, incoming, ping_interval)
+        })
+    }
+
+    /// Like `connect_via`, but over a Unix domain socket at `path`, e.g. the local end of an SSH
+    /// tunnel to the browser's debugging port (`ssh -L /tmp/chrome.sock:localhost:9222 host`).
+    #[cfg(unix)]
+    pub fn connect_via_unix_socket(
+        path: impl AsRef<std::path::Path>,
+        ws_url: &Url,
+        idle_browser_timeout: Duration,
+        ping_interval: Option<Duration>,
+    ) -> Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Self::with_connection(None, idle_browser_timeout, |incoming| {
+            WebSocketConnection::over_unix_stream(stream, ws_url, None, incoming, ping_interval)
+        })
+    }
+
+    /// Talks to a browser launched with `--remote-debugging-pipe` over the pipes connected to
+    /// its file descriptors 4 (`reader`) and 3 (`writer`), rather than a WebSocket.
+    pub fn with_pipe(
+        reader: std::io::PipeReader,
+        writer: std::io::PipeWriter,
+        process_id: Option<u32>,
+        idle_browser_timeout: Duration,
+    ) -> Result<Self> {
+        Self::with_connection(process_id, idle_browser_timeout, |incoming| {
+            Ok(PipeConnection::new(reader, writer, process_id, incoming))
+        })
+    }
+
+    /// **Experimental**: talks to Firefox over WebDriver BiDi at `ws_url`, translating the
+    /// DevTools methods it can, see `FirefoxConnection`.
+    #[cfg(feature = "firefox")]
+    pub fn connect_firefox(ws_url: &Url, idle_browser_timeout: Duration) -> Result<Self> {
+        Self::with_connection(None, idle_browser_timeout, |incoming| {
+            FirefoxConnection::connect(ws_url.as_str(), idle_browser_timeout, incoming)
+        })
+    }
+
+    /// Talks to the browser over the connection returned by `connect` rather than a WebSocket,
+    /// e.g. a fake one in tests. `connect` is given where to hand the messages it receives.
+    pub fn with_connection<F, C>(
+        process_id: Option<u32>,
+        idle_browser_timeout: Duration,
+        connect: F,
+    ) -> Result<Self>
+    where
+        F: FnOnce(IncomingMessages) -> Result<C>,
+        C: Connection + 'static,
+    {
         let (messages_tx, messages_rx) = mpsc::channel();
-        let web_socket_connection =
-            Arc::new(WebSocketConnection::new(&ws_url, process_id, messages_tx)?);
+        let protocol_logger = Arc::new(RwLock::new(None));
+        let protocol_recorder = Arc::new(RwLock::new(None));
+        let connection: Arc<dyn Connection> = Arc::new(connect(IncomingMessages::new(
+            messages_tx,
+            Arc::clone(&protocol_logger),
+            Arc::clone(&protocol_recorder),
+        ))?);
 
         let waiting_call_registry = Arc::new(WaitingCallRegistry::new());
+        let call_counters = Arc::new(CallCounters::default());
 
         let listeners = Arc::new(Mutex::new(HashMap::new()));
+        let binary_listeners = Arc::new(Mutex::new(Vec::new()));
 
         let open = Arc::new(AtomicBool::new(true));
 
@@ -89,32 +295,85 @@ impl Transport {
 
         let guarded_shutdown_tx = Mutex::new(shutdown_tx);
 
-        Self::handle_incoming_messages(
+        let dispatcher = Self::handle_incoming_messages(
             messages_rx,
             Arc::clone(&waiting_call_registry),
             Arc::clone(&listeners),
+            Arc::clone(&binary_listeners),
             Arc::clone(&open),
-            Arc::clone(&web_socket_connection),
+            Arc::clone(&connection),
+            Arc::clone(&call_counters),
             shutdown_rx,
             process_id,
             idle_browser_timeout,
         );
 
         Ok(Self {
-            web_socket_connection,
+            connection,
             waiting_call_registry,
             listeners,
             open,
+            accepting_calls: AtomicBool::new(true),
+            dispatcher: Mutex::new(Some(dispatcher)),
             call_id_counter: Arc::new(AtomicU32::new(0)),
             loop_shutdown_tx: guarded_shutdown_tx,
             idle_browser_timeout,
+            binary_listeners,
+            protocol_logger,
+            protocol_recorder,
+            send_lock: Mutex::new(()),
+            call_counters,
+            event_backpressure: Arc::new(RwLock::new(EventBackpressure::default())),
         })
     }
 
+    /// Starts logging all protocol traffic with `logger`, or stops logging if it's `None`.
+    pub fn set_protocol_logger(&self, logger: Option<ProtocolLogger>) {
+        *self.protocol_logger.write().unwrap() = logger.map(Arc::new);
+    }
+
+    /// Starts recording the raw protocol traffic with `recorder`, or stops recording if it's
+    /// `None`. Only recordings started before the first call can be replayed.
+    pub fn set_protocol_recorder(&self, recorder: Option<ProtocolRecorder>) {
+        *self.protocol_recorder.write().unwrap() = recorder.map(Arc::new);
+    }
+
+    /// Limits how many events may be waiting for each listener, see `EventBackpressure`. Applies
+    /// to existing listeners as well as new ones.
+    pub fn set_event_backpressure(&self, backpressure: EventBackpressure) {
+        *self.event_backpressure.write().unwrap() = backpressure;
+    }
+
+    /// Whether the connection translates the DevTools protocol for a browser which doesn't speak
+    /// it, see `Connection::translates_protocol`.
+    pub fn translates_protocol(&self) -> bool {
+        self.connection.translates_protocol()
+    }
+
+    /// Counts of the calls made and events received so far.
+    pub fn metrics(&self) -> TransportMetrics {
+        self.call_counters.snapshot()
+    }
+
     /// Returns a number based on thread-safe unique counter, incrementing it so that the
-    /// next CallId is different.
+    /// next CallId is different. Wraps around to 0 after `MAX_CALL_ID`.
     pub fn unique_call_id(&self) -> CallId {
-        self.call_id_counter.fetch_add(1, Ordering::SeqCst)
+        self.call_id_counter
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |id| {
+                Some(if id >= MAX_CALL_ID { 0 } else { id + 1 })
+            })
+            .unwrap()
+    }
+
+    /// Registers a call under a new id, skipping any ids still in use by calls made before the
+    /// ids wrapped around.
+    fn register_new_call(&self) -> (CallId, Receiver<Result<Response>>) {
+        loop {
+            let call_id = self.unique_call_id();
+            if let Ok(response_rx) = self.waiting_call_registry.register_call(call_id) {
+                return (call_id, response_rx);
+            }
+        }
     }
 
     pub fn call_method<C>(
@@ -122,21 +381,153 @@ impl Transport {
         method: C,
         destination: MethodDestination,
     ) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize,
+    {
+        let pending_call = self.send_call(method, destination)?;
+        self.wait_for_response(pending_call)
+    }
+
+    /// Makes all of the `methods` calls before waiting for any of their responses, which saves a
+    /// round trip per call when making lots of small calls (e.g. reading the attributes of a
+    /// hundred elements). Returns each call's result, in the same order as `methods`; fails
+    /// outright only if the calls couldn't be sent.
+    pub fn call_batch<C>(
+        &self,
+        methods: Vec<C>,
+        destination: &MethodDestination,
+    ) -> Result<Vec<Result<C::ReturnObject>>>
+    where
+        C: Method + serde::Serialize,
+    {
+        let mut pending_calls = Vec::with_capacity(methods.len());
+        let mut send_error = None;
+        for method in methods {
+            match self.send_call(method, destination.clone()) {
+                Ok(pending_call) => pending_calls.push(pending_call),
+                Err(err) => {
+                    send_error = Some(err);
+                    break;
+                }
+            }
+        }
+
+        // The calls which were sent get waited for either way, as the responses to them arrive
+        let results = pending_calls
+            .into_iter()
+            .map(|pending_call| self.wait_for_response(pending_call))
+            .collect();
+        match send_error {
+            Some(err) => Err(err),
+            None => Ok(results),
+        }
+    }
+
+    /// Sends a call without waiting for its response.
+    fn send_call<C>(&self, method: C, destination: MethodDestination) -> Result<PendingCall>
     where
         C: Method + serde::Serialize,
     {
         // TODO: use get_mut to get exclusive access for entire block... maybe.
-        if !self.open.load(Ordering::SeqCst) {
+        if !self.is_accepting_calls() {
             return Err(ConnectionClosed {}.into());
         }
-        let call_id = self.unique_call_id();
-        let call = method.to_method_call(call_id);
+        // Calls wrapping other targets' calls are logged as the calls they wrap
+        let logged_params = if C::NAME != Target::SendMessageToTarget::NAME
+            && self.protocol_logger.read().unwrap().is_some()
+        {
+            Some(serde_json::to_value(&method)?)
+        } else {
+            None
+        };
+        let (call_id, response_rx) = self.register_new_call();
+        let message_text = match serde_json::to_string(&method.to_method_call(call_id)) {
+            Ok(message_text) => message_text,
+            Err(err) => {
+                self.waiting_call_registry.unregister_call(call_id);
+                return Err(err.into());
+            }
+        };
+        self.send_message_text(
+            call_id,
+            response_rx,
+            Cow::Borrowed(C::NAME),
+            message_text,
+            logged_params.as_ref(),
+            destination,
+        )
+    }
 
-        let message_text = serde_json::to_string(&call)?;
+    /// Sends a call to a method by name, for methods without a `Method` type.
+    fn send_raw_call(
+        &self,
+        method_name: &str,
+        params: &serde_json::Value,
+        destination: MethodDestination,
+    ) -> Result<PendingCall> {
+        if !self.is_accepting_calls() {
+            return Err(ConnectionClosed {}.into());
+        }
+        let (call_id, response_rx) = self.register_new_call();
+        let message_text = serde_json::json!({
+            "method": method_name,
+            "id": call_id,
+            "params": params,
+        })
+        .to_string();
+        self.send_message_text(
+            call_id,
+            response_rx,
+            Cow::Owned(method_name.to_string()),
+            message_text,
+            Some(params),
+            destination,
+        )
+    }
 
-        let response_rx = self.waiting_call_registry.register_call(call.id);
+    fn is_accepting_calls(&self) -> bool {
+        self.open.load(Ordering::SeqCst) && self.accepting_calls.load(Ordering::SeqCst)
+    }
 
-        match destination {
+    /// Sends a call's `message_text`, logging it with its `logged_params` if there's a protocol
+    /// logger.
+    fn send_message_text(
+        &self,
+        call_id: CallId,
+        response_rx: Receiver<Result<Response>>,
+        method_name: Cow<'static, str>,
+        message_text: String,
+        logged_params: Option<&serde_json::Value>,
+        destination: MethodDestination,
+    ) -> Result<PendingCall> {
+        let session_id = match &destination {
+            MethodDestination::Target(session_id)
+            | MethodDestination::ChildTarget(_, session_id) => Some(session_id),
+            MethodDestination::Browser => None,
+        };
+        // Calls wrapping other targets' calls are logged, traced and counted as the calls they
+        // wrap
+        let is_wrapper = method_name == Target::SendMessageToTarget::NAME;
+        if let Some(logger) = self.protocol_logger.read().unwrap().as_ref() {
+            if let (false, Some(params)) = (is_wrapper, logged_params) {
+                logger.log_command(call_id, &method_name, params, session_id);
+            }
+        }
+        #[cfg(feature = "tracing")]
+        let span = if is_wrapper {
+            tracing::Span::none()
+        } else {
+            tracing::debug_span!(
+                "cdp_call",
+                method = &*method_name,
+                call_id,
+                session_id = session_id.map(SessionId::as_str),
+                latency_ms = tracing::field::Empty,
+                outcome = tracing::field::Empty,
+            )
+        };
+
+        let wrapper = match destination {
             MethodDestination::Target(session_id) => {
                 let message = message_text.clone();
                 let target_method = Target::SendMessageToTarget {
@@ -148,33 +539,180 @@ impl Transport {
                     "Msg to tab: {}",
                     message_text.chars().take(300).collect::<String>()
                 );
-                if let Err(e) = self.call_method_on_browser(target_method) {
-                    warn!("Failed to call method on browser: {:?}", e);
-                    self.waiting_call_registry.unregister_call(call.id);
-                    trace!("Unregistered callback: {:?}", call.id);
-                    return Err(e);
+                match self.send_call(target_method, MethodDestination::Browser) {
+                    Ok(wrapper) => Some(Box::new(wrapper)),
+                    Err(e) => {
+                        warn!("Failed to call method on browser: {:?}", e);
+                        self.waiting_call_registry.unregister_call(call_id);
+                        trace!("Unregistered callback: {call_id:?}");
+                        return Err(e);
+                    }
+                }
+            }
+            MethodDestination::ChildTarget(parent_session_id, session_id) => {
+                let target_method = Target::SendMessageToTarget {
+                    target_id: None,
+                    session_id: Some(session_id.0),
+                    message: message_text.clone(),
+                };
+                trace!(
+                    "Msg to child target: {}",
+                    message_text.chars().take(300).collect::<String>()
+                );
+                match self.send_call(target_method, MethodDestination::Target(parent_session_id)) {
+                    Ok(wrapper) => Some(Box::new(wrapper)),
+                    Err(e) => {
+                        warn!("Failed to call method on parent target: {e:?}");
+                        self.waiting_call_registry.unregister_call(call_id);
+                        return Err(e);
+                    }
                 }
             }
             MethodDestination::Browser => {
-                if let Err(e) = self.web_socket_connection.send_message(&message_text) {
-                    self.waiting_call_registry.unregister_call(call.id);
+                let _sending = self.send_lock.lock().unwrap();
+                if let Some(recorder) = self.protocol_recorder.read().unwrap().as_ref() {
+                    recorder.record_sent(&message_text);
+                }
+                if let Err(e) = self.connection.send_message(&message_text) {
+                    self.waiting_call_registry.unregister_call(call_id);
                     return Err(e);
                 }
                 trace!("sent method call to browser via websocket");
+                None
             }
+        };
+
+        if !is_wrapper {
+            self.call_counters.command_sent();
         }
 
-        let params_string = format!("{:?}", call.get_params());
-        trace!(
-            "waiting for response from call registry: {} {:?}",
-            &call_id,
-            params_string.chars().take(400).collect::<String>()
-        );
+        Ok(PendingCall {
+            call_id,
+            method_name,
+            response_rx,
+            sent_at: Instant::now(),
+            wrapper,
+            #[cfg(feature = "tracing")]
+            span,
+        })
+    }
+
+    /// Like `call_method`, but returns the result's JSON as it arrived rather than parsing it, so
+    /// that large results (e.g. screenshots) can be picked apart without building the whole
+    /// return object.
+    pub fn call_method_raw<C>(
+        &self,
+        method: C,
+        destination: MethodDestination,
+    ) -> Result<Box<RawValue>>
+    where
+        C: Method + serde::Serialize,
+    {
+        let pending_call = self.send_call(method, destination)?;
+        let response = self.wait_for_raw_response(pending_call)?;
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+        match response.result {
+            Some(result) => Ok(result),
+            None => Ok(RawValue::from_string("null".to_string())?),
+        }
+    }
 
+    /// Calls a method by name, e.g. one newer than this crate's protocol definitions or one only
+    /// some browsers have, and returns its result's JSON.
+    pub fn call_raw(
+        &self,
+        method_name: &str,
+        params: serde_json::Value,
+        destination: MethodDestination,
+    ) -> Result<serde_json::Value> {
+        let pending_call = self.send_raw_call(method_name, &params, destination)?;
+        let response = self.wait_for_raw_response(pending_call)?;
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+        match response.result {
+            Some(result) => Ok(serde_json::from_str(result.get())?),
+            None => Ok(serde_json::Value::Null),
+        }
+    }
+
+    fn wait_for_response<T>(&self, pending_call: PendingCall) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        parse_response::<T>(self.wait_for_raw_response(pending_call)?)
+    }
+
+    fn wait_for_raw_response(&self, pending_call: PendingCall) -> Result<Response> {
+        let PendingCall {
+            call_id,
+            method_name,
+            response_rx,
+            sent_at,
+            wrapper,
+            #[cfg(feature = "tracing")]
+            span,
+        } = pending_call;
+
+        if let Some(wrapper) = wrapper {
+            if let Err(e) =
+                self.wait_for_response::<Target::SendMessageToTargetReturnObject>(*wrapper)
+            {
+                warn!("Failed to pass method call on to target: {e:?}");
+                self.waiting_call_registry.unregister_call(call_id);
+                #[cfg(feature = "tracing")]
+                trace_call_finished(&span, sent_at.elapsed(), "send_failed");
+                return Err(e);
+            }
+        }
+
+        #[cfg(not(feature = "tracing"))]
+        trace!("waiting for response from call registry: {call_id} {method_name}");
         let response_result = util::Wait::new(self.idle_browser_timeout, Duration::from_millis(5))
             .until(|| response_rx.try_recv().ok());
-        trace!("received response for: {} {:?}", &call_id, params_string);
-        parse_response::<C::ReturnObject>((response_result?)?)
+        #[cfg(not(feature = "tracing"))]
+        trace!("received response for: {call_id} {method_name}");
+        #[cfg(feature = "tracing")]
+        trace_call_finished(
+            &span,
+            sent_at.elapsed(),
+            match &response_result {
+                Ok(Ok(response)) if response.error.is_some() => "error",
+                Ok(Ok(_)) => "ok",
+                Ok(Err(_)) => "cancelled",
+                Err(_) => "timeout",
+            },
+        );
+        if response_result.is_err() {
+            // So that a response arriving later is known to be late, and the id can be reused
+            self.waiting_call_registry.unregister_call(call_id);
+        }
+        if method_name != Target::SendMessageToTarget::NAME {
+            match &response_result {
+                Ok(Ok(response)) => self
+                    .call_counters
+                    .response_received(sent_at.elapsed(), response.error.is_some()),
+                Ok(Err(_)) => {}
+                Err(_) => self.call_counters.timed_out(),
+            }
+        }
+        response_result?
+    }
+
+    /// Like `call_method`, but retries the call according to `policy` while it fails with a
+    /// transient error, such as the target being detached or the call timing out.
+    pub fn call_with_retry<C>(
+        &self,
+        method: C,
+        destination: &MethodDestination,
+        policy: &RetryPolicy,
+    ) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize + Clone,
+    {
+        policy.run(|| self.call_method(method.clone(), destination.clone()))
     }
 
     pub fn call_method_on_target<C>(
@@ -189,6 +727,21 @@ impl Transport {
         self.call_method(method, MethodDestination::Target(session_id))
     }
 
+    pub fn call_method_on_child_target<C>(
+        &self,
+        parent_session_id: SessionId,
+        session_id: SessionId,
+        method: C,
+    ) -> Result<C::ReturnObject>
+    where
+        C: Method + serde::Serialize,
+    {
+        self.call_method(
+            method,
+            MethodDestination::ChildTarget(parent_session_id, session_id),
+        )
+    }
+
     pub fn call_method_on_browser<C>(&self, method: C) -> Result<C::ReturnObject>
     where
         C: Method + serde::Serialize,
@@ -196,8 +749,8 @@ impl Transport {
         self.call_method(method, MethodDestination::Browser)
     }
 
-    pub fn listen_to_browser_events(&self) -> Receiver<Event> {
-        let (events_tx, events_rx) = mpsc::channel();
+    pub fn listen_to_browser_events(&self) -> EventReceiver {
+        let (events_tx, events_rx) = self.event_channel();
 
         let mut listeners = self.listeners.lock().unwrap();
         listeners.insert(ListenerId::Browser, events_tx);
@@ -205,8 +758,15 @@ impl Transport {
         events_rx
     }
 
-    pub fn listen_to_target_events(&self, session_id: SessionId) -> Receiver<Event> {
-        let (events_tx, events_rx) = mpsc::channel();
+    /// Receives the payloads of binary WebSocket frames which aren't protocol messages.
+    pub fn listen_to_binary_messages(&self) -> Receiver<Vec<u8>> {
+        let (binary_tx, binary_rx) = mpsc::channel();
+        self.binary_listeners.lock().unwrap().push(binary_tx);
+        binary_rx
+    }
+
+    pub fn listen_to_target_events(&self, session_id: SessionId) -> EventReceiver {
+        let (events_tx, events_rx) = self.event_channel();
 
         let mut listeners = self.listeners.lock().unwrap();
         listeners.insert(ListenerId::SessionId(session_id), events_tx);
@@ -214,23 +774,81 @@ impl Transport {
         events_rx
     }
 
+    /// Drops the listener `listen_to_target_events` registered for `session_id`, ending its
+    /// receiver, e.g. once the target has detached.
+    pub fn stop_listening_to_target_events(&self, session_id: &SessionId) {
+        self.listeners
+            .lock()
+            .unwrap()
+            .remove(&ListenerId::SessionId(session_id.clone()));
+    }
+
+    fn event_channel(&self) -> (EventSender, EventReceiver) {
+        event_channel(
+            Arc::clone(&self.event_backpressure),
+            Arc::clone(&self.call_counters),
+            self.idle_browser_timeout,
+        )
+    }
+
     pub fn shutdown(&self) {
-        self.web_socket_connection.shutdown();
+        self.connection.shutdown();
         let shutdown_tx = self.loop_shutdown_tx.lock().unwrap();
         let _ = shutdown_tx.send(());
     }
 
+    /// Shuts down cleanly, e.g. before a service restarts: stops making new calls (which fail
+    /// with `ConnectionClosed`), waits up to `timeout` for the responses to the calls already
+    /// made, then closes the connection and waits up to `timeout` again for the thread handling
+    /// incoming messages to finish.
+    ///
+    /// Calls which are still waiting at the deadline fail with `ConnectionClosed`, and are
+    /// counted in the `CallsAbandoned` error this returns.
+    pub fn shutdown_gracefully(&self, timeout: Duration) -> Result<()> {
+        self.accepting_calls.store(false, Ordering::SeqCst);
+
+        let drained = util::Wait::new(timeout, Duration::from_millis(5))
+            .until(|| (self.waiting_call_registry.outstanding() == 0).then_some(()))
+            .is_ok();
+        let abandoned_calls = self.waiting_call_registry.outstanding();
+        if !drained {
+            warn!("Shutting down with {abandoned_calls} calls still waiting for their responses");
+            self.waiting_call_registry.cancel_outstanding_method_calls();
+        }
+
+        self.shutdown();
+
+        if let Some(dispatcher) = self.dispatcher.lock().unwrap().take() {
+            let finished = util::Wait::new(timeout, Duration::from_millis(5))
+                .until(|| dispatcher.is_finished().then_some(()))
+                .is_ok();
+            if finished {
+                let _ = dispatcher.join();
+            } else {
+                warn!("Transport's message handling loop didn't stop in time");
+            }
+        }
+
+        if drained {
+            Ok(())
+        } else {
+            Err(CallsAbandoned { abandoned_calls }.into())
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn handle_incoming_messages(
         messages_rx: Receiver<Message>,
         waiting_call_registry: Arc<WaitingCallRegistry>,
         listeners: Listeners,
+        binary_listeners: BinaryListeners,
         open: Arc<AtomicBool>,
-        conn: Arc<WebSocketConnection>,
+        conn: Arc<dyn Connection>,
+        call_counters: Arc<CallCounters>,
         shutdown_rx: Receiver<()>,
         process_id: Option<u32>,
         idle_browser_timeout: Duration,
-    ) {
+    ) -> std::thread::JoinHandle<()> {
         trace!("Starting handle_incoming_messages");
         std::thread::spawn(move || {
             trace!("Inside handle_incoming_messages thread");
@@ -267,54 +885,34 @@ impl Transport {
                             info!("Received shutdown message");
                             break;
                         }
+                        Message::Binary(payload) => {
+                            binary_listeners
+                                .lock()
+                                .unwrap()
+                                .retain(|tx| tx.send(payload.clone()).is_ok());
+                        }
                         Message::Response(response_to_browser_method_call) => {
-                            if waiting_call_registry
-                                .resolve_call(response_to_browser_method_call)
-                                .is_err()
-                            {
-                                warn!("The browser registered a call but then closed its receiving channel");
-                                break;
-                            }
+                            waiting_call_registry.resolve_call(response_to_browser_method_call);
                         }
 
                         Message::Event(browser_event) => match browser_event {
                             Event::ReceivedMessageFromTarget(target_message_event) => {
-                                let session_id = target_message_event.params.session_id.into();
-                                let raw_message = target_message_event.params.message;
-
-                                let msg_res = parse_raw_message(&raw_message);
-                                match msg_res {
-                                    Ok(target_message) => match target_message {
-                                        Message::Event(target_event) => {
-                                            if let Some(tx) = listeners
-                                                .lock()
-                                                .unwrap()
-                                                .get(&ListenerId::SessionId(session_id))
-                                            {
-                                                tx.send(target_event)
-                                                    .expect("Couldn't send event to listener");
-                                            }
-                                        }
-
-                                        Message::Response(resp) => {
-                                            if waiting_call_registry.resolve_call(resp).is_err() {
-                                                warn!("The browser registered a call but then closed its receiving channel");
-                                                break;
-                                            }
-                                        }
-                                        Message::ConnectionShutdown => {}
-                                    },
-                                    Err(e) => {
-                                        trace!(
-                                            "Message from target isn't recognised: {:?} - {}",
-                                            &raw_message,
-                                            e,
-                                        );
-                                    }
+                                let params = target_message_event.params;
+                                if !Self::handle_target_message(
+                                    params.session_id.into(),
+                                    &params.message,
+                                    &waiting_call_registry,
+                                    &listeners,
+                                    &call_counters,
+                                ) {
+                                    break;
                                 }
                             }
 
                             _ => {
+                                call_counters.event_received();
+                                #[cfg(feature = "tracing")]
+                                trace_event(&browser_event, None);
                                 if let Some(tx) =
                                     listeners.lock().unwrap().get(&ListenerId::Browser)
                                 {
@@ -344,8 +942,76 @@ impl Transport {
             let mut listeners = listeners.lock().unwrap();
             *listeners = HashMap::new();
             info!("cleared listeners, I think");
-        });
+        })
+    }
+
+    /// Routes a message a target sent to us, unwrapping messages its own child targets (such as
+    /// auto-attached workers) sent through it. Returns false if the message loop should stop.
+    fn handle_target_message(
+        session_id: SessionId,
+        raw_message: &str,
+        waiting_call_registry: &WaitingCallRegistry,
+        listeners: &Listeners,
+        call_counters: &CallCounters,
+    ) -> bool {
+        match parse_raw_message(raw_message) {
+            Ok(Message::Event(Event::ReceivedMessageFromTarget(child_message_event))) => {
+                let params = child_message_event.params;
+                Self::handle_target_message(
+                    params.session_id.into(),
+                    &params.message,
+                    waiting_call_registry,
+                    listeners,
+                    call_counters,
+                )
+            }
+            Ok(Message::Event(target_event)) => {
+                call_counters.event_received();
+                #[cfg(feature = "tracing")]
+                trace_event(&target_event, Some(&session_id));
+                if let Some(tx) = listeners
+                    .lock()
+                    .unwrap()
+                    .get(&ListenerId::SessionId(session_id))
+                {
+                    tx.send(target_event)
+                        .expect("Couldn't send event to listener");
+                }
+                true
+            }
+            Ok(Message::Response(resp)) => {
+                waiting_call_registry.resolve_call(resp);
+                true
+            }
+            Ok(Message::ConnectionShutdown | Message::Binary(_)) => true,
+            Err(e) => {
+                trace!("Message from target isn't recognised: {raw_message:?} - {e}");
+                true
+            }
+        }
+    }
+}
+
+/// Records how a call went on its `cdp_call` span, and emits an event in the span saying so.
+#[cfg(feature = "tracing")]
+fn trace_call_finished(span: &tracing::Span, latency: Duration, outcome: &'static str) {
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    span.record("latency_ms", latency_ms);
+    span.record("outcome", outcome);
+    tracing::debug!(parent: span, latency_ms, outcome, "CDP call finished");
+}
+
+/// Emits a `tracing` event for an event the browser or one of its targets sent.
+#[cfg(feature = "tracing")]
+fn trace_event(event: &Event, session_id: Option<&SessionId>) {
+    if !tracing::enabled!(tracing::Level::TRACE) {
+        return;
     }
+    tracing::trace!(
+        method = event.method(),
+        session_id = session_id.map(SessionId::as_str),
+        "CDP event"
+    );
 }
 
 impl Drop for Transport {
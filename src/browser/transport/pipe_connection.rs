@@ -0,0 +1,98 @@
+use std::io::{BufRead, BufReader, PipeReader, PipeWriter, Write};
+use std::sync::Mutex;
+
+use crate::logging::{debug, info, trace};
+use anyhow::Result;
+
+use super::connection::{Connection, IncomingMessages};
+use super::ConnectionClosed;
+
+/// Talks to a browser launched with `--remote-debugging-pipe`, which reads messages from its
+/// file descriptor 3 and writes them to its file descriptor 4, each terminated by a NUL byte.
+pub struct PipeConnection {
+    /// None once shut down; closing it tells the browser we're done.
+    writer: Mutex<Option<PipeWriter>>,
+    process_id: Option<u32>,
+}
+
+impl std::fmt::Debug for PipeConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "PipeConnection {{}}")
+    }
+}
+
+impl PipeConnection {
+    /// `reader` is the end of the pipe connected to the browser's fd 4, `writer` the one
+    /// connected to its fd 3.
+    pub fn new(
+        reader: PipeReader,
+        writer: PipeWriter,
+        process_id: Option<u32>,
+        incoming: IncomingMessages,
+    ) -> Self {
+        std::thread::spawn(move || {
+            trace!("Starting pipe msg dispatching loop");
+            Self::dispatch_incoming_messages(reader, &incoming, process_id);
+            trace!("Quit pipe msg dispatching loop");
+        });
+
+        Self {
+            writer: Mutex::new(Some(writer)),
+            process_id,
+        }
+    }
+
+    fn dispatch_incoming_messages(
+        reader: PipeReader,
+        incoming: &IncomingMessages,
+        process_id: Option<u32>,
+    ) {
+        let mut reader = BufReader::new(reader);
+        let mut message = Vec::new();
+        loop {
+            message.clear();
+            match reader.read_until(0, &mut message) {
+                // The browser closed its end, i.e. it exited
+                Ok(0) => break,
+                Ok(_) => {
+                    if message.last() == Some(&0) {
+                        message.pop();
+                    }
+                    match std::str::from_utf8(&message) {
+                        Ok(message_text) => {
+                            if !incoming.text(message_text) {
+                                break;
+                            }
+                        }
+                        Err(err) => debug!("Non-UTF-8 message from Chrome #{process_id:?}: {err}"),
+                    }
+                }
+                Err(err) => {
+                    debug!("Pipe IO Error for Chrome #{process_id:?}: {err}");
+                    break;
+                }
+            }
+        }
+
+        incoming.closed();
+    }
+}
+
+impl Connection for PipeConnection {
+    fn send_message(&self, message_text: &str) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        let writer = writer.as_mut().ok_or(ConnectionClosed {})?;
+        writer.write_all(message_text.as_bytes())?;
+        writer.write_all(&[0])?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        info!(
+            "Shutting down pipe connection for Chrome {:?}",
+            self.process_id
+        );
+        self.writer.lock().unwrap().take();
+    }
+}
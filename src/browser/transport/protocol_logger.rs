@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::logging::warn;
+use anyhow::Result;
+use serde_json::{json, Value as Json};
+
+use super::SessionId;
+use crate::types::CallId;
+
+/// The fields redacted by default: anything holding cookies or credentials.
+pub(crate) const DEFAULT_REDACTED_FIELDS: &[&str] = &[
+    "associatedcookies",
+    "authorization",
+    "cookie",
+    "cookies",
+    "password",
+    "proxy-authorization",
+    "set-cookie",
+];
+
+pub(crate) type SharedProtocolLogger = Arc<RwLock<Option<Arc<ProtocolLogger>>>>;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// The methods whose params set cookies, either as a single cookie or as a list of them.
+const COOKIE_SETTERS: &[&str] = &[
+    "Network.setCookie",
+    "Network.setCookies",
+    "Storage.setCookies",
+];
+
+/// The fields redacted when nothing else is configured, see `ProtocolLogger::redact_fields`.
+fn default_redacted_fields() -> Vec<String> {
+    DEFAULT_REDACTED_FIELDS
+        .iter()
+        .map(|field| (*field).to_string())
+        .collect()
+}
+
+/// Redacts the `params` of a call to `method` (or of a `method` event): the values of
+/// `redacted_fields` at any depth, headers given as `{name, value}` entries or as `headersText`,
+/// and, if `cookie` is redacted, the values of the cookies set by the cookie setting methods.
+pub(crate) fn redact<S: AsRef<str>>(method: &str, params: &mut Json, redacted_fields: &[S]) {
+    if COOKIE_SETTERS.contains(&method)
+        && redacted_fields
+            .iter()
+            .any(|field| field.as_ref() == "cookie")
+    {
+        let cookies: Vec<&mut Json> = match &mut *params {
+            Json::Object(object) if object.get("cookies").is_some_and(Json::is_array) => object
+                ["cookies"]
+                .as_array_mut()
+                .unwrap()
+                .iter_mut()
+                .collect(),
+            params => vec![params],
+        };
+        for cookie in cookies {
+            if let Some(value) = cookie.get_mut("value") {
+                *value = json!(REDACTED);
+            }
+        }
+    }
+    redact_value(params, redacted_fields);
+}
+
+fn redact_value<S: AsRef<str>>(value: &mut Json, redacted_fields: &[S]) {
+    let is_redacted = |name: &str| {
+        let name = name.to_lowercase();
+        redacted_fields.iter().any(|field| field.as_ref() == name)
+    };
+    match value {
+        Json::Object(object) => {
+            // A header (or cookie) entry, as in `Fetch.continueRequest`'s `headers`
+            let entry_name = object.get("name").and_then(Json::as_str);
+            if entry_name.is_some_and(is_redacted) {
+                if let Some(value) = object.get_mut("value") {
+                    *value = json!(REDACTED);
+                }
+            }
+            for (key, value) in object.iter_mut() {
+                if is_redacted(key) {
+                    *value = json!(REDACTED);
+                } else if let ("headersText", Json::String(text)) = (key.as_str(), &mut *value) {
+                    *text = redact_headers_text(text, is_redacted);
+                } else {
+                    redact_value(value, redacted_fields);
+                }
+            }
+        }
+        Json::Array(array) => array
+            .iter_mut()
+            .for_each(|value| redact_value(value, redacted_fields)),
+        _ => {}
+    }
+}
+
+/// Redacts the values of the headers in raw HTTP headers, e.g. `Cookie: a=b\r\nAccept: */*`.
+fn redact_headers_text(text: &str, is_redacted: impl Fn(&str) -> bool) -> String {
+    text.split('\n')
+        .map(|line| match line.split_once(':') {
+            Some((name, _)) if is_redacted(name.trim()) => {
+                let line_end = if line.ends_with('\r') { "\r" } else { "" };
+                format!("{name}: {REDACTED}{line_end}")
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Records every command sent to the browser, and every response and event received from it, as
+/// one JSON object per line, for debugging after the fact.
+///
+/// Commands and responses are matched up by their `id`, and responses include how long the
+/// command took (`latency_ms`). Commands and events of a tab or worker carry its `session_id`.
+/// The values of sensitive fields (cookies, auth headers and passwords by default) are replaced
+/// with `"[REDACTED]"`, wherever they're nested, as are the matching headers in `{name, value}`
+/// header lists and raw `headersText`, and the values of cookies being set.
+///
+/// ```rust,no_run
+/// # use anyhow::Result;
+/// # fn main() -> Result<()> {
+/// #
+/// use headless_chrome::browser::transport::ProtocolLogger;
+/// use headless_chrome::Browser;
+///
+/// let browser = Browser::default()?;
+/// browser.set_protocol_logger(Some(
+///     ProtocolLogger::to_file("protocol.jsonl")?.redact_fields(&["cookie", "x-api-key"]),
+/// ));
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProtocolLogger {
+    writer: Mutex<Box<dyn Write + Send>>,
+    redacted_fields: Vec<String>,
+    /// When each command still waiting for a response was sent.
+    pending_commands: Mutex<HashMap<CallId, Instant>>,
+}
+
+impl std::fmt::Debug for ProtocolLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "ProtocolLogger {{ redacted_fields: {:?} }}",
+            self.redacted_fields
+        )
+    }
+}
+
+impl ProtocolLogger {
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            redacted_fields: default_redacted_fields(),
+            pending_commands: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Logs to the file at `path`, replacing it if it exists.
+    pub fn to_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Replaces the names of the fields whose values are redacted. Names are matched
+    /// case-insensitively, against object keys at any depth (including header names).
+    #[must_use]
+    pub fn redact_fields(mut self, fields: &[&str]) -> Self {
+        self.redacted_fields = fields.iter().map(|field| field.to_lowercase()).collect();
+        self
+    }
+
+    pub(crate) fn log_command(
+        &self,
+        call_id: CallId,
+        method: &str,
+        params: &Json,
+        session_id: Option<&SessionId>,
+    ) {
+        self.pending_commands
+            .lock()
+            .unwrap()
+            .insert(call_id, Instant::now());
+        self.write(json!({
+            "type": "command",
+            "id": call_id,
+            "session_id": session_id.map(SessionId::as_str),
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    /// Logs a raw message received from the browser, unwrapping the messages of targets it
+    /// carries.
+    pub(crate) fn log_incoming(&self, raw_message: &str) {
+        self.log_incoming_from(raw_message, None);
+    }
+
+    fn log_incoming_from(&self, raw_message: &str, session_id: Option<&str>) {
+        let Ok(message) = serde_json::from_str::<Json>(raw_message) else {
+            return;
+        };
+
+        if message["method"] == "Target.receivedMessageFromTarget" {
+            if let (Some(inner), Some(inner_session_id)) = (
+                message["params"]["message"].as_str(),
+                message["params"]["sessionId"].as_str(),
+            ) {
+                self.log_incoming_from(inner, Some(inner_session_id));
+            }
+            return;
+        }
+
+        if let Some(call_id) = message["id"].as_u64() {
+            // Responses to the commands wrapping other targets' commands weren't logged as sent
+            let Some(sent_at) = self
+                .pending_commands
+                .lock()
+                .unwrap()
+                .remove(&(call_id as CallId))
+            else {
+                return;
+            };
+            self.write(json!({
+                "type": "response",
+                "id": call_id,
+                "session_id": session_id,
+                "latency_ms": sent_at.elapsed().as_secs_f64() * 1000.0,
+                "result": message["result"],
+                "error": message["error"],
+            }));
+        } else {
+            self.write(json!({
+                "type": "event",
+                "session_id": session_id,
+                "method": message["method"],
+                "params": message["params"],
+            }));
+        }
+    }
+
+    fn write(&self, mut entry: Json) {
+        let method = entry["method"].as_str().unwrap_or_default().to_string();
+        if let Some(params) = entry.get_mut("params") {
+            redact(&method, params, &self.redacted_fields);
+        }
+        redact_value(&mut entry, &self.redacted_fields);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_millis());
+        entry["timestamp_ms"] = json!(timestamp);
+
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(err) = writeln!(writer, "{entry}").and_then(|()| writer.flush()) {
+            warn!("Couldn't write to protocol log: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn entries(&self) -> Vec<Json> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn logs_commands_responses_and_target_events() {
+        let buffer = SharedBuffer::default();
+        let logger = ProtocolLogger::new(buffer.clone());
+        let session_id = SessionId::from("session".to_string());
+
+        logger.log_command(
+            1,
+            "Network.setExtraHTTPHeaders",
+            &json!({"headers": {"Authorization": "Basic abc", "Accept": "*/*"}}),
+            Some(&session_id),
+        );
+        logger.log_incoming(
+            &json!({
+                "method": "Target.receivedMessageFromTarget",
+                "params": {"sessionId": "session", "message": json!({"id": 1, "result": {}}).to_string()}
+            })
+            .to_string(),
+        );
+        logger.log_incoming(r#"{"method": "Page.loadEventFired", "params": {"timestamp": 1.5}}"#);
+        // Responses to commands which weren't logged are skipped
+        logger.log_incoming(r#"{"id": 2, "result": {}}"#);
+
+        let entries = buffer.entries();
+        assert_eq!(3, entries.len());
+
+        assert_eq!("command", entries[0]["type"]);
+        assert_eq!("session", entries[0]["session_id"]);
+        assert_eq!(REDACTED, entries[0]["params"]["headers"]["Authorization"]);
+        assert_eq!("*/*", entries[0]["params"]["headers"]["Accept"]);
+
+        assert_eq!("response", entries[1]["type"]);
+        assert_eq!(1, entries[1]["id"]);
+        assert_eq!("session", entries[1]["session_id"]);
+        assert!(entries[1]["latency_ms"].is_number());
+
+        assert_eq!("event", entries[2]["type"]);
+        assert_eq!("Page.loadEventFired", entries[2]["method"]);
+        assert!(entries[2]["session_id"].is_null());
+    }
+
+    #[test]
+    fn redacts_header_entries_and_raw_headers() {
+        let fields = default_redacted_fields();
+
+        let mut params = json!({
+            "requestId": "1",
+            "headers": [
+                {"name": "Authorization", "value": "Bearer abc"},
+                {"name": "Accept", "value": "*/*"},
+            ],
+        });
+        redact("Fetch.continueRequest", &mut params, &fields);
+        assert_eq!(REDACTED, params["headers"][0]["value"]);
+        assert_eq!("*/*", params["headers"][1]["value"]);
+
+        let mut params = json!({
+            "responseCode": 200,
+            "responseHeaders": [{"name": "Set-Cookie", "value": "session=abc"}],
+        });
+        redact("Fetch.fulfillRequest", &mut params, &fields);
+        assert_eq!(REDACTED, params["responseHeaders"][0]["value"]);
+
+        let mut params = json!({
+            "headersText": "HTTP/1.1 200 OK\r\nSet-Cookie: session=abc\r\nContent-Type: text/html\r\n",
+        });
+        redact("Network.responseReceivedExtraInfo", &mut params, &fields);
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nSet-Cookie: [REDACTED]\r\nContent-Type: text/html\r\n",
+            params["headersText"]
+        );
+    }
+
+    #[test]
+    fn redacts_cookies() {
+        let fields = default_redacted_fields();
+
+        let mut params = json!({
+            "associatedCookies": [{"blockedReasons": [], "cookie": {"name": "session", "value": "abc"}}],
+        });
+        redact("Network.requestWillBeSentExtraInfo", &mut params, &fields);
+        assert_eq!(REDACTED, params["associatedCookies"]);
+
+        let mut params = json!({"name": "session", "value": "abc", "domain": "example.com"});
+        redact("Network.setCookie", &mut params, &fields);
+        assert_eq!(REDACTED, params["value"]);
+        assert_eq!("session", params["name"]);
+
+        // Only the cookie values, if the whole list isn't redacted
+        let fields = vec!["cookie".to_string()];
+        let mut params = json!({"cookies": [{"name": "session", "value": "abc"}]});
+        redact("Network.setCookies", &mut params, &fields);
+        assert_eq!(REDACTED, params["cookies"][0]["value"]);
+        assert_eq!("session", params["cookies"][0]["name"]);
+
+        // Other methods' `value`s are left alone
+        let mut params = json!({"name": "session", "value": "abc"});
+        redact("Runtime.addBinding", &mut params, &fields);
+        assert_eq!("abc", params["value"]);
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+use crate::logging::{debug, trace, warn};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as Json};
+
+pub(crate) type SharedProtocolRecorder = Arc<RwLock<Option<Arc<ProtocolRecorder>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Sent,
+    Received,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedMessage {
+    direction: Direction,
+    message: Json,
+}
+
+/// Records the raw protocol traffic of a browser connection to a file, one message per line, so
+/// that it can be played back later by a [`ReplayServer`].
+///
+/// Recording has to start with the connection for the recording to be replayable, so it's turned
+/// on with `LaunchOptions.record_protocol_to` or `ConnectOptions.record_protocol_to`.
+pub struct ProtocolRecorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl std::fmt::Debug for ProtocolRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "ProtocolRecorder {{}}")
+    }
+}
+
+impl ProtocolRecorder {
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Records to the file at `path`, replacing it if it exists.
+    pub fn to_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub(crate) fn record_sent(&self, raw_message: &str) {
+        self.record(Direction::Sent, raw_message);
+    }
+
+    pub(crate) fn record_received(&self, raw_message: &str) {
+        self.record(Direction::Received, raw_message);
+    }
+
+    fn record(&self, direction: Direction, raw_message: &str) {
+        let Ok(message) = serde_json::from_str(raw_message) else {
+            return;
+        };
+        let line = match serde_json::to_string(&RecordedMessage { direction, message }) {
+            Ok(line) => line,
+            Err(err) => {
+                warn!("Couldn't serialize recorded message: {err:?}");
+                return;
+            }
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(err) = writeln!(writer, "{line}").and_then(|()| writer.flush()) {
+            warn!("Couldn't write to protocol recording: {err:?}");
+        }
+    }
+}
+
+/// The messages of a target wrapped in a `Target.sendMessageToTarget` or
+/// `Target.receivedMessageFromTarget` message, if it is one.
+fn unwrap_target_message(message: &Json) -> Option<Json> {
+    let method = message["method"].as_str()?;
+    if method != "Target.sendMessageToTarget" && method != "Target.receivedMessageFromTarget" {
+        return None;
+    }
+    serde_json::from_str(message["params"]["message"].as_str()?).ok()
+}
+
+/// The methods of a command and of the commands it wraps, outermost first.
+fn method_chain(message: &Json) -> Vec<String> {
+    let mut chain = vec![message["method"].as_str().unwrap_or_default().to_string()];
+    if let Some(inner) = unwrap_target_message(message) {
+        chain.extend(method_chain(&inner));
+    }
+    chain
+}
+
+/// The IDs of a command and of the commands it wraps (or of the responses to them).
+fn id_chain(message: &Json) -> Vec<u64> {
+    let mut chain: Vec<u64> = message["id"].as_u64().into_iter().collect();
+    if let Some(inner) = unwrap_target_message(message) {
+        chain.extend(id_chain(&inner));
+    }
+    chain
+}
+
+/// Rewrites the IDs of responses from the recorded calls' to the replayed calls'.
+fn rewrite_ids(message: &mut Json, id_map: &HashMap<u64, u64>) {
+    if let Some(id) = message["id"].as_u64() {
+        if let Some(new_id) = id_map.get(&id) {
+            message["id"] = json!(new_id);
+        }
+    }
+    if let Some(mut inner) = unwrap_target_message(message) {
+        rewrite_ids(&mut inner, id_map);
+        message["params"]["message"] = json!(inner.to_string());
+    }
+}
+
+/// Plays back one recording over one connection.
+struct Replay {
+    messages: Vec<RecordedMessage>,
+    /// Which messages have been matched (if sent) or played back (if received).
+    used: Vec<bool>,
+    /// Maps the IDs of recorded calls to the IDs of the replayed calls they were matched with.
+    id_map: HashMap<u64, u64>,
+}
+
+impl Replay {
+    fn new(messages: Vec<RecordedMessage>) -> Self {
+        let used = vec![false; messages.len()];
+        Self {
+            messages,
+            used,
+            id_map: HashMap::new(),
+        }
+    }
+
+    /// The messages to send back in reply to `sent`.
+    fn reply_to(&mut self, sent: &Json) -> Vec<Json> {
+        let methods = method_chain(sent);
+        let matched = (0..self.messages.len()).find(|&index| {
+            !self.used[index]
+                && self.messages[index].direction == Direction::Sent
+                && method_chain(&self.messages[index].message) == methods
+        });
+        let Some(matched) = matched else {
+            debug!("No recorded call matches {methods:?}");
+            return sent["id"]
+                .as_u64()
+                .map(|id| {
+                    json!({
+                        "id": id,
+                        "error": {
+                            "code": -32000,
+                            "message": format!("No recorded call matches {}", methods.join(" > ")),
+                        },
+                    })
+                })
+                .into_iter()
+                .collect();
+        };
+        self.used[matched] = true;
+
+        let recorded_ids = id_chain(&self.messages[matched].message);
+        self.id_map
+            .extend(recorded_ids.iter().copied().zip(id_chain(sent)));
+        let recorded_ids: HashSet<u64> = recorded_ids.into_iter().collect();
+
+        // Everything received after the call until the next one, as well as the responses to the
+        // call, wherever they ended up in the recording
+        let next_sent = (matched + 1..self.messages.len())
+            .find(|&index| self.messages[index].direction == Direction::Sent)
+            .unwrap_or(self.messages.len());
+        let mut replies = Vec::new();
+        for index in matched + 1..self.messages.len() {
+            let recorded = &self.messages[index];
+            if self.used[index] || recorded.direction != Direction::Received {
+                continue;
+            }
+            let responds_to_call = id_chain(&recorded.message)
+                .iter()
+                .any(|id| recorded_ids.contains(id));
+            if index < next_sent || responds_to_call {
+                self.used[index] = true;
+                let mut reply = recorded.message.clone();
+                rewrite_ids(&mut reply, &self.id_map);
+                replies.push(reply);
+            }
+        }
+        replies
+    }
+}
+
+/// Plays back a recording made by a [`ProtocolRecorder`] to a `Browser` connected to it, instead
+/// of a real browser, for fast and deterministic tests of code built on this crate.
+///
+/// Each call the browser makes is matched with the first recorded call of the same method which
+/// hasn't been matched yet, and is answered with what the real browser sent back to that call
+/// (the recorded response, and the events received until the next recorded call). Calls which
+/// weren't recorded get an error, so the code under test should make the same calls it made
+/// while being recorded.
+///
+/// ```rust,no_run
+/// # use anyhow::Result;
+/// # fn main() -> Result<()> {
+/// #
+/// use headless_chrome::browser::transport::ReplayServer;
+/// use headless_chrome::{Browser, LaunchOptions};
+///
+/// // Once, against a real browser:
+/// let browser = Browser::new(
+///     LaunchOptions::default_builder()
+///         .record_protocol_to(Some("example.jsonl".into()))
+///         .build()?,
+/// )?;
+/// let tab = browser.new_tab()?;
+/// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+/// drop(browser);
+///
+/// // In tests, without one:
+/// let server = ReplayServer::from_file("example.jsonl")?;
+/// let browser = Browser::connect(server.ws_url())?;
+/// let tab = browser.new_tab()?;
+/// tab.navigate_to("https://example.com")?.wait_until_navigated()?;
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReplayServer {
+    port: u16,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl std::fmt::Debug for ReplayServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "ReplayServer {{ port: {} }}", self.port)
+    }
+}
+
+impl ReplayServer {
+    /// Plays back the recording in the file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(&fs::read_to_string(path)?)
+    }
+
+    /// Plays back `recording`, in the format written by a [`ProtocolRecorder`]. Every connection
+    /// made to the server gets its own playback of the whole recording.
+    pub fn new(recording: &str) -> Result<Self> {
+        let messages = recording
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<RecordedMessage>, _>>()?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let server_shutdown = Arc::clone(&shutdown);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let messages = messages.clone();
+                        thread::spawn(move || Self::serve(stream, messages));
+                    }
+                    Err(err) => debug!("Replay server couldn't accept a connection: {err}"),
+                }
+            }
+        });
+
+        Ok(Self { port, shutdown })
+    }
+
+    /// The URL to pass to `Browser::connect`.
+    pub fn ws_url(&self) -> String {
+        format!("ws://127.0.0.1:{}", self.port)
+    }
+
+    fn serve(stream: TcpStream, messages: Vec<RecordedMessage>) {
+        let mut socket = match tungstenite::accept(stream) {
+            Ok(socket) => socket,
+            Err(err) => {
+                debug!("Replay server couldn't accept a WebSocket: {err}");
+                return;
+            }
+        };
+        let mut replay = Replay::new(messages);
+
+        while let Ok(message) = socket.read_message() {
+            let tungstenite::protocol::Message::Text(text) = message else {
+                continue;
+            };
+            let Ok(sent) = serde_json::from_str::<Json>(&text) else {
+                continue;
+            };
+            trace!("Replaying reply to: {text}");
+            for reply in replay.reply_to(&sent) {
+                let frame = tungstenite::protocol::Message::Text(reply.to_string());
+                if socket.write_message(frame).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accepting thread so that it notices
+        TcpStream::connect(("127.0.0.1", self.port)).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(direction: Direction, message: Json) -> RecordedMessage {
+        RecordedMessage { direction, message }
+    }
+
+    fn wrapped(method: &str, session_id: &str, message: &Json) -> Json {
+        json!({
+            "method": method,
+            "params": {"sessionId": session_id, "message": message.to_string()},
+        })
+    }
+
+    #[test]
+    fn replays_responses_with_new_ids() {
+        let inner_call = json!({"id": 7, "method": "Page.navigate", "params": {"url": "a"}});
+        let mut outer_call = wrapped("Target.sendMessageToTarget", "s", &inner_call);
+        outer_call["id"] = json!(8);
+        let inner_response = json!({"id": 7, "result": {"frameId": "f"}});
+
+        let mut replay = Replay::new(vec![
+            recorded(
+                Direction::Sent,
+                json!({"id": 1, "method": "Browser.getVersion"}),
+            ),
+            recorded(Direction::Sent, outer_call),
+            recorded(Direction::Received, json!({"id": 8, "result": {}})),
+            recorded(
+                Direction::Received,
+                json!({"method": "Page.frameStartedLoading"}),
+            ),
+            recorded(
+                Direction::Received,
+                json!({"id": 1, "result": {"product": "x"}}),
+            ),
+            recorded(
+                Direction::Received,
+                wrapped("Target.receivedMessageFromTarget", "s", &inner_response),
+            ),
+        ]);
+
+        let replies = replay.reply_to(&json!({"id": 100, "method": "Browser.getVersion"}));
+        assert_eq!(
+            vec![json!({"id": 100, "result": {"product": "x"}})],
+            replies
+        );
+
+        let mut new_outer_call = wrapped(
+            "Target.sendMessageToTarget",
+            "s",
+            &json!({"id": 101, "method": "Page.navigate", "params": {"url": "a"}}),
+        );
+        new_outer_call["id"] = json!(102);
+        let replies = replay.reply_to(&new_outer_call);
+        assert_eq!(3, replies.len());
+        assert_eq!(json!(102), replies[0]["id"]);
+        assert_eq!("Page.frameStartedLoading", replies[1]["method"]);
+        assert_eq!(
+            json!({"id": 101, "result": {"frameId": "f"}}),
+            unwrap_target_message(&replies[2]).unwrap()
+        );
+
+        let replies = replay.reply_to(&json!({"id": 103, "method": "Browser.getVersion"}));
+        assert_eq!(json!(103), replies[0]["id"]);
+        assert!(replies[0]["error"].is_object());
+    }
+}
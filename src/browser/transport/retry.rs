@@ -0,0 +1,175 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::logging::debug;
+use anyhow::Result;
+use rand::Rng;
+use thiserror::Error;
+
+use crate::types::RemoteError;
+use crate::util::Timeout;
+
+/// Errors the browser reports while a target is going away or being replaced (e.g. by a
+/// navigation), which usually go away when the call is made again.
+const TRANSIENT_ERROR_MESSAGES: &[&str] = &[
+    "target closed",
+    "session with given id not found",
+    "no target with given id",
+    "inspected target navigated or closed",
+    "execution context was destroyed",
+    "cannot find context with specified id",
+];
+
+#[derive(Debug, Error)]
+#[error("Call still failed after {} attempts: {}", errors.len(), errors.last().map(ToString::to_string).unwrap_or_default())]
+pub struct RetriesExhausted {
+    /// The error of every attempt, oldest first.
+    pub errors: Vec<anyhow::Error>,
+}
+
+/// How to retry calls which failed with a transient error, see `Transport::call_with_retry` and
+/// `Tab::call_method_with_retry`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// How many times to make the call at most, including the first attempt.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry.
+    pub initial_backoff: Duration,
+    /// The backoff is multiplied by this after every retry.
+    pub multiplier: f64,
+    /// The backoff never grows beyond this.
+    pub max_backoff: Duration,
+    /// How much each backoff is randomly shortened or lengthened by, as a fraction of it (e.g.
+    /// 0.2 for ±20%), so that callers failing together don't all retry together.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(100),
+            multiplier: 2.0,
+            max_backoff: Duration::from_secs(2),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether `error` is worth retrying: a timeout, or the browser reporting that the target or
+    /// session the call was for is (temporarily) gone.
+    pub fn is_transient(error: &anyhow::Error) -> bool {
+        if error.is::<Timeout>() {
+            return true;
+        }
+        error.downcast_ref::<RemoteError>().is_some_and(|error| {
+            let message = error.message.to_lowercase();
+            TRANSIENT_ERROR_MESSAGES
+                .iter()
+                .any(|transient| message.contains(transient))
+        })
+    }
+
+    /// Calls `call` until it succeeds, fails with an error which isn't transient (which is
+    /// returned as is), or `max_attempts` is reached (which returns a `RetriesExhausted` error
+    /// with every attempt's error).
+    pub fn run<T, F>(&self, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>,
+    {
+        let mut errors = Vec::new();
+        let mut backoff = self.initial_backoff;
+        loop {
+            let error = match call() {
+                Ok(result) => return Ok(result),
+                Err(error) if !Self::is_transient(&error) => return Err(error),
+                Err(error) => error,
+            };
+            debug!("Transient error, attempt {}: {error:?}", errors.len() + 1);
+            errors.push(error);
+            if errors.len() >= self.max_attempts as usize {
+                return Err(RetriesExhausted { errors }.into());
+            }
+
+            thread::sleep(self.jittered(backoff));
+            backoff = backoff.mul_f64(self.multiplier).min(self.max_backoff);
+        }
+    }
+
+    fn jittered(&self, backoff: Duration) -> Duration {
+        if self.jitter <= 0.0 || backoff.is_zero() {
+            return backoff;
+        }
+        let factor = rand::thread_rng().gen_range(1.0 - self.jitter..=1.0 + self.jitter);
+        backoff.mul_f64(factor.max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote_error(message: &str) -> anyhow::Error {
+        RemoteError {
+            code: -32000,
+            message: message.to_string(),
+        }
+        .into()
+    }
+
+    fn immediate(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::ZERO,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let mut attempts = 0;
+        let result = immediate(3).run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(remote_error("Session with given id not found."))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(3, result.unwrap());
+    }
+
+    #[test]
+    fn gives_up_with_every_error() {
+        let mut attempts = 0;
+        let error = immediate(2)
+            .run(|| -> Result<()> {
+                attempts += 1;
+                Err(Timeout.into())
+            })
+            .unwrap_err();
+        assert_eq!(2, attempts);
+        assert_eq!(
+            2,
+            error
+                .downcast_ref::<RetriesExhausted>()
+                .unwrap()
+                .errors
+                .len()
+        );
+    }
+
+    #[test]
+    fn returns_other_errors_straight_away() {
+        let mut attempts = 0;
+        let error = immediate(5)
+            .run(|| -> Result<()> {
+                attempts += 1;
+                Err(remote_error("Invalid parameters"))
+            })
+            .unwrap_err();
+        assert_eq!(1, attempts);
+        assert!(error.is::<RemoteError>());
+    }
+}
@@ -2,8 +2,9 @@ use std::collections::HashMap;
 use std::sync::mpsc;
 use std::sync::Mutex;
 
+use crate::logging::{trace, warn};
 use anyhow::Result;
-use log::trace;
+use thiserror::Error;
 
 use crate::types::{CallId, Response};
 
@@ -13,6 +14,12 @@ trait IdentifiableResponse {
     fn call_id(&self) -> CallId;
 }
 
+#[derive(Debug, Error)]
+#[error("Call {call_id} is already waiting for a response")]
+pub struct DuplicateCallId {
+    pub call_id: CallId,
+}
+
 #[derive(Debug)]
 pub struct WaitingCallRegistry {
     calls: Mutex<HashMap<CallId, mpsc::Sender<Result<Response>>>>,
@@ -37,28 +44,47 @@ impl WaitingCallRegistry {
         Default::default()
     }
 
-    pub fn resolve_call(&self, response: Response) -> Result<()> {
+    /// Hands `response` to the call waiting for it. Responses nothing is waiting for, e.g. late
+    /// ones to calls which timed out, or a second one to the same call, are logged and dropped.
+    pub fn resolve_call(&self, response: Response) {
         trace!("Resolving call");
-        let waiting_call_tx: mpsc::Sender<Result<Response>> = {
-            let mut waiting_calls = self.calls.lock().unwrap();
-            waiting_calls.remove(&response.call_id()).unwrap()
+        let call_id = response.call_id();
+        let waiting_call_tx = self.calls.lock().unwrap().remove(&call_id);
+        let Some(waiting_call_tx) = waiting_call_tx else {
+            warn!("Got a response to call {call_id}, which isn't waiting for one (it may have timed out or already been answered)");
+            return;
         };
-        waiting_call_tx.send(Ok(response))?;
-        Ok(())
+        if waiting_call_tx.send(Ok(response)).is_err() {
+            warn!("Got a response to call {call_id} after it stopped waiting for it");
+        }
     }
 
-    pub fn register_call(&self, call_id: CallId) -> mpsc::Receiver<Result<Response>> {
-        let (tx, rx) = mpsc::channel::<Result<Response>>();
+    /// Registers a call which is about to be sent. Fails if there's already one with the same
+    /// id waiting, which can only happen once ids wrap around on a very long-lived connection.
+    pub fn register_call(
+        &self,
+        call_id: CallId,
+    ) -> Result<mpsc::Receiver<Result<Response>>, DuplicateCallId> {
         let mut calls = self.calls.lock().unwrap();
+        if calls.contains_key(&call_id) {
+            warn!("Call {call_id} is still waiting for a response, not reusing its id");
+            return Err(DuplicateCallId { call_id });
+        }
+        let (tx, rx) = mpsc::channel::<Result<Response>>();
         calls.insert(call_id, tx);
         trace!("registered {:?}", call_id);
-        rx
+        Ok(rx)
+    }
+
+    /// How many calls are waiting for their responses.
+    pub fn outstanding(&self) -> usize {
+        self.calls.lock().unwrap().len()
     }
 
     pub fn unregister_call(&self, call_id: CallId) {
         trace!("Deregistering call");
         let mut calls = self.calls.lock().unwrap();
-        calls.remove(&call_id).unwrap();
+        calls.remove(&call_id);
     }
 
     // TODO: make it so we can pass in whatever error we want here
@@ -84,7 +110,7 @@ impl WaitingCallRegistry {
 
 #[cfg(test)]
 mod tests {
-    use serde_json::json;
+    use serde_json::value::to_raw_value;
 
     use super::*;
 
@@ -94,27 +120,53 @@ mod tests {
 
         let waiting_calls = WaitingCallRegistry::new();
 
-        let call_rx = waiting_calls.register_call(431);
+        let call_rx = waiting_calls.register_call(431).unwrap();
         let resp = Response {
             call_id: 431,
-            result: Some(json! {true}),
+            result: Some(to_raw_value(&true).unwrap()),
             error: None,
         };
         let resp_clone = resp.clone();
 
-        let call_rx2 = waiting_calls.register_call(123);
+        let call_rx2 = waiting_calls.register_call(123).unwrap();
         let resp2 = Response {
             call_id: 123,
-            result: Some(json! {false}),
+            result: Some(to_raw_value(&false).unwrap()),
             error: None,
         };
         let cloned_resp = resp2.clone();
 
-        waiting_calls.resolve_call(resp).unwrap();
-        waiting_calls.resolve_call(resp2).unwrap();
+        waiting_calls.resolve_call(resp);
+        waiting_calls.resolve_call(resp2);
 
         // note how they're in reverse order to that in which they were called!
         assert_eq!(cloned_resp, call_rx2.recv().unwrap().unwrap());
         assert_eq!(resp_clone, call_rx.recv().unwrap().unwrap());
     }
+
+    #[test]
+    fn rejects_duplicate_ids_and_ignores_stray_responses() {
+        let waiting_calls = WaitingCallRegistry::new();
+
+        let call_rx = waiting_calls.register_call(7).unwrap();
+        assert_eq!(7, waiting_calls.register_call(7).unwrap_err().call_id);
+
+        let resp = Response {
+            call_id: 7,
+            result: Some(to_raw_value(&true).unwrap()),
+            error: None,
+        };
+        waiting_calls.resolve_call(resp.clone());
+        // A second response to the same call, and one to a call nobody made
+        waiting_calls.resolve_call(resp.clone());
+        waiting_calls.resolve_call(Response {
+            call_id: 8,
+            ..resp.clone()
+        });
+
+        assert_eq!(resp, call_rx.recv().unwrap().unwrap());
+        assert_eq!(0, waiting_calls.outstanding());
+        // The id can be used again once its call has been answered
+        assert!(waiting_calls.register_call(7).is_ok());
+    }
 }
@@ -1,28 +1,37 @@
+use std::io::{Read, Write};
 use std::net::TcpStream;
-use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use crate::logging::{debug, info, trace};
 use anyhow::Result;
-use log::{debug, info, trace, warn};
 use tungstenite::http::Response;
 use tungstenite::stream::MaybeTlsStream;
 use url::Url;
 
-use crate::types::{parse_raw_message, Message};
+use thiserror::Error;
 
-type TungsteniteWebsocketConnection = tungstenite::protocol::WebSocket<MaybeTlsStream<TcpStream>>;
+use super::connection::{Connection, MessageHandler};
+
+type TungsteniteWebsocketConnection<S> = tungstenite::protocol::WebSocket<S>;
 
 const READ_TIMEOUT_DURATION: std::time::Duration = std::time::Duration::from_millis(100);
 
-pub struct WebSocketConnection {
-    connection: Arc<Mutex<TungsteniteWebsocketConnection>>,
+#[derive(Debug, Error)]
+#[error("The WebSocket connection uses a TLS implementation this build doesn't support; enable the `native-tls` or `rustls` feature")]
+pub struct UnsupportedStream {}
+
+/// A WebSocket connection to the browser over a stream of type `S`: a TCP (or TLS) stream unless
+/// made with [`WebSocketConnection::over_unix_stream`].
+pub struct WebSocketConnection<S = MaybeTlsStream<TcpStream>> {
+    connection: Arc<Mutex<TungsteniteWebsocketConnection<S>>>,
     thread: std::thread::JoinHandle<()>,
     process_id: Option<u32>,
 }
 
 // TODO websocket::sender::Writer is not :Debug...
-impl std::fmt::Debug for WebSocketConnection {
+impl<S> std::fmt::Debug for WebSocketConnection<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         write!(f, "WebSocketConnection {{}}")
     }
@@ -32,50 +41,117 @@ impl WebSocketConnection {
     pub fn new(
         ws_url: &Url,
         process_id: Option<u32>,
-        messages_tx: mpsc::Sender<Message>,
+        incoming: impl MessageHandler,
+        ping_interval: Option<Duration>,
     ) -> Result<Self> {
         let (connection, _) = Self::websocket_connection(ws_url)?;
+        Ok(Self::start(connection, process_id, incoming, ping_interval))
+    }
+
+    /// Like `new`, but does the WebSocket handshake over an already connected `stream`, e.g. one
+    /// bound to a particular local interface or opened through a tunnel, rather than connecting
+    /// to `ws_url`'s host.
+    pub fn over_stream(
+        stream: TcpStream,
+        ws_url: &Url,
+        process_id: Option<u32>,
+        incoming: impl MessageHandler,
+        ping_interval: Option<Duration>,
+    ) -> Result<Self> {
+        stream.set_read_timeout(Some(READ_TIMEOUT_DURATION))?;
+        let connection = handshake(MaybeTlsStream::Plain(stream), ws_url)?;
+        Ok(Self::start(connection, process_id, incoming, ping_interval))
+    }
+
+    pub fn websocket_connection(
+        ws_url: &Url,
+    ) -> Result<(
+        tungstenite::WebSocket<MaybeTlsStream<TcpStream>>,
+        Response<Option<Vec<u8>>>,
+    )> {
+        let mut client = tungstenite::connect(ws_url)?;
+
+        let stream = client.0.get_mut();
+
+        // this should be handled in tungstenite
+        let stream = match stream {
+            MaybeTlsStream::Plain(s) => s,
+            #[cfg(feature = "native-tls")]
+            MaybeTlsStream::NativeTls(s) => s.get_mut(),
+            #[cfg(feature = "rustls")]
+            MaybeTlsStream::Rustls(s) => &mut s.sock,
+
+            _ => return Err(UnsupportedStream {}.into()),
+        };
+        stream.set_read_timeout(Some(READ_TIMEOUT_DURATION))?;
 
+        debug!("Successfully connected to WebSocket: {}", ws_url);
+
+        Ok(client)
+    }
+}
+
+#[cfg(unix)]
+impl WebSocketConnection<std::os::unix::net::UnixStream> {
+    /// Like `over_stream`, but over a Unix domain socket, e.g. one an SSH tunnel forwards to the
+    /// browser's debugging port (`ssh -L /tmp/chrome.sock:localhost:9222 farm-host`).
+    pub fn over_unix_stream(
+        stream: std::os::unix::net::UnixStream,
+        ws_url: &Url,
+        process_id: Option<u32>,
+        incoming: impl MessageHandler,
+        ping_interval: Option<Duration>,
+    ) -> Result<Self> {
+        stream.set_read_timeout(Some(READ_TIMEOUT_DURATION))?;
+        let connection = handshake(stream, ws_url)?;
+        Ok(Self::start(connection, process_id, incoming, ping_interval))
+    }
+}
+
+impl<S: Read + Write + Send + 'static> WebSocketConnection<S> {
+    fn start(
+        connection: TungsteniteWebsocketConnection<S>,
+        process_id: Option<u32>,
+        incoming: impl MessageHandler,
+        ping_interval: Option<Duration>,
+    ) -> Self {
         let connection = Arc::new(Mutex::new(connection));
 
         let thread = {
             let sender = connection.clone();
             std::thread::spawn(move || {
                 trace!("Starting msg dispatching loop");
-                Self::dispatch_incoming_messages(sender, messages_tx, process_id);
+                Self::dispatch_incoming_messages(sender, &incoming, process_id, ping_interval);
                 trace!("Quit loop msg dispatching loop");
             })
         };
 
-        Ok(Self {
+        Self {
             connection,
             thread,
             process_id,
-        })
-    }
-
-    pub fn shutdown(&self) {
-        trace!(
-            "Shutting down WebSocket connection for Chrome {:?}",
-            self.process_id
-        );
-        if let Err(err) = self.connection.lock().unwrap().close(None) {
-            debug!(
-                "Couldn't shut down WS connection for Chrome {:?}: {}",
-                self.process_id, err
-            );
         }
-
-        self.connection.lock().unwrap().write_pending().ok();
-        self.thread.thread().unpark();
     }
 
     fn dispatch_incoming_messages(
-        receiver: Arc<Mutex<TungsteniteWebsocketConnection>>,
-        messages_tx: mpsc::Sender<Message>,
+        receiver: Arc<Mutex<TungsteniteWebsocketConnection<S>>>,
+        incoming: &impl MessageHandler,
         process_id: Option<u32>,
+        ping_interval: Option<Duration>,
     ) {
+        let mut last_ping = Instant::now();
         loop {
+            if let Some(ping_interval) = ping_interval {
+                if last_ping.elapsed() >= ping_interval {
+                    last_ping = Instant::now();
+                    trace!("Sending keep-alive ping to Chrome #{process_id:?}");
+                    let ping = tungstenite::protocol::Message::Ping(Vec::new());
+                    if let Err(err) = receiver.lock().unwrap().write_message(ping) {
+                        debug!("Couldn't ping Chrome #{process_id:?}: {err}");
+                    }
+                }
+            }
+
             let message = receiver.lock().unwrap().read_message();
 
             match message {
@@ -100,68 +176,87 @@ impl WebSocketConnection {
                         panic!("Unhandled WebSocket error for Chrome #{process_id:?}: {error:?}");
                     }
                 },
-                Ok(message) => {
-                    if let tungstenite::protocol::Message::Text(message_string) = message {
-                        if let Ok(message) = parse_raw_message(&message_string) {
-                            if messages_tx.send(message).is_err() {
-                                break;
-                            }
-                        } else {
-                            trace!(
-                                "Incoming message isn't recognised as event or method response: {}",
-                                message_string
-                            );
+                Ok(message) => match message {
+                    tungstenite::protocol::Message::Text(message_string) => {
+                        if !incoming.text(&message_string) {
+                            break;
                         }
-                    } else {
-                        panic!("Got a weird message: {message:?}");
                     }
-                }
+                    tungstenite::protocol::Message::Binary(payload) => {
+                        if !incoming.binary(payload) {
+                            break;
+                        }
+                    }
+                    tungstenite::protocol::Message::Ping(_) => {
+                        // tungstenite has queued the Pong reply, send it straight away
+                        trace!("Got a ping from Chrome #{process_id:?}");
+                        if let Err(err) = receiver.lock().unwrap().write_pending() {
+                            debug!("Couldn't answer ping from Chrome #{process_id:?}: {err}");
+                        }
+                    }
+                    tungstenite::protocol::Message::Pong(_) => {
+                        trace!("Got a pong from Chrome #{process_id:?}");
+                    }
+                    tungstenite::protocol::Message::Close(frame) => {
+                        debug!("Chrome #{process_id:?} closed the WebSocket: {frame:?}");
+                        // Sends tungstenite's reply to the Close frame
+                        receiver.lock().unwrap().write_pending().ok();
+                        break;
+                    }
+                    tungstenite::protocol::Message::Frame(_) => {
+                        trace!("Got a raw frame from Chrome #{process_id:?}");
+                    }
+                },
             }
         }
 
-        info!("Sending shutdown message to message handling loop");
-        if messages_tx.send(Message::ConnectionShutdown).is_err() {
-            warn!("Couldn't send message to transport loop telling it to shut down");
-        }
+        incoming.closed();
     }
+}
 
-    pub fn websocket_connection(
-        ws_url: &Url,
-    ) -> Result<(
-        tungstenite::WebSocket<MaybeTlsStream<TcpStream>>,
-        Response<Option<Vec<u8>>>,
-    )> {
-        let mut client = tungstenite::connect(ws_url)?;
-
-        let stream = client.0.get_mut();
-
-        // this should be handled in tungstenite
-        let stream = match stream {
-            MaybeTlsStream::Plain(s) => s,
-            #[cfg(features = "native-tls")]
-            MaybeTlsStream::NativeTls(s) => s.get_mut(),
-            #[cfg(features = "rustls")]
-            MaybeTlsStream::Rustls(s) => &mut s.sock,
-
-            _ => todo!(),
-        };
-        stream.set_read_timeout(Some(READ_TIMEOUT_DURATION))?;
-
-        debug!("Successfully connected to WebSocket: {}", ws_url);
-
-        Ok(client)
-    }
+/// Does the WebSocket handshake for `ws_url` over `stream`, which must already have its read
+/// timeout set.
+fn handshake<S: Read + Write>(
+    stream: S,
+    ws_url: &Url,
+) -> Result<TungsteniteWebsocketConnection<S>> {
+    let (connection, _) = tungstenite::client(ws_url.as_str(), stream)
+        .map_err(|err| anyhow::anyhow!("WebSocket handshake failed: {err}"))?;
+    debug!("Successfully connected to WebSocket: {}", ws_url);
+    Ok(connection)
+}
 
-    pub fn send_message(&self, message_text: &str) -> Result<()> {
+impl<S: Read + Write + Send + 'static> Connection for WebSocketConnection<S> {
+    fn send_message(&self, message_text: &str) -> Result<()> {
         let message = tungstenite::protocol::Message::text(message_text);
         let mut sender = self.connection.lock().unwrap();
         sender.write_message(message)?;
         self.thread.thread().unpark();
         Ok(())
     }
+
+    fn shutdown(&self) {
+        trace!(
+            "Shutting down WebSocket connection for Chrome {:?}",
+            self.process_id
+        );
+        let close_frame = tungstenite::protocol::CloseFrame {
+            code: tungstenite::protocol::frame::coding::CloseCode::Normal,
+            reason: "".into(),
+        };
+        if let Err(err) = self.connection.lock().unwrap().close(Some(close_frame)) {
+            debug!(
+                "Couldn't shut down WS connection for Chrome {:?}: {}",
+                self.process_id, err
+            );
+        }
+
+        self.connection.lock().unwrap().write_pending().ok();
+        self.thread.thread().unpark();
+    }
 }
 
-impl Drop for WebSocketConnection {
+impl<S> Drop for WebSocketConnection<S> {
     fn drop(&mut self) {
         info!("dropping websocket connection");
     }
@@ -88,14 +88,19 @@ extern crate log;
 
 pub use browser::{
     tab::{element::Element, Tab},
-    Browser, LaunchOptions, LaunchOptionsBuilder,
+    Browser, BrowserProduct, FontRenderHinting, HeadlessMode, LaunchOptions, LaunchOptionsBuilder,
+    LaunchProfile,
 };
 
 #[cfg(feature = "fetch")]
 pub use browser::FetcherOptions;
 
 pub mod browser;
+mod logging;
 pub mod protocol;
+pub mod render;
+#[cfg(feature = "image")]
+pub mod screenshots;
 pub mod types;
 pub mod util;
 
@@ -0,0 +1,8 @@
+//! The macros the crate logs with: `tracing`'s with the `tracing` feature, so that what's logged
+//! during a call lands in its `cdp_call` span, and `log`'s otherwise. `tracing`'s still reach
+//! `log` loggers while no `tracing` subscriber is set.
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use log::{debug, error, info, trace, warn};
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{debug, error, info, trace, warn};
@@ -0,0 +1,199 @@
+//! Rendering HTML or a URL to a PNG in one call, for services which turn markup into images
+//! (social cards, receipts, charts...).
+//!
+//! The functions here share a `BrowserPool`, launched the first time one of them is called.
+//! Render with a `Renderer` instead to use a pool of your own.
+//!
+//! ```rust,no_run
+//! # use anyhow::Result;
+//! # fn main() -> Result<()> {
+//! #
+//! use headless_chrome::render::{self, RenderOptions, Viewport};
+//!
+//! let png = render::html_to_png(
+//!     "<h1 style='font-family: serif'>Hello!</h1>",
+//!     Viewport { width: 1200, height: 630, device_scale_factor: 1.0 },
+//!     &RenderOptions::default(),
+//! )?;
+//! std::fs::write("card.png", png)?;
+//! #
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::logging::warn;
+use anyhow::Result;
+
+use crate::browser::pool::{BrowserPool, PoolOptions};
+use crate::browser::tab::WaitUntil;
+use crate::protocol::cdp::Page;
+use crate::{Browser, Tab};
+
+/// Waits for the page's web fonts to load and its images to load (or fail to) and decode.
+const ASSETS_READY: &str = r"(async () => {
+    await document.fonts.ready;
+    await Promise.all(Array.from(document.images, image =>
+        image.decode().catch(() => {})
+    ));
+})()";
+
+/// The size of the page being rendered, in CSS pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+    /// How many image pixels make up a CSS pixel, e.g. 2.0 for a "retina" image.
+    pub device_scale_factor: f64,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 800,
+            device_scale_factor: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Captures the whole page rather than just the viewport.
+    pub full_page: bool,
+    /// Leaves the background transparent where the page doesn't paint one, rather than white.
+    pub transparent_background: bool,
+    /// How long the page, its fonts and its images may take to load.
+    pub timeout: Duration,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            full_page: false,
+            transparent_background: false,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Renders pages to images in tabs of the browsers of a `BrowserPool`, each in a new tab which
+/// is closed afterwards. Cheap to clone.
+#[derive(Clone)]
+pub struct Renderer {
+    pool: BrowserPool,
+}
+
+impl Renderer {
+    pub fn new(pool: BrowserPool) -> Self {
+        Self { pool }
+    }
+
+    /// The renderer `html_to_png` and `url_to_png` use, whose pool launches browsers with the
+    /// default `LaunchOptions`.
+    pub fn shared() -> &'static Self {
+        static SHARED: OnceLock<Renderer> = OnceLock::new();
+        SHARED.get_or_init(|| Self::new(BrowserPool::new(PoolOptions::default(), Browser::default)))
+    }
+
+    /// Renders `html` to a PNG. The markup has no URL, so relative URLs in it can't be
+    /// resolved; use absolute ones or a `<base>` element.
+    pub fn html_to_png(
+        &self,
+        html: &str,
+        viewport: Viewport,
+        options: &RenderOptions,
+    ) -> Result<Vec<u8>> {
+        self.render(viewport, options, |tab| {
+            tab.set_content(html, WaitUntil::Load)?;
+            Ok(())
+        })
+    }
+
+    /// Navigates to `url` and renders the page to a PNG.
+    pub fn url_to_png(
+        &self,
+        url: &str,
+        viewport: Viewport,
+        options: &RenderOptions,
+    ) -> Result<Vec<u8>> {
+        self.render(viewport, options, |tab| {
+            tab.navigate_to(url)?.wait_until_navigated()?;
+            Ok(())
+        })
+    }
+
+    fn render<F>(&self, viewport: Viewport, options: &RenderOptions, load: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&Arc<Tab>) -> Result<()>,
+    {
+        self.pool.run(|browser| {
+            let tab = browser.new_tab()?;
+            let png = capture(&tab, viewport, options, load);
+            if let Err(err) = tab.close(false) {
+                warn!("Failed to close the tab rendered in: {err:?}");
+            }
+            png
+        })
+    }
+}
+
+fn capture<F>(
+    tab: &Arc<Tab>,
+    viewport: Viewport,
+    options: &RenderOptions,
+    load: F,
+) -> Result<Vec<u8>>
+where
+    F: FnOnce(&Arc<Tab>) -> Result<()>,
+{
+    tab.set_default_timeout(options.timeout);
+    tab.set_viewport(
+        viewport.width,
+        viewport.height,
+        viewport.device_scale_factor,
+        false,
+    )?;
+    if options.transparent_background {
+        tab.set_transparent_background_color()?;
+    }
+    load(tab)?;
+    tab.evaluate(ASSETS_READY, true)?;
+
+    let clip = if options.full_page {
+        let content_size = tab
+            .call_method(Page::GetLayoutMetrics(None))?
+            .css_content_size;
+        Some(Page::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: content_size.width,
+            height: content_size.height,
+            scale: 1.0,
+        })
+    } else {
+        None
+    };
+    let data = tab
+        .call_method(Page::CaptureScreenshot {
+            format: Some(Page::CaptureScreenshotFormatOption::Png),
+            quality: None,
+            clip,
+            from_surface: Some(true),
+            capture_beyond_viewport: Some(options.full_page),
+        })?
+        .data;
+    base64::decode(data).map_err(Into::into)
+}
+
+/// Renders `html` to a PNG with the shared `Renderer`, see `Renderer::html_to_png`.
+pub fn html_to_png(html: &str, viewport: Viewport, options: &RenderOptions) -> Result<Vec<u8>> {
+    Renderer::shared().html_to_png(html, viewport, options)
+}
+
+/// Renders the page at `url` to a PNG with the shared `Renderer`.
+pub fn url_to_png(url: &str, viewport: Viewport, options: &RenderOptions) -> Result<Vec<u8>> {
+    Renderer::shared().url_to_png(url, viewport, options)
+}
@@ -0,0 +1,209 @@
+//! Comparing screenshots, for visual regression tests. Requires the `image` feature.
+//!
+//! ```rust,no_run
+//! # use anyhow::Result;
+//! # fn main() -> Result<()> {
+//! #
+//! use headless_chrome::screenshots;
+//!
+//! let baseline = std::fs::read("baseline.png")?;
+//! let current = std::fs::read("current.png")?;
+//! let diff = screenshots::diff(&baseline, &current, 0.1)?;
+//! if diff.mismatched_pixels > 0 {
+//!     std::fs::write("diff.png", &diff.diff_png)?;
+//! }
+//! #
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::Result;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Can't compare a {}x{} screenshot to a {}x{} one", first.0, first.1, second.0, second.1)]
+pub struct SizeMismatch {
+    pub first: (u32, u32),
+    pub second: (u32, u32),
+}
+
+#[derive(Debug, Error)]
+#[error("Screenshot differs from {baseline} in {mismatch_percentage:.2}% of pixels, see {diff}")]
+pub struct ScreenshotMismatch {
+    pub baseline: String,
+    /// Where the image highlighting the differences was written.
+    pub diff: String,
+    pub mismatch_percentage: f64,
+}
+
+/// The result of comparing two screenshots with `diff`.
+#[derive(Debug, Clone)]
+pub struct ScreenshotDiff {
+    pub width: u32,
+    pub height: u32,
+    pub mismatched_pixels: u64,
+    /// The share of pixels which differ, from 0 to 100.
+    pub mismatch_percentage: f64,
+    /// A PNG of the first screenshot faded to grey, with the mismatched pixels in red.
+    pub diff_png: Vec<u8>,
+}
+
+/// An RGBA image, 8 bits per channel.
+struct Rgba {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// Compares two PNG screenshots pixel by pixel. `threshold` is how different two pixels may be
+/// while still counting as a match, from 0 (exactly equal) to 1 (anything goes), as a share of
+/// the largest possible difference in any of their channels; around 0.1 ignores anti-aliasing
+/// noise.
+#[allow(clippy::cast_precision_loss)] // pixel counts are nowhere near 2^52
+pub fn diff(a: &[u8], b: &[u8], threshold: f64) -> Result<ScreenshotDiff> {
+    let a = decode(a)?;
+    let b = decode(b)?;
+    if (a.width, a.height) != (b.width, b.height) {
+        return Err(SizeMismatch {
+            first: (a.width, a.height),
+            second: (b.width, b.height),
+        }
+        .into());
+    }
+
+    let tolerance = (threshold.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut mismatched_pixels = 0;
+    let mut diff_pixels = Vec::with_capacity(a.pixels.len());
+    for (pixel_a, pixel_b) in a.pixels.chunks_exact(4).zip(b.pixels.chunks_exact(4)) {
+        let matches = pixel_a
+            .iter()
+            .zip(pixel_b)
+            .all(|(channel_a, channel_b)| channel_a.abs_diff(*channel_b) <= tolerance);
+        if matches {
+            let grey = faded_grey(pixel_a);
+            diff_pixels.extend([grey, grey, grey, 255]);
+        } else {
+            mismatched_pixels += 1;
+            diff_pixels.extend([255, 0, 0, 255]);
+        }
+    }
+
+    let total_pixels = u64::from(a.width) * u64::from(a.height);
+    let mismatch_percentage = if total_pixels == 0 {
+        0.0
+    } else {
+        mismatched_pixels as f64 * 100.0 / total_pixels as f64
+    };
+    Ok(ScreenshotDiff {
+        width: a.width,
+        height: a.height,
+        mismatched_pixels,
+        mismatch_percentage,
+        diff_png: encode(&Rgba {
+            width: a.width,
+            height: a.height,
+            pixels: diff_pixels,
+        })?,
+    })
+}
+
+/// A light grey of the pixel's luminance, so the mismatches stand out.
+fn faded_grey(pixel: &[u8]) -> u8 {
+    let luminance =
+        (u32::from(pixel[0]) * 299 + u32::from(pixel[1]) * 587 + u32::from(pixel[2]) * 114) / 1000;
+    (180 + luminance * 75 / 255) as u8
+}
+
+fn decode(png_data: &[u8]) -> Result<Rgba> {
+    let mut decoder = png::Decoder::new(png_data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    buffer.truncate(frame.buffer_size());
+
+    let pixels = match frame.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer
+            .iter()
+            .flat_map(|grey| [*grey, *grey, *grey, 255])
+            .collect(),
+        // Expanded into RGB(A) by normalize_to_color8
+        png::ColorType::Indexed => unreachable!(),
+    };
+    Ok(Rgba {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+    })
+}
+
+fn encode(image: &Rgba) -> Result<Vec<u8>> {
+    let mut png_data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png_data, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    writer.finish()?;
+    Ok(png_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> Vec<u8> {
+        encode(&Rgba {
+            width,
+            height,
+            pixels: rgba.repeat((width * height) as usize),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn counts_mismatched_pixels() {
+        let mut pixels = [255, 255, 255, 255].repeat(4);
+        pixels[..4].copy_from_slice(&[0, 0, 0, 255]);
+        let changed = encode(&Rgba {
+            width: 2,
+            height: 2,
+            pixels,
+        })
+        .unwrap();
+
+        let diff = diff(&solid(2, 2, [255, 255, 255, 255]), &changed, 0.1).unwrap();
+        assert_eq!(1, diff.mismatched_pixels);
+        assert!((diff.mismatch_percentage - 25.0).abs() < f64::EPSILON);
+        assert_eq!(
+            [255, 0, 0, 255],
+            decode(&diff.diff_png).unwrap().pixels[..4]
+        );
+    }
+
+    #[test]
+    fn tolerates_differences_within_threshold() {
+        let diff = diff(
+            &solid(3, 1, [100, 100, 100, 255]),
+            &solid(3, 1, [110, 100, 100, 255]),
+            0.1,
+        )
+        .unwrap();
+        assert_eq!(0, diff.mismatched_pixels);
+    }
+
+    #[test]
+    fn rejects_different_sizes() {
+        let error = diff(&solid(2, 2, [0; 4]), &solid(2, 3, [0; 4]), 0.0).unwrap_err();
+        assert!(error.is::<SizeMismatch>());
+    }
+}
@@ -9,6 +9,7 @@ use crate::protocol::cdp::{
 
 use serde::{Deserialize, Serialize};
 
+use serde_json::value::RawValue;
 use serde_json::Value;
 
 pub type CallId = JsUInt;
@@ -26,14 +27,25 @@ pub struct RemoteError {
     pub message: String,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Response {
     #[serde(rename(deserialize = "id"))]
     pub call_id: CallId,
-    pub result: Option<Value>,
+    /// The result's JSON as it arrived, so that it only gets parsed once, straight into the
+    /// method's return object.
+    pub result: Option<Box<RawValue>>,
     pub error: Option<RemoteError>,
 }
 
+impl PartialEq for Response {
+    fn eq(&self, other: &Self) -> bool {
+        self.call_id == other.call_id
+            && self.error == other.error
+            && self.result.as_ref().map(|result| result.get())
+                == other.result.as_ref().map(|result| result.get())
+    }
+}
+
 pub fn parse_response<T>(response: Response) -> Result<T>
 where
     T: serde::de::DeserializeOwned + std::fmt::Debug,
@@ -42,18 +54,95 @@ where
         return Err(error.into());
     }
 
-    let result: T = serde_json::from_value(response.result.unwrap()).unwrap();
+    let result: T = serde_json::from_str(response.result.unwrap().get()).unwrap();
 
     Ok(result)
 }
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(untagged)]
+include!(concat!(env!("OUT_DIR"), "/event_methods.rs"));
+
+/// Events the protocol definitions don't know, e.g. from a newer or vendor-specific domain, arrive
+/// as `Event::Other`. Known events whose params don't match the definitions fail to parse.
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        /// Just the name of an event, which is read without parsing its params.
+        #[derive(Deserialize)]
+        struct EventMethod<'a> {
+            #[serde(borrow)]
+            method: std::borrow::Cow<'a, str>,
+        }
+
+        #[derive(Deserialize)]
+        struct UnknownEventParams {
+            #[serde(default)]
+            params: Value,
+        }
+
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        let method = serde_json::from_str::<EventMethod>(raw.get())
+            .map_err(D::Error::custom)?
+            .method;
+        if EVENT_METHODS.binary_search(&method.as_ref()).is_ok() {
+            // The derived impl, see `build.rs`
+            let mut event_deserializer = serde_json::Deserializer::from_str(raw.get());
+            return Event::deserialize(&mut event_deserializer).map_err(D::Error::custom);
+        }
+
+        let params = serde_json::from_str::<UnknownEventParams>(raw.get())
+            .map_err(D::Error::custom)?
+            .params;
+        Ok(Event::Other {
+            method: method.into_owned(),
+            params,
+        })
+    }
+}
+
+impl Serialize for Event {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        match self {
+            Event::Other { method, params } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("method", method)?;
+                map.serialize_entry("params", params)?;
+                map.end()
+            }
+            // The derived impl, see `build.rs`
+            event => Event::serialize(event, serializer),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 #[allow(clippy::large_enum_variant)]
 pub enum Message {
     Event(Event),
     Response(Response),
     ConnectionShutdown,
+    /// A binary WebSocket frame which isn't a protocol message.
+    Binary(Vec<u8>),
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        parse_raw_message(raw.get()).map_err(D::Error::custom)
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -108,10 +197,56 @@ pub struct PrintToPdfOptions {
     pub prefer_css_page_size: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transfer_mode: Option<TransferMode>,
+    /// Whether to tag the PDF with the page's structure (headings, lists, tables, alt text...),
+    /// which screen readers and accessibility (PDF/UA) checks rely on.
+    #[serde(rename = "generateTaggedPDF", skip_serializing_if = "Option::is_none")]
+    pub generate_tagged_pdf: Option<bool>,
+    /// Whether to give the PDF an outline (bookmarks) generated from the page's headings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generate_document_outline: Option<bool>,
+}
+
+impl From<PrintToPdfOptions> for PrintToPDF {
+    fn from(options: PrintToPdfOptions) -> Self {
+        PrintToPDF {
+            landscape: options.landscape,
+            display_header_footer: options.display_header_footer,
+            print_background: options.print_background,
+            scale: options.scale,
+            paper_width: options.paper_width,
+            paper_height: options.paper_height,
+            margin_top: options.margin_top,
+            margin_bottom: options.margin_bottom,
+            margin_left: options.margin_left,
+            margin_right: options.margin_right,
+            page_ranges: options.page_ranges,
+            ignore_invalid_page_ranges: options.ignore_invalid_page_ranges,
+            header_template: options.header_template,
+            footer_template: options.footer_template,
+            prefer_css_page_size: options.prefer_css_page_size,
+            transfer_mode: options.transfer_mode.and_then(Into::into),
+        }
+    }
+}
+
+/// Just enough of a message to tell responses from events.
+#[derive(Deserialize)]
+struct MessageKind {
+    id: Option<CallId>,
 }
 
+/// Parses a message straight from its text, without going through a `serde_json::Value` first:
+/// responses can be tens of megabytes (e.g. screenshots).
 pub fn parse_raw_message(raw_message: &str) -> Result<Message> {
-    Ok(serde_json::from_str::<Message>(raw_message)?)
+    Ok(
+        match serde_json::from_str::<Option<MessageKind>>(raw_message)? {
+            None => Message::ConnectionShutdown,
+            Some(MessageKind { id: Some(_) }) => {
+                Message::Response(serde_json::from_str(raw_message)?)
+            }
+            Some(MessageKind { id: None }) => Message::Event(serde_json::from_str(raw_message)?),
+        },
+    )
 }
 
 #[derive(Clone, Debug)]
@@ -141,6 +276,26 @@ impl Bounds {
             height: None,
         }
     }
+
+    /// Set normal window state and resize the window, leaving its position unchanged
+    pub fn size(width: f64, height: f64) -> Self {
+        Self::Normal {
+            left: None,
+            top: None,
+            width: Some(width),
+            height: Some(height),
+        }
+    }
+
+    /// Set normal window state and move the window, leaving its size unchanged
+    pub fn position(left: JsUInt, top: JsUInt) -> Self {
+        Self::Normal {
+            left: Some(left),
+            top: Some(top),
+            width: None,
+            height: None,
+        }
+    }
 }
 
 impl From<CookieParam> for DeleteCookies {
@@ -364,4 +519,60 @@ mod tests {
             let _message: super::Message = parse_raw_message(msg_string).unwrap();
         }
     }
+
+    #[test]
+    fn parse_unknown_events() {
+        let raw = r#"{"method":"Vendor.somethingHappened","params":{"answer":42}}"#;
+        let Message::Event(event) = parse_raw_message(raw).unwrap() else {
+            panic!("Expected an event");
+        };
+        assert_eq!(
+            Event::Other {
+                method: "Vendor.somethingHappened".to_string(),
+                params: json!({"answer": 42}),
+            },
+            event
+        );
+        assert_eq!(raw, serde_json::to_string(&event).unwrap());
+        assert_eq!("Vendor.somethingHappened", event.method());
+
+        // Known events still parse into their own variants, and round-trip
+        let raw = r#"{"method":"Target.targetDestroyed","params":{"targetId":"26DEBCB2"}}"#;
+        let event: Event = serde_json::from_str(raw).unwrap();
+        assert!(matches!(event, Event::TargetDestroyed(_)));
+        assert_eq!(raw, serde_json::to_string(&event).unwrap());
+        assert_eq!("Target.targetDestroyed", event.method());
+
+        // Rather than passing for unknown ones when their params don't match the definitions
+        let raw = r#"{"method":"Target.targetDestroyed","params":{"targetId":42}}"#;
+        assert!(serde_json::from_str::<Event>(raw).is_err());
+    }
+
+    #[test]
+    fn parse_response_without_value() {
+        let message = parse_raw_message(r#"{"id":7,"result":{"data":"kDEgAABII="}}"#).unwrap();
+        let Message::Response(response) = message else {
+            panic!("Expected a response, got {message:?}");
+        };
+        assert_eq!(7, response.call_id);
+        assert_eq!(r#"{"data":"kDEgAABII="}"#, response.result.unwrap().get());
+
+        let message = parse_raw_message(
+            r#"{"method":"Target.targetDestroyed","params":{"targetId":"26DEBCB2"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(message, Message::Event(Event::TargetDestroyed(_))));
+    }
+
+    #[test]
+    fn bounds_helpers_only_set_their_own_fields() {
+        let bounds: Browser::Bounds = Bounds::size(200.0, 100.0).into();
+        assert_eq!((None, None), (bounds.left, bounds.top));
+        assert_eq!((Some(200), Some(100)), (bounds.width, bounds.height));
+        assert_eq!(Some(Browser::WindowState::Normal), bounds.window_state);
+
+        let bounds: Browser::Bounds = Bounds::position(5, 10).into();
+        assert_eq!((Some(5), Some(10)), (bounds.left, bounds.top));
+        assert_eq!((None, None), (bounds.width, bounds.height));
+    }
 }
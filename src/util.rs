@@ -46,6 +46,21 @@ pub fn extract_midpoint(remote_obj: RemoteObject) -> Result<Point> {
     }
 }
 
+/// Whether JavaScript would consider the value `remote_obj` refers to truthy.
+pub fn is_truthy(remote_obj: &RemoteObject) -> bool {
+    match &remote_obj.value {
+        Some(serde_json::Value::Null) => false,
+        Some(serde_json::Value::Bool(value)) => *value,
+        Some(serde_json::Value::Number(value)) => value.as_f64() != Some(0.0),
+        Some(serde_json::Value::String(value)) => !value.is_empty(),
+        Some(_) => true,
+        None => match &remote_obj.unserializable_value {
+            Some(value) => !matches!(value.as_str(), "NaN" | "-0" | "0n" | "-0n"),
+            None => remote_obj.object_id.is_some(),
+        },
+    }
+}
+
 impl Wait {
     pub fn new(timeout: Duration, sleep: Duration) -> Self {
         Self { timeout, sleep }
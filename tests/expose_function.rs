@@ -2,6 +2,7 @@ use std::sync::{Arc, Mutex};
 
 use headless_chrome::browser::tab::Tab;
 use headless_chrome::Browser;
+use serde_json::{json, Value};
 
 mod server;
 
@@ -28,9 +29,60 @@ fn expose_function() -> Result<()> {
         }),
     )?;
 
-    tab.evaluate("window.simple('100')", false)?;
+    tab.evaluate("window.simple('100')", true)?;
 
     assert_eq!(*function_called_entries.lock().unwrap(), 1);
 
     Ok(())
 }
+
+#[test]
+fn expose_function_returns_value() -> Result<()> {
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+
+    let browser = Browser::default()?;
+    let tab: Arc<Tab> = browser.wait_for_initial_tab()?;
+
+    tab.expose_function(
+        "add",
+        Arc::new(|args: Value| {
+            args.as_array()
+                .unwrap()
+                .iter()
+                .filter_map(Value::as_i64)
+                .sum::<i64>()
+        }),
+    )?;
+
+    let url = format!("http://127.0.0.1:{}", server.port());
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+
+    let sum = tab.evaluate("window.add(1, 2, 3)", true)?;
+    assert_eq!(sum.value, Some(json!(6)));
+
+    tab.remove_function("add")?;
+
+    Ok(())
+}
+
+#[test]
+fn expose_function_rejects_unserializable_value() -> Result<()> {
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+
+    let browser = Browser::default()?;
+    let tab: Arc<Tab> = browser.wait_for_initial_tab()?;
+
+    tab.expose_function(
+        "pairs",
+        // JSON object keys have to be strings
+        Arc::new(|_args: Value| std::collections::HashMap::from([((1, 2), 3)])),
+    )?;
+
+    let url = format!("http://127.0.0.1:{}", server.port());
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+
+    let rejected = tab.evaluate("window.pairs().then(() => false, () => true)", true)?;
+    assert_eq!(rejected.value, Some(json!(true)));
+
+    Ok(())
+}
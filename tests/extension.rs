@@ -2,7 +2,8 @@ use std::ffi::OsStr;
 
 use anyhow::Result;
 
-use headless_chrome::{browser::default_executable, Browser, LaunchOptions};
+use headless_chrome::browser::extension::extension_id;
+use headless_chrome::{browser::default_executable, Browser, HeadlessMode, LaunchOptions};
 
 #[test]
 fn test_extension() -> Result<()> {
@@ -18,3 +19,27 @@ fn test_extension() -> Result<()> {
     // that could probably mean that extension didn't load successfully
     Ok(())
 }
+
+#[test]
+fn attach_to_extension_service_worker() -> Result<()> {
+    let browser = Browser::new(
+        LaunchOptions::default_builder()
+            .headless_mode(Some(HeadlessMode::New))
+            .load_extension("tests/extension_mv3_sample")
+            .build()
+            .unwrap(),
+    )?;
+    let extension_id = headless_chrome::util::Wait::default().until(|| {
+        browser
+            .extension_targets()
+            .ok()?
+            .iter()
+            .find_map(|target| extension_id(target).map(String::from))
+    })?;
+
+    let background = browser.attach_to_extension(&extension_id)?;
+    assert_eq!(extension_id, background.extension_id());
+    let runtime_id = background.evaluate("chrome.runtime.id", false)?.value;
+    assert_eq!(Some(serde_json::json!(extension_id)), runtime_id);
+    Ok(())
+}
@@ -0,0 +1,3 @@
+chrome.runtime.onInstalled.addListener(() => {
+  chrome.storage.local.set({ installed: true });
+});
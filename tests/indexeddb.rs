@@ -0,0 +1,53 @@
+use anyhow::Result;
+use headless_chrome::Browser;
+use serde::Deserialize;
+
+mod server;
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Todo {
+    title: String,
+    done: bool,
+}
+
+#[test]
+fn read_indexeddb_entries() -> Result<()> {
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+
+    let browser = Browser::default()?;
+    let tab = browser.wait_for_initial_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    tab.evaluate(
+        r#"new Promise((resolve, reject) => {
+            const request = indexedDB.open("todos-db", 1);
+            request.onupgradeneeded = () => {
+                const store = request.result.createObjectStore("todos", { keyPath: "id" });
+                store.put({ id: 1, title: "write tests", done: true });
+                store.put({ id: 2, title: "ship it", done: false });
+            };
+            request.onsuccess = () => resolve(true);
+            request.onerror = () => reject(request.error);
+        })"#,
+        true,
+    )?;
+
+    assert_eq!(vec!["todos-db"], tab.indexeddb_databases(&server.url())?);
+
+    let indexeddb = tab.indexeddb(&server.url())?;
+    let database = indexeddb.database("todos-db")?;
+    assert_eq!(1, database.object_stores.len());
+
+    let entries = indexeddb.request_data::<Todo>("todos-db", "todos", None)?;
+    assert_eq!(2, entries.len());
+    assert_eq!(serde_json::json!(1), entries[0].key);
+    assert_eq!(
+        Todo {
+            title: "ship it".to_string(),
+            done: false
+        },
+        entries[1].value
+    );
+
+    Ok(())
+}
@@ -48,3 +48,49 @@ fn read_write_local_storage() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn typed_dom_storage() -> Result<()> {
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+
+    let browser = Browser::new(
+        LaunchOptionsBuilder::default()
+            .headless(true)
+            .build()
+            .unwrap(),
+    )
+    .unwrap();
+
+    let tab = browser.wait_for_initial_tab()?;
+
+    let url = format!("http://127.0.0.1:{}", server.port());
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+
+    let local_storage = tab.local_storage()?;
+
+    let item: Item = local_storage.get("testItem")?.unwrap();
+    assert_eq!(300, item.value);
+
+    let hash: String = local_storage.get("translationHash")?.unwrap();
+    assert_eq!("cb2a8ce9", hash);
+
+    local_storage.set("newItem", Item { value: 42 })?;
+    let item: Item = local_storage.get("newItem")?.unwrap();
+    assert_eq!(42, item.value);
+
+    local_storage.remove("newItem")?;
+    assert!(local_storage.get::<Item>("newItem")?.is_none());
+
+    local_storage.clear()?;
+    assert!(local_storage.items()?.is_empty());
+
+    let session_storage = tab.session_storage()?;
+    session_storage.set("sessionItem", "value")?;
+    assert_eq!(
+        Some("value".to_string()),
+        session_storage.get::<String>("sessionItem")?
+    );
+    assert!(tab.local_storage()?.get::<String>("sessionItem")?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use headless_chrome::browser::pool::{BrowserPool, PoolCheckoutTimeout, PoolOptions};
+use headless_chrome::util::Wait;
+use headless_chrome::Browser;
+
+mod server;
+
+fn pool(options: PoolOptions) -> BrowserPool {
+    BrowserPool::new(options, Browser::default)
+}
+
+#[test]
+fn checkout_reuses_and_limits_browsers() -> Result<()> {
+    let pool = pool(PoolOptions {
+        max_browsers: 1,
+        checkout_timeout: Duration::from_millis(500),
+        ..PoolOptions::default()
+    });
+
+    let browser = pool.checkout()?;
+    let process_id = browser.get_process_id();
+    assert_eq!((1, 0), pool.size());
+
+    let err = pool.checkout().err().unwrap();
+    assert!(err.downcast_ref::<PoolCheckoutTimeout>().is_some());
+
+    drop(browser);
+    assert_eq!((1, 1), pool.size());
+    assert_eq!(process_id, pool.checkout()?.get_process_id());
+
+    pool.checkout()?.discard();
+    assert_eq!((0, 0), pool.size());
+    Ok(())
+}
+
+#[test]
+fn counts_launch_failures() {
+    let pool = BrowserPool::new(PoolOptions::default(), || {
+        Err(anyhow::anyhow!("no browser here"))
+    });
+
+    assert!(pool.checkout().is_err());
+    let metrics = pool.metrics();
+    assert_eq!(0, metrics.launches);
+    assert_eq!(1, metrics.launch_failures);
+    assert_eq!(0, metrics.running);
+    assert!(metrics
+        .to_prometheus()
+        .contains("headless_chrome_pool_launch_failures_total 1\n"));
+}
+
+#[test]
+fn recycles_old_browsers() -> Result<()> {
+    let pool = pool(PoolOptions {
+        max_age: Some(Duration::from_millis(1)),
+        ..PoolOptions::default()
+    });
+
+    let process_id = pool.checkout()?.get_process_id();
+    std::thread::sleep(Duration::from_millis(10));
+    assert_ne!(process_id, pool.checkout()?.get_process_id());
+    assert_eq!((1, 1), pool.size());
+    Ok(())
+}
+
+#[test]
+fn spawned_jobs_share_the_pool() -> Result<()> {
+    let server = server::Server::with_dumb_html(r#"<title>pooled</title>"#);
+    let pool = pool(PoolOptions {
+        max_browsers: 2,
+        ..PoolOptions::default()
+    });
+
+    let jobs: Vec<_> = (0..4)
+        .map(|_| {
+            let url = server.url();
+            pool.spawn(move |browser| {
+                let tab = browser.new_tab()?;
+                tab.navigate_to(&url)?.wait_until_navigated()?;
+                let title = Wait::with_timeout(Duration::from_secs(5))
+                    .until(|| tab.get_title().ok().filter(|title| title == "pooled"))?;
+                tab.close(false)?;
+                Ok(title)
+            })
+        })
+        .collect();
+    for job in jobs {
+        assert_eq!("pooled", job.join().unwrap()?);
+    }
+    assert!(pool.size().0 <= 2);
+    Ok(())
+}
+
+#[test]
+fn spawned_jobs_wait_for_a_browser() -> Result<()> {
+    let pool = pool(PoolOptions {
+        max_browsers: 1,
+        checkout_timeout: Duration::from_millis(1),
+        ..PoolOptions::default()
+    });
+
+    let jobs: Vec<_> = (0..3)
+        .map(|_| {
+            pool.spawn(|browser| {
+                std::thread::sleep(Duration::from_millis(50));
+                Ok(browser.get_process_id())
+            })
+        })
+        .collect();
+    let process_ids = jobs
+        .into_iter()
+        .map(|job| job.join().unwrap())
+        .collect::<Result<Vec<_>>>()?;
+    assert!(process_ids.windows(2).all(|ids| ids[0] == ids[1]));
+    assert_eq!((1, 1), pool.size());
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use headless_chrome::browser::pool::{BrowserPool, PoolOptions};
+use headless_chrome::render::{RenderOptions, Renderer, Viewport};
+use headless_chrome::Browser;
+
+fn png_size(png: &[u8]) -> (u32, u32) {
+    let info = png::Decoder::new(png).read_info().unwrap().info().clone();
+    (info.width, info.height)
+}
+
+#[test]
+fn renders_html_at_the_viewport_size() -> Result<()> {
+    let renderer = Renderer::new(BrowserPool::new(PoolOptions::default(), Browser::default));
+    let viewport = Viewport {
+        width: 600,
+        height: 300,
+        device_scale_factor: 2.0,
+    };
+
+    let png = renderer.html_to_png("<h1>Hello</h1>", viewport, &RenderOptions::default())?;
+    assert_eq!((1200, 600), png_size(&png));
+
+    let full_page = renderer.html_to_png(
+        "<div style='height: 1000px'>Tall</div>",
+        viewport,
+        &RenderOptions {
+            full_page: true,
+            ..RenderOptions::default()
+        },
+    )?;
+    assert!(png_size(&full_page).1 > 600);
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use headless_chrome::util::Wait;
+use headless_chrome::Browser;
+
+mod server;
+
+fn service_worker_server() -> server::Server {
+    server::Server::new(|request: tiny_http::Request| {
+        let (content_type, body): (&[u8], &str) = if request.url() == "/sw.js" {
+            (
+                b"application/javascript",
+                "self.addEventListener('fetch', () => {});",
+            )
+        } else {
+            (
+                b"text/html",
+                "<script>navigator.serviceWorker.register('/sw.js');</script>",
+            )
+        };
+        let response = tiny_http::Response::new(
+            200.into(),
+            vec![tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type).unwrap()],
+            std::io::Cursor::new(body),
+            Some(body.len()),
+            None,
+        );
+        request.respond(response)
+    })
+}
+
+#[test]
+fn track_and_unregister_service_workers() -> Result<()> {
+    let server = service_worker_server();
+
+    let browser = Browser::default()?;
+    let tab = browser.wait_for_initial_tab()?;
+
+    let service_workers = tab.service_workers()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    let wait = Wait::with_timeout(Duration::from_secs(10));
+    let registration = wait.until(|| service_workers.registrations().into_iter().next())?;
+    assert_eq!(format!("{}/", server.url()), registration.scope_url);
+
+    service_workers.stop_all_workers()?;
+    service_workers.unregister(&registration.scope_url)?;
+    wait.until(|| {
+        if service_workers.registrations().is_empty() {
+            Some(())
+        } else {
+            None
+        }
+    })?;
+
+    Ok(())
+}
@@ -1,26 +1,49 @@
 #![allow(unused_variables)]
 
+use std::ffi::OsStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 use anyhow::Result;
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::BackgroundService;
+use headless_chrome::protocol::cdp::BluetoothEmulation;
 use headless_chrome::protocol::cdp::Browser::WindowState;
+use headless_chrome::protocol::cdp::Emulation;
 use headless_chrome::protocol::cdp::Fetch::events::RequestPausedEvent;
 use headless_chrome::protocol::cdp::Fetch::{
     FulfillRequest, HeaderEntry, RequestPattern, RequestStage,
 };
-use headless_chrome::protocol::cdp::Network::{Cookie, CookieParam};
+use headless_chrome::protocol::cdp::Log::{LogEntryLevel, LogEntrySource};
+use headless_chrome::protocol::cdp::Network::{Cookie, CookieParam, ResourceType};
+use headless_chrome::protocol::cdp::Page;
 use headless_chrome::protocol::cdp::Page::CaptureScreenshotFormatOption;
 use headless_chrome::protocol::cdp::Runtime::{RemoteObjectSubtype, RemoteObjectType};
+use headless_chrome::protocol::cdp::Security::SecurityState;
+use headless_chrome::protocol::cdp::Target;
 use headless_chrome::protocol::cdp::DOM::RGBA;
-use headless_chrome::types::{Bounds, RemoteError};
+use headless_chrome::types::{Bounds, PrintToPdfOptions, RemoteError};
 use headless_chrome::LaunchOptionsBuilder;
 use log::*;
 use rand::prelude::*;
 
-use headless_chrome::browser::tab::RequestPausedDecision;
+use headless_chrome::browser::context::{ContextProxy, Permission, StorageType};
+use headless_chrome::browser::tab::crash_recovery::{CrashRecovery, TabCrashed};
+use headless_chrome::browser::tab::media::MediaPlayerEvent;
+use headless_chrome::browser::tab::memory_guard::MemoryLimitAction;
+use headless_chrome::browser::tab::middleware::{
+    InterceptedRequest, InterceptedResponse, Middleware,
+};
+use headless_chrome::browser::tab::navigation_policy::{NavigationBlocked, NavigationPolicy};
+use headless_chrome::browser::tab::point::Point;
+use headless_chrome::browser::tab::{
+    ConsoleMessageSource, DomMutation, Domain, InvalidDelayRange, Issue, RequestPausedDecision,
+    VisionDeficiency, WaitUntil, WebSocketFrameDirection,
+};
 use headless_chrome::browser::transport::{SessionId, Transport};
+use headless_chrome::browser::{Crash, NotAPage};
 use headless_chrome::util::Wait;
 use headless_chrome::{Browser, Tab};
 use std::collections::HashMap;
@@ -195,6 +218,241 @@ fn send_character() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn form_controls() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("form.html"));
+
+    let checkbox = tab.wait_for_element("input#sneakattack")?;
+    checkbox.check()?.check()?;
+    assert_eq!(
+        Some(serde_json::json!(true)),
+        checkbox
+            .call_js_fn("function() { return this.checked }", vec![], false)?
+            .value
+    );
+    checkbox.uncheck()?;
+    assert_eq!(
+        Some(serde_json::json!(false)),
+        checkbox
+            .call_js_fn("function() { return this.checked }", vec![], false)?
+            .value
+    );
+    assert!(tab.find_element("input#target")?.check().is_err());
+
+    let select = tab.find_element("select#silo")?;
+    select.select_option("omega")?;
+    assert_eq!(
+        Some(serde_json::json!(["omega", "yes"])),
+        select
+            .call_js_fn(
+                "function() { return [this.value, this.dataset.changed] }",
+                vec![],
+                false
+            )?
+            .value
+    );
+    assert!(select.select_option("gamma").is_err());
+
+    tab.find_element("button")?.hover()?;
+
+    Ok(())
+}
+
+#[test]
+fn fill_and_submit_form() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("form.html"));
+
+    let input = tab.wait_for_element("input#target")?;
+    input.type_into("mothership")?;
+    tab.fill_form(&[("input#target", "moon base"), ("select#silo", "omega")])?;
+    assert_eq!("moon base", input.get_value()?);
+    assert_eq!("omega", tab.find_element("select#silo")?.get_value()?);
+    input.clear()?;
+    assert_eq!("", input.get_value()?);
+    assert!(tab.fill_form(&[("div#protocol", "text")]).is_err());
+
+    tab.evaluate(
+        "document.getElementById('control').addEventListener('submit', event => {
+            event.preventDefault();
+            window.submitted = true;
+        })",
+        false,
+    )?;
+    input.submit()?;
+    assert_eq!(
+        Some(serde_json::json!(true)),
+        tab.evaluate("window.submitted", false)?.value
+    );
+    assert!(tab.find_element("div#protocol")?.submit().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn type_with_delay() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("form.html"));
+
+    let input = tab.wait_for_element("input#target")?;
+    let started = std::time::Instant::now();
+    input.type_with_delay(
+        "moon",
+        Duration::from_millis(50)..=Duration::from_millis(100),
+    )?;
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert_eq!("moon", input.get_value()?);
+
+    let reversed = tab
+        .type_with_delay("sun", Duration::from_millis(2)..=Duration::from_millis(1))
+        .err()
+        .unwrap();
+    assert!(reversed.downcast_ref::<InvalidDelayRange>().is_some());
+    assert_eq!("moon", input.get_value()?);
+
+    Ok(())
+}
+
+#[test]
+fn scroll_infinite_page() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+
+    // Loads two more screens of content whenever it's scrolled to the bottom, five times
+    tab.evaluate(
+        "window.loads = 0;
+        const more = () => {
+            const block = document.createElement('div');
+            block.className = 'feed';
+            block.style.height = (2 * innerHeight) + 'px';
+            document.body.appendChild(block);
+        };
+        more();
+        addEventListener('scroll', () => {
+            const atBottom = scrollY + innerHeight >= document.body.scrollHeight - 1;
+            if (atBottom && window.loads < 5) {
+                window.loads++;
+                setTimeout(more, 100);
+            }
+        });",
+        false,
+    )?;
+
+    assert!(tab.scroll_to_bottom(400.0, Duration::from_millis(500), 500)?);
+    assert_eq!(6, tab.find_elements("div.feed")?.len());
+
+    let first = tab.find_element("div.feed")?;
+    tab.scroll_element_into_view_center(&first)?;
+    let midpoint = first.get_midpoint()?;
+    let viewport_middle = tab.evaluate("innerHeight / 2", false)?.value.unwrap();
+    assert!((midpoint.y - viewport_middle.as_f64().unwrap()).abs() < 2.0);
+
+    Ok(())
+}
+
+#[test]
+fn extract_data() -> Result<()> {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Control {
+        input_type: String,
+        sneak_attack: bool,
+        options: Vec<String>,
+        missing: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct SiloOption {
+        value: String,
+        label: String,
+    }
+
+    let (_, _browser, tab) = dumb_server(include_str!("form.html"));
+    tab.wait_for_element("form")?;
+
+    let control: Control = tab.extract(&[
+        ("input_type", "input#target@type"),
+        ("sneak_attack", "input#sneakattack@checked"),
+        ("options", "select#silo option[]"),
+        ("missing", "div.nope"),
+    ])?;
+    assert_eq!(
+        Control {
+            input_type: "text".to_string(),
+            sneak_attack: false,
+            options: vec!["Alpha".to_string(), "Omega".to_string()],
+            missing: None,
+        },
+        control
+    );
+
+    let options: Vec<SiloOption> =
+        tab.extract_all("select#silo option", &[("value", "@value"), ("label", "")])?;
+    assert_eq!(
+        vec![
+            SiloOption {
+                value: "alpha".to_string(),
+                label: "Alpha".to_string(),
+            },
+            SiloOption {
+                value: "omega".to_string(),
+                label: "Omega".to_string(),
+            },
+        ],
+        options
+    );
+
+    Ok(())
+}
+
+#[test]
+fn element_value_and_attributes() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("form.html"));
+
+    let input = tab.wait_for_element("input#target")?;
+    input.type_into("mothership")?;
+    assert_eq!("mothership", input.get_value()?);
+    assert_eq!(Some("text".to_string()), input.get_attribute("type")?);
+    assert_eq!(None, input.get_attribute("placeholder")?);
+    assert_eq!(
+        r#"<input type="text" id="target">"#,
+        input.get_outer_html()?
+    );
+    assert!(tab.find_element("div#protocol")?.get_value().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn insert_text_and_compose() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("form.html"));
+    let input = tab.wait_for_element("input#target")?;
+    input.call_js_fn(
+        "function() {
+            window.compositionEvents = [];
+            for (const type of ['compositionstart', 'compositionupdate', 'compositionend'])
+                this.addEventListener(type, () => window.compositionEvents.push(type));
+        }",
+        vec![],
+        false,
+    )?;
+    input.click()?;
+
+    tab.insert_text("🚀 ")?;
+    tab.ime_set_composition("にほん", 3, 3)?
+        .ime_set_composition("日本", 2, 2)?
+        .insert_text("日本")?;
+    assert_eq!("🚀 日本", input.get_value()?);
+
+    let events = tab
+        .evaluate("window.compositionEvents.join()", false)?
+        .value;
+    assert_eq!(
+        Some(serde_json::json!(
+            "compositionstart,compositionupdate,compositionupdate,compositionend"
+        )),
+        events
+    );
+
+    Ok(())
+}
+
 #[test]
 fn tab_get_content() -> Result<()> {
     logging::enable_logging();
@@ -278,6 +536,16 @@ fn capture_screenshot_png() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn capture_mhtml() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+    let mhtml = tab.capture_mhtml()?;
+    assert!(mhtml.starts_with("From: <Saved by Blink>"));
+    assert!(mhtml.contains("multipart/related"));
+    Ok(())
+}
+
 #[test]
 fn capture_screenshot_element() -> Result<()> {
     logging::enable_logging();
@@ -333,6 +601,70 @@ fn test_print_file_to_pdf() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn print_to_pdf_as_stream() -> Result<()> {
+    logging::enable_logging();
+    let (_, browser, tab) = dumb_server(include_str!("./pdfassets/index.html"));
+    tab.wait_until_navigated()?;
+    let mut streamed = Vec::new();
+    let written = tab.print_to_pdf_to_writer(None, &mut streamed)?;
+    assert_eq!(written, streamed.len() as u64);
+    assert!(streamed.starts_with(b"%PDF"));
+    Ok(())
+}
+
+#[test]
+fn print_tagged_pdf_through_post_processor() -> Result<()> {
+    logging::enable_logging();
+    let (_, browser, tab) = dumb_server(include_str!("./pdfassets/index.html"));
+    tab.wait_until_navigated()?;
+    tab.set_pdf_post_processor(Some(Arc::new(|mut pdf: Vec<u8>| {
+        pdf.extend_from_slice(b"% post-processed\n");
+        Ok(pdf)
+    })));
+    let options = || {
+        Some(PrintToPdfOptions {
+            generate_tagged_pdf: Some(true),
+            generate_document_outline: Some(true),
+            ..Default::default()
+        })
+    };
+    let contains = |pdf: &[u8], name: &[u8]| pdf.windows(name.len()).any(|window| window == name);
+
+    let pdf = tab.print_to_pdf(options())?;
+    assert!(pdf.starts_with(b"%PDF"));
+    assert!(pdf.ends_with(b"% post-processed\n"));
+    // Tagged, with an outline generated from the page's headings
+    assert!(contains(&pdf, b"/StructTreeRoot"));
+    assert!(contains(&pdf, b"/Outlines"));
+
+    let mut streamed = Vec::new();
+    tab.print_to_pdf_to_writer(options(), &mut streamed)?;
+    assert!(streamed.ends_with(b"% post-processed\n"));
+    Ok(())
+}
+
+#[test]
+fn read_stream_in_small_reads() -> Result<()> {
+    use std::io::Read;
+
+    logging::enable_logging();
+    let (_, browser, tab) = dumb_server(include_str!("./pdfassets/index.html"));
+    let pdf = tab.wait_until_navigated()?.call_method(Page::PrintToPDF {
+        transfer_mode: Some(Page::PrintToPDFTransfer_modeOption::ReturnAsStream),
+        ..Default::default()
+    })?;
+    let mut reader = tab.stream_reader(pdf.stream.unwrap());
+    let mut header = [0; 4];
+    reader.read_exact(&mut header)?;
+    assert_eq!(b"%PDF", &header);
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest)?;
+    assert!(rest.len() > 1000);
+    reader.close()?;
+    Ok(())
+}
+
 #[test]
 fn get_box_model() -> Result<()> {
     logging::enable_logging();
@@ -420,7 +752,16 @@ fn reload() -> Result<()> {
         .get_description()?
         .find(|n| n.node_value == "1")
         .is_some());
-    // TODO test effect of scriptEvaluateOnLoad
+
+    tab.reload(true, Some("window.reloadMarker = 'reloaded';"))?
+        .wait_until_navigated()?;
+    assert!(tab
+        .wait_for_element("div#counter")?
+        .get_description()?
+        .find(|n| n.node_value == "2")
+        .is_some());
+    let marker = tab.evaluate("window.reloadMarker", false)?.value;
+    assert_eq!(Some(serde_json::json!("reloaded")), marker);
     Ok(())
 }
 
@@ -956,6 +1297,26 @@ fn set_extra_http_headers() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn matched_css_rules() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    let element = tab.wait_for_element("#foobar")?;
+
+    assert_eq!(
+        Some("100px".to_string()),
+        element.get_computed_style("width")?
+    );
+    assert_eq!(None, element.get_computed_style("not-a-property")?);
+    // Both calls share the one claim on the CSS and DOM domains, rather than enabling them again
+    assert_eq!(vec!["css".to_string()], tab.domain_holders(Domain::Css));
+
+    let rules = element.get_matched_css_rules()?;
+    assert!(rules
+        .iter()
+        .any(|rule_match| rule_match.rule.selector_list.text == "div#foobar"));
+    Ok(())
+}
+
 #[test]
 fn get_css_styles() -> Result<()> {
     let (server, browser, tab) = dumb_server(include_str!("simple.html"));
@@ -984,3 +1345,1888 @@ fn get_css_styles() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn evaluate_on_new_document() -> Result<()> {
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+
+    tab.set_bypass_csp(true)?;
+    let identifier = tab.evaluate_on_new_document("window.injectedValue = 42;")?;
+
+    tab.navigate_to(&format!("http://127.0.0.1:{}", server.port()))?
+        .wait_until_navigated()?;
+    let injected = tab.evaluate("window.injectedValue", false)?;
+    assert_eq!(injected.value, Some(serde_json::json!(42)));
+
+    tab.remove_script_on_new_document(identifier)?;
+    tab.reload(false, None)?.wait_until_navigated()?;
+    let injected = tab.evaluate("window.injectedValue", false)?;
+    assert_eq!(injected.value, None);
+
+    Ok(())
+}
+
+#[test]
+fn clipboard_round_trip() -> Result<()> {
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+
+    tab.set_clipboard_text("copied from a test")?;
+    assert_eq!("copied from a test", tab.get_clipboard_text()?);
+
+    Ok(())
+}
+
+#[test]
+fn grant_and_reset_permissions() -> Result<()> {
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+
+    let context = browser.new_context()?;
+    let context_tab = context.new_tab()?;
+    context_tab
+        .navigate_to(&server.url())?
+        .wait_until_navigated()?;
+
+    let query_state = || -> Result<String> {
+        let state = context_tab.evaluate(
+            "navigator.permissions.query({name: 'geolocation'}).then(p => p.state)",
+            true,
+        )?;
+        Ok(serde_json::from_value(state.value.unwrap())?)
+    };
+
+    context.grant_permissions(&server.url(), &[Permission::Geolocation])?;
+    assert_eq!("granted", query_state()?);
+
+    context.reset_permissions()?;
+    assert_eq!("prompt", query_state()?);
+
+    Ok(())
+}
+
+#[test]
+fn restore_context_storage_state() -> Result<()> {
+    let (server, browser, _tab) = dumb_server(include_str!("simple.html"));
+
+    let context = browser.new_context()?;
+    let context_tab = context.new_tab()?;
+    context_tab
+        .navigate_to(&server.url())?
+        .wait_until_navigated()?;
+    context_tab.evaluate(
+        r"(async () => {
+            document.cookie = 'session=abc123';
+            localStorage.setItem('user', 'alice');
+            await new Promise((resolve) => {
+                const request = indexedDB.open('app', 1);
+                request.onupgradeneeded = () =>
+                    request.result.createObjectStore('todos', { keyPath: 'id' });
+                request.onsuccess = () => {
+                    const transaction = request.result.transaction('todos', 'readwrite');
+                    transaction.objectStore('todos').put({ id: 1, title: 'Write tests' });
+                    transaction.oncomplete = () => resolve();
+                };
+            });
+        })()",
+        true,
+    )?;
+
+    let state = context.storage_state()?;
+    assert_eq!(1, state.origins.len());
+    // It's meant to be stored between runs
+    let state = serde_json::from_str(&serde_json::to_string(&state)?)?;
+
+    let restored = browser.new_context_with_state(&state)?;
+    let restored_tab = restored.new_tab()?;
+    restored_tab
+        .navigate_to(&server.url())?
+        .wait_until_navigated()?;
+    let restored_state = restored_tab.evaluate(
+        r"(async () => {
+            const title = await new Promise((resolve) => {
+                indexedDB.open('app').onsuccess = (event) => {
+                    const request = event.target.result
+                        .transaction('todos')
+                        .objectStore('todos')
+                        .get(1);
+                    request.onsuccess = () => resolve(request.result.title);
+                };
+            });
+            return [document.cookie, localStorage.getItem('user'), title].join(';');
+        })()",
+        true,
+    )?;
+    assert_eq!(
+        Some(serde_json::json!("session=abc123;alice;Write tests")),
+        restored_state.value
+    );
+
+    Ok(())
+}
+
+#[test]
+fn clear_context_storage() -> Result<()> {
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+
+    let context = browser.new_context()?;
+    let context_tab = context.new_tab()?;
+    context_tab
+        .navigate_to(&server.url())?
+        .wait_until_navigated()?;
+    assert!(context_tab.get_storage::<String>("translationHash").is_ok());
+
+    let usage = context.get_usage_and_quota(&server.url())?;
+    assert!(usage.quota > 0.0);
+
+    context.clear_storage(&server.url(), &[StorageType::LocalStorage])?;
+    assert!(context_tab
+        .get_storage::<String>("translationHash")
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn context_with_proxy() -> Result<()> {
+    // The dumb server answers every request with the same page, so it doubles as a proxy which
+    // serves that page for any URL
+    let (proxy, browser, _tab) = dumb_server(include_str!("simple.html"));
+
+    let context = browser.new_context_with_proxy(ContextProxy {
+        server: proxy.url(),
+        bypass_list: None,
+    })?;
+    let context_tab = context.new_tab()?;
+    context_tab
+        .navigate_to("http://proxied.invalid/")?
+        .wait_until_navigated()?;
+    context_tab.wait_for_element("div#foobar")?;
+
+    Ok(())
+}
+
+#[test]
+fn authenticated_proxy() -> Result<()> {
+    let proxy = server::Server::new(|request: tiny_http::Request| {
+        let authorized = request.headers().iter().any(|header| {
+            header.field.equiv("Proxy-Authorization") && header.value == "Basic dXNlcjpzZWNyZXQ="
+        });
+        let response = if authorized {
+            tiny_http::Response::from_string("<div id='proxied'></div>").with_header(
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+            )
+        } else {
+            tiny_http::Response::from_string("")
+                .with_status_code(407)
+                .with_header(
+                    tiny_http::Header::from_bytes(
+                        &b"Proxy-Authenticate"[..],
+                        &b"Basic realm=\"proxy\""[..],
+                    )
+                    .unwrap(),
+                )
+        };
+        request.respond(response)
+    });
+    let proxy_url = proxy.url();
+
+    let browser = Browser::new(
+        LaunchOptionsBuilder::default()
+            .headless(true)
+            .proxy(&proxy_url, "user", "secret")
+            .build()
+            .unwrap(),
+    )?;
+    let tab = browser.new_tab()?;
+    tab.navigate_to("http://proxied.invalid/")?
+        .wait_until_navigated()?;
+    tab.wait_for_element("div#proxied")?;
+
+    Ok(())
+}
+
+#[test]
+fn capture_dom_snapshot() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+
+    let snapshot = tab.capture_dom_snapshot(&["display"])?;
+    assert_eq!(1, snapshot.documents.len());
+    assert!(snapshot.strings.iter().any(|string| string == "foobar"));
+    assert!(!snapshot.documents[0].layout.styles.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn set_content() -> Result<()> {
+    let browser = browser();
+    let tab = browser.new_tab()?;
+
+    tab.set_content(
+        "<p id='greeting'>Hello from set_content</p>",
+        WaitUntil::Load,
+    )?;
+    let text = tab.find_element("#greeting")?.get_inner_text()?;
+    assert_eq!("Hello from set_content", text);
+    assert!(tab.get_content()?.contains("Hello from set_content"));
+
+    Ok(())
+}
+
+#[test]
+fn navigate_history() -> Result<()> {
+    let (server, browser, _) = dumb_server(include_str!("simple.html"));
+    let tab = browser.new_tab()?;
+
+    tab.navigate_to(&format!("{}/first", server.url()))?
+        .wait_until_navigated()?;
+    tab.navigate_to(&format!("{}/second", server.url()))?
+        .wait_until_navigated()?;
+
+    let current_url = || -> Result<String> {
+        let history = tab.navigation_history()?;
+        Ok(history.entries[history.current_index as usize].url.clone())
+    };
+    assert_eq!(format!("{}/second", server.url()), current_url()?);
+
+    tab.go_back()?;
+    assert_eq!(format!("{}/first", server.url()), current_url()?);
+    tab.go_forward()?;
+    assert_eq!(format!("{}/second", server.url()), current_url()?);
+    assert!(tab.go_forward().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn wait_for_navigation_and_url() -> Result<()> {
+    let server = server::Server::new(|request: tiny_http::Request| {
+        let body = if request.url() == "/next" {
+            r#"<div id="next"></div>"#
+        } else {
+            r#"<a id="go" href="/next">go</a>"#
+        };
+        let response = tiny_http::Response::from_string(body).with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+        );
+        request.respond(response)
+    });
+    let browser = browser();
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    tab.wait_for_element("#go")?.click()?;
+    tab.wait_for_navigation()?;
+    tab.find_element("#next")?;
+    tab.wait_for_url(|url| url.ends_with("/next"))?;
+
+    tab.evaluate("history.pushState({}, '', '/pushed')", false)?;
+    tab.wait_for_url(|url| url.ends_with("/pushed"))?;
+
+    Ok(())
+}
+
+#[test]
+fn wait_for_network_idle() -> Result<()> {
+    let server = server::Server::new(|request: tiny_http::Request| {
+        let body = if request.url() == "/data" {
+            "loaded"
+        } else {
+            r#"<script>
+                fetch('/data')
+                    .then(response => response.text())
+                    .then(text => document.body.dataset.state = text);
+            </script>"#
+        };
+        let response = tiny_http::Response::from_string(body).with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+        );
+        request.respond(response)
+    });
+    let browser = browser();
+    let tab = browser.new_tab()?;
+
+    tab.wait_for_network_idle(Duration::ZERO, usize::MAX)?;
+    assert_eq!(
+        vec!["wait_for_network_idle"],
+        tab.domain_holders(Domain::Network)
+    );
+    tab.navigate_to(&server.url())?
+        .wait_for_network_idle(Duration::from_millis(300), 0)?;
+    let state = tab.evaluate("document.body.dataset.state", false)?.value;
+    assert_eq!(Some(serde_json::json!("loaded")), state);
+
+    Ok(())
+}
+
+#[test]
+fn wait_for_function() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.evaluate(
+        "setTimeout(() => { window.appState = { ready: true, answer: 42 }; }, 300)",
+        false,
+    )?;
+
+    let answer = tab.wait_for_function(
+        "() => window.appState && window.appState.answer",
+        Duration::from_secs(5),
+        Duration::from_millis(50),
+    )?;
+    assert_eq!(Some(serde_json::json!(42)), answer.value);
+
+    assert!(tab
+        .wait_for_function(
+            "() => { throw new Error('not yet'); }",
+            Duration::from_secs(1),
+            Duration::from_millis(50),
+        )
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn touch_input() -> Result<()> {
+    let browser = browser();
+    let tab = browser.new_tab()?;
+    tab.set_touch_emulation_enabled(true, Some(1))?;
+    tab.set_content(
+        r#"<div id="pad" style="width: 300px; height: 300px"></div>
+        <script>
+            window.touches = [];
+            const pad = document.getElementById('pad');
+            for (const type of ['touchstart', 'touchmove', 'touchend'])
+                pad.addEventListener(type, () => window.touches.push(type));
+        </script>"#,
+        WaitUntil::Load,
+    )?;
+
+    tab.find_element("#pad")?.tap()?;
+    let touches = tab.evaluate("window.touches.join()", false)?.value;
+    assert_eq!(Some(serde_json::json!("touchstart,touchend")), touches);
+
+    tab.evaluate("window.touches = []", false)?;
+    tab.swipe(Point { x: 250.0, y: 50.0 }, Point { x: 50.0, y: 50.0 })?;
+    let moves = tab
+        .evaluate(
+            "window.touches.filter(t => t === 'touchmove').length",
+            false,
+        )?
+        .value;
+    assert_eq!(Some(serde_json::json!(10)), moves);
+
+    Ok(())
+}
+
+#[test]
+fn drag_and_drop() -> Result<()> {
+    let browser = browser();
+    let tab = browser.new_tab()?;
+    tab.set_content(
+        r#"<div id="card" draggable="true" style="width: 100px; height: 50px">card</div>
+        <div id="done" style="margin-top: 100px; width: 200px; height: 200px"></div>
+        <script>
+            const card = document.getElementById('card');
+            const done = document.getElementById('done');
+            card.addEventListener('dragstart', e => e.dataTransfer.setData('text/plain', 'card'));
+            done.addEventListener('dragover', e => e.preventDefault());
+            done.addEventListener('drop', e => {
+                e.preventDefault();
+                done.dataset.dropped = e.dataTransfer.getData('text/plain');
+            });
+        </script>"#,
+        WaitUntil::Load,
+    )?;
+
+    let card = tab.find_element("#card")?;
+    let done = tab.find_element("#done")?;
+    card.drag_to(&done)?;
+    assert_eq!(
+        Some("card".to_string()),
+        done.get_attribute("data-dropped")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn emulate_media() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    let matches = |query: &str| -> Result<Option<serde_json::Value>> {
+        Ok(tab
+            .evaluate(&format!("matchMedia('{query}').matches"), false)?
+            .value)
+    };
+
+    tab.emulate_media_type(Some("print"))?;
+    tab.emulate_media_features(&[("prefers-color-scheme", "dark")])?;
+    assert_eq!(Some(serde_json::json!(true)), matches("print")?);
+    assert_eq!(
+        Some(serde_json::json!(true)),
+        matches("(prefers-color-scheme: dark)")?
+    );
+
+    tab.emulate_media_type(None)?;
+    assert_eq!(Some(serde_json::json!(false)), matches("print")?);
+    assert_eq!(
+        Some(serde_json::json!(true)),
+        matches("(prefers-color-scheme: dark)")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn emulate_vision_deficiency_and_forced_colors() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+
+    tab.emulate_vision_deficiency(VisionDeficiency::Achromatopsia)?;
+    let png_data = tab.capture_screenshot(CaptureScreenshotFormatOption::Png, None, None, true)?;
+    let buf = decode_png(&png_data[..])?;
+    // Without colour vision, the page's background is a shade of grey
+    assert!(buf[0].abs_diff(buf[1]) < 5 && buf[1].abs_diff(buf[2]) < 5);
+    tab.emulate_vision_deficiency(VisionDeficiency::None)?;
+
+    tab.emulate_forced_colors(true)?;
+    let forced = tab.evaluate("matchMedia('(forced-colors: active)').matches", false)?;
+    assert_eq!(Some(serde_json::json!(true)), forced.value);
+
+    Ok(())
+}
+
+#[test]
+fn cached_target_info() -> Result<()> {
+    let (server, _browser, tab) =
+        dumb_server("<html><head><title>Cached title</title></head><body></body></html>");
+    tab.wait_for_element("body")?;
+
+    // Target info changes are reported asynchronously, after the navigation
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (tab.target_info().title == "Cached title").then_some(()))?;
+    assert_eq!("Cached title", tab.get_title()?);
+    assert_eq!(tab.get_url(), tab.target_info().url);
+    assert!(tab.get_url().contains(&server.port().to_string()));
+    Ok(())
+}
+
+#[test]
+fn stealth_mode_for_new_tabs() -> Result<()> {
+    let (server, browser, _tab) = dumb_server(include_str!("simple.html"));
+    browser.set_stealth_mode(true)?;
+
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    let value = |expression: &str| -> Result<serde_json::Value> {
+        Ok(tab.evaluate(expression, false)?.value.unwrap())
+    };
+    assert_eq!(
+        serde_json::json!(true),
+        value("navigator.webdriver === undefined")?
+    );
+    assert_eq!(
+        serde_json::json!("en-US,en"),
+        value("navigator.languages.join()")?
+    );
+    assert_eq!(
+        serde_json::json!(false),
+        value("navigator.userAgent.includes('Headless')")?
+    );
+    assert_eq!(
+        serde_json::json!("Windows"),
+        value("navigator.userAgentData.platform")?
+    );
+    Ok(())
+}
+
+#[test]
+fn wait_for_popup() -> Result<()> {
+    let (server, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+
+    tab.evaluate(&format!("window.open('{}?popup')", server.url()), false)?;
+    let popup = tab.wait_for_popup()?;
+    assert_eq!(
+        Some(tab.get_target_id()),
+        popup.target_info().opener_id.as_ref()
+    );
+    popup.wait_for_element("div#foobar")?;
+    assert!(popup.get_url().ends_with("?popup"));
+    Ok(())
+}
+
+#[test]
+fn close_and_wait_for_destruction() -> Result<()> {
+    let (server, browser, _tab) = dumb_server(include_str!("simple.html"));
+    let count_tabs = || browser.get_tabs().lock().unwrap().len();
+
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    let tabs_before = count_tabs();
+    tab.close_and_wait(false, false)?;
+    assert_eq!(tabs_before - 1, count_tabs());
+
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    // beforeunload dialogs are only shown once the user has interacted with the page
+    tab.evaluate(
+        "addEventListener('beforeunload', (e) => { e.preventDefault(); e.returnValue = ''; })",
+        false,
+    )?;
+    tab.find_element("div#foobar")?.click()?;
+    tab.close_and_wait(true, true)?;
+    assert_eq!(tabs_before - 1, count_tabs());
+    Ok(())
+}
+
+#[test]
+fn background_tab_and_activation() -> Result<()> {
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+
+    let background_tab = browser.new_background_tab(&server.url())?;
+    background_tab.wait_until_navigated()?;
+    let visibility = |tab: &Tab| -> Result<serde_json::Value> {
+        Ok(tab
+            .evaluate("document.visibilityState", false)?
+            .value
+            .unwrap())
+    };
+    assert_eq!("hidden", visibility(&background_tab)?);
+
+    background_tab.activate()?;
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (visibility(&background_tab).ok()? == "visible").then_some(()))?;
+    Ok(())
+}
+
+#[test]
+fn find_and_attach_to_target() -> Result<()> {
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+
+    let port = server.port().to_string();
+    let found = browser
+        .find_target(|info| info.Type == "page" && info.url.contains(&port))?
+        .unwrap();
+    assert_eq!(tab.get_target_id(), found.get_target_id());
+    assert!(Arc::ptr_eq(
+        &tab,
+        &browser.attach_to_target(tab.get_target_id())?
+    ));
+
+    assert!(browser
+        .find_target(|info| info.url.contains("no-such-page"))?
+        .is_none());
+
+    tab.evaluate(
+        "window.worker = new Worker(URL.createObjectURL(new Blob(['setInterval(() => {}, 1000)'])))",
+        false,
+    )?;
+    let worker_id = Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        tab.call_method(Target::GetTargets { filter: None })
+            .ok()?
+            .target_infos
+            .into_iter()
+            .find(|info| info.Type == "worker")
+            .map(|info| info.target_id)
+    })?;
+    let error = browser.attach_to_target(&worker_id).err().unwrap();
+    assert!(error.downcast_ref::<NotAPage>().is_some());
+    assert!(browser.find_target(|info| info.Type == "worker")?.is_none());
+    Ok(())
+}
+
+#[test]
+fn protocol_capabilities() -> Result<()> {
+    let (_, browser, tab) = dumb_server(include_str!("simple.html"));
+    let version = browser.get_version()?;
+    assert!(!version.protocol_version.is_empty());
+
+    let domains = tab.get_protocol_domains()?;
+    assert!(domains.iter().any(|domain| domain.name == "Page"));
+    assert!(tab.supports::<headless_chrome::protocol::cdp::Page::Navigate>()?);
+    Ok(())
+}
+
+#[test]
+fn viewport_override() -> Result<()> {
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_for_element("div#foobar")?;
+    let size = || -> Result<serde_json::Value> {
+        Ok(tab
+            .evaluate("`${innerWidth}x${innerHeight}@${devicePixelRatio}`", false)?
+            .value
+            .unwrap())
+    };
+    let original = size()?;
+
+    tab.set_viewport(400, 300, 2.0, false)?;
+    assert_eq!(serde_json::json!("400x300@2"), size()?);
+
+    tab.clear_viewport()?;
+    assert_eq!(original, size()?);
+    Ok(())
+}
+
+#[test]
+fn security_state() -> Result<()> {
+    let server = server::Server::new(|request: tiny_http::Request| {
+        let response = tiny_http::Response::from_string("document.title = 'insecure script ran';")
+            .with_header(
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/javascript"[..])
+                    .unwrap(),
+            );
+        request.respond(response)
+    });
+    // Lets the page run the script it loads over plain HTTP, rather than blocking it
+    let browser = Browser::new(
+        LaunchOptionsBuilder::default()
+            .headless(true)
+            .args(vec![OsStr::new("--allow-running-insecure-content")])
+            .build()
+            .unwrap(),
+    )?;
+    let tab = browser.new_tab()?;
+
+    // Serves the page itself over HTTPS, so that its HTTP script is mixed content
+    let page = format!(
+        r#"<script src="http://127.0.0.1:{}/insecure.js"></script>"#,
+        server.port()
+    );
+    tab.enable_fetch(
+        Some(&[RequestPattern {
+            url_pattern: Some("https://secure.test/*".to_string()),
+            resource_Type: None,
+            request_stage: Some(RequestStage::Request),
+        }]),
+        None,
+    )?;
+    tab.enable_request_interception(Arc::new(
+        move |_transport: Arc<Transport>,
+              _session_id: SessionId,
+              intercepted: RequestPausedEvent| {
+            RequestPausedDecision::Fulfill(FulfillRequest {
+                request_id: intercepted.params.request_id,
+                response_code: 200,
+                response_headers: Some(vec![HeaderEntry {
+                    name: "Content-Type".to_string(),
+                    value: "text/html".to_string(),
+                }]),
+                binary_response_headers: None,
+                body: Some(base64::encode(&page)),
+                response_phrase: None,
+            })
+        },
+    ))?;
+
+    let changes = Arc::new(AtomicUsize::new(0));
+    let changes_clone = Arc::clone(&changes);
+    let listener = tab.on_security_state_changed(move |_state| {
+        changes_clone.fetch_add(1, Ordering::SeqCst);
+    })?;
+
+    tab.navigate_to("https://secure.test/")?
+        .wait_until_navigated()?;
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (tab.get_title().ok()? == "insecure script ran").then_some(()))?;
+
+    let state = Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        let state = tab.get_security_state().ok()?;
+        state
+            .security_state_issue_ids
+            .iter()
+            .any(|issue| issue == "ran-mixed-content")
+            .then_some(state)
+    })?;
+    assert_ne!(SecurityState::Secure, state.security_state);
+    assert!(changes.load(Ordering::SeqCst) > 0);
+
+    tab.remove_event_listener(&listener)?;
+    Ok(())
+}
+
+#[test]
+fn block_urls_and_resource_types() -> Result<()> {
+    let responder = move |r: tiny_http::Request| {
+        let response = match r.url() {
+            "/" => tiny_http::Response::from_string(
+                r#"<img id="img" src="/pixel.gif"><script src="/script.js"></script>"#,
+            )
+            .with_header(
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+            ),
+            "/script.js" => tiny_http::Response::from_string("window.scriptLoaded = true;")
+                .with_header(
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/javascript"[..])
+                        .unwrap(),
+                ),
+            _ => tiny_http::Response::from_string("GIF89a"),
+        };
+        r.respond(response)
+    };
+    let server = server::Server::new(responder);
+    let browser = browser();
+    let url = format!("http://127.0.0.1:{}", server.port());
+
+    let tab = browser.new_tab()?;
+    tab.block_urls(&["*.js"])?;
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+    let loaded = tab.evaluate("window.scriptLoaded === true", false)?;
+    assert_eq!(Some(serde_json::json!(false)), loaded.value);
+    tab.block_urls(&[])?;
+    assert!(tab.domain_holders(Domain::Network).is_empty());
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+    let loaded = tab.evaluate("window.scriptLoaded === true", false)?;
+    assert_eq!(Some(serde_json::json!(true)), loaded.value);
+
+    let tab = browser.new_tab()?;
+    tab.block_resource_types(&[ResourceType::Image])?;
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+    let loaded = tab.evaluate("window.scriptLoaded === true", false)?;
+    assert_eq!(Some(serde_json::json!(true)), loaded.value);
+    let image_loaded = tab.evaluate("document.getElementById('img').complete && document.getElementById('img').naturalWidth > 0", false)?;
+    assert_eq!(Some(serde_json::json!(false)), image_loaded.value);
+
+    Ok(())
+}
+
+#[test]
+fn middleware_chain() -> Result<()> {
+    struct AddHeader;
+
+    impl Middleware for AddHeader {
+        fn on_request(&self, request: &mut InterceptedRequest) {
+            request
+                .headers
+                .push(("X-Middleware".to_string(), "yes".to_string()));
+        }
+    }
+
+    struct StubApi;
+
+    impl Middleware for StubApi {
+        fn intercepts_response(&self, request: &InterceptedRequest) -> bool {
+            request.url.ends_with("/api/data")
+        }
+
+        fn on_response(&self, response: &mut InterceptedResponse) {
+            response
+                .set_json_body(&serde_json::json!({ "stubbed": true }))
+                .unwrap();
+        }
+    }
+
+    let server = server::Server::new(|request: tiny_http::Request| {
+        let saw_header = request
+            .headers()
+            .iter()
+            .any(|header| header.field.equiv("X-Middleware"));
+        let body = match request.url() {
+            "/api/data" => format!(r#"{{"stubbed": false, "header": {saw_header}}}"#),
+            _ => format!(r#"<div id="header">{saw_header}</div>"#),
+        };
+        request.respond(tiny_http::Response::from_string(body).with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+        ))
+    });
+    let browser = browser();
+    let url = format!("http://127.0.0.1:{}", server.port());
+
+    let context = browser.new_context()?;
+    let tab = context.new_tab()?;
+    let header = context.add_middleware(Arc::new(AddHeader))?;
+    let stub = tab.add_middleware(Arc::new(StubApi))?;
+
+    tab.navigate_to(&url)?.wait_until_navigated()?;
+    assert_eq!("true", tab.find_element("#header")?.get_inner_text()?);
+
+    let fetch_stubbed = "fetch('/api/data').then(r => r.json()).then(data => data.stubbed)";
+    let stubbed = tab.evaluate(fetch_stubbed, true)?;
+    assert_eq!(Some(serde_json::json!(true)), stubbed.value);
+
+    assert!(tab.remove_middleware(stub)?);
+    assert!(context.remove_middleware(header)?);
+    let stubbed = tab.evaluate(fetch_stubbed, true)?;
+    assert_eq!(Some(serde_json::json!(false)), stubbed.value);
+    assert!(tab.domain_holders(Domain::Fetch).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn websocket_frames() -> Result<()> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let ws_port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        while let Ok(message) = socket.read_message() {
+            if message.is_text() {
+                socket
+                    .write_message(format!("echo: {message}").into())
+                    .unwrap();
+            }
+        }
+    });
+
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let frames_clone = Arc::clone(&frames);
+    tab.on_websocket_frame(move |frame| {
+        frames_clone.lock().unwrap().push(frame.clone());
+    })?;
+
+    tab.evaluate(
+        &format!(
+            "const socket = new WebSocket('ws://127.0.0.1:{ws_port}'); \
+             socket.onopen = () => socket.send('hello'); true"
+        ),
+        false,
+    )?;
+
+    let received = Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        frames
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|frame| frame.direction == WebSocketFrameDirection::Received)
+            .cloned()
+    })?;
+    assert_eq!("echo: hello", received.payload_data);
+    assert_eq!(1, received.opcode);
+    assert_eq!(Some(format!("ws://127.0.0.1:{ws_port}/")), received.url);
+
+    let frames = frames.lock().unwrap();
+    let sent = frames
+        .iter()
+        .find(|frame| frame.direction == WebSocketFrameDirection::Sent)
+        .unwrap();
+    assert_eq!("hello", sent.payload_data);
+
+    Ok(())
+}
+
+#[test]
+fn wait_for_response_and_event_source_messages() -> Result<()> {
+    let server = server::Server::new(|request: tiny_http::Request| {
+        let (body, content_type) = match request.url() {
+            "/api/data" => (r#"{"answer": 42}"#, "application/json"),
+            "/events" => ("data: first\n\ndata: second\n\n", "text/event-stream"),
+            _ => ("<div id='root'></div>", "text/html"),
+        };
+        request.respond(tiny_http::Response::from_string(body).with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap(),
+        ))
+    });
+    let (browser, tab) = dumb_client(&server);
+
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let messages_clone = Arc::clone(&messages);
+    tab.on_event_source_message(move |message| {
+        messages_clone.lock().unwrap().push(message.data.clone());
+    })?;
+
+    tab.evaluate(
+        "setTimeout(() => fetch('/api/data'), 200); new EventSource('/events'); true",
+        false,
+    )?;
+    let captured = tab.wait_for_response(|response| response.url.ends_with("/api/data"))?;
+    assert_eq!(200, captured.response.status);
+    assert_eq!(r#"{"answer": 42}"#, captured.body_text()?);
+
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        let messages = messages.lock().unwrap();
+        (messages.len() >= 2).then_some(())
+    })?;
+    assert_eq!("first", messages.lock().unwrap()[0]);
+
+    drop(browser);
+    Ok(())
+}
+
+#[test]
+fn stream_response_content() -> Result<()> {
+    /// A response body which arrives in chunks, with a pause before each but the first.
+    struct SlowChunks {
+        chunks: std::collections::VecDeque<Vec<u8>>,
+        pause: bool,
+    }
+
+    impl std::io::Read for SlowChunks {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::take(&mut self.pause) {
+                sleep(Duration::from_millis(500));
+            }
+            let Some(chunk) = self.chunks.front_mut() else {
+                return Ok(0);
+            };
+            let len = buf.len().min(chunk.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+            chunk.drain(..len);
+            if chunk.is_empty() {
+                self.chunks.pop_front();
+                self.pause = true;
+            }
+            Ok(len)
+        }
+    }
+
+    // Big enough for each chunk to be written out straight away
+    let chunks: Vec<Vec<u8>> = [b'a', b'b', b'c']
+        .iter()
+        .map(|byte| vec![*byte; 2048])
+        .collect();
+    let body = chunks.concat();
+    let server = server::Server::new(move |request: tiny_http::Request| {
+        if request.url() != "/stream" {
+            return request.respond(
+                tiny_http::Response::from_string("<div id='root'></div>").with_header(
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+                ),
+            );
+        }
+        let chunks = chunks.clone();
+        std::thread::spawn(move || {
+            let len = chunks.iter().map(Vec::len).sum();
+            let reader = SlowChunks {
+                chunks: chunks.into(),
+                pause: false,
+            };
+            request.respond(tiny_http::Response::new(
+                200.into(),
+                vec![],
+                reader,
+                Some(len),
+                None,
+            ))
+        });
+        Ok(())
+    });
+    let (_browser, tab) = dumb_client(&server);
+    tab.wait_for_element("div#root")?;
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let received_clone = Arc::clone(&received);
+    tab.on_response_data(move |_, data| received_clone.lock().unwrap().extend(data))?;
+    let request_id = Arc::new(Mutex::new(None));
+    let request_id_clone = Arc::clone(&request_id);
+    tab.add_event_listener(Arc::new(move |event: &Event| {
+        if let Event::NetworkResponseReceived(ev) = event {
+            if ev.params.response.url.ends_with("/stream") {
+                *request_id_clone.lock().unwrap() = Some(ev.params.request_id.clone());
+            }
+        }
+    }))?;
+
+    tab.evaluate(
+        "fetch('/stream').then(response => response.text()); true",
+        false,
+    )?;
+    let request_id =
+        Wait::with_timeout(Duration::from_secs(5)).until(|| request_id.lock().unwrap().clone())?;
+
+    // The rest of the body is still on its way
+    let buffered = tab.stream_response_content(&request_id)?;
+    assert!(buffered.len() < body.len());
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (buffered.len() + received.lock().unwrap().len() >= body.len()).then_some(()))?;
+    assert_eq!(body, [buffered, received.lock().unwrap().clone()].concat());
+    Ok(())
+}
+
+#[test]
+fn max_concurrent_navigations() -> Result<()> {
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+    let browser = browser();
+    browser.set_max_concurrent_navigations(Some(1));
+    let url = format!("http://127.0.0.1:{}", server.port());
+
+    let first = browser.new_tab()?;
+    let second = browser.new_tab()?;
+    first.navigate_to(&url)?;
+    // Waits for the first tab's navigation to finish before starting
+    second.navigate_to(&url)?.wait_until_navigated()?;
+    first.wait_until_navigated()?;
+
+    let metrics = browser.navigation_metrics();
+    assert_eq!(2, metrics.started);
+    assert_eq!(0, metrics.in_flight);
+    assert_eq!(0, metrics.queue_depth);
+    Ok(())
+}
+
+#[test]
+fn crash_detection_and_resource_usage() -> Result<()> {
+    let (_, browser, tab) = dumb_server(include_str!("simple.html"));
+
+    let metrics = tab.get_performance_metrics()?;
+    assert!(metrics["JSHeapUsedSize"] > 0.0);
+    let processes = browser.get_process_info()?;
+    assert!(processes.iter().any(|process| process.Type == "browser"));
+
+    let crashes = Arc::new(Mutex::new(Vec::new()));
+    let crashes_clone = Arc::clone(&crashes);
+    browser.on_crash(move |crash| crashes_clone.lock().unwrap().push(crash.clone()));
+    browser.set_restore_crashed_tabs(true);
+
+    let _ = tab.navigate_to("chrome://crash");
+    let crash = Wait::with_timeout(Duration::from_secs(10))
+        .until(|| crashes.lock().unwrap().first().cloned())?;
+    assert!(
+        matches!(crash, Crash::Renderer { target_id, .. } if target_id == *tab.get_target_id())
+    );
+
+    // The tab is reloaded, so it's usable again
+    Wait::with_timeout(Duration::from_secs(10)).until(|| tab.evaluate("1 + 1", false).ok())?;
+    Ok(())
+}
+
+#[test]
+fn keep_alive_pings() -> Result<()> {
+    let browser = Browser::new(
+        LaunchOptionsBuilder::default()
+            .ping_interval(Some(Duration::from_millis(50)))
+            .build()
+            .unwrap(),
+    )?;
+    let tab = browser.new_tab()?;
+    sleep(Duration::from_millis(300));
+    let result = tab.evaluate("1 + 1", false)?;
+    assert_eq!(Some(serde_json::json!(2)), result.value);
+    Ok(())
+}
+
+#[test]
+fn dom_mutations() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) =
+        dumb_server(r#"<div id="scope"><p id="inner">old</p></div><div id="outside"></div>"#);
+    tab.wait_until_navigated()?;
+    let mutations = Arc::new(Mutex::new(Vec::new()));
+    let mutations_clone = Arc::clone(&mutations);
+    tab.on_dom_mutation("#scope", move |mutation| {
+        mutations_clone.lock().unwrap().push(mutation.clone());
+    })?;
+
+    tab.evaluate(
+        r#"
+        document.getElementById("outside").setAttribute("data-ignored", "1");
+        document.getElementById("inner").setAttribute("data-state", "new");
+        document.getElementById("scope").appendChild(document.createElement("span"));
+        "#,
+        false,
+    )?;
+
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        let mutations = mutations.lock().unwrap();
+        let inserted = mutations.iter().any(|mutation| {
+            matches!(mutation, DomMutation::ChildNodeInserted { node, .. } if node.node_name == "SPAN")
+        });
+        (mutations.len() >= 2 && inserted).then_some(())
+    })?;
+    let mutations = mutations.lock().unwrap();
+    assert!(mutations.iter().any(|mutation| matches!(
+        mutation,
+        DomMutation::AttributeModified { name, value, .. } if name == "data-state" && value == "new"
+    )));
+    assert!(!mutations.iter().any(|mutation| matches!(
+        mutation,
+        DomMutation::AttributeModified { name, .. } if name == "data-ignored"
+    )));
+    Ok(())
+}
+
+#[test]
+fn control_animations() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(
+        r#"<style>
+            @keyframes grow { from { width: 10px; } to { width: 210px; } }
+            .growing { animation: grow 10s linear infinite; }
+        </style>
+        <div id="box" style="width: 10px; height: 10px; background: red"></div>"#,
+    );
+    tab.wait_until_navigated()?;
+
+    // Animations are only reported once the domain is enabled, so pause them before starting one
+    tab.set_animation_playback_rate(0.0)?;
+    tab.evaluate(
+        "document.querySelector('#box').classList.add('growing')",
+        false,
+    )?;
+    let element = tab.wait_for_element("#box")?;
+
+    // Halfway through, once the animation has been reported and can be seeked
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        tab.seek_animations(5000.0).ok()?;
+        let width = element.get_computed_style("width").ok()??;
+        (width == "110px").then_some(())
+    })?;
+
+    tab.disable_css_animations()?;
+    assert_eq!(
+        Some("0s".to_string()),
+        element.get_computed_style("animation-duration")?
+    );
+
+    tab.set_animation_playback_rate(1.0)?;
+    Ok(())
+}
+
+#[test]
+fn font_settings() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(r#"<p id="text">Text</p><code id="code">code</code>"#);
+    tab.wait_until_navigated()?
+        .set_font_sizes(Some(20), Some(11))?
+        .set_font_families(Page::FontFamilies {
+            standard: Some("serif".to_string()),
+            fixed: None,
+            serif: None,
+            sans_serif: None,
+            cursive: None,
+            fantasy: None,
+            math: None,
+        })?;
+    assert_eq!(
+        Some("20px".to_string()),
+        tab.find_element("#text")?.get_computed_style("font-size")?
+    );
+    assert_eq!(
+        Some("11px".to_string()),
+        tab.find_element("#code")?.get_computed_style("font-size")?
+    );
+    Ok(())
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn screenshot_matches_baseline() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?.disable_css_animations()?;
+    let dir = tempfile::tempdir()?;
+    let baseline = dir.path().join("simple.png");
+
+    // The first run saves the baseline, the second compares against it
+    tab.assert_screenshot_matches(&baseline)?;
+    assert!(baseline.exists());
+    tab.assert_screenshot_matches(&baseline)?;
+
+    tab.evaluate("document.body.style.background = 'white'", false)?;
+    let error = tab.assert_screenshot_matches(&baseline).unwrap_err();
+    assert!(error.is::<headless_chrome::screenshots::ScreenshotMismatch>());
+    assert!(dir.path().join("simple.diff.png").exists());
+    Ok(())
+}
+
+#[test]
+fn debugger_breakpoints_and_stepping() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?.enable_debugger()?;
+    let breakpoint = tab.set_breakpoint_by_url("breakpoint.js", 2, None)?;
+
+    let (paused_tx, paused_rx) = std::sync::mpsc::channel();
+    let paused_tx = Mutex::new(paused_tx);
+    tab.on_debugger_paused(move |paused| {
+        let frames = paused
+            .call_frames
+            .iter()
+            .map(|frame| (frame.function_name.clone(), frame.location.line_number))
+            .collect::<Vec<_>>();
+        paused_tx.lock().unwrap().send(frames).unwrap();
+    })?;
+
+    let evaluating_tab = Arc::clone(&tab);
+    let evaluation = std::thread::spawn(move || {
+        evaluating_tab
+            .evaluate(
+                "function add(a, b) {\n  const sum = a + b;\n  return sum;\n}\nadd(1, 2);\n//# sourceURL=breakpoint.js",
+                false,
+            )
+            .map(|result| result.value)
+    });
+
+    let frames = paused_rx.recv_timeout(Duration::from_secs(5))?;
+    assert_eq!(("add".to_string(), 2), frames[0]);
+    tab.step_out()?;
+    let frames = paused_rx.recv_timeout(Duration::from_secs(5))?;
+    assert_ne!("add", frames[0].0);
+
+    tab.remove_breakpoint(&breakpoint.breakpoint_id)?
+        .resume_debugger()?;
+    assert_eq!(Some(serde_json::json!(3)), evaluation.join().unwrap()?);
+    Ok(())
+}
+
+#[test]
+fn exceptions_with_source_maps() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+    let exceptions = Arc::new(Mutex::new(Vec::new()));
+    let exceptions_clone = Arc::clone(&exceptions);
+    tab.on_exception_thrown(true, move |details| {
+        exceptions_clone.lock().unwrap().push(details.clone());
+    })?;
+
+    // What TypeScript generates for:
+    //   function greet(name: string) {
+    //     throw new Error(name);
+    //   }
+    let source_map = base64::encode(
+        r#"{
+            "version": 3,
+            "sourceRoot": "src",
+            "sources": ["greet.ts"],
+            "names": ["greet", "name", "Error"],
+            "mappings": "AAAA,SAASA,MAAMC;IACb,MAAM,IAAIC,MAAMD,IAAI,CAAC,CAAC;AACxB,CAAC"
+        }"#,
+    );
+    tab.evaluate(
+        &format!(
+            "function greet(name) {{\n    throw new Error(name);\n}}\n\
+             setTimeout(() => greet('boom'), 0);\n\
+             //# sourceURL=greet.js\n\
+             //# sourceMappingURL=data:application/json;base64,{source_map}"
+        ),
+        false,
+    )?;
+
+    let details = Wait::with_timeout(Duration::from_secs(5))
+        .until(|| exceptions.lock().unwrap().first().cloned())?;
+    let frame = &details.stack_trace.unwrap().call_frames[0];
+    assert_eq!("greet", frame.function_name);
+    assert_eq!("src/greet.ts", frame.url);
+    assert_eq!(1, frame.line_number);
+    Ok(())
+}
+
+#[test]
+fn console_and_browser_messages() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let messages_clone = Arc::clone(&messages);
+    tab.on_console_message(move |message| {
+        messages_clone.lock().unwrap().push(message.clone());
+    })?;
+
+    tab.evaluate(
+        r#"console.warn("careful", 42);
+        const img = document.createElement("img");
+        img.src = "http://127.0.0.1:1/missing.png";
+        document.body.appendChild(img);"#,
+        false,
+    )?;
+
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        let messages = messages.lock().unwrap();
+        let from_browser = messages.iter().any(|message| {
+            message.source == ConsoleMessageSource::Browser(LogEntrySource::Network)
+                && message.level == LogEntryLevel::Error
+        });
+        (from_browser && messages.len() >= 2).then_some(())
+    })?;
+    let messages = messages.lock().unwrap();
+    let warning = messages
+        .iter()
+        .find(|message| matches!(message.source, ConsoleMessageSource::ConsoleApi(_)))
+        .unwrap();
+    assert_eq!("careful 42", warning.text);
+    assert_eq!(LogEntryLevel::Warning, warning.level);
+    Ok(())
+}
+
+#[test]
+fn cors_issues() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+    tab.enable_audits()?;
+    // Another port is another origin, and the server doesn't send any CORS headers
+    let other_origin = server::Server::with_dumb_html("");
+    tab.evaluate(
+        &format!("fetch('{}').catch(() => {{}})", other_origin.url()),
+        false,
+    )?;
+
+    let mut issues = Vec::new();
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        issues.extend(tab.take_issues().ok()?);
+        issues
+            .iter()
+            .any(|issue| matches!(issue, Issue::Cors(_)))
+            .then_some(())
+    })?;
+    // Taken issues aren't returned again
+    assert!(!tab
+        .take_issues()?
+        .iter()
+        .any(|issue| matches!(issue, Issue::Cors(_))));
+    Ok(())
+}
+
+#[test]
+fn observe_background_service() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::new(|request: tiny_http::Request| {
+        let (body, content_type) = if request.url() == "/sw.js" {
+            (
+                "self.addEventListener('sync', () => {});",
+                &b"text/javascript"[..],
+            )
+        } else {
+            ("<p>background sync</p>", &b"text/html"[..])
+        };
+        let response = tiny_http::Response::from_string(body).with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type).unwrap(),
+        );
+        request.respond(response)
+    });
+    let browser = browser();
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = Arc::clone(&events);
+    let listener = tab.observe_background_service(
+        BackgroundService::ServiceName::BackgroundSync,
+        move |event| events_clone.lock().unwrap().push(event.clone()),
+    )?;
+
+    tab.evaluate(
+        "navigator.serviceWorker.register('/sw.js')
+            .then(() => navigator.serviceWorker.ready)
+            .then(registration => registration.sync.register('test-sync'))",
+        true,
+    )?;
+    let event = Wait::with_timeout(Duration::from_secs(10)).until(|| {
+        events
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|event| event.instance_id == "test-sync")
+            .cloned()
+    })?;
+    assert_eq!(
+        BackgroundService::ServiceName::BackgroundSync,
+        event.service
+    );
+    assert!(event.origin.starts_with(&server.url()));
+    assert!(!event.event_name.is_empty());
+
+    tab.stop_observing_background_service(BackgroundService::ServiceName::BackgroundSync)?;
+    tab.remove_event_listener(&listener)?;
+    Ok(())
+}
+
+#[test]
+fn rtc_stats() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+    // Two connections in the same page talking to each other over a data channel
+    tab.evaluate(
+        r#"(async () => {
+            window.local = new RTCPeerConnection();
+            const remote = new RTCPeerConnection();
+            local.onicecandidate = e => e.candidate && remote.addIceCandidate(e.candidate);
+            remote.onicecandidate = e => e.candidate && local.addIceCandidate(e.candidate);
+            const channel = local.createDataChannel("test");
+            const opened = new Promise(resolve => channel.onopen = resolve);
+            await local.setLocalDescription(await local.createOffer());
+            await remote.setRemoteDescription(local.localDescription);
+            await remote.setLocalDescription(await remote.createAnswer());
+            await local.setRemoteDescription(remote.localDescription);
+            await opened;
+            channel.send("hello");
+        })()"#,
+        true,
+    )?;
+
+    let stats = tab.get_rtc_stats("window.local")?;
+    assert!(stats.iter().any(|stats| stats.stats_type == "data-channel"));
+    assert!(stats.iter().all(|stats| stats.timestamp > 0.0));
+
+    assert!(tab.get_rtc_stats("window.missing").is_err());
+    Ok(())
+}
+
+#[test]
+fn media_player_events() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = Arc::clone(&events);
+    tab.on_media_player_event(move |event| events_clone.lock().unwrap().push(event.clone()))?;
+
+    // Not a video, so the player can't play it
+    tab.evaluate(
+        r#"const video = document.createElement("video");
+        video.src = "/not-a-video.mp4";
+        document.body.appendChild(video);
+        video.play().catch(() => {});"#,
+        false,
+    )?;
+
+    Wait::with_timeout(Duration::from_secs(10)).until(|| {
+        let events = events.lock().unwrap();
+        let created = events
+            .iter()
+            .any(|event| matches!(event, MediaPlayerEvent::Created(_)));
+        let failed = events
+            .iter()
+            .any(|event| matches!(event, MediaPlayerEvent::ErrorsRaised { .. }));
+        (created && failed).then_some(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn device_orientation_and_sensors() -> Result<()> {
+    logging::enable_logging();
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+
+    tab.set_device_orientation(10.0, 20.0, 30.0)?;
+    let orientation = tab.evaluate(
+        r#"new Promise(resolve => window.addEventListener(
+            "deviceorientation",
+            e => resolve([e.alpha, e.beta, e.gamma].join(",")),
+            { once: true }
+        ))"#,
+        true,
+    )?;
+    assert_eq!(Some(serde_json::json!("10,20,30")), orientation.value);
+    tab.clear_device_orientation()?;
+
+    // The Generic Sensor API needs the sensors permission
+    let context = browser.new_context()?;
+    context.grant_permissions(&server.url(), &[Permission::Sensors])?;
+    let context_tab = context.new_tab()?;
+    context_tab
+        .navigate_to(&server.url())?
+        .wait_until_navigated()?;
+    let read_accelerometer = || -> Result<Option<serde_json::Value>> {
+        Ok(context_tab
+            .evaluate(
+                r#"new Promise((resolve, reject) => {
+                    const sensor = new Accelerometer();
+                    sensor.onreading = () => {
+                        sensor.stop();
+                        resolve([sensor.x, sensor.y, sensor.z].join(","));
+                    };
+                    sensor.onerror = e => reject(e.error.name);
+                    sensor.start();
+                })"#,
+                true,
+            )?
+            .value)
+    };
+
+    let reading = |x| Emulation::SensorReading {
+        single: None,
+        xyz: Some(Emulation::SensorReadingXYZ { x, y: 0.0, z: 9.8 }),
+        quaternion: None,
+    };
+    context_tab.set_sensor_reading(Emulation::SensorType::Accelerometer, reading(1.0))?;
+    assert_eq!(Some(serde_json::json!("1,0,9.8")), read_accelerometer()?);
+    // Changing the reading of an overridden sensor
+    context_tab.set_sensor_reading(Emulation::SensorType::Accelerometer, reading(2.0))?;
+    assert_eq!(Some(serde_json::json!("2,0,9.8")), read_accelerometer()?);
+    context_tab.clear_sensor_override(Emulation::SensorType::Accelerometer)?;
+    Ok(())
+}
+
+#[test]
+fn emulate_focus_and_idle_state() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.wait_until_navigated()?;
+
+    tab.emulate_focus(true)?;
+    let has_focus = tab.evaluate("document.hasFocus()", false)?;
+    assert_eq!(Some(serde_json::json!(true)), has_focus.value);
+
+    tab.emulate_idle_state(true, true)?.clear_idle_state()?;
+    Ok(())
+}
+
+#[test]
+fn bluetooth_emulation() -> Result<()> {
+    logging::enable_logging();
+    // requestDevice needs a user gesture, hence the button
+    let (_, _browser, tab) = dumb_server(
+        r#"<button id="connect" onclick="window.connected = navigator.bluetooth
+            .requestDevice({ filters: [{ name: 'Heart Rate' }], optionalServices: ['heart_rate'] })
+            .then(device => device.gatt.connect())
+            .then(server => server.getPrimaryService('heart_rate'))
+            .then(service => service.getCharacteristic('heart_rate_measurement'))
+            .then(characteristic => [characteristic.service.device.name, characteristic.uuid].join())
+        ">connect</button>"#,
+    );
+    tab.wait_until_navigated()?;
+
+    tab.emulate_bluetooth_adapter(BluetoothEmulation::CentralState::PoweredOn)?;
+    // Changing the state of the already simulated adapter
+    tab.emulate_bluetooth_adapter(BluetoothEmulation::CentralState::PoweredOff)?
+        .emulate_bluetooth_adapter(BluetoothEmulation::CentralState::PoweredOn)?;
+
+    let address = "09:09:09:09:09:09";
+    tab.simulate_bluetooth_peripheral(address, "Heart Rate", &[])?;
+    let service_id = tab.add_bluetooth_service(address, "0000180d-0000-1000-8000-00805f9b34fb")?;
+    let characteristic_id = tab.add_bluetooth_characteristic(
+        &service_id,
+        "00002a37-0000-1000-8000-00805f9b34fb",
+        BluetoothEmulation::CharacteristicProperties {
+            broadcast: None,
+            read: Some(true),
+            write_without_response: None,
+            write: None,
+            notify: Some(true),
+            indicate: None,
+            authenticated_signed_writes: None,
+            extended_properties: None,
+        },
+    )?;
+    assert!(!characteristic_id.is_empty());
+
+    tab.accept_bluetooth_gatt_operations()?;
+    tab.auto_select_device_prompts(|devices| {
+        devices
+            .iter()
+            .find(|device| device.name == "Heart Rate")
+            .map(|device| device.id.clone())
+    })?;
+
+    tab.find_element("#connect")?.click()?;
+    let connected = Wait::with_timeout(Duration::from_secs(10)).until(|| {
+        tab.evaluate("window.connected", true)
+            .ok()?
+            .value
+            .filter(|value| !value.is_null())
+    })?;
+    assert_eq!(
+        serde_json::json!("Heart Rate,00002a37-0000-1000-8000-00805f9b34fb"),
+        connected
+    );
+
+    tab.stop_bluetooth_emulation()?;
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn launch_with_debugging_pipe() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+    let browser = Browser::new(
+        LaunchOptionsBuilder::default()
+            .headless(true)
+            .remote_debugging_pipe(true)
+            .build()
+            .unwrap(),
+    )?;
+    let tab = browser.wait_for_initial_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    tab.wait_for_element("div#foobar")?;
+    Ok(())
+}
+
+#[test]
+fn shared_domains_stay_enabled_until_released_by_all() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+
+    tab.register_response_handling("test", Box::new(|_, _| {}))?;
+    tab.acquire_domain(Domain::Network, "test")?;
+    assert_eq!(
+        vec!["register_response_handling", "test"],
+        tab.domain_holders(Domain::Network)
+    );
+
+    tab.release_domain(Domain::Network, "test")?;
+    assert_eq!(
+        vec!["register_response_handling"],
+        tab.domain_holders(Domain::Network)
+    );
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    tab.deregister_response_handling("test")?;
+    assert!(tab.domain_holders(Domain::Network).is_empty());
+    let listener = tab.on_console_message(|_| {})?;
+    assert_eq!(1, tab.domain_holders(Domain::Log).len());
+    tab.remove_event_listener(&listener)?;
+    assert!(tab.domain_holders(Domain::Log).is_empty());
+    Ok(())
+}
+
+#[test]
+fn merges_fetch_patterns_of_its_holders() -> Result<()> {
+    logging::enable_logging();
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+
+    tab.block_resource_types(&[ResourceType::Image])?;
+    tab.enable_fetch(None, None)?;
+    assert_eq!(
+        vec!["block_resource_types", "user"],
+        tab.domain_holders(Domain::Fetch)
+    );
+    tab.disable_fetch()?;
+    assert_eq!(
+        vec!["block_resource_types"],
+        tab.domain_holders(Domain::Fetch)
+    );
+    tab.block_resource_types(&[])?;
+    assert!(tab.domain_holders(Domain::Fetch).is_empty());
+    Ok(())
+}
+
+#[test]
+fn enables_domains_on_first_use_or_eagerly() -> Result<()> {
+    logging::enable_logging();
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+
+    // No enable_profiler needed
+    tab.start_js_coverage()?;
+    assert_eq!(vec!["js_coverage"], tab.domain_holders(Domain::Profiler));
+
+    browser.set_eager_domains(&[Domain::Log])?;
+    assert_eq!(vec!["eager"], tab.domain_holders(Domain::Log));
+    let new_tab = browser.new_tab()?;
+    assert_eq!(vec!["eager"], new_tab.domain_holders(Domain::Log));
+
+    browser.set_eager_domains(&[])?;
+    assert!(new_tab.domain_holders(Domain::Log).is_empty());
+    Ok(())
+}
+
+#[test]
+fn waits_for_spa_to_settle() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(
+        r#"<html><body><ul id="app"></ul><script>
+            let rendered = 0;
+            const render = setInterval(() => {
+                document.getElementById("app").appendChild(document.createElement("li"));
+                if (++rendered == 5) clearInterval(render);
+            }, 200);
+        </script></body></html>"#,
+    );
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?
+        .wait_for_stable_dom(Duration::from_millis(500))?;
+    assert_eq!(5, tab.find_elements("#app li")?.len());
+    Ok(())
+}
+
+#[test]
+fn records_navigation_waterfall() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+    assert!(tab.last_navigation_waterfall()?.is_none());
+
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    let waterfall = tab.last_navigation_waterfall()?.unwrap();
+    let document = &waterfall.requests[0];
+    assert_eq!(server.url(), document.url);
+    assert_eq!(Some(200), document.status);
+    assert!(document.ttfb.is_some());
+    Ok(())
+}
+
+#[test]
+fn freezes_time_and_seeds_randomness() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+    let frozen_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    tab.freeze_time(frozen_at)?;
+    tab.seed_random(42)?;
+
+    let mut samples = Vec::new();
+    for _ in 0..2 {
+        tab.navigate_to(&server.url())?.wait_until_navigated()?;
+        let sample = tab
+            .evaluate(
+                "JSON.stringify([Date.now(), new Date().getTime(), performance.now(), \
+                 Math.random(), Math.random()])",
+                false,
+            )?
+            .value
+            .unwrap();
+        samples.push(sample);
+    }
+    assert_eq!(samples[0], samples[1]);
+    let values: Vec<f64> = serde_json::from_str(samples[0].as_str().unwrap())?;
+    assert_eq!(1_700_000_000_000.0, values[0]);
+    assert_eq!(1_700_000_000_000.0, values[1]);
+    assert_eq!(0.0, values[2]);
+    assert_ne!(values[3], values[4]);
+    Ok(())
+}
+
+#[test]
+fn runs_timers_in_virtual_time() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(
+        r#"<html><body><script>
+            setTimeout(() => { document.title = "done"; }, 60000);
+        </script></body></html>"#,
+    );
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    let started = Instant::now();
+    tab.run_with_virtual_time(Duration::from_secs(61))?;
+    assert!(started.elapsed() < Duration::from_secs(20));
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (tab.get_title().ok()? == "done").then_some(()))?;
+    Ok(())
+}
+
+#[test]
+fn navigation_policy_blocks_disallowed_urls() -> Result<()> {
+    logging::enable_logging();
+    let server = server::Server::with_dumb_html(include_str!("simple.html"));
+    let browser = Browser::default()?;
+    let tab = browser.new_tab()?;
+
+    tab.set_navigation_policy(Some(NavigationPolicy::default()))?;
+    let Err(err) = tab.navigate_to(&server.url()) else {
+        panic!("navigated to a private address");
+    };
+    assert!(err.downcast_ref::<NavigationBlocked>().is_some());
+    assert!(tab.navigate_to("file:///etc/passwd").is_err());
+
+    tab.set_navigation_policy(Some(NavigationPolicy {
+        block_private_networks: false,
+        denied_hosts: vec!["localhost".to_string()],
+        ..NavigationPolicy::default()
+    }))?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    let fetched = tab.evaluate(
+        &format!(
+            "fetch('http://localhost:{}/').then(() => 'loaded', () => 'blocked')",
+            server.port()
+        ),
+        true,
+    )?;
+    assert_eq!(Some(serde_json::json!("blocked")), fetched.value);
+
+    tab.set_navigation_policy(None)?;
+    assert!(tab.domain_holders(Domain::Fetch).is_empty());
+    Ok(())
+}
+
+#[test]
+fn recovers_crashed_tabs() -> Result<()> {
+    logging::enable_logging();
+    let (server, browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.evaluate_on_new_document("window.setUp = true")?;
+
+    // Left crashed, calls fail straight away rather than timing out
+    let _ = tab.navigate_to("chrome://crash");
+    Wait::with_timeout(Duration::from_secs(10)).until(|| tab.is_crashed().then_some(()))?;
+    let Err(err) = tab.evaluate("1 + 1", false) else {
+        panic!("evaluated in a crashed tab");
+    };
+    assert!(err.downcast_ref::<TabCrashed>().is_some());
+    tab.recover_from_crash()?;
+    Wait::with_timeout(Duration::from_secs(10)).until(|| tab.evaluate("1 + 1", false).ok())?;
+
+    tab.set_crash_recovery(CrashRecovery::Recreate);
+    // Only tabs following the browser-wide default are changed by it
+    let other_tab = browser.new_tab()?;
+    browser.set_restore_crashed_tabs(true);
+    browser.set_restore_crashed_tabs(false);
+    assert_eq!(CrashRecovery::Recreate, tab.crash_recovery());
+    assert_eq!(CrashRecovery::Error, other_tab.crash_recovery());
+
+    tab.block_resource_types(&[ResourceType::Image])?;
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+    let _ = tab.navigate_to("chrome://crash");
+    let replacement = Wait::with_timeout(Duration::from_secs(20)).until(|| tab.replacement())?;
+    replacement.wait_until_navigated()?;
+    assert_eq!(CrashRecovery::Recreate, replacement.crash_recovery());
+    assert!(replacement
+        .domain_holders(Domain::Fetch)
+        .contains(&"block_resource_types".to_string()));
+    let set_up = replacement.evaluate("window.setUp", false)?.value;
+    assert_eq!(Some(serde_json::json!(true)), set_up);
+    assert!(browser
+        .get_tabs()
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|open_tab| open_tab.get_target_id() != tab.get_target_id()));
+    Ok(())
+}
+
+#[test]
+fn closes_tabs_over_their_memory_limit() -> Result<()> {
+    logging::enable_logging();
+    let (_, browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.set_memory_limit(32 * 1024 * 1024, MemoryLimitAction::Close)?;
+    tab.evaluate(
+        "window.leak = Array.from({ length: 64 }, () => new Array(256 * 1024).fill(Math.random()))",
+        false,
+    )?;
+    Wait::with_timeout(Duration::from_secs(10)).until(|| {
+        let tabs = browser.get_tabs().lock().unwrap();
+        tabs.iter()
+            .all(|open_tab| open_tab.get_target_id() != tab.get_target_id())
+            .then_some(())
+    })?;
+    // Having closed the tab, the guard gives up its claim on the Performance domain
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        tab.domain_holders(Domain::Performance)
+            .is_empty()
+            .then_some(())
+    })?;
+    Ok(())
+}
+
+#[test]
+fn debug_snapshot_reports_console_and_recent_calls() -> Result<()> {
+    logging::enable_logging();
+    let (_, _browser, tab) = dumb_server(include_str!("simple.html"));
+    tab.start_debug_recording()?;
+    tab.evaluate("console.warn('about to fail')", false)?;
+    tab.register_response_handling("log_responses", Box::new(|_, _| {}))?;
+
+    let snapshot = Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        let snapshot = tab.debug_snapshot().ok()?;
+        (!snapshot.console.is_empty()).then_some(snapshot)
+    })?;
+    assert_eq!("about to fail", snapshot.console[0].text);
+    assert_eq!(
+        vec!["log_responses".to_string()],
+        snapshot.interceptors.response_handlers
+    );
+    assert!(snapshot.frame_tree.is_some());
+
+    let json = serde_json::to_value(&snapshot)?;
+    assert_eq!(tab.get_url(), json["url"]);
+    assert!(json["recentMessages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|message| message["method"] == "Runtime.evaluate"));
+    Ok(())
+}
@@ -0,0 +1,625 @@
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use headless_chrome::browser::transport::{
+    forwarded_ws_url, CallsAbandoned, ChannelLimit, Connection, EventBackpressure,
+    IncomingMessages, MethodDestination, OverflowPolicy, ProtocolRecorder, ReplayServer, Transport,
+};
+use headless_chrome::browser::ConnectionClosed;
+use headless_chrome::protocol::cdp::types::Event;
+use headless_chrome::protocol::cdp::Browser::GetVersion;
+use headless_chrome::util::Wait;
+use tungstenite::protocol::Message;
+
+/// Accepts a single WebSocket connection and hands it to `serve`.
+fn fake_browser<F>(serve: F) -> Result<url::Url>
+where
+    F: FnOnce(tungstenite::WebSocket<std::net::TcpStream>) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        serve(tungstenite::accept(stream).unwrap());
+    });
+    Ok(url::Url::parse(&format!("ws://127.0.0.1:{port}"))?)
+}
+
+#[test]
+fn forwards_binary_frames_and_shuts_down_on_close() -> Result<()> {
+    let url = fake_browser(|mut socket| {
+        // Give the client time to start listening
+        thread::sleep(Duration::from_millis(200));
+        socket.write_message(Message::Ping(vec![1])).unwrap();
+        socket
+            .write_message(Message::Binary(vec![0, 159, 146, 150]))
+            .unwrap();
+        socket.close(None).unwrap();
+        while socket.read_message().is_ok() {}
+    })?;
+
+    let transport = Transport::new(url, None, Duration::from_secs(5))?;
+    let binary_rx = transport.listen_to_binary_messages();
+
+    let payload = binary_rx.recv_timeout(Duration::from_secs(5))?;
+    assert_eq!(vec![0, 159, 146, 150], payload);
+
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| transport.call_method_on_browser(GetVersion(None)).err())?;
+    Ok(())
+}
+
+#[test]
+fn replays_recorded_traffic() -> Result<()> {
+    let url = fake_browser(|mut socket| {
+        while let Ok(message) = socket.read_message() {
+            if let Message::Text(text) = message {
+                let call: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = serde_json::json!({
+                    "id": call["id"],
+                    "result": {"product": "Recorded/1.0"},
+                });
+                socket
+                    .write_message(Message::Text(response.to_string()))
+                    .unwrap();
+            }
+        }
+    })?;
+
+    let recording = tempfile::NamedTempFile::new()?;
+    let transport = Transport::new(url, None, Duration::from_secs(5))?;
+    transport.set_protocol_recorder(Some(ProtocolRecorder::to_file(recording.path())?));
+    let version = transport.call_method_on_browser(GetVersion(None))?;
+    assert_eq!("Recorded/1.0", version.product);
+    transport.shutdown();
+
+    let server = ReplayServer::from_file(recording.path())?;
+    let replayed = Transport::new(server.ws_url().parse()?, None, Duration::from_secs(5))?;
+    // Offset the call IDs, to check that the replayed response gets the new one
+    replayed.unique_call_id();
+    let version = replayed.call_method_on_browser(GetVersion(None))?;
+    assert_eq!("Recorded/1.0", version.product);
+    // Only one call was recorded
+    assert!(replayed.call_method_on_browser(GetVersion(None)).is_err());
+    Ok(())
+}
+
+type CallDelay = dyn Fn(usize) -> Option<Duration> + Send + Sync;
+
+/// Answers every call with the same result, after the delay `delay` gives for the call's index
+/// (counting from 0): straight away if that's zero, or never if it's `None`.
+struct FakeConnection {
+    incoming: IncomingMessages,
+    result: serde_json::Value,
+    delay: Box<CallDelay>,
+    calls: AtomicUsize,
+}
+
+impl std::fmt::Debug for FakeConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "FakeConnection {{}}")
+    }
+}
+
+impl FakeConnection {
+    fn new<D>(incoming: IncomingMessages, result: serde_json::Value, delay: D) -> Self
+    where
+        D: Fn(usize) -> Option<Duration> + Send + Sync + 'static,
+    {
+        Self {
+            incoming,
+            result,
+            delay: Box::new(delay),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Answers every call straight away.
+    fn canned(incoming: IncomingMessages, result: serde_json::Value) -> Self {
+        Self::new(incoming, result, |_| Some(Duration::ZERO))
+    }
+}
+
+impl Connection for FakeConnection {
+    fn send_message(&self, message_text: &str) -> Result<()> {
+        let call: serde_json::Value = serde_json::from_str(message_text)?;
+        let Some(delay) = (self.delay)(self.calls.fetch_add(1, Ordering::SeqCst)) else {
+            return Ok(());
+        };
+        let response = serde_json::json!({"id": call["id"], "result": self.result});
+        if delay.is_zero() {
+            self.incoming.text(&response.to_string());
+        } else {
+            let incoming = self.incoming.clone();
+            thread::spawn(move || {
+                thread::sleep(delay);
+                incoming.text(&response.to_string());
+            });
+        }
+        Ok(())
+    }
+
+    fn shutdown(&self) {
+        self.incoming.closed();
+    }
+}
+
+#[test]
+fn uses_injected_connection() -> Result<()> {
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        Ok(FakeConnection::canned(
+            incoming,
+            serde_json::json!({"product": "Fake/1.0"}),
+        ))
+    })?;
+
+    let version = transport.call_method_on_browser(GetVersion(None))?;
+    assert_eq!("Fake/1.0", version.product);
+
+    transport.shutdown();
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| transport.call_method_on_browser(GetVersion(None)).err())?;
+    Ok(())
+}
+
+#[test]
+fn stops_listening_to_target_events() -> Result<()> {
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        Ok(FakeConnection::canned(incoming, serde_json::json!({})))
+    })?;
+
+    let events_rx = transport.listen_to_target_events("worker-session".to_string().into());
+    transport.stop_listening_to_target_events(&"worker-session".to_string().into());
+    assert!(events_rx.recv().is_err());
+    Ok(())
+}
+
+#[test]
+fn counts_calls() -> Result<()> {
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        Ok(FakeConnection::canned(
+            incoming,
+            serde_json::json!({"product": "Fake/1.0"}),
+        ))
+    })?;
+
+    transport.call_method_on_browser(GetVersion(None))?;
+    transport.call_method_on_browser(GetVersion(None))?;
+
+    let metrics = transport.metrics();
+    assert_eq!(2, metrics.commands_sent);
+    assert_eq!(2, metrics.responses_received);
+    assert_eq!(0, metrics.error_responses);
+    assert_eq!(2, metrics.call_latency.count);
+    Ok(())
+}
+
+#[test]
+fn drops_oldest_events_over_capacity() -> Result<()> {
+    let (incoming_tx, incoming_rx) = std::sync::mpsc::channel();
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        incoming_tx.send(incoming.clone())?;
+        Ok(FakeConnection::canned(incoming, serde_json::json!({})))
+    })?;
+    let incoming = incoming_rx.recv()?;
+    transport.set_event_backpressure(EventBackpressure {
+        network: Some(ChannelLimit {
+            capacity: 2,
+            overflow: OverflowPolicy::DropOldest,
+        }),
+        ..EventBackpressure::default()
+    });
+    let events_rx = transport.listen_to_browser_events();
+
+    for request_id in ["1", "2", "3"] {
+        let event = serde_json::json!({
+            "method": "Network.dataReceived",
+            "params": {
+                "requestId": request_id,
+                "timestamp": 0,
+                "dataLength": 1,
+                "encodedDataLength": 1,
+            },
+        });
+        incoming.text(&event.to_string());
+    }
+    incoming.text(r#"{"method": "Target.targetDestroyed", "params": {"targetId": "a"}}"#);
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (transport.metrics().events_received == 4).then_some(()))?;
+
+    // The event of another class gets through regardless, after the newest network events
+    let mut request_ids = Vec::new();
+    loop {
+        match events_rx.recv_timeout(Duration::from_secs(5))? {
+            Event::NetworkDataReceived(event) => request_ids.push(event.params.request_id),
+            Event::TargetDestroyed(_) => break,
+            other => panic!("Unexpected event: {other:?}"),
+        }
+    }
+    assert_eq!(vec!["2".to_string(), "3".to_string()], request_ids);
+    assert_eq!(1, events_rx.dropped());
+    assert_eq!(1, transport.metrics().events_dropped);
+    Ok(())
+}
+
+fn slow_transport(delay: Option<Duration>) -> Result<std::sync::Arc<Transport>> {
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        Ok(FakeConnection::new(
+            incoming,
+            serde_json::json!({"product": "Slow/1.0"}),
+            move |_| delay,
+        ))
+    })?;
+    Ok(std::sync::Arc::new(transport))
+}
+
+#[test]
+fn graceful_shutdown_waits_for_outstanding_calls() -> Result<()> {
+    let transport = slow_transport(Some(Duration::from_millis(300)))?;
+    let call = {
+        let transport = std::sync::Arc::clone(&transport);
+        thread::spawn(move || transport.call_method_on_browser(GetVersion(None)))
+    };
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (transport.metrics().commands_sent == 1).then_some(()))?;
+
+    transport.shutdown_gracefully(Duration::from_secs(5))?;
+    assert_eq!("Slow/1.0", call.join().unwrap()?.product);
+
+    let err = transport
+        .call_method_on_browser(GetVersion(None))
+        .unwrap_err();
+    assert!(err.downcast_ref::<ConnectionClosed>().is_some());
+    Ok(())
+}
+
+#[test]
+fn graceful_shutdown_abandons_calls_after_timeout() -> Result<()> {
+    let transport = slow_transport(None)?;
+    let call = {
+        let transport = std::sync::Arc::clone(&transport);
+        thread::spawn(move || transport.call_method_on_browser(GetVersion(None)))
+    };
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| (transport.metrics().commands_sent == 1).then_some(()))?;
+
+    let err = transport
+        .shutdown_gracefully(Duration::from_millis(100))
+        .unwrap_err();
+    assert_eq!(
+        1,
+        err.downcast_ref::<CallsAbandoned>()
+            .unwrap()
+            .abandoned_calls
+    );
+    let err = call.join().unwrap().unwrap_err();
+    assert!(err.downcast_ref::<ConnectionClosed>().is_some());
+    Ok(())
+}
+
+#[test]
+fn survives_late_responses() -> Result<()> {
+    let transport = Transport::with_connection(None, Duration::from_millis(400), |incoming| {
+        // Only the first call is answered late
+        Ok(FakeConnection::new(
+            incoming,
+            serde_json::json!({"product": "Late/1.0"}),
+            |call| Some(Duration::from_millis(if call == 0 { 600 } else { 0 })),
+        ))
+    })?;
+    let transport = std::sync::Arc::new(transport);
+
+    let late_call = {
+        let transport = std::sync::Arc::clone(&transport);
+        thread::spawn(move || transport.call_method_on_browser(GetVersion(None)))
+    };
+    // Keep the connection busy until well after the first call's response arrives
+    for _ in 0..3 {
+        thread::sleep(Duration::from_millis(250));
+        transport.call_method_on_browser(GetVersion(None))?;
+    }
+    assert!(late_call.join().unwrap().is_err());
+    assert_eq!(1, transport.metrics().timeouts);
+    Ok(())
+}
+
+#[test]
+fn pipelines_batched_calls() -> Result<()> {
+    let url = fake_browser(|mut socket| {
+        // Only answer once every call has arrived, and in reverse order
+        let mut ids = Vec::new();
+        while ids.len() < 3 {
+            if let Message::Text(text) = socket.read_message().unwrap() {
+                let call: serde_json::Value = serde_json::from_str(&text).unwrap();
+                ids.push(call["id"].clone());
+            }
+        }
+        for id in ids.into_iter().rev() {
+            let product = format!("Fake/{id}");
+            let response = serde_json::json!({"id": id, "result": {"product": product}});
+            socket
+                .write_message(Message::Text(response.to_string()))
+                .unwrap();
+        }
+        while socket.read_message().is_ok() {}
+    })?;
+
+    let transport = Transport::new(url, None, Duration::from_secs(5))?;
+    let versions = transport.call_batch(
+        vec![GetVersion(None), GetVersion(None), GetVersion(None)],
+        &MethodDestination::Browser,
+    )?;
+
+    let products = versions
+        .into_iter()
+        .map(|version| version.map(|version| version.product))
+        .collect::<Result<Vec<_>>>()?;
+    let mut sorted = products.clone();
+    sorted.sort();
+    assert_eq!(sorted, products);
+    assert_eq!(3, products.len());
+    Ok(())
+}
+
+#[test]
+fn returns_raw_results() -> Result<()> {
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        Ok(FakeConnection::canned(
+            incoming,
+            serde_json::json!({"product": "Fake/1.0"}),
+        ))
+    })?;
+
+    let raw = transport.call_method_raw(GetVersion(None), MethodDestination::Browser)?;
+    assert_eq!(r#"{"product":"Fake/1.0"}"#, raw.get());
+    Ok(())
+}
+
+#[test]
+fn talks_over_a_pipe() -> Result<()> {
+    let (browser_reader, our_writer) = std::io::pipe()?;
+    let (our_reader, mut browser_writer) = std::io::pipe()?;
+    thread::spawn(move || {
+        use std::io::{BufRead, Write};
+
+        let mut browser_reader = std::io::BufReader::new(browser_reader);
+        let mut message = Vec::new();
+        while browser_reader.read_until(0, &mut message).unwrap_or(0) > 0 {
+            message.pop();
+            let call: serde_json::Value = serde_json::from_slice(&message).unwrap();
+            let response = serde_json::json!({
+                "id": call["id"],
+                "result": {"product": "Piped/1.0"},
+            });
+            browser_writer
+                .write_all(format!("{response}\0").as_bytes())
+                .unwrap();
+            message.clear();
+        }
+    });
+
+    let transport = Transport::with_pipe(our_reader, our_writer, None, Duration::from_secs(5))?;
+    let version = transport.call_method_on_browser(GetVersion(None))?;
+    assert_eq!("Piped/1.0", version.product);
+
+    // Closing our end ends the fake browser, which closes its own
+    transport.shutdown();
+    Wait::with_timeout(Duration::from_secs(5))
+        .until(|| transport.call_method_on_browser(GetVersion(None)).err())?;
+    Ok(())
+}
+
+#[test]
+fn connects_via_a_given_stream() -> Result<()> {
+    let url = fake_browser(|mut socket| {
+        while let Ok(message) = socket.read_message() {
+            if let Message::Text(text) = message {
+                let call: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = serde_json::json!({
+                    "id": call["id"],
+                    "result": {"product": "Tunnelled/1.0"},
+                });
+                socket
+                    .write_message(Message::Text(response.to_string()))
+                    .unwrap();
+            }
+        }
+    })?;
+
+    // The browser's own address, which only the stream knows how to reach
+    let stream = std::net::TcpStream::connect(format!("127.0.0.1:{}", url.port().unwrap()))?;
+    let ws_url = url::Url::parse("ws://farm-host:9222/devtools/browser/1a2b3c")?;
+    let transport = Transport::connect_via(stream, &ws_url, Duration::from_secs(5), None)?;
+    let version = transport.call_method_on_browser(GetVersion(None))?;
+    assert_eq!("Tunnelled/1.0", version.product);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn connects_via_a_unix_socket() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("headless_chrome-{}.sock", std::process::id()));
+    let listener = std::os::unix::net::UnixListener::bind(&path)?;
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        while let Ok(message) = socket.read_message() {
+            if let Message::Text(text) = message {
+                let call: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = serde_json::json!({
+                    "id": call["id"],
+                    "result": {"product": "Socketed/1.0"},
+                });
+                socket
+                    .write_message(Message::Text(response.to_string()))
+                    .unwrap();
+            }
+        }
+    });
+
+    let ws_url = url::Url::parse("ws://localhost:9222/devtools/browser/1a2b3c")?;
+    let transport =
+        Transport::connect_via_unix_socket(&path, &ws_url, Duration::from_secs(5), None);
+    std::fs::remove_file(&path)?;
+    let version = transport?.call_method_on_browser(GetVersion(None))?;
+    assert_eq!("Socketed/1.0", version.product);
+    Ok(())
+}
+
+#[test]
+fn finds_ws_url_through_forwarded_port() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let endpoint = listener.local_addr()?.to_string();
+    thread::spawn(move || {
+        use std::io::{BufRead, Write};
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut request = std::io::BufReader::new(&stream);
+        let mut line = String::new();
+        while request.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        let body = r#"{"Browser": "Chrome/120.0", "webSocketDebuggerUrl": "ws://localhost:9222/devtools/browser/1a2b3c"}"#;
+        write!(
+            &stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+    });
+
+    let ws_url = forwarded_ws_url(&endpoint)?;
+    assert_eq!(
+        format!("ws://{endpoint}/devtools/browser/1a2b3c"),
+        ws_url.as_str()
+    );
+    Ok(())
+}
+
+#[test]
+fn calls_methods_by_name() -> Result<()> {
+    let url = fake_browser(|mut socket| {
+        while let Ok(message) = socket.read_message() {
+            if let Message::Text(text) = message {
+                let call: serde_json::Value = serde_json::from_str(&text).unwrap();
+                let response = if call["method"] == "Vendor.echo" {
+                    serde_json::json!({"id": call["id"], "result": {"echoed": call["params"]}})
+                } else {
+                    serde_json::json!({
+                        "id": call["id"],
+                        "error": {"code": -32601, "message": "'Vendor.nope' wasn't found"},
+                    })
+                };
+                socket
+                    .write_message(Message::Text(response.to_string()))
+                    .unwrap();
+            }
+        }
+    })?;
+
+    let transport = Transport::new(url, None, Duration::from_secs(5))?;
+    let params = serde_json::json!({"answer": 42});
+    let result = transport.call_raw("Vendor.echo", params.clone(), MethodDestination::Browser)?;
+    assert_eq!(serde_json::json!({"echoed": {"answer": 42}}), result);
+
+    assert!(transport
+        .call_raw("Vendor.nope", params, MethodDestination::Browser)
+        .is_err());
+    Ok(())
+}
+
+/// Records the fields of every span and event, as `name=value`.
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct FieldRecorder(std::sync::Mutex<Vec<String>>);
+
+#[cfg(feature = "tracing")]
+impl FieldRecorder {
+    fn push(&self, fields: &dyn Fn(&mut dyn tracing::field::Visit)) {
+        struct Visitor<'a>(&'a mut Vec<String>);
+        impl tracing::field::Visit for Visitor<'_> {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                self.0.push(format!("{}={value:?}", field.name()));
+            }
+        }
+        fields(&mut Visitor(&mut self.0.lock().unwrap()));
+    }
+
+    fn contains(&self, field: &str) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|recorded| recorded == field)
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for &'static FieldRecorder {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        self.push(&|visitor| span.record(visitor));
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+        self.push(&|visitor| values.record(visitor));
+    }
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        self.push(&|visitor| event.record(visitor));
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[test]
+#[cfg(feature = "tracing")]
+fn traces_calls_and_events() -> Result<()> {
+    /// Sends an event before answering each call.
+    #[derive(Debug)]
+    struct EventfulConnection(IncomingMessages);
+
+    impl Connection for EventfulConnection {
+        fn send_message(&self, message_text: &str) -> Result<()> {
+            let call: serde_json::Value = serde_json::from_str(message_text)?;
+            self.0
+                .text(r#"{"method":"Page.loadEventFired","params":{"timestamp":1.0}}"#);
+            let response = serde_json::json!({"id": call["id"], "result": {"product": "Fake/1.0"}});
+            self.0.text(&response.to_string());
+            Ok(())
+        }
+
+        fn shutdown(&self) {
+            self.0.closed();
+        }
+    }
+
+    // Events are traced on the transport's own thread
+    let recorder: &'static FieldRecorder = Box::leak(Box::default());
+    tracing::subscriber::set_global_default(recorder)?;
+
+    let transport = Transport::with_connection(None, Duration::from_secs(5), |incoming| {
+        Ok(EventfulConnection(incoming))
+    })?;
+    transport.call_method_on_browser(GetVersion(None))?;
+
+    assert!(recorder.contains(r#"method="Browser.getVersion""#));
+    assert!(recorder.contains(r#"outcome="ok""#));
+    Wait::with_timeout(Duration::from_secs(5)).until(|| {
+        recorder
+            .contains(r#"method="Page.loadEventFired""#)
+            .then_some(())
+    })?;
+    Ok(())
+}
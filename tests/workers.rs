@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use headless_chrome::util::Wait;
+use headless_chrome::Browser;
+
+mod server;
+
+#[test]
+fn evaluate_in_worker_and_capture_its_console_and_errors() -> Result<()> {
+    let server = server::Server::with_dumb_html(
+        r#"<script>
+            const source = "console.log('hello from worker'); setTimeout(() => { throw new Error('worker failed'); }, 100);";
+            new Worker(URL.createObjectURL(new Blob([source])));
+        </script>"#,
+    );
+
+    let browser = Browser::default()?;
+    let tab = browser.wait_for_initial_tab()?;
+
+    assert!(tab.workers()?.is_empty());
+    tab.navigate_to(&server.url())?.wait_until_navigated()?;
+
+    let wait = Wait::with_timeout(Duration::from_secs(10));
+    let worker = wait.until(|| tab.workers().ok()?.into_iter().next())?;
+
+    let result = worker.evaluate("6 * 7", false)?;
+    assert_eq!(Some(serde_json::json!(42)), result.value);
+
+    let message = wait.until(|| worker.get_console_messages().into_iter().next())?;
+    assert_eq!(
+        Some(serde_json::json!("hello from worker")),
+        message.args[0].value
+    );
+
+    let exception = wait.until(|| worker.get_exceptions().into_iter().next())?;
+    let description = exception.exception.and_then(|e| e.description).unwrap();
+    assert!(description.contains("worker failed"));
+
+    Ok(())
+}